@@ -0,0 +1,22 @@
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use flate2::read::GzDecoder;
+
+
+pub const SAMPLE_RECORDS: usize = 1000;
+
+// First records of $INPUT_FILE, or of the default corpus, the same input the binary benchmarks use.
+pub fn sample() -> Vec<String> {
+    let input = std::env::var("INPUT_FILE").unwrap_or_else(|_| "TweetsChampions.json.gz".to_owned());
+    let file = File::open(&input).unwrap_or_else(|e| panic!("{}: {} (set INPUT_FILE)", input, e));
+    let lines: Box<dyn BufRead> = if input.ends_with(".gz") {
+        Box::new(BufReader::new(GzDecoder::new(file)))
+    } else {
+        Box::new(BufReader::new(file))
+    };
+    lines.lines().take(SAMPLE_RECORDS).map(|line| line.unwrap()).collect()
+}
+
+pub fn bytes(sample: &[String]) -> u64 {
+    sample.iter().map(|line| line.len() as u64).sum()
+}
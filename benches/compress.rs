@@ -0,0 +1,27 @@
+// Statistical counterpart of `bench compress:<codec>`: one block per record at each codec's default
+// level, on an in-memory sample.
+use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
+
+mod common;
+use learningrust::compress;
+
+
+fn compress(c: &mut Criterion) {
+    let sample = common::sample();
+    let mut group = c.benchmark_group("compress");
+    group.throughput(Throughput::Bytes(common::bytes(&sample)));
+    for compressor in compress::compressors() {
+        let level = compressor.default_level();
+        group.bench_function(format!("{}:{}", compressor.name(), level), |b| {
+            b.iter(|| {
+                for line in &sample {
+                    black_box(compressor.compress_block(line.as_bytes(), level).unwrap());
+                }
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, compress);
+criterion_main!(benches);
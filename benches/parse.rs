@@ -0,0 +1,30 @@
+// Statistical counterpart of `bench parse:<parser>`: the same parsers on an in-memory sample, so
+// decompression and IO are out of the picture.
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, Criterion, Throughput};
+
+mod common;
+use learningrust::parser;
+
+
+fn parse(c: &mut Criterion) {
+    let sample = common::sample();
+    let mut group = c.benchmark_group("parse");
+    group.throughput(Throughput::Bytes(common::bytes(&sample)));
+    for parser in parser::parsers() {
+        group.bench_function(parser.name(), |b| {
+            // simd-json parses in place, every iteration gets a fresh copy outside the timing
+            b.iter_batched(
+                || sample.iter().map(|line| line.clone().into_bytes()).collect::<Vec<_>>(),
+                |mut lines| {
+                    for line in lines.iter_mut() {
+                        black_box(parser.parse_native(line).unwrap());
+                    }
+                },
+                BatchSize::LargeInput)
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, parse);
+criterion_main!(benches);
@@ -0,0 +1,39 @@
+// Compiles the C and C++ shims of the optional parser benchmarks in shim/, with their features only.
+fn main() {
+    #[cfg(feature = "yyjson")]
+    yyjson();
+    #[cfg(feature = "rapidjson")]
+    rapidjson();
+}
+
+// Against a yyjson checkout in $YYJSON_DIR, compiled along with the shim, or else the system
+// library.
+#[cfg(feature = "yyjson")]
+fn yyjson() {
+    println!("cargo:rerun-if-changed=shim/yyjson.c");
+    println!("cargo:rerun-if-env-changed=YYJSON_DIR");
+    let checkout = std::env::var("YYJSON_DIR").ok();
+    let mut build = cc::Build::new();
+    build.file("shim/yyjson.c").opt_level(3);
+    if let Some(dir) = &checkout {
+        build.include(format!("{}/src", dir)).file(format!("{}/src/yyjson.c", dir));
+    }
+    build.compile("yyjson_shim");
+    // after the shim, which needs it
+    if checkout.is_none() {
+        println!("cargo:rustc-link-lib=yyjson");
+    }
+}
+
+// Header only, found in $RAPIDJSON_DIR/include or else on the system include path.
+#[cfg(feature = "rapidjson")]
+fn rapidjson() {
+    println!("cargo:rerun-if-changed=shim/rapidjson.cpp");
+    println!("cargo:rerun-if-env-changed=RAPIDJSON_DIR");
+    let mut build = cc::Build::new();
+    build.cpp(true).flag_if_supported("-std=c++14").file("shim/rapidjson.cpp").opt_level(3);
+    if let Ok(dir) = std::env::var("RAPIDJSON_DIR") {
+        build.include(format!("{}/include", dir));
+    }
+    build.compile("rapidjson_shim");
+}
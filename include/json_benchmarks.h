@@ -0,0 +1,27 @@
+/* C interface of the json-benchmarks cdylib, built with `cargo build --release --features ffi`. */
+#ifndef JSON_BENCHMARKS_H
+#define JSON_BENCHMARKS_H
+
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* Avro schema inferred from a gzipped NDJSON file as JSON text, NULL on failure. Free with free_string. */
+char *infer_schema_from_ndjson(const char *path);
+
+/* Streams a gzipped NDJSON file into a deflated Avro file. schema is Avro JSON text, or NULL to infer
+ * it from the input. Returns the number of records written, -1 on failure. */
+int64_t convert_ndjson_to_avro(const char *input, const char *schema, const char *output);
+
+/* Message of the last failure on the calling thread, NULL when the last call succeeded. */
+const char *last_error(void);
+
+void free_string(char *s);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
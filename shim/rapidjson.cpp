@@ -0,0 +1,59 @@
+/* RapidJSON behind plain C functions for src/rapidjson.rs, it being a C++ header library. */
+#include <cstddef>
+#include <cstdint>
+#include <rapidjson/document.h>
+#include <rapidjson/memorystream.h>
+#include <rapidjson/reader.h>
+
+static uint64_t size_of(const rapidjson::Value &value) {
+    uint64_t size = 0;
+    if (value.IsObject()) {
+        for (const auto &member : value.GetObject()) {
+            size += 1 + size_of(member.value);
+        }
+    } else if (value.IsArray()) {
+        for (const auto &item : value.GetArray()) {
+            size += 1 + size_of(item);
+        }
+    }
+    return size;
+}
+
+/* Every value the reader sees but the root is an object member or an array item. */
+struct Counter : public rapidjson::BaseReaderHandler<rapidjson::UTF8<>, Counter> {
+    uint64_t values = 0;
+
+    bool Default() {
+        values++;
+        return true;
+    }
+
+    bool Key(const char *, rapidjson::SizeType, bool) { return true; }
+    bool EndObject(rapidjson::SizeType) { return true; }
+    bool EndArray(rapidjson::SizeType) { return true; }
+};
+
+extern "C" {
+
+/* Parses len bytes of JSON into a Document and returns the object keys and array items in it,
+ * counted as src/sink.rs counts them, or -1 when the input is not JSON. */
+int64_t rapidjson_shim_dom_size(const char *data, size_t len) {
+    rapidjson::Document document;
+    if (document.Parse(data, len).HasParseError()) {
+        return -1;
+    }
+    return (int64_t) size_of(document);
+}
+
+/* The same count from the SAX events of a Reader, without building a document. */
+int64_t rapidjson_shim_sax_size(const char *data, size_t len) {
+    rapidjson::MemoryStream stream(data, len);
+    rapidjson::Reader reader;
+    Counter counter;
+    if (reader.Parse(stream, counter).IsError()) {
+        return -1;
+    }
+    return (int64_t) counter.values - 1;
+}
+
+}
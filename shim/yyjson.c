@@ -0,0 +1,33 @@
+/* yyjson behind plain functions for src/yyjson.rs, most of its API being inline. */
+#include <stddef.h>
+#include <stdint.h>
+#include <yyjson.h>
+
+static uint64_t size_of(yyjson_val *val) {
+    uint64_t size = 0;
+    size_t idx, max;
+    yyjson_val *key, *item;
+    if (yyjson_is_obj(val)) {
+        yyjson_obj_foreach(val, idx, max, key, item) {
+            size += 1 + size_of(item);
+        }
+    } else if (yyjson_is_arr(val)) {
+        yyjson_arr_foreach(val, idx, max, item) {
+            size += 1 + size_of(item);
+        }
+    }
+    return size;
+}
+
+/* Parses len bytes of JSON and returns the object keys and array items in it, counted as
+ * src/sink.rs counts them, or -1 when the input is not JSON. */
+int64_t yyjson_shim_size(const char *data, size_t len) {
+    yyjson_doc *doc = yyjson_read(data, len, 0);
+    int64_t size;
+    if (!doc) {
+        return -1;
+    }
+    size = (int64_t) size_of(yyjson_doc_get_root(doc));
+    yyjson_doc_free(doc);
+    return size;
+}
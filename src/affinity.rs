@@ -0,0 +1,73 @@
+use std::io;
+
+
+// Keeps the calling thread, and the threads it starts afterwards, on the given cores, so the
+// scheduler does not migrate a benchmark between cores with different caches and clocks. Returns
+// false where pinning is not supported.
+#[cfg(target_os = "linux")]
+pub fn pin_to_cores(cores: &[usize]) -> io::Result<bool> {
+    let mut set: libc::cpu_set_t = unsafe { std::mem::zeroed() };
+    for core in cores {
+        if *core >= libc::CPU_SETSIZE as usize {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("no core {}", core)));
+        }
+        unsafe { libc::CPU_SET(*core, &mut set) };
+    }
+    let result = unsafe { libc::sched_setaffinity(0, std::mem::size_of::<libc::cpu_set_t>(), &set) };
+    if result != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(true)
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn pin_to_cores(_cores: &[usize]) -> io::Result<bool> {
+    Ok(false)
+}
+
+// Raises the scheduling priority of the process to nice -20, which usually takes
+// root or CAP_SYS_NICE. Returns false where it is not supported.
+#[cfg(unix)]
+pub fn raise_priority() -> io::Result<bool> {
+    let result = unsafe { libc::setpriority(libc::PRIO_PROCESS, 0, -20) };
+    if result != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(true)
+}
+
+#[cfg(not(unix))]
+pub fn raise_priority() -> io::Result<bool> {
+    Ok(false)
+}
+
+// What bench and batch do with --pin-cores and --high-priority: failures are warnings, a run on an
+// unpinned core still being a run.
+pub fn apply(cores: &[usize], high_priority: bool) {
+    if !cores.is_empty() {
+        match pin_to_cores(cores) {
+            Ok(true) => eprintln!("Pinned to cores {:?}", cores),
+            Ok(false) => eprintln!("Pinning to cores is not supported on this platform, running unpinned"),
+            Err(e) => eprintln!("Could not pin to cores {:?}: {}", cores, e)
+        }
+    }
+    if high_priority {
+        match raise_priority() {
+            Ok(true) => eprintln!("Running at raised priority"),
+            Ok(false) => eprintln!("Raising the priority is not supported on this platform"),
+            Err(e) => eprintln!("Could not raise the priority: {}", e)
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn test_pin_to_cores() {
+        assert!(pin_to_cores(&[100_000]).is_err());
+    }
+}
@@ -0,0 +1,125 @@
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::fmt;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use serde::{Deserialize, Serialize};
+
+
+static CURRENT: AtomicUsize = AtomicUsize::new(0);
+static PEAK: AtomicUsize = AtomicUsize::new(0);
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+static ALLOCATED: AtomicUsize = AtomicUsize::new(0);
+
+// The system allocator with process wide counters, installed as the global allocator in main.
+// Allocations of other threads during a measurement are counted too. The counters stay at zero
+// unless it is the global allocator, so applications embedding the library that want allocation
+// counts from measure have to install it themselves:
+//
+//   #[global_allocator]
+//   static ALLOCATOR: learningrust::allocation::CountingAllocator = learningrust::allocation::CountingAllocator;
+pub struct CountingAllocator;
+
+// for the library's own tests, the binary and the tests of main have the one of main
+#[cfg(test)]
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+fn allocated(size: usize) {
+    ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+    ALLOCATED.fetch_add(size, Ordering::Relaxed);
+    let current = CURRENT.fetch_add(size, Ordering::Relaxed) + size;
+    PEAK.fetch_max(current, Ordering::Relaxed);
+}
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let ptr = System.alloc(layout);
+        if !ptr.is_null() {
+            allocated(layout.size());
+        }
+        ptr
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        let ptr = System.alloc_zeroed(layout);
+        if !ptr.is_null() {
+            allocated(layout.size());
+        }
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout);
+        CURRENT.fetch_sub(layout.size(), Ordering::Relaxed);
+    }
+
+    // counted as a free of the old block and an allocation of the new one
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let new_ptr = System.realloc(ptr, layout, new_size);
+        if !new_ptr.is_null() {
+            CURRENT.fetch_sub(layout.size(), Ordering::Relaxed);
+            allocated(new_size);
+        }
+        new_ptr
+    }
+}
+
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct Allocations {
+    // highest heap use above what was allocated when the meter started
+    pub peak_bytes: usize,
+    pub allocations: usize,
+    pub allocated_bytes: usize
+}
+
+impl fmt::Display for Allocations {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Peak heap: {:.1} MiB, allocations: {}, allocated: {:.1} MiB", self.peak_bytes as f64 / 1048576.0,
+               self.allocations, self.allocated_bytes as f64 / 1048576.0)
+    }
+}
+
+// what is allocated now, process wide
+pub fn current_bytes() -> usize {
+    CURRENT.load(Ordering::Relaxed)
+}
+
+pub struct AllocationMeter {
+    current: usize,
+    allocations: usize,
+    allocated: usize
+}
+
+impl AllocationMeter {
+    // Starts a new peak from the current heap use, so meters should not overlap.
+    pub fn start() -> Self {
+        let current = CURRENT.load(Ordering::Relaxed);
+        PEAK.store(current, Ordering::Relaxed);
+        AllocationMeter { current, allocations: ALLOCATIONS.load(Ordering::Relaxed), allocated: ALLOCATED.load(Ordering::Relaxed) }
+    }
+
+    pub fn allocations(&self) -> Allocations {
+        Allocations {
+            peak_bytes: PEAK.load(Ordering::Relaxed).saturating_sub(self.current),
+            allocations: ALLOCATIONS.load(Ordering::Relaxed) - self.allocations,
+            allocated_bytes: ALLOCATED.load(Ordering::Relaxed) - self.allocated
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_allocation_meter() {
+        let meter = AllocationMeter::start();
+        let buffer = vec![0u8; 1 << 20];
+        drop(buffer);
+        let allocations = meter.allocations();
+        // other tests allocate in parallel, so only lower bounds hold
+        assert!(allocations.allocations >= 1);
+        assert!(allocations.allocated_bytes >= 1 << 20);
+    }
+}
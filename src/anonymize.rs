@@ -0,0 +1,198 @@
+use json::JsonValue;
+use json::number::Number;
+use sha2::{Sha256, Digest};
+use failure::Error;
+use crate::convert::{Location, RecordWriter};
+
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Redaction {
+    // deterministic salted hash, equal inputs stay equal so joins and group-bys still work
+    Hash,
+    // masks the value, strings keep their length
+    Redact
+}
+
+
+// Paths are dotted field names, a segment ending in "[]" applies to every item of that array,
+// e.g. "entities.user_mentions[].screen_name". Values keep their JSON type so the inferred
+// schema of an anonymized corpus is the same as the original one.
+pub struct Anonymizer {
+    rules: Vec<(Vec<String>, Redaction)>,
+    salt: String
+}
+
+impl Anonymizer {
+    pub fn new(salt: &str) -> Self {
+        Anonymizer { rules: Vec::new(), salt: salt.to_owned() }
+    }
+
+    pub fn rule(mut self, path: &str, redaction: Redaction) -> Self {
+        self.rules.push((path.split('.').map(|s| s.to_owned()).collect(), redaction));
+        self
+    }
+
+    pub fn apply(&self, json: &mut JsonValue) {
+        for (segments, redaction) in &self.rules {
+            apply_at(json, segments, &mut |value| self.anonymize(value, *redaction));
+        }
+    }
+
+    fn anonymize(&self, json: &mut JsonValue, redaction: Redaction) {
+        let replacement = match (&*json, redaction) {
+            (JsonValue::Null, _) => return,
+            (JsonValue::Array(_), _) | (JsonValue::Object(_), _) => {
+                match json {
+                    JsonValue::Array(items) => items.iter_mut().for_each(|item| self.anonymize(item, redaction)),
+                    _ => json.entries_mut().for_each(|(_, value)| self.anonymize(value, redaction))
+                }
+                return;
+            },
+            (JsonValue::Boolean(_), Redaction::Redact) => JsonValue::Boolean(false),
+            (JsonValue::Boolean(_), Redaction::Hash) => JsonValue::Boolean(self.digest(json)[0] & 1 == 1),
+            // a number with a non-zero exponent is inferred as a double, keep the exponent so it stays one
+            (JsonValue::Number(number), Redaction::Redact) => {
+                let (_, _, exponent) = number.as_parts();
+                JsonValue::Number(unsafe { Number::from_parts_unchecked(true, 0, exponent) })
+            },
+            (JsonValue::Number(number), Redaction::Hash) => {
+                let (_, _, exponent) = number.as_parts();
+                let digest = self.digest(json);
+                let mut bytes = [0u8; 8];
+                bytes.copy_from_slice(&digest[..8]);
+                // keep integers integral, only the lower 53 bits so the value survives a double
+                let hashed = u64::from_le_bytes(bytes) & ((1 << 53) - 1);
+                if exponent == 0 {
+                    JsonValue::from(hashed)
+                } else {
+                    JsonValue::Number(unsafe { Number::from_parts_unchecked(true, hashed, -3) })
+                }
+            },
+            (_, Redaction::Redact) => {
+                let s = json.as_str().unwrap_or("");
+                JsonValue::from("*".repeat(s.chars().count()))
+            },
+            (_, Redaction::Hash) => {
+                let hex: String = self.digest(json)[..8].iter().map(|b| format!("{:02x}", b)).collect();
+                JsonValue::from(hex)
+            }
+        };
+        *json = replacement;
+    }
+
+    fn digest(&self, json: &JsonValue) -> Vec<u8> {
+        let mut hasher = Sha256::new();
+        hasher.input(self.salt.as_bytes());
+        hasher.input(json.dump().as_bytes());
+        hasher.result().to_vec()
+    }
+}
+
+pub fn apply_at(json: &mut JsonValue, segments: &[String], f: &mut dyn FnMut(&mut JsonValue)) {
+    match segments.split_first() {
+        None => f(json),
+        Some((segment, rest)) => {
+            if segment.ends_with("[]") {
+                let name = segment.trim_end_matches("[]");
+                // indexing a missing key would insert it, leave records without the field untouched
+                let array = if name.is_empty() { json } else if json.has_key(name) { &mut json[name] } else { return };
+                if let JsonValue::Array(items) = array {
+                    items.iter_mut().for_each(|item| apply_at(item, rest, f));
+                }
+            } else if json.has_key(segment) {
+                apply_at(&mut json[segment.as_str()], rest, f);
+            }
+        }
+    }
+}
+
+
+pub struct AnonymizingWriter<'w> {
+    inner: &'w mut dyn RecordWriter,
+    anonymizer: Anonymizer
+}
+
+impl<'w> AnonymizingWriter<'w> {
+    pub fn new(inner: &'w mut dyn RecordWriter, anonymizer: Anonymizer) -> Self {
+        AnonymizingWriter { inner, anonymizer }
+    }
+}
+
+impl<'w> RecordWriter for AnonymizingWriter<'w> {
+    fn write(&mut self, record: &JsonValue) -> Result<(), Error> {
+        let mut record = record.clone();
+        self.anonymizer.apply(&mut record);
+        self.inner.write(&record)
+    }
+
+    fn finish(&mut self) -> Result<(), Error> {
+        self.inner.finish()
+    }
+
+    fn locate(&mut self, location: &Location) {
+        self.inner.locate(location);
+    }
+
+    fn full(&self) -> bool {
+        self.inner.full()
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_anonymize() {
+        let anonymizer =
+            Anonymizer::new("salt")
+                .rule("user.name", Redaction::Redact)
+                .rule("user.id", Redaction::Hash)
+                .rule("entities.user_mentions[].screen_name", Redaction::Hash);
+        let mut json = json::parse(r#"{"user": {"name": "LFC", "id": 904718268}, "entities": {"user_mentions": [{"screen_name": "ThaiLFC"}, {"screen_name": "ThaiLFC"}]}}"#).unwrap();
+        anonymizer.apply(&mut json);
+
+        assert_eq!(json["user"]["name"].as_str(), Some("***"));
+        assert!(json["user"]["id"].as_i64().is_some());
+        assert_ne!(json["user"]["id"].as_i64(), Some(904718268));
+        let mentions = &json["entities"]["user_mentions"];
+        assert_eq!(mentions[0]["screen_name"], mentions[1]["screen_name"]);
+        assert_ne!(mentions[0]["screen_name"].as_str(), Some("ThaiLFC"));
+    }
+
+    #[test]
+    fn test_anonymize_missing_field() {
+        let anonymizer =
+            Anonymizer::new("salt")
+                .rule("entities.urls[].url", Redaction::Redact)
+                .rule("user.name", Redaction::Hash);
+        let mut json = json::parse(r#"{"id": 1, "user": "LFC"}"#).unwrap();
+        anonymizer.apply(&mut json);
+
+        assert_eq!(json.dump(), r#"{"id":1,"user":"LFC"}"#);
+    }
+
+    #[test]
+    fn test_anonymize_double() {
+        let anonymizer =
+            Anonymizer::new("salt")
+                .rule("coordinates[]", Redaction::Redact)
+                .rule("score", Redaction::Hash)
+                .rule("count", Redaction::Redact);
+        let mut json = json::parse(r#"{"coordinates": [-0.12, 51.5], "score": 2.5, "count": 7}"#).unwrap();
+        anonymizer.apply(&mut json);
+
+        let exponent = |value: &JsonValue| match value {
+            JsonValue::Number(number) => number.as_parts().2,
+            _ => panic!("not a number: {}", value)
+        };
+        assert_eq!(json["coordinates"][0].as_f64(), Some(0.0));
+        assert_ne!(exponent(&json["coordinates"][0]), 0);
+        assert_ne!(exponent(&json["coordinates"][1]), 0);
+        assert_ne!(exponent(&json["score"]), 0);
+        assert_ne!(json["score"].as_f64(), Some(2.5));
+        assert_eq!(json["count"].as_i64(), Some(0));
+        assert_eq!(exponent(&json["count"]), 0);
+    }
+}
@@ -7,10 +7,7 @@ use avro_rs::Schema;
 use avro_rs::schema::{Name, UnionSchema, RecordField, RecordFieldOrder, SchemaKind};
 use serde_json;
 use serde_json::{Value, Map};
-use failure::Error;
-use std::fs::File;
-use flate2::read::GzDecoder;
-use std::io::{BufReader, BufRead, Lines};
+use failure::{Error, format_err};
 use regex::Regex;
 use std::borrow::{Cow, BorrowMut};
 use std::ops::{DerefMut, Deref};
@@ -30,21 +27,6 @@ lazy_static! {
 }
 
 
-pub struct GzipFile {
-    pub lines: Lines<BufReader<GzDecoder<File>>>
-}
-
-impl GzipFile {
-    pub fn new(file_path: &str) -> Self {
-        let file = File::open(file_path).unwrap();
-        let lines = GzDecoder::new(file);
-        let buf_reader = BufReader::new(lines);
-        let lines: Lines<BufReader<GzDecoder<File>>> = buf_reader.lines();
-        GzipFile{lines}
-    }
-}
-
-
 pub fn infer_schema_serde(json_value: Value, name: &str) -> Result<Schema, Error> {
     match json_value {
         Value::Bool(_) => Ok(Schema::Boolean),
@@ -164,7 +146,7 @@ pub fn infer_schema(json_value: &JsonValue, name: &str) -> Result<Schema, Error>
             })
         },
         JsonValue::Null => { Ok(Schema::Null) },
-        JsonValue::Short(_) => { Ok(Schema::Long) },
+        JsonValue::Short(_) => { Ok(Schema::String) },
         _ => { Ok(Schema::Null) }
     }
 }
@@ -370,44 +352,181 @@ pub fn merge_schemas(schema1: Schema, schema2: Schema) -> Result<Schema, Error>
 //    clean
 //}
 //
-//
-//pub fn json_to_avro(mut json: JsonValue, schema: Schema) -> Result<AvroValue, Error> {
-//    let sk = SchemaKind::from(schema);
-//    match (json, schema) {
-//        (JsonValue::String(s), _) => { Ok(AvroValue::String(s)) }
-//        (JsonValue::Number(n), _) => {
-//            if let Some(l) = n.as_fixed_point_i64(0) {
-//                Ok(AvroValue::Long(l))
-//            }
-//            else {
-//                let (sign, mantissa, exp) = n.as_parts();
-//                let v = mantissa as f64 * 10_f64.powi(exp as i32) * (sign as i8 * 2 - 1) as f64;
-//                Ok(AvroValue::Double(v))
-//            }
-//        }
-//        (JsonValue::Null, _) => { Ok(AvroValue::Null) }
-//        (JsonValue::Boolean(b), _) => { Ok(AvroValue::Boolean(b)) }
-//        (JsonValue::Short(s), _) => { Ok(AvroValue::String(s.to_string())) }
-//        (JsonValue::Array(vector), _) => {
-//            let mut avro_values = Vec::with_capacity(vector.len());
-//            for item in vector {
-//                avro_values.push(json_to_avro(item)?);
-//            }
-//            Ok(AvroValue::Array(avro_values))
-//        }
-//        (JsonValue::Object(_), Schema::Record {fields, ..}) => {
-//            let mut record_fields = Vec::new();
-//            for field in fields {
-//                let json = json.remove(field.name.as_str());
-//                let avro = json_to_avro(json, field.schema)?;
-//                record_fields.push((field.name, avro));
-//            }
-//
-//            Ok(AvroValue::Record(record_fields))
-//        }
-//        _ => Ok(AvroValue::Null)
-//    }
-//}
+// Appends a field to a record schema, for values added to records during conversion.
+pub fn with_extra_field(schema: Schema, field_name: &str, field_schema: Schema) -> Result<Schema, Error> {
+    match schema {
+        Schema::Record {name, doc, mut fields, mut lookup} => {
+            if lookup.contains_key(field_name) {
+                return Err(format_err!("record already has a field named {}", field_name));
+            }
+            lookup.insert(field_name.to_owned(), fields.len());
+            fields.push(RecordField{
+                name: field_name.to_owned(),
+                doc: None,
+                default: None,
+                schema: field_schema,
+                order: RecordFieldOrder::Ascending,
+                position: fields.len()
+            });
+            Ok(Schema::Record {name, doc, fields, lookup})
+        },
+        _ => Err(format_err!("fields can only be added to a record schema"))
+    }
+}
+
+
+pub fn json_to_avro(json: &JsonValue, schema: &Schema) -> Result<AvroValue, Error> {
+    match (json, schema) {
+        (JsonValue::Null, Schema::Null) => Ok(AvroValue::Null),
+        (JsonValue::Boolean(b), Schema::Boolean) => Ok(AvroValue::Boolean(*b)),
+        (JsonValue::Number(_), Schema::Long) => {
+            json.as_i64()
+                .map(AvroValue::Long)
+                .ok_or_else(|| format_err!("{} is not a valid long", json.dump()))
+        },
+        (JsonValue::Number(_), Schema::Double) => {
+            json.as_f64()
+                .map(AvroValue::Double)
+                .ok_or_else(|| format_err!("{} is not a valid double", json.dump()))
+        },
+        (JsonValue::String(_), Schema::String) | (JsonValue::Short(_), Schema::String) => {
+            Ok(AvroValue::String(json.as_str().unwrap().to_owned()))
+        },
+        (JsonValue::Array(vector), Schema::Array(items_schema)) => {
+            let mut avro_values = Vec::with_capacity(vector.len());
+            for item in vector {
+                avro_values.push(json_to_avro(item, items_schema)?);
+            }
+            Ok(AvroValue::Array(avro_values))
+        },
+        (JsonValue::Object(_), Schema::Map(values_schema)) => {
+            let mut avro_values = HashMap::new();
+            for (key, value) in json.entries() {
+                avro_values.insert(key.to_owned(), json_to_avro(value, values_schema)?);
+            }
+            Ok(AvroValue::Map(avro_values))
+        },
+        (JsonValue::Object(_), Schema::Record {fields, ..}) => {
+            let mut record_fields = Vec::with_capacity(fields.len());
+            for field in fields {
+                // missing fields are read as null, same as an explicit null
+                let avro = json_to_avro(&json[field.name.as_str()], &field.schema)
+                    .map_err(|e| format_err!("{}: {}", field.name, e))?;
+                record_fields.push((field.name.clone(), avro));
+            }
+            Ok(AvroValue::Record(record_fields))
+        },
+        (json, Schema::Union(union)) => {
+            let sk = json_kind(json);
+            // exact kind first so that a long is not widened when the union also has a double
+            let variant =
+                union
+                    .variants()
+                    .iter()
+                    .find(|variant| SchemaKind::from(*variant) == sk)
+                    .map(|variant| json_to_avro(json, variant))
+                    .or_else(|| union.variants().iter().map(|variant| json_to_avro(json, variant)).find(|r| r.is_ok()))
+                    .ok_or_else(|| format_err!("{} does not match any union variant", json.dump()))?;
+            Ok(AvroValue::Union(Box::new(variant?)))
+        },
+        (JsonValue::Number(_), Schema::Float) => Ok(AvroValue::Float(json.as_f32().unwrap_or_default())),
+        (JsonValue::Number(_), Schema::Int) => {
+            json.as_i32()
+                .map(AvroValue::Int)
+                .ok_or_else(|| format_err!("{} is not a valid int", json.dump()))
+        },
+        (json, schema) => Err(format_err!("can not convert {} to {:?}", json.dump(), SchemaKind::from(schema)))
+    }
+}
+
+
+// The schema kind infer_schema assigns to a value.
+pub fn json_kind(json: &JsonValue) -> SchemaKind {
+    match json {
+        JsonValue::Null => SchemaKind::Null,
+        JsonValue::Boolean(_) => SchemaKind::Boolean,
+        JsonValue::Number(number) => {
+            let (_, _, exponent) = number.as_parts();
+            if exponent == 0 { SchemaKind::Long } else { SchemaKind::Double }
+        },
+        JsonValue::String(_) | JsonValue::Short(_) => SchemaKind::String,
+        JsonValue::Array(_) => SchemaKind::Array,
+        JsonValue::Object(_) => SchemaKind::Record
+    }
+}
+
+
+// Avro JSON encoding as defined by the spec: values are plain JSON except non-null union values,
+// which are wrapped in an object keyed by the name of the selected branch.
+pub fn avro_json_encoding(value: &AvroValue, schema: &Schema) -> Result<Value, Error> {
+    match (value, schema) {
+        (AvroValue::Null, _) => Ok(Value::Null),
+        (AvroValue::Boolean(b), _) => Ok(Value::Bool(*b)),
+        (AvroValue::Int(i), _) => Ok(Value::from(*i)),
+        (AvroValue::Long(l), _) => Ok(Value::from(*l)),
+        (AvroValue::Float(f), _) => float_to_json(*f as f64),
+        (AvroValue::Double(d), _) => float_to_json(*d),
+        (AvroValue::String(s), _) | (AvroValue::Enum(_, s), _) => Ok(Value::String(s.clone())),
+        (AvroValue::Bytes(bytes), _) | (AvroValue::Fixed(_, bytes), _) => {
+            Ok(Value::String(bytes.iter().map(|b| *b as char).collect()))
+        },
+        (AvroValue::Array(items), Schema::Array(items_schema)) => {
+            let items = items.iter().map(|item| avro_json_encoding(item, items_schema)).collect::<Result<Vec<_>, _>>()?;
+            Ok(Value::Array(items))
+        },
+        (AvroValue::Map(values), Schema::Map(values_schema)) => {
+            let mut map = Map::new();
+            for (key, value) in values {
+                map.insert(key.clone(), avro_json_encoding(value, values_schema)?);
+            }
+            Ok(Value::Object(map))
+        },
+        (AvroValue::Record(record_fields), Schema::Record {fields, ..}) => {
+            let mut map = Map::new();
+            for ((name, value), field) in record_fields.iter().zip(fields) {
+                map.insert(name.clone(), avro_json_encoding(value, &field.schema)?);
+            }
+            Ok(Value::Object(map))
+        },
+        (AvroValue::Union(inner), Schema::Union(union)) => {
+            if let AvroValue::Null = **inner {
+                return Ok(Value::Null);
+            }
+            let (_, variant) =
+                union
+                    .find_schema(inner)
+                    .ok_or_else(|| format_err!("value does not match any union variant: {:?}", inner))?;
+            let mut wrapper = Map::new();
+            wrapper.insert(type_name(variant), avro_json_encoding(inner, variant)?);
+            Ok(Value::Object(wrapper))
+        },
+        (value, schema) => Err(format_err!("{:?} does not match schema {:?}", value, SchemaKind::from(schema)))
+    }
+}
+
+fn float_to_json(f: f64) -> Result<Value, Error> {
+    serde_json::Number::from_f64(f)
+        .map(Value::Number)
+        .ok_or_else(|| format_err!("{} can not be represented in JSON", f))
+}
+
+// Name used for a union branch in JSON encoding: the full name for named types, the type name otherwise.
+fn type_name(schema: &Schema) -> String {
+    match schema {
+        Schema::Record {name, ..} | Schema::Enum {name, ..} | Schema::Fixed {name, ..} => name.fullname(None),
+        Schema::Null => "null".to_owned(),
+        Schema::Boolean => "boolean".to_owned(),
+        Schema::Int => "int".to_owned(),
+        Schema::Long => "long".to_owned(),
+        Schema::Float => "float".to_owned(),
+        Schema::Double => "double".to_owned(),
+        Schema::Bytes => "bytes".to_owned(),
+        Schema::String => "string".to_owned(),
+        Schema::Array(_) => "array".to_owned(),
+        Schema::Map(_) => "map".to_owned(),
+        Schema::Union(_) => "union".to_owned()
+    }
+}
 
 
 //fn clean_json(json_value: &mut JsonValue) {
@@ -425,6 +544,18 @@ mod test {
     use super::*;
     use std::time::Instant;
     use avro_rs::{Writer, Codec};
+    use crate::io::{input_file, GzipFile, DEFAULT_INPUT};
+
+    // $INPUT_FILE or the default corpus in the working directory, None when it is not there
+    fn test_input() -> Option<String> {
+        let input = input_file(None, DEFAULT_INPUT);
+        if std::path::Path::new(&input).exists() {
+            Some(input)
+        } else {
+            println!("skipped, {} not found (set INPUT_FILE)", input);
+            None
+        }
+    }
 
 //    #[test]
 //    fn test_clean_name() {
@@ -444,9 +575,15 @@ mod test {
 
     #[test]
     fn test_infer_schema_performance() {
+        let input = match test_input() {
+            Some(input) => input,
+            None => return
+        };
         let now = Instant::now();
+        let file = GzipFile::new(&input);
+        let counts = file.counts.clone();
         let mut schemas =
-            GzipFile::new("/usr/local/google/home/shafirasulov/IdeaProjects/learningrust/TweetsChampions.json.gz")
+            file
                 .lines
                 .take(5000)
 //                .map(|line| serde_json::from_str(line.unwrap().as_str()).unwrap())
@@ -465,7 +602,7 @@ mod test {
                 f
             });
 
-        println!("Elapsed: {}", now.elapsed().as_millis());
+        println!("Elapsed: {}, {}", now.elapsed().as_millis(), counts.throughput(now.elapsed()));
     }
 
     #[test]
@@ -478,16 +615,32 @@ mod test {
         println!("{:?}", &merged_schema.unwrap().canonical_form());
     }
 
-//    #[test]
-//    fn test_json_to_avro() {
-//        let txt = r#"{"a": 1, "b": 2, "c": [1, "alma", true]}"#;
-//        let json = json::parse(txt).unwrap();
-//        let avro = json_to_avro(json).unwrap();
-//        println!("{:?}", avro);
-//    }
+    #[test]
+    fn test_json_to_avro() {
+        let txt = r#"{"a": 1, "b": 2.5, "c": [1, "alma", true]}"#;
+        let json = json::parse(txt).unwrap();
+        let schema = infer_schema(&json, "test").unwrap();
+        let avro = json_to_avro(&json, &schema).unwrap();
+        match avro {
+            AvroValue::Record(fields) => {
+                assert_eq!(fields[0], ("a".to_owned(), AvroValue::Long(1)));
+                assert_eq!(fields[1], ("b".to_owned(), AvroValue::Double(2.5)));
+            },
+            other => panic!("expected a record, got {:?}", other)
+        }
+    }
+
+    #[test]
+    fn test_avro_json_encoding() {
+        let schema = Schema::parse_str(r#"{"name":"test","type":"record","fields":[{"name":"a","type":["null","long"]},{"name":"b","type":["null","string"]}]}"#).unwrap();
+        let json = json::parse(r#"{"a": 1}"#).unwrap();
+        let avro = json_to_avro(&json, &schema).unwrap();
+        let encoded = avro_json_encoding(&avro, &schema).unwrap();
+        assert_eq!(encoded.to_string(), r#"{"a":{"long":1},"b":null}"#);
+    }
 
     fn test_file(n_rows: usize) -> impl Iterator<Item=String> {
-        GzipFile::new("/usr/local/google/home/shafirasulov/IdeaProjects/learningrust/TweetsChampions.json.gz")
+        GzipFile::new(&input_file(None, DEFAULT_INPUT))
             .lines
             .map(|l| l.unwrap())
             .take(n_rows)
@@ -0,0 +1,121 @@
+use std::fmt;
+use std::fs;
+use std::path::PathBuf;
+use failure::{Error, format_err};
+use crate::bench::BenchResults;
+
+
+// Named baselines live next to the working directory, one bench --output style file each.
+pub const BASELINE_DIR: &str = "baselines";
+
+pub fn baseline_path(name: &str) -> PathBuf {
+    PathBuf::from(BASELINE_DIR).join(format!("{}.json", name))
+}
+
+pub fn save(name: &str, results: &BenchResults) -> Result<PathBuf, Error> {
+    let path = baseline_path(name);
+    fs::create_dir_all(BASELINE_DIR)?;
+    fs::write(&path, serde_json::to_string_pretty(results)?)?;
+    Ok(path)
+}
+
+pub fn load(name: &str) -> Result<BenchResults, Error> {
+    let path = baseline_path(name);
+    let text = fs::read_to_string(&path).map_err(|e| format_err!("baseline {}: {}", path.display(), e))?;
+    Ok(serde_json::from_str(&text)?)
+}
+
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Comparison {
+    pub name: String,
+    pub baseline_ms: f64,
+    pub current_ms: f64,
+    // relative change of the median time, 0.1 is 10% slower
+    pub change: f64,
+    pub regressed: bool
+}
+
+impl fmt::Display for Comparison {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:<32} {:>10.1} {:>10.1} {:>+8.1}%{}", self.name, self.baseline_ms, self.current_ms, self.change * 100.0,
+               if self.regressed { "  REGRESSION" } else { "" })
+    }
+}
+
+// Benchmarks present in both runs, in the order of the current run. A benchmark regresses when its
+// median time grew by more than the threshold.
+pub fn compare(baseline: &BenchResults, current: &BenchResults, threshold: f64) -> Vec<Comparison> {
+    current.results
+        .iter()
+        .filter_map(|result| {
+            let base = baseline.results.iter().find(|base| base.name == result.name)?;
+            let change = result.elapsed_ms / base.elapsed_ms.max(0.001) - 1.0;
+            Some(Comparison {
+                name: result.name.clone(),
+                baseline_ms: base.elapsed_ms,
+                current_ms: result.elapsed_ms,
+                change,
+                regressed: change > threshold
+            })
+        })
+        .collect()
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::collections::BTreeMap;
+    use std::time::Duration;
+    use crate::bench::BenchResult;
+    use crate::io::Decoder;
+    use crate::measure::Stats;
+
+    fn results(times: &[(&str, u64)]) -> BenchResults {
+        BenchResults {
+            input: "tweets.json.gz".to_owned(),
+            input_bytes: 0,
+            decoder: Decoder::Flate2,
+            warmup: 0,
+            runs: 1,
+            results: times.iter().map(|(name, ms)| BenchResult {
+                name: name.to_string(),
+                kind: name.split(':').next().unwrap().to_owned(),
+                parameters: Vec::new(),
+                records: 100,
+                errors: 0,
+                elapsed_ms: *ms as f64,
+                records_per_second: 0.0,
+                input_bytes_per_second: 0.0,
+                decompressed_bytes_per_second: 0.0,
+                joules: None,
+                allocations: Default::default(),
+                latency: None,
+                passes: 1,
+                checksum: 0,
+                samples_ms: Vec::new(),
+                perf: None,
+                positions: Vec::new(),
+                compression_ratio: None,
+                stats: Stats::of(&[Duration::from_millis(*ms)]).unwrap()
+            }).collect(),
+            failed: BTreeMap::new()
+        }
+    }
+
+    #[test]
+    fn test_compare() {
+        let baseline = results(&[("parse:serde", 100), ("parse:simd", 100)]);
+        let current = results(&[("parse:simd", 105), ("parse:serde", 120), ("read", 10)]);
+        let comparisons = compare(&baseline, &current, 0.1);
+        assert_eq!(comparisons.len(), 2);
+        assert_eq!(comparisons[0].name, "parse:simd");
+        assert!(!comparisons[0].regressed);
+        assert!(comparisons[1].regressed);
+        assert!((comparisons[1].change - 0.2).abs() < 1e-9);
+
+        let parsed: BenchResults = serde_json::from_str(&serde_json::to_string(&current).unwrap()).unwrap();
+        assert_eq!(parsed.results.len(), 3);
+    }
+}
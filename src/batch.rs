@@ -0,0 +1,316 @@
+use std::fs;
+use std::path::Path;
+use serde::{Deserialize, Serialize};
+use failure::Error;
+use crate::bench::{self, Input, Repetitions};
+use crate::cache::{self, CacheState, PageCache};
+use crate::io::{input_file, Decoder, DEFAULT_INPUT};
+use crate::manifest::Manifest;
+use crate::measure::Stats;
+use crate::seed::Seed;
+
+
+pub const EXIT_OK: i32 = 0;
+// config could not be read, input missing, report directory not writable
+pub const EXIT_ERROR: i32 = 1;
+// a benchmark failed on more records than max_error_rate allows
+pub const EXIT_ERROR_RATE: i32 = 2;
+// a benchmark was slower than its max_ms
+pub const EXIT_REGRESSION: i32 = 3;
+
+
+#[derive(Debug, Deserialize)]
+pub struct SuiteConfig {
+    // overridden by --input and $INPUT_FILE
+    #[serde(default)]
+    pub input: Option<String>,
+    pub report_dir: String,
+    #[serde(default)]
+    pub max_error_rate: f64,
+    #[serde(default)]
+    pub page_cache: PageCache,
+    // gzip decoder of the input
+    #[serde(default)]
+    pub decoder: Decoder,
+    // overridden by --seed
+    #[serde(default)]
+    pub seed: u64,
+    #[serde(default)]
+    pub warmup: usize,
+    // measured runs per benchmark and cache state, max_ms and elapsed_ms are their median
+    #[serde(default = "default_runs")]
+    pub runs: usize,
+    #[serde(rename = "benchmark", default)]
+    pub benchmarks: Vec<BenchmarkConfig>
+}
+
+#[derive(Debug, Deserialize)]
+pub struct BenchmarkConfig {
+    // a bench registry name, e.g. "parse:serde" or "compress:zstd:19"
+    pub name: String,
+    pub max_ms: Option<u64>,
+    // overrides the suite page_cache for this benchmark
+    pub page_cache: Option<PageCache>,
+    // override the suite decoder, warmup and runs
+    pub decoder: Option<Decoder>,
+    pub warmup: Option<usize>,
+    pub runs: Option<usize>
+}
+
+fn default_runs() -> usize {
+    1
+}
+
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Status {
+    Ok,
+    ErrorRate,
+    Regression,
+    Error
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BenchmarkReport {
+    pub name: String,
+    pub cache: CacheState,
+    #[serde(default)]
+    pub decoder: Decoder,
+    pub status: Status,
+    pub records: usize,
+    pub errors: usize,
+    pub elapsed_ms: u128,
+    pub joules: Option<f64>,
+    #[serde(default)]
+    pub peak_heap_bytes: usize,
+    #[serde(default)]
+    pub allocations: usize,
+    // time spent reading and decompressing records vs processing them
+    pub read_ms: u128,
+    pub process_ms: u128,
+    // bucket i counts records that took [2^i, 2^(i+1)) ns to process
+    pub latency_log2_ns: Vec<usize>,
+    // at the median time, MB of decompressed input
+    #[serde(default)]
+    pub records_per_second: f64,
+    #[serde(default)]
+    pub mb_per_second: f64,
+    #[serde(default)]
+    pub stats: Option<Stats>,
+    #[serde(default)]
+    pub error: Option<String>
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BatchReport {
+    pub input: String,
+    pub seed: u64,
+    pub benchmarks: Vec<BenchmarkReport>,
+    pub exit_code: i32
+}
+
+impl BatchReport {
+    // read back by the TUI baseline and the Parquet export, builds without either don't need them
+    #[cfg(any(feature = "tui", feature = "results-parquet"))]
+    pub fn load(report_path: &str) -> Result<Self, Error> {
+        Ok(serde_json::from_str(&fs::read_to_string(report_path)?)?)
+    }
+
+    #[cfg(feature = "tui")]
+    pub fn find(&self, name: &str, cache: CacheState) -> Option<&BenchmarkReport> {
+        self.benchmarks.iter().find(|b| b.name == name && b.cache == cache)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum Event {
+    // benchmark runs, one per page cache state
+    Planned { runs: usize, seed: u64 },
+    Started { name: String, cache: CacheState },
+    Finished(BenchmarkReport),
+    Report(String)
+}
+
+
+// command line values that take precedence over the suite config
+#[derive(Debug, Clone, Default)]
+pub struct Overrides {
+    pub seed: Option<u64>,
+    pub input: Option<String>
+}
+
+
+pub fn parse_config(config: &str) -> Result<SuiteConfig, Error> {
+    Ok(toml::from_str(config)?)
+}
+
+
+// Runs the whole suite with plain line-per-event logging and writes manifest.json and report.json
+// (and results.parquet with the results-parquet feature) into report_dir.
+// Returns the process exit code; the most severe failure wins.
+pub fn run(config_path: &str, overrides: &Overrides) -> i32 {
+    exit_code(run_suite(config_path, overrides, &mut log_event))
+}
+
+pub fn exit_code(result: Result<i32, Error>) -> i32 {
+    match result {
+        Ok(code) => code,
+        Err(e) => {
+            eprintln!("batch failed: {}", e);
+            EXIT_ERROR
+        }
+    }
+}
+
+pub fn log_event(event: Event) {
+    match event {
+        Event::Planned { runs, seed } => println!("planned {} runs seed={}", runs, seed),
+        Event::Started { name, cache } => println!("start {} cache={:?}", name, cache),
+        Event::Finished(report) => {
+            if let Some(error) = &report.error {
+                println!("error {}: {}", report.name, error);
+            }
+            println!("done {} cache={:?} status={:?} records={} errors={} elapsed_ms={} records_per_second={:.0} mb_per_second={:.1}",
+                     report.name, report.cache, report.status, report.records, report.errors, report.elapsed_ms,
+                     report.records_per_second, report.mb_per_second);
+        },
+        Event::Report(path) => println!("report {}", path)
+    }
+}
+
+pub fn run_suite(config_path: &str, overrides: &Overrides, on_event: &mut dyn FnMut(Event)) -> Result<i32, Error> {
+    let config_text = fs::read_to_string(config_path)?;
+    let config = parse_config(&config_text)?;
+    let seed = Seed(overrides.seed.unwrap_or(config.seed));
+    let input = input_file(overrides.input.as_deref(), config.input.as_deref().unwrap_or(DEFAULT_INPUT));
+    fs::create_dir_all(&config.report_dir)?;
+    // hashed before the run, so the manifest exists even when the suite fails half way
+    Manifest::new(Some(config_text), &[input.as_str()], seed.0)?.write(Path::new(&config.report_dir))?;
+
+    let runs: Vec<(&BenchmarkConfig, CacheState)> =
+        config.benchmarks
+            .iter()
+            .flat_map(|benchmark| {
+                benchmark.page_cache.unwrap_or(config.page_cache).states().into_iter().map(move |state| (benchmark, state))
+            })
+            .collect();
+    on_event(Event::Planned { runs: runs.len(), seed: seed.0 });
+
+    let mut exit_code = EXIT_OK;
+    let mut reports = Vec::new();
+    for (benchmark, state) in runs {
+        on_event(Event::Started { name: benchmark.name.clone(), cache: state });
+        let mut report = BenchmarkReport {
+            name: benchmark.name.clone(),
+            cache: state,
+            decoder: benchmark.decoder.unwrap_or(config.decoder),
+            status: Status::Error,
+            records: 0,
+            errors: 0,
+            elapsed_ms: 0,
+            joules: None,
+            peak_heap_bytes: 0,
+            allocations: 0,
+            read_ms: 0,
+            process_ms: 0,
+            latency_log2_ns: Vec::new(),
+            records_per_second: 0.0,
+            mb_per_second: 0.0,
+            stats: None,
+            error: None
+        };
+        let repetitions = Repetitions {
+            warmup: benchmark.warmup.unwrap_or(config.warmup),
+            runs: benchmark.runs.unwrap_or(config.runs),
+            latency: false,
+            duration: None
+        };
+        // labelled with the state the runs got, a cold run is warm where the cache can't be dropped
+        let mut prepared = state;
+        let run = bench::run_named(&benchmark.name, &Input::new(&input, report.decoder), repetitions, &mut || {
+            prepared = cache::prepare(&input, state)?;
+            Ok(())
+        });
+        if prepared != state {
+            eprintln!("{}: the page cache can not be dropped here, ran with a warm cache", benchmark.name);
+            report.cache = prepared;
+        }
+        match run {
+            Ok(run) => {
+                let (measurement, timings) = (run.last(), &run.timings);
+                let error_rate = timings.errors as f64 / measurement.records.max(1) as f64;
+                report.elapsed_ms = run.stats.median_ms.round() as u128;
+                report.status =
+                    if error_rate > config.max_error_rate {
+                        exit_code = exit_code.max(EXIT_ERROR_RATE);
+                        Status::ErrorRate
+                    } else if benchmark.max_ms.map_or(false, |max_ms| report.elapsed_ms > max_ms as u128) {
+                        exit_code = exit_code.max(EXIT_REGRESSION);
+                        Status::Regression
+                    } else {
+                        Status::Ok
+                    };
+                report.records = measurement.records;
+                report.errors = timings.errors;
+                report.joules = measurement.joules;
+                report.peak_heap_bytes = measurement.allocations.peak_bytes;
+                report.allocations = measurement.allocations.allocations;
+                report.read_ms = timings.read.as_millis();
+                report.process_ms = timings.process.as_millis();
+                report.latency_log2_ns = timings.histogram.clone();
+                let throughput = run.throughput(0);
+                report.records_per_second = throughput.records_per_second;
+                report.mb_per_second = throughput.decompressed_bytes_per_second / 1_000_000.0;
+                report.stats = Some(run.stats.clone());
+            },
+            Err(e) => {
+                report.error = Some(e.to_string());
+                exit_code = exit_code.max(EXIT_ERROR);
+            }
+        }
+        on_event(Event::Finished(report.clone()));
+        reports.push(report);
+    }
+
+    let report = BatchReport { input: input.clone(), seed: seed.0, benchmarks: reports, exit_code };
+    let report_path = Path::new(&config.report_dir).join("report.json");
+    fs::write(&report_path, serde_json::to_string_pretty(&report)?)?;
+    #[cfg(feature = "results-parquet")]
+    {
+        let report_name = report_path.display().to_string();
+        crate::results::write_parquet(&[(report_name, report)], &Path::new(&config.report_dir).join("results.parquet").to_string_lossy())?;
+    }
+    on_event(Event::Report(report_path.display().to_string()));
+    Ok(exit_code)
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_load_config() {
+        let config = parse_config(r#"
+            input = "TweetsChampions.json.gz"
+            report_dir = "/reports"
+            max_error_rate = 0.01
+            page_cache = "both"
+
+            [[benchmark]]
+            name = "parse:serde"
+            max_ms = 5000
+            page_cache = "warm"
+
+            [[benchmark]]
+            name = "compress:zstd:3"
+        "#).unwrap();
+        assert_eq!(config.benchmarks.len(), 2);
+        assert_eq!(config.benchmarks[0].max_ms, Some(5000));
+        assert_eq!(config.benchmarks[1].max_ms, None);
+        assert_eq!(config.page_cache.states(), vec![CacheState::Cold, CacheState::Warm]);
+        assert_eq!(config.benchmarks[0].page_cache, Some(PageCache::Warm));
+        assert_eq!((config.warmup, config.runs), (0, 1));
+    }
+}
@@ -0,0 +1,2207 @@
+use std::borrow::Cow;
+use std::cell::Cell;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::rc::Rc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use avro_rs::{Schema, Codec, Writer};
+use hdrhistogram::Histogram;
+use jiter::{Jiter, JiterResult, Peek};
+use json::JsonValue;
+use rand::Rng;
+use rand::seq::SliceRandom;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use serde_json::value::RawValue;
+use failure::{Error, bail, format_err};
+use crate::canonical::{canonicalize, canonicalize_serde, canonicalize_simd};
+use crate::compress::{compressors, find_compressor, Compressor};
+use crate::convert::{infer_file_schema, AvroJsonWriter, AvroWriter, RecordWriter};
+use crate::io::{block_to_lines, gunzip, lines_to_blocks, open_input, open_lines_with, subset, Decoder};
+use crate::limit::parse_size;
+use crate::allocation::Allocations;
+use crate::avro::json_to_avro;
+use crate::measure::{measure, Measurement, Stats, Throughput};
+use crate::models;
+use crate::parser::{parsers, find_parser, Parser};
+use crate::perf::PerfCounters;
+use crate::progress::Progress;
+use crate::scan::{FieldScanner, PathScanner};
+use crate::sink::{json_size, serde_size, simd_owned_size, simd_size, Sink};
+use crate::tweet::{self, Tweet};
+use crate::window::{WindowAggregator, WindowSpec};
+
+
+#[derive(Debug, Default)]
+pub struct RecordTimings {
+    pub errors: usize,
+    // uncompressed input read, newlines included
+    pub bytes: u64,
+    pub read: Duration,
+    pub process: Duration,
+    pub histogram: Vec<usize>,
+    // nanoseconds per record at 3 significant digits, only in latency mode
+    pub latency: Option<Histogram<u64>>,
+    // what processing returned for every record
+    pub sink: Sink,
+    // written by the compress benchmarks, for their compression ratio
+    pub compressed_bytes: u64
+}
+
+impl RecordTimings {
+    pub fn with_latency() -> Self {
+        RecordTimings { latency: Some(Histogram::new(3).unwrap()), ..RecordTimings::default() }
+    }
+
+    fn record(&mut self, elapsed: Duration) {
+        let nanos = elapsed.as_nanos().max(1) as u64;
+        let bucket = 63 - nanos.leading_zeros() as usize;
+        if self.histogram.len() <= bucket {
+            self.histogram.resize(bucket + 1, 0);
+        }
+        self.histogram[bucket] += 1;
+        if let Some(latency) = &mut self.latency {
+            // grows to the slowest record, saturating_record would clamp to the initial range
+            let _ = latency.record(nanos);
+        }
+        self.process += elapsed;
+    }
+
+    pub fn percentiles(&self) -> Option<Percentiles> {
+        let latency = self.latency.as_ref().filter(|latency| !latency.is_empty())?;
+        Some(Percentiles {
+            p50_ns: latency.value_at_quantile(0.5),
+            p90_ns: latency.value_at_quantile(0.9),
+            p99_ns: latency.value_at_quantile(0.99),
+            p999_ns: latency.value_at_quantile(0.999),
+            max_ns: latency.max()
+        })
+    }
+
+    // uncompressed over compressed bytes, None unless something was compressed
+    pub fn compression_ratio(&self) -> Option<f64> {
+        if self.compressed_bytes == 0 {
+            return None;
+        }
+        Some(self.bytes as f64 / self.compressed_bytes as f64)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Percentiles {
+    pub p50_ns: u64,
+    pub p90_ns: u64,
+    pub p99_ns: u64,
+    pub p999_ns: u64,
+    pub max_ns: u64
+}
+
+impl fmt::Display for Percentiles {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let us = |ns: u64| ns as f64 / 1000.0;
+        write!(f, "p50: {:.1} us, p90: {:.1} us, p99: {:.1} us, p99.9: {:.1} us, max: {:.1} us",
+               us(self.p50_ns), us(self.p90_ns), us(self.p99_ns), us(self.p999_ns), us(self.max_ns))
+    }
+}
+
+// An NDJSON file and the decoder its gzip compression is read with, so the cost of the input side
+// can be compared under every benchmark.
+#[derive(Debug, Clone)]
+pub struct Input {
+    pub path: String,
+    pub decoder: Decoder
+}
+
+impl Input {
+    pub fn new(path: &str, decoder: Decoder) -> Self {
+        Input { path: path.to_owned(), decoder }
+    }
+}
+
+
+// Feeds every line of the input to process, timing the read and the processing of each record
+// separately. Failed records are counted, not fatal. Process returns a value computed from its
+// result, which goes to the sink. With --progress a bar follows the lines, outside the timings.
+pub fn timed_records<F>(input: &Input, timings: &mut RecordTimings, mut process: F) -> Result<usize, Error>
+    where F: FnMut(String) -> Result<u64, Error> {
+    let mut lines = open_input(&input.path, input.decoder)?;
+    let progress = Progress::new(&input.path, "bench");
+    let mut records = 0;
+    loop {
+        let now = Instant::now();
+        let line = match lines.next() {
+            Some(line) => line,
+            None => break
+        };
+        timings.read += now.elapsed();
+        records += 1;
+        match line {
+            Ok(line) => {
+                timings.bytes += line.len() as u64 + 1;
+                progress.record(line.len());
+                let now = Instant::now();
+                let result = process(line);
+                timings.record(now.elapsed());
+                match result {
+                    Ok(value) => timings.sink.consume(value),
+                    Err(_) => timings.errors += 1
+                }
+            },
+            Err(_) => timings.errors += 1
+        }
+    }
+    Ok(records)
+}
+
+
+// A benchmark over an NDJSON input. Only run is timed; setup and teardown hold the work that
+// should not be, like inferring a schema or removing output files.
+pub trait Benchmark {
+    fn name(&self) -> String;
+
+    fn setup(&mut self, _input: &Input) -> Result<(), Error> {
+        Ok(())
+    }
+
+    // returns the number of records processed
+    fn run(&mut self, input: &Input, timings: &mut RecordTimings) -> Result<usize, Error>;
+
+    fn teardown(&mut self) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+
+// decompression and line splitting only, the IO floor of the other benchmarks
+pub struct ReadBenchmark;
+
+// kinds whose benchmarks read the input line by line as read does, so that its time is part of
+// theirs, see report::net_of_read
+pub const LINE_KINDS: [&str; 14] = ["parse", "typed", "zerocopy", "simd", "tokens", "numbers", "reserialize", "canonicalize", "extract", "fields", "filter", "window", "convert", "roundtrip"];
+
+impl Benchmark for ReadBenchmark {
+    fn name(&self) -> String {
+        "read".to_owned()
+    }
+
+    fn run(&mut self, input: &Input, timings: &mut RecordTimings) -> Result<usize, Error> {
+        timed_records(input, timings, |line| Ok(line.len() as u64))
+    }
+}
+
+
+// Decompression of the whole input into memory with one decoder, whatever the input decoder is. The
+// difference between decoders is what every other benchmark carries along in its read time.
+pub struct DecodeBenchmark {
+    decoder: Decoder
+}
+
+impl Benchmark for DecodeBenchmark {
+    fn name(&self) -> String {
+        format!("decode:{}", self.decoder.name())
+    }
+
+    fn run(&mut self, input: &Input, timings: &mut RecordTimings) -> Result<usize, Error> {
+        let now = Instant::now();
+        let data = std::fs::read(&input.path)?;
+        timings.read += now.elapsed();
+        let now = Instant::now();
+        let decoded = gunzip(&data, self.decoder)?;
+        timings.process += now.elapsed();
+        timings.bytes = decoded.len() as u64;
+        timings.sink.consume(decoded.iter().map(|byte| *byte as u64).sum());
+        Ok(decoded.iter().filter(|byte| **byte == b'\n').count())
+    }
+}
+
+
+// The two ways of getting lines out of a gzip file with flate2, at a size: lazy pulls them through
+// BufRead::lines over the GzDecoder as the parse benchmarks do, allocating a String per line with
+// memory bounded by the buffer; eager decodes the whole file into memory first and splits it in
+// place, which costs the decoded size in memory. Sized inputs are the first lines of the input up
+// to that many decoded bytes, written to a temporary file in setup; the whole input without one.
+// Empty lines are skipped by both.
+pub struct LinesBenchmark {
+    eager: bool,
+    size: Option<u64>,
+    sized: Option<tempfile::NamedTempFile>
+}
+
+impl LinesBenchmark {
+    pub fn new(eager: bool, size: Option<u64>) -> Self {
+        LinesBenchmark { eager, size, sized: None }
+    }
+}
+
+impl Benchmark for LinesBenchmark {
+    fn name(&self) -> String {
+        let strategy = if self.eager { "eager" } else { "lazy" };
+        match self.size {
+            Some(size) if size % (1 << 20) == 0 => format!("lines:{}:{}M", strategy, size >> 20),
+            Some(size) => format!("lines:{}:{}", strategy, size),
+            None => format!("lines:{}", strategy)
+        }
+    }
+
+    fn setup(&mut self, input: &Input) -> Result<(), Error> {
+        if let Some(size) = self.size {
+            let file = tempfile::Builder::new().suffix(".json.gz").tempfile()?;
+            let mut encoder = flate2::write::GzEncoder::new(io::BufWriter::new(file.reopen()?), flate2::Compression::default());
+            let mut written = 0;
+            for line in open_lines_with(&input.path, input.decoder)? {
+                if written >= size {
+                    break;
+                }
+                let line = line?;
+                writeln!(encoder, "{}", line)?;
+                written += line.len() as u64 + 1;
+            }
+            encoder.finish()?.flush()?;
+            self.sized = Some(file);
+        }
+        Ok(())
+    }
+
+    fn run(&mut self, input: &Input, timings: &mut RecordTimings) -> Result<usize, Error> {
+        let path = match &self.sized {
+            Some(file) => file.path().to_string_lossy().into_owned(),
+            None => input.path.clone()
+        };
+        let gzipped = path.ends_with(".gz");
+        let mut records = 0;
+        if self.eager {
+            let now = Instant::now();
+            let data = fs::read(&path)?;
+            let data = if gzipped { gunzip(&data, Decoder::Flate2)? } else { data };
+            timings.read += now.elapsed();
+            timings.bytes = data.len() as u64;
+            let now = Instant::now();
+            for line in data.split(|byte| *byte == b'\n').filter(|line| !line.is_empty()) {
+                match std::str::from_utf8(line) {
+                    Ok(line) => timings.sink.consume(line.len() as u64),
+                    Err(_) => timings.errors += 1
+                }
+                records += 1;
+            }
+            timings.process += now.elapsed();
+        } else {
+            let now = Instant::now();
+            let file = fs::File::open(&path)?;
+            let reader: Box<dyn io::BufRead> = if gzipped {
+                Box::new(io::BufReader::new(flate2::read::GzDecoder::new(file)))
+            } else {
+                Box::new(io::BufReader::new(file))
+            };
+            for line in io::BufRead::lines(reader) {
+                match line {
+                    Ok(line) if line.is_empty() => continue,
+                    Ok(line) => {
+                        timings.bytes += line.len() as u64 + 1;
+                        timings.sink.consume(line.len() as u64);
+                    },
+                    Err(_) => timings.errors += 1
+                }
+                records += 1;
+            }
+            timings.read += now.elapsed();
+        }
+        Ok(records)
+    }
+
+    fn teardown(&mut self) -> Result<(), Error> {
+        if let Some(file) = self.sized.take() {
+            file.close()?;
+        }
+        Ok(())
+    }
+}
+
+
+pub struct ParseBenchmark {
+    parser: Box<dyn Parser>
+}
+
+impl Benchmark for ParseBenchmark {
+    fn name(&self) -> String {
+        format!("parse:{}", self.parser.name())
+    }
+
+    fn run(&mut self, input: &Input, timings: &mut RecordTimings) -> Result<usize, Error> {
+        let parser = &self.parser;
+        timed_records(input, timings, |line| parser.parse_native(&mut line.into_bytes()))
+    }
+}
+
+
+// yyjson, the fastest C parser, through its FFI shim on the same lines as the Rust parsers, with
+// the yyjson feature. The line is read, not parsed in place.
+#[cfg(feature = "yyjson")]
+pub struct YyjsonBenchmark;
+
+#[cfg(feature = "yyjson")]
+impl Benchmark for YyjsonBenchmark {
+    fn name(&self) -> String {
+        "parse:yyjson".to_owned()
+    }
+
+    fn run(&mut self, input: &Input, timings: &mut RecordTimings) -> Result<usize, Error> {
+        timed_records(input, timings, |line| crate::yyjson::parse_size(line.as_bytes()))
+    }
+}
+
+
+// RapidJSON, the best known C++ parser, through its FFI shim with the rapidjson feature: dom builds
+// a Document, sax only counts the events of its Reader.
+#[cfg(feature = "rapidjson")]
+pub struct RapidjsonBenchmark {
+    sax: bool
+}
+
+#[cfg(feature = "rapidjson")]
+impl Benchmark for RapidjsonBenchmark {
+    fn name(&self) -> String {
+        format!("parse:rapidjson:{}", if self.sax { "sax" } else { "dom" })
+    }
+
+    fn run(&mut self, input: &Input, timings: &mut RecordTimings) -> Result<usize, Error> {
+        let sax = self.sax;
+        timed_records(input, timings, |line| crate::rapidjson::parse_size(line.as_bytes(), sax))
+    }
+}
+
+// The parse benchmark with the input decompressed into one buffer up front instead of read line by
+// line: documents parses every line in place in the buffer, array joins the lines into one JSON
+// array parsed as a single document, as a file holding one array would be, which fails as a whole
+// on a bad record. Read time is the decompression, and the joining for array. Empty lines are
+// skipped.
+pub struct WholeFileBenchmark {
+    parser: Box<dyn Parser>,
+    array: bool
+}
+
+impl Benchmark for WholeFileBenchmark {
+    fn name(&self) -> String {
+        format!("wholefile:{}:{}", self.parser.name(), if self.array { "array" } else { "documents" })
+    }
+
+    fn run(&mut self, input: &Input, timings: &mut RecordTimings) -> Result<usize, Error> {
+        let now = Instant::now();
+        let data = fs::read(&input.path)?;
+        let mut data = if input.path.ends_with(".gz") { gunzip(&data, input.decoder)? } else { data };
+        let mut records = 0;
+        if self.array {
+            let mut array = Vec::with_capacity(data.len() + 2);
+            array.push(b'[');
+            for line in subset().apply(data.split(|byte| *byte == b'\n').filter(|line| !line.is_empty())) {
+                if records > 0 {
+                    array.push(b',');
+                }
+                array.extend_from_slice(line);
+                timings.bytes += line.len() as u64 + 1;
+                records += 1;
+            }
+            array.push(b']');
+            timings.read += now.elapsed();
+            let now = Instant::now();
+            let value = self.parser.parse_native(&mut array)?;
+            timings.record(now.elapsed());
+            timings.sink.consume(value);
+            return Ok(records);
+        }
+        timings.read += now.elapsed();
+        for line in subset().apply(data.split_mut(|byte| *byte == b'\n').filter(|line| !line.is_empty())) {
+            timings.bytes += line.len() as u64 + 1;
+            records += 1;
+            let now = Instant::now();
+            let result = self.parser.parse_native(line);
+            timings.record(now.elapsed());
+            match result {
+                Ok(value) => timings.sink.consume(value),
+                Err(_) => timings.errors += 1
+            }
+        }
+        Ok(records)
+    }
+}
+
+
+// Deserialization straight into the structs of src/models.rs instead of a DOM, the usual serde
+// use, comparable with parse:serde and parse:simd.
+pub struct TypedBenchmark {
+    backend: &'static str
+}
+
+pub const TYPED_BACKENDS: [&str; 2] = ["serde", "simd"];
+
+fn typed_sink(tweet: &models::Tweet) -> u64 {
+    tweet.id.wrapping_add(tweet.hashtag_count() as u64)
+}
+
+impl Benchmark for TypedBenchmark {
+    fn name(&self) -> String {
+        format!("typed:{}", self.backend)
+    }
+
+    fn run(&mut self, input: &Input, timings: &mut RecordTimings) -> Result<usize, Error> {
+        match self.backend {
+            "serde" => timed_records(input, timings, |line| Ok(typed_sink(&serde_json::from_str(&line)?))),
+            _ => timed_records(input, timings, |line| Ok(typed_sink(&simd_json::serde::from_slice(&mut line.into_bytes())?)))
+        }
+    }
+}
+
+
+// typed:serde with entities and the retweeted and quoted tweets kept as RawValue, models::LazyTweet
+// against models::Tweet: what deferring the subtrees an application does not read saves. They are
+// still scanned for their end, the sink has their length instead of the hashtag count.
+pub struct LazyTypedBenchmark;
+
+impl Benchmark for LazyTypedBenchmark {
+    fn name(&self) -> String {
+        "typed:serde:raw".to_owned()
+    }
+
+    fn run(&mut self, input: &Input, timings: &mut RecordTimings) -> Result<usize, Error> {
+        timed_records(input, timings, |line| {
+            let tweet: models::LazyTweet = serde_json::from_str(&line)?;
+            let nested = tweet.retweeted_status.iter().chain(&tweet.quoted_status).map(|raw| raw.get().len() as u64).sum::<u64>();
+            Ok(tweet.id.wrapping_add(tweet.entities.get().len() as u64).wrapping_add(nested))
+        })
+    }
+}
+
+// What zero-copy buys: each backend parsing into owned values and into ones borrowing strings from
+// the line, models::Tweet against models::BorrowedTweet for serde and OwnedValue against
+// BorrowedValue for simd. Both modes of a backend sink the same, so their checksums match.
+pub struct ZeroCopyBenchmark {
+    backend: &'static str,
+    borrowed: bool
+}
+
+pub const ZERO_COPY_BACKENDS: [&str; 2] = ["serde", "simd"];
+
+impl Benchmark for ZeroCopyBenchmark {
+    fn name(&self) -> String {
+        format!("zerocopy:{}:{}", self.backend, if self.borrowed { "borrowed" } else { "owned" })
+    }
+
+    fn run(&mut self, input: &Input, timings: &mut RecordTimings) -> Result<usize, Error> {
+        match (self.backend, self.borrowed) {
+            ("serde", false) => timed_records(input, timings, |line| Ok(typed_sink(&serde_json::from_str(&line)?))),
+            ("serde", true) => timed_records(input, timings, |line| {
+                let tweet: models::BorrowedTweet = serde_json::from_str(&line)?;
+                Ok(tweet.id.wrapping_add(tweet.hashtag_count() as u64))
+            }),
+            (_, false) => timed_records(input, timings, |line| Ok(simd_owned_size(&simd_json::to_owned_value(&mut line.into_bytes())?))),
+            (_, true) => timed_records(input, timings, |line| Ok(simd_size(&simd_json::to_borrowed_value(&mut line.into_bytes())?)))
+        }
+    }
+}
+
+
+// Counts what is read through it into a counter that outlives the reader, which the
+// deserializer owns.
+struct CountingReader<R> {
+    inner: R,
+    count: Rc<Cell<u64>>
+}
+
+impl<R: io::Read> io::Read for CountingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        self.count.set(self.count.get() + read as u64);
+        Ok(read)
+    }
+}
+
+// jiter's iterator API walking every token of the line without building a DOM: strings are
+// decoded and numbers parsed, but nothing is kept. Against the parse benchmarks, what is left is
+// the cost of DOM construction. It sinks the same, so the checksums match.
+pub struct TokensBenchmark;
+
+fn jiter_size(jiter: &mut Jiter, peek: Peek) -> JiterResult<u64> {
+    let mut size = 0;
+    match peek {
+        Peek::Object => {
+            let mut member = jiter.known_object()?.is_some();
+            while member {
+                let peek = jiter.peek()?;
+                size += 1 + jiter_size(jiter, peek)?;
+                member = jiter.next_key()?.is_some();
+            }
+        },
+        Peek::Array => {
+            let mut item = jiter.known_array()?;
+            while let Some(peek) = item {
+                size += 1 + jiter_size(jiter, peek)?;
+                item = jiter.array_step()?;
+            }
+        },
+        Peek::Null => jiter.known_null()?,
+        Peek::True | Peek::False => {
+            jiter.known_bool(peek)?;
+        },
+        Peek::String => {
+            jiter.known_str()?;
+        },
+        _ => {
+            jiter.known_number(peek)?;
+        }
+    }
+    Ok(size)
+}
+
+impl Benchmark for TokensBenchmark {
+    fn name(&self) -> String {
+        "tokens:jiter".to_owned()
+    }
+
+    fn run(&mut self, input: &Input, timings: &mut RecordTimings) -> Result<usize, Error> {
+        timed_records(input, timings, |line| {
+            let mut jiter = Jiter::new(line.as_bytes());
+            let walk = |jiter: &mut Jiter| -> JiterResult<u64> {
+                let peek = jiter.peek()?;
+                let size = jiter_size(jiter, peek)?;
+                jiter.finish()?;
+                Ok(size)
+            };
+            walk(&mut jiter).map_err(|e| format_err!("{}", e))
+        })
+    }
+}
+
+
+// What a number strategy made of the numbers of a record: the sum of its integers, the same for
+// every strategy keeping them exact, and the count of integral doubles of 2^53 or more, whose
+// integer may have been rounded away, as a tweet id is by a JavaScript parser, and which infer
+// would type as a double instead of a long.
+#[derive(Default)]
+struct Numbers {
+    integers: u64,
+    lossy: u64
+}
+
+impl Numbers {
+    fn integer(&mut self, integer: u64) {
+        self.integers = self.integers.wrapping_add(integer);
+    }
+
+    fn double(&mut self, double: f64) {
+        if double.fract() != 0.0 {
+            return;
+        }
+        if double.abs() >= 9_007_199_254_740_992.0 {
+            self.lossy += 1;
+        } else {
+            self.integer(double as i64 as u64);
+        }
+    }
+
+    fn checked(self) -> Result<u64, Error> {
+        if self.lossy > 0 {
+            bail!("{} integers of 2^53 or more read as doubles", self.lossy);
+        }
+        Ok(self.integers)
+    }
+}
+
+fn serde_numbers(value: &Value, numbers: &mut Numbers) {
+    match value {
+        Value::Number(number) => match (number.as_u64(), number.as_i64()) {
+            (Some(integer), _) => numbers.integer(integer),
+            (None, Some(integer)) => numbers.integer(integer as u64),
+            (None, None) => numbers.double(number.as_f64().unwrap_or(f64::NAN))
+        },
+        Value::Object(map) => map.values().for_each(|value| serde_numbers(value, numbers)),
+        Value::Array(items) => items.iter().for_each(|item| serde_numbers(item, numbers)),
+        _ => {}
+    }
+}
+
+fn simd_numbers(value: &simd_json::value::borrowed::Value, numbers: &mut Numbers) {
+    use simd_json::StaticNode;
+    use simd_json::value::borrowed::Value as BorrowedValue;
+    match value {
+        BorrowedValue::Static(StaticNode::I64(integer)) => numbers.integer(*integer as u64),
+        BorrowedValue::Static(StaticNode::U64(integer)) => numbers.integer(*integer),
+        BorrowedValue::Static(StaticNode::F64(double)) => numbers.double(*double),
+        BorrowedValue::Object(map) => map.values().for_each(|value| simd_numbers(value, numbers)),
+        BorrowedValue::Array(items) => items.iter().for_each(|item| simd_numbers(item, numbers)),
+        _ => {}
+    }
+}
+
+// every number read as a double, as JavaScript does
+fn jiter_f64_numbers(jiter: &mut Jiter, peek: Peek, numbers: &mut Numbers) -> JiterResult<()> {
+    match peek {
+        Peek::Object => {
+            let mut member = jiter.known_object()?.is_some();
+            while member {
+                let peek = jiter.peek()?;
+                jiter_f64_numbers(jiter, peek, numbers)?;
+                member = jiter.next_key()?.is_some();
+            }
+        },
+        Peek::Array => {
+            let mut item = jiter.known_array()?;
+            while let Some(peek) = item {
+                jiter_f64_numbers(jiter, peek, numbers)?;
+                item = jiter.array_step()?;
+            }
+        },
+        Peek::Null => jiter.known_null()?,
+        Peek::True | Peek::False => {
+            jiter.known_bool(peek)?;
+        },
+        Peek::String => {
+            jiter.known_str()?;
+        },
+        _ => numbers.double(jiter.known_float(peek)?)
+    }
+    Ok(())
+}
+
+// How numbers are read, since tweet ids are beyond the 2^53 a double holds exactly: serde_json's
+// u64, i64 or f64, which with the arbitrary-precision feature keeps the text of the number instead
+// and is numbers:serde:arbitrary, simd-json's i64, u64 or f64, and f64 for every number. Records
+// with an integer lost to a double count as errors, so a lossy strategy shows in the error count
+// as well as in the time.
+pub struct NumbersBenchmark {
+    strategy: &'static str
+}
+
+pub const NUMBER_STRATEGIES: [&str; 3] = ["serde", "simd", "f64"];
+
+impl Benchmark for NumbersBenchmark {
+    fn name(&self) -> String {
+        match self.strategy {
+            "serde" if cfg!(feature = "arbitrary-precision") => "numbers:serde:arbitrary".to_owned(),
+            strategy => format!("numbers:{}", strategy)
+        }
+    }
+
+    fn run(&mut self, input: &Input, timings: &mut RecordTimings) -> Result<usize, Error> {
+        match self.strategy {
+            "serde" => timed_records(input, timings, |line| {
+                let mut numbers = Numbers::default();
+                serde_numbers(&serde_json::from_str(&line)?, &mut numbers);
+                numbers.checked()
+            }),
+            "simd" => timed_records(input, timings, |mut line| {
+                let mut numbers = Numbers::default();
+                simd_numbers(&unsafe { simd_json::to_borrowed_value(line.as_bytes_mut())? }, &mut numbers);
+                numbers.checked()
+            }),
+            _ => timed_records(input, timings, |line| {
+                let mut numbers = Numbers::default();
+                let mut jiter = Jiter::new(line.as_bytes());
+                let mut walk = |jiter: &mut Jiter| -> JiterResult<()> {
+                    let peek = jiter.peek()?;
+                    jiter_f64_numbers(jiter, peek, &mut numbers)?;
+                    jiter.finish()
+                };
+                walk(&mut jiter).map_err(|e| format_err!("{}", e))?;
+                numbers.checked()
+            })
+        }
+    }
+}
+
+
+// serde_json's streaming API over the decompressed input, which finds the document boundaries
+// itself instead of lines being split and allocated first, against parse:serde's from_str per
+// line. It sinks the same, so the checksums match. A syntax error ends the stream, the rest of the
+// input is not read.
+pub struct StreamBenchmark;
+
+impl Benchmark for StreamBenchmark {
+    fn name(&self) -> String {
+        "stream:serde".to_owned()
+    }
+
+    fn run(&mut self, input: &Input, timings: &mut RecordTimings) -> Result<usize, Error> {
+        let file = fs::File::open(&input.path)?;
+        let reader: Box<dyn io::Read> = if input.path.ends_with(".gz") { Box::new(flate2::read::GzDecoder::new(file)) } else { Box::new(file) };
+        let count = Rc::new(Cell::new(0));
+        let reader = io::BufReader::new(CountingReader { inner: reader, count: count.clone() });
+        let mut documents = subset().apply(serde_json::Deserializer::from_reader(reader).into_iter::<Value>());
+        let mut records = 0;
+        loop {
+            let now = Instant::now();
+            let document = match documents.next() {
+                Some(document) => document,
+                None => break
+            };
+            timings.record(now.elapsed());
+            records += 1;
+            match document {
+                Ok(value) => timings.sink.consume(serde_size(&value)),
+                Err(_) => {
+                    timings.errors += 1;
+                    break;
+                }
+            }
+        }
+        timings.bytes = count.get();
+        Ok(records)
+    }
+}
+
+
+// The parse benchmark over a pipeline: this thread reads and decompresses the input and hands
+// batches of lines to worker threads that parse them, the shape of a parallel ingestion job.
+// Per record latencies are not kept and the sink gets the sum of what the workers returned, which
+// unlike the order dependent checksum does not depend on which worker got which batch.
+pub struct ParallelParseBenchmark {
+    parser: &'static str,
+    threads: usize
+}
+
+const PARALLEL_BATCH: usize = 1000;
+
+impl Benchmark for ParallelParseBenchmark {
+    fn name(&self) -> String {
+        format!("parallel:{}:{}", self.parser, self.threads)
+    }
+
+    fn run(&mut self, input: &Input, timings: &mut RecordTimings) -> Result<usize, Error> {
+        let parser = self.parser;
+        let (sender, receiver) = std::sync::mpsc::sync_channel::<Vec<String>>(self.threads * 4);
+        let receiver = std::sync::Arc::new(std::sync::Mutex::new(receiver));
+        let workers: Vec<_> = (0..self.threads)
+            .map(|_| {
+                let receiver = receiver.clone();
+                std::thread::spawn(move || {
+                    let parser = find_parser(parser).unwrap();
+                    let (mut sum, mut errors) = (0u64, 0);
+                    loop {
+                        // the lock is held only while waiting for the next batch
+                        let batch = match receiver.lock().unwrap().recv() {
+                            Ok(batch) => batch,
+                            Err(_) => break
+                        };
+                        for line in batch {
+                            match parser.parse_native(&mut line.into_bytes()) {
+                                Ok(value) => sum = sum.wrapping_add(value),
+                                Err(_) => errors += 1
+                            }
+                        }
+                    }
+                    (sum, errors)
+                })
+            })
+            .collect();
+        // only the workers hold the receiver, so sending fails rather than blocks if they all died
+        drop(receiver);
+
+        let now = Instant::now();
+        let mut records = 0;
+        let mut batch = Vec::with_capacity(PARALLEL_BATCH);
+        for line in open_input(&input.path, input.decoder)? {
+            records += 1;
+            match line {
+                Ok(line) => {
+                    timings.bytes += line.len() as u64 + 1;
+                    batch.push(line);
+                    if batch.len() == PARALLEL_BATCH {
+                        sender.send(std::mem::replace(&mut batch, Vec::with_capacity(PARALLEL_BATCH)))?;
+                    }
+                },
+                Err(_) => timings.errors += 1
+            }
+        }
+        if !batch.is_empty() {
+            sender.send(batch)?;
+        }
+        drop(sender);
+        // reading includes waiting for the workers to take batches off the full channel
+        timings.read = now.elapsed();
+        let mut sum = 0u64;
+        for worker in workers {
+            let (worker_sum, errors) = worker.join().map_err(|_| format_err!("a {} worker panicked", parser))?;
+            sum = sum.wrapping_add(worker_sum);
+            timings.errors += errors;
+        }
+        timings.process = now.elapsed();
+        timings.sink.consume(sum);
+        Ok(records)
+    }
+}
+
+// parallel:PARSER:N for 1, 2, 4, .. threads up to and including max_threads
+pub fn scaling_names(parser: &str, max_threads: usize) -> Vec<String> {
+    let mut threads: Vec<usize> = std::iter::successors(Some(1), |threads| Some(threads * 2)).take_while(|threads| *threads < max_threads).collect();
+    threads.push(max_threads.max(1));
+    threads.iter().map(|threads| format!("parallel:{}:{}", parser, threads)).collect()
+}
+
+// compress:CODEC:LEVEL:64k for every level of the codecs, all of them when none are given
+pub fn level_names(codecs: &[String]) -> Result<Vec<String>, Error> {
+    let compressors = if codecs.is_empty() {
+        compressors()
+    } else {
+        codecs.iter()
+            .map(|codec| find_compressor(codec).ok_or_else(|| format_err!("unknown codec {}", codec)))
+            .collect::<Result<_, Error>>()?
+    };
+    Ok(compressors.iter()
+        .flat_map(|compressor| compressor.levels().map(move |level| format!("compress:{}:{}:{}k", compressor.name(), level, DEFAULT_BLOCK_SIZE / 1024)))
+        .collect())
+}
+
+
+// simd-json's APIs side by side over every line: the structural tape of its first stage only,
+// then the DOM borrowing strings from the line and the one owning them, which sink the same.
+pub struct SimdBenchmark {
+    api: &'static str
+}
+
+pub const SIMD_APIS: [&str; 3] = ["tape", "borrowed", "owned"];
+
+impl Benchmark for SimdBenchmark {
+    fn name(&self) -> String {
+        format!("simd:{}", self.api)
+    }
+
+    fn run(&mut self, input: &Input, timings: &mut RecordTimings) -> Result<usize, Error> {
+        match self.api {
+            "tape" => timed_records(input, timings, |mut line| {
+                let tape = unsafe { simd_json::to_tape(line.as_bytes_mut())? };
+                Ok(tape.len() as u64)
+            }),
+            "borrowed" => timed_records(input, timings, |mut line| Ok(simd_size(&unsafe { simd_json::to_borrowed_value(line.as_bytes_mut())? }))),
+            _ => timed_records(input, timings, |mut line| Ok(simd_owned_size(&unsafe { simd_json::to_owned_value(line.as_bytes_mut())? })))
+        }
+    }
+}
+
+
+pub const DEFAULT_BLOCK_SIZE: usize = 64 * 1024;
+
+// One record per compressed block, or with a block size, records packed into blocks of up to that
+// many bytes. The latency histogram is per block then.
+pub struct CompressBenchmark {
+    compressor: Box<dyn Compressor>,
+    level: i32,
+    block_size: Option<usize>
+}
+
+impl Benchmark for CompressBenchmark {
+    fn name(&self) -> String {
+        match self.block_size {
+            Some(block_size) if block_size % 1024 == 0 => format!("compress:{}:{}:{}k", self.compressor.name(), self.level, block_size / 1024),
+            Some(block_size) => format!("compress:{}:{}:{}", self.compressor.name(), self.level, block_size),
+            None => format!("compress:{}:{}", self.compressor.name(), self.level)
+        }
+    }
+
+    fn run(&mut self, input: &Input, timings: &mut RecordTimings) -> Result<usize, Error> {
+        let (compressor, level) = (&self.compressor, self.level);
+        let block_size = match self.block_size {
+            Some(block_size) => block_size,
+            None => {
+                let mut compressed_bytes = 0;
+                let records = timed_records(input, timings, |line| {
+                    let block = compressor.compress_block(line.as_bytes(), level)?;
+                    compressed_bytes += block.len() as u64;
+                    Ok(block.len() as u64)
+                });
+                timings.compressed_bytes += compressed_bytes;
+                return records;
+            }
+        };
+        let mut blocks = lines_to_blocks(open_input(&input.path, input.decoder)?, block_size);
+        let mut records = 0;
+        loop {
+            let now = Instant::now();
+            let block = match blocks.next() {
+                Some(block) => block?,
+                None => break
+            };
+            timings.read += now.elapsed();
+            timings.bytes += block.len() as u64;
+            records += block_to_lines(&block).count();
+            let now = Instant::now();
+            let compressed = compressor.compress_block(&block, level)?;
+            timings.record(now.elapsed());
+            timings.compressed_bytes += compressed.len() as u64;
+            timings.sink.consume(compressed.len() as u64);
+        }
+        Ok(records)
+    }
+}
+
+// "64k" or a plain number of bytes
+fn parse_block_size(size: &str) -> Result<usize, Error> {
+    let block_size = match size.strip_suffix('k') {
+        Some(kib) => kib.parse::<usize>()? * 1024,
+        None => size.parse()?
+    };
+    if block_size == 0 {
+        bail!("block size must be positive");
+    }
+    Ok(block_size)
+}
+
+
+// RFC 8785 canonical form, starting from each backend's own DOM
+pub struct CanonicalizeBenchmark {
+    backend: &'static str
+}
+
+impl Benchmark for CanonicalizeBenchmark {
+    fn name(&self) -> String {
+        format!("canonicalize:{}", self.backend)
+    }
+
+    fn run(&mut self, input: &Input, timings: &mut RecordTimings) -> Result<usize, Error> {
+        match self.backend {
+            "json" => timed_records(input, timings, |line| canonicalize(&json::parse(&line)?).map(|canonical| canonical.len() as u64)),
+            "serde" => timed_records(input, timings, |line| {
+                let value: Value = serde_json::from_str(&line)?;
+                canonicalize_serde(&value).map(|canonical| canonical.len() as u64)
+            }),
+            _ => timed_records(input, timings, |mut line| {
+                let value = unsafe { simd_json::to_borrowed_value(line.as_bytes_mut())? };
+                canonicalize_simd(&value).map(|canonical| canonical.len() as u64)
+            })
+        }
+    }
+}
+
+
+// The write path: every record of the input parsed in setup into one backend's DOM and written
+// back to a string in run, so the times are serialization only. The DOMs of the whole input are
+// held in memory, --limit keeps them small.
+pub struct SerializeBenchmark {
+    backend: &'static str,
+    documents: Option<Documents>
+}
+
+enum Documents {
+    Json(Vec<JsonValue>),
+    Serde(Vec<Value>),
+    Simd(Vec<simd_json::OwnedValue>)
+}
+
+pub const SERIALIZE_BACKENDS: [&str; 3] = ["json", "serde", "simd"];
+
+impl Benchmark for SerializeBenchmark {
+    fn name(&self) -> String {
+        format!("serialize:{}", self.backend)
+    }
+
+    fn setup(&mut self, input: &Input) -> Result<(), Error> {
+        let lines = open_input(&input.path, input.decoder)?.collect::<io::Result<Vec<String>>>()?;
+        self.documents = Some(match self.backend {
+            "json" => Documents::Json(lines.iter().map(|line| json::parse(line)).collect::<Result<_, _>>()?),
+            "serde" => Documents::Serde(lines.iter().map(|line| serde_json::from_str(line)).collect::<Result<_, _>>()?),
+            _ => Documents::Simd(lines.into_iter().map(|line| simd_json::to_owned_value(&mut line.into_bytes())).collect::<Result<_, _>>()?)
+        });
+        Ok(())
+    }
+
+    fn run(&mut self, _input: &Input, timings: &mut RecordTimings) -> Result<usize, Error> {
+        let documents = self.documents.as_ref().ok_or_else(|| format_err!("setup was not run"))?;
+        let mut serialize = |serialize: &mut dyn FnMut() -> Result<String, Error>| -> Result<(), Error> {
+            let now = Instant::now();
+            let text = serialize()?;
+            timings.record(now.elapsed());
+            timings.bytes += text.len() as u64 + 1;
+            timings.sink.consume(text.len() as u64);
+            Ok(())
+        };
+        Ok(match documents {
+            Documents::Json(values) => {
+                for value in values {
+                    serialize(&mut || Ok(value.dump()))?;
+                }
+                values.len()
+            },
+            Documents::Serde(values) => {
+                for value in values {
+                    serialize(&mut || Ok(serde_json::to_string(value)?))?;
+                }
+                values.len()
+            },
+            Documents::Simd(values) => {
+                for value in values {
+                    serialize(&mut || Ok(value.encode()))?;
+                }
+                values.len()
+            }
+        })
+    }
+
+    fn teardown(&mut self) -> Result<(), Error> {
+        self.documents = None;
+        Ok(())
+    }
+}
+
+
+// Parsing every line and writing it back with one backend, against serialize, which times the
+// writing only. Setup checks, untimed, that each backend's output parses to
+// what its line did, whatever the order of keys; the lines it changed count as errors of every
+// run, so silent mangling shows up next to the speed.
+pub struct ReserializeBenchmark {
+    backend: &'static str,
+    changed: usize
+}
+
+fn reserialize(backend: &str, line: String) -> Result<String, Error> {
+    Ok(match backend {
+        "json" => json::parse(&line)?.dump(),
+        "serde" => serde_json::to_string(&serde_json::from_str::<Value>(&line)?)?,
+        _ => simd_json::to_owned_value(&mut line.into_bytes())?.encode()
+    })
+}
+
+// Equality with integers compared as integers and doubles as doubles, so 1.0 written back as 1 is
+// the same, but only below 2^53, and an id off by one is not. Map keys are sorted.
+fn same_json(a: &Value, b: &Value) -> bool {
+    let integer = |number: &serde_json::Number| number.as_i64().map(i128::from).or_else(|| number.as_u64().map(i128::from));
+    match (a, b) {
+        (Value::Number(a), Value::Number(b)) => match (integer(a), integer(b)) {
+            (Some(a), Some(b)) => a == b,
+            (None, None) => a.as_f64() == b.as_f64(),
+            _ => a.as_f64() == b.as_f64() && a.as_f64().map_or(false, |double| double.abs() < 9_007_199_254_740_992.0)
+        },
+        (Value::Array(a), Value::Array(b)) => a.len() == b.len() && a.iter().zip(b).all(|(a, b)| same_json(a, b)),
+        (Value::Object(a), Value::Object(b)) => a.len() == b.len() && a.iter().all(|(key, a)| b.get(key).map_or(false, |b| same_json(a, b))),
+        _ => a == b
+    }
+}
+
+impl Benchmark for ReserializeBenchmark {
+    fn name(&self) -> String {
+        format!("reserialize:{}", self.backend)
+    }
+
+    fn setup(&mut self, input: &Input) -> Result<(), Error> {
+        self.changed = 0;
+        for line in open_input(&input.path, input.decoder)? {
+            let line = line?;
+            let original = match serde_json::from_str::<Value>(&line) {
+                Ok(original) => original,
+                Err(_) => continue
+            };
+            let written = reserialize(self.backend, line).ok().and_then(|written| serde_json::from_str::<Value>(&written).ok());
+            if !written.map_or(false, |written| same_json(&original, &written)) {
+                self.changed += 1;
+            }
+        }
+        Ok(())
+    }
+
+    fn run(&mut self, input: &Input, timings: &mut RecordTimings) -> Result<usize, Error> {
+        let records = timed_records(input, timings, |line| Ok(reserialize(self.backend, line)?.len() as u64))?;
+        timings.errors += self.changed;
+        Ok(records)
+    }
+}
+
+
+// Tumbling window counts by lang and top hashtags over the tweet corpus, an analytics pass where
+// parsing is only part of the cost.
+pub struct WindowBenchmark {
+    window_seconds: i64
+}
+
+impl Benchmark for WindowBenchmark {
+    fn name(&self) -> String {
+        format!("window:{}", self.window_seconds)
+    }
+
+    fn run(&mut self, input: &Input, timings: &mut RecordTimings) -> Result<usize, Error> {
+        let mut aggregator = WindowAggregator::new(WindowSpec { window_ms: self.window_seconds * 1000, ..WindowSpec::default() });
+        let records = timed_records(input, timings, |line| {
+            aggregator.update(&json::parse(&line)?);
+            Ok(0)
+        })?;
+        timings.sink.consume(aggregator.summaries().len() as u64);
+        Ok(records)
+    }
+}
+
+
+pub const EXTRACT_BACKENDS: [&str; 4] = ["scan", "json", "serde", "simd"];
+
+// Pulls one top level field out of every record, with a raw scan or a full parse. Records without
+// the field count as errors, so a wrong scan shows up in the error count as well as in the time.
+pub struct ExtractBenchmark {
+    backend: &'static str,
+    field: String
+}
+
+impl Benchmark for ExtractBenchmark {
+    fn name(&self) -> String {
+        format!("extract:{}:{}", self.backend, self.field)
+    }
+
+    fn run(&mut self, input: &Input, timings: &mut RecordTimings) -> Result<usize, Error> {
+        let field = self.field.as_str();
+        let missing = || format_err!("no field {}", field);
+        match self.backend {
+            "scan" => {
+                let scanner = FieldScanner::new(field);
+                timed_records(input, timings, |line| scanner.scan(line.as_bytes()).map(|value| value.len() as u64).ok_or_else(missing))
+            },
+            "json" => timed_records(input, timings, |line| {
+                let value = &json::parse(&line)?[field];
+                if value.is_null() { Err(missing()) } else { Ok(json_size(value)) }
+            }),
+            "serde" => timed_records(input, timings, |line| {
+                let value: Value = serde_json::from_str(&line)?;
+                value.get(field).map(serde_size).ok_or_else(missing)
+            }),
+            _ => timed_records(input, timings, |mut line| {
+                match unsafe { simd_json::to_borrowed_value(line.as_bytes_mut())? } {
+                    simd_json::value::borrowed::Value::Object(map) => map.get(field).map(simd_size).ok_or_else(missing),
+                    _ => Err(missing())
+                }
+            })
+        }
+    }
+}
+
+
+pub const FIELDS_BACKENDS: [&str; 6] = ["json", "serde", "simd", "typed", "raw", "path"];
+
+#[derive(Deserialize)]
+struct TweetFields<'a> {
+    #[serde(borrow)]
+    user: UserFields<'a>,
+    #[serde(borrow)]
+    text: Cow<'a, str>,
+    retweet_count: u64
+}
+
+#[derive(Deserialize)]
+struct UserFields<'a> {
+    #[serde(borrow)]
+    screen_name: Cow<'a, str>
+}
+
+// the user left unparsed until the screen name is needed
+#[derive(Deserialize)]
+struct RawTweetFields<'a> {
+    #[serde(borrow)]
+    user: &'a RawValue,
+    #[serde(borrow)]
+    text: Cow<'a, str>,
+    retweet_count: u64
+}
+
+fn fields_sink(screen_name: &str, text: &str, retweet_count: u64) -> u64 {
+    (screen_name.len() + text.len()) as u64 + retweet_count
+}
+
+fn simd_member<'v, 'a>(value: &'v simd_json::value::borrowed::Value<'a>, key: &str) -> Option<&'v simd_json::value::borrowed::Value<'a>> {
+    match value {
+        simd_json::value::borrowed::Value::Object(map) => map.get(key),
+        _ => None
+    }
+}
+
+// user.screen_name, text and retweet_count of every tweet, the partial access most applications
+// do: through each DOM, a struct of just those fields, one with the user kept as a RawValue, and
+// a gjson style PathScanner over the raw line. All sink the unescaped values, so the checksums
+// match, and records missing a field, like deletes, are errors in every backend.
+pub struct FieldsBenchmark {
+    backend: &'static str
+}
+
+impl Benchmark for FieldsBenchmark {
+    fn name(&self) -> String {
+        format!("fields:{}", self.backend)
+    }
+
+    fn run(&mut self, input: &Input, timings: &mut RecordTimings) -> Result<usize, Error> {
+        let missing = || format_err!("missing user.screen_name, text or retweet_count");
+        match self.backend {
+            "json" => timed_records(input, timings, |line| {
+                let tweet = json::parse(&line)?;
+                match (tweet["user"]["screen_name"].as_str(), tweet["text"].as_str(), tweet["retweet_count"].as_u64()) {
+                    (Some(screen_name), Some(text), Some(retweet_count)) => Ok(fields_sink(screen_name, text, retweet_count)),
+                    _ => Err(missing())
+                }
+            }),
+            "serde" => timed_records(input, timings, |line| {
+                let tweet: Value = serde_json::from_str(&line)?;
+                match (tweet.pointer("/user/screen_name").and_then(Value::as_str), tweet["text"].as_str(), tweet["retweet_count"].as_u64()) {
+                    (Some(screen_name), Some(text), Some(retweet_count)) => Ok(fields_sink(screen_name, text, retweet_count)),
+                    _ => Err(missing())
+                }
+            }),
+            "simd" => timed_records(input, timings, |mut line| {
+                use simd_json::StaticNode;
+                use simd_json::value::borrowed::Value as BorrowedValue;
+                let tweet = unsafe { simd_json::to_borrowed_value(line.as_bytes_mut())? };
+                let screen_name = simd_member(&tweet, "user").and_then(|user| simd_member(user, "screen_name"));
+                // as_u64 of the other backends
+                let retweet_count = match simd_member(&tweet, "retweet_count") {
+                    Some(BorrowedValue::Static(StaticNode::I64(count))) if *count >= 0 => Some(*count as u64),
+                    Some(BorrowedValue::Static(StaticNode::U64(count))) => Some(*count),
+                    _ => None
+                };
+                match (screen_name, simd_member(&tweet, "text"), retweet_count) {
+                    (Some(BorrowedValue::String(screen_name)), Some(BorrowedValue::String(text)), Some(retweet_count)) => {
+                        Ok(fields_sink(screen_name, text, retweet_count))
+                    },
+                    _ => Err(missing())
+                }
+            }),
+            "typed" => timed_records(input, timings, |line| {
+                let tweet: TweetFields = serde_json::from_str(&line)?;
+                Ok(fields_sink(&tweet.user.screen_name, &tweet.text, tweet.retweet_count))
+            }),
+            "raw" => timed_records(input, timings, |line| {
+                let tweet: RawTweetFields = serde_json::from_str(&line)?;
+                let user: UserFields = serde_json::from_str(tweet.user.get())?;
+                Ok(fields_sink(&user.screen_name, &tweet.text, tweet.retweet_count))
+            }),
+            _ => {
+                let scanners = [PathScanner::new("user.screen_name"), PathScanner::new("text"), PathScanner::new("retweet_count")];
+                timed_records(input, timings, |line| {
+                    let mut values = scanners.iter().map(|scanner| scanner.scan(line.as_bytes()));
+                    match (values.next().flatten(), values.next().flatten(), values.next().flatten()) {
+                        (Some(screen_name), Some(text), Some(retweet_count)) => {
+                            // strings are unescaped, the rest is left unparsed
+                            let (screen_name, text): (String, String) = (serde_json::from_slice(screen_name)?, serde_json::from_slice(text)?);
+                            Ok(fields_sink(&screen_name, &text, std::str::from_utf8(retweet_count)?.parse()?))
+                        },
+                        _ => Err(missing())
+                    }
+                })
+            }
+        }
+    }
+}
+
+
+// Whether a tweet passes a filter keeping about selectivity percent of them: those whose id_str
+// ends in two digits below the selectivity.
+fn selected(record: &JsonValue, selectivity: u32) -> bool {
+    record["id_str"].as_str()
+        .and_then(|id| id.get(id.len().saturating_sub(2)..))
+        .and_then(|digits| digits.parse::<u32>().ok())
+        .map_or(false, |digits| digits < selectivity)
+}
+
+// The same filter on the raw line. Nested tweets carry an id_str too, so a match is a candidate
+// to parse and check, never a result on its own.
+fn selectivity_regex(selectivity: u32) -> Regex {
+    let (tens, ones) = (selectivity / 10, selectivity % 10);
+    let mut alternatives = Vec::new();
+    if tens > 0 {
+        alternatives.push(format!("[0-{}][0-9]", tens - 1));
+    }
+    if ones > 0 {
+        alternatives.push(format!("{}[0-{}]", tens, ones - 1));
+    }
+    Regex::new(&format!(r#""id_str":"[0-9]*(?:{})""#, alternatives.join("|"))).unwrap()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FilterMode {
+    // parse every record, then filter
+    Parse,
+    // match the raw line first and parse only the candidates
+    Regex
+}
+
+// Filtering the corpus down to a share of the records, to see at which selectivity a regex pass
+// over the raw lines stops paying for itself.
+pub struct FilterBenchmark {
+    mode: FilterMode,
+    // percent of the records kept, 1 to 100
+    selectivity: u32,
+    pattern: Option<Regex>
+}
+
+impl FilterBenchmark {
+    pub fn new(mode: FilterMode, selectivity: u32) -> Self {
+        FilterBenchmark { mode, selectivity, pattern: None }
+    }
+}
+
+impl Benchmark for FilterBenchmark {
+    fn name(&self) -> String {
+        match self.mode {
+            FilterMode::Parse => format!("filter:parse:{}", self.selectivity),
+            FilterMode::Regex => format!("filter:regex:{}", self.selectivity)
+        }
+    }
+
+    fn setup(&mut self, _input: &Input) -> Result<(), Error> {
+        self.pattern = Some(selectivity_regex(self.selectivity));
+        Ok(())
+    }
+
+    fn run(&mut self, input: &Input, timings: &mut RecordTimings) -> Result<usize, Error> {
+        let pattern = self.pattern.as_ref().ok_or_else(|| format_err!("setup was not run"))?;
+        let (mode, selectivity) = (self.mode, self.selectivity);
+        timed_records(input, timings, |line| {
+            if mode == FilterMode::Regex && !pattern.is_match(&line) {
+                return Ok(0);
+            }
+            Ok(selected(&json::parse(&line)?, selectivity) as u64)
+        })
+    }
+}
+
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ConvertFormat {
+    Avro,
+    AvroJson
+}
+
+// Conversion into a discarding sink with the schema inferred from the input in setup.
+pub struct ConvertBenchmark {
+    format: ConvertFormat,
+    schema: Option<Schema>
+}
+
+impl Benchmark for ConvertBenchmark {
+    fn name(&self) -> String {
+        match self.format {
+            ConvertFormat::Avro => "convert:avro".to_owned(),
+            ConvertFormat::AvroJson => "convert:avro-json".to_owned()
+        }
+    }
+
+    fn setup(&mut self, input: &Input) -> Result<(), Error> {
+        self.schema = Some(infer_file_schema(&input.path, "inferred_schema")?);
+        Ok(())
+    }
+
+    fn run(&mut self, input: &Input, timings: &mut RecordTimings) -> Result<usize, Error> {
+        let schema = self.schema.as_ref().ok_or_else(|| format_err!("setup was not run"))?;
+        let mut writer: Box<dyn RecordWriter + '_> = match self.format {
+            ConvertFormat::Avro => Box::new(AvroWriter::new(schema, io::sink(), Codec::Deflate)),
+            ConvertFormat::AvroJson => Box::new(AvroJsonWriter::new(schema, io::sink()))
+        };
+        let records = timed_records(input, timings, |line| writer.write(&json::parse(&line)?).map(|_| 1))?;
+        writer.finish()?;
+        Ok(records)
+    }
+}
+
+
+// JSON to uncompressed Avro against the schema of src/tweet.rs, once through the structs codegen
+// made of it and once through JsonValue and json_to_avro, which is what codegen saves. Both sink
+// the tweet ids, so their checksums match.
+pub struct RoundtripBenchmark {
+    typed: bool,
+    schema: Option<Schema>
+}
+
+impl Benchmark for RoundtripBenchmark {
+    fn name(&self) -> String {
+        if self.typed { "roundtrip:typed".to_owned() } else { "roundtrip:dynamic".to_owned() }
+    }
+
+    fn setup(&mut self, _input: &Input) -> Result<(), Error> {
+        self.schema = Some(Schema::parse_str(tweet::SCHEMA)?);
+        Ok(())
+    }
+
+    fn run(&mut self, input: &Input, timings: &mut RecordTimings) -> Result<usize, Error> {
+        let schema = self.schema.as_ref().ok_or_else(|| format_err!("setup was not run"))?;
+        let mut writer = Writer::with_codec(schema, io::sink(), Codec::Null);
+        let records = if self.typed {
+            timed_records(input, timings, |line| {
+                let tweet: Tweet = serde_json::from_str(&line)?;
+                let id = tweet.id as u64;
+                writer.append_ser(tweet)?;
+                Ok(id)
+            })?
+        } else {
+            timed_records(input, timings, |line| {
+                let json = json::parse(&line)?;
+                writer.append(json_to_avro(&json, schema)?)?;
+                Ok(json["id"].as_i64().unwrap_or_default() as u64)
+            })?
+        };
+        writer.flush()?;
+        Ok(records)
+    }
+}
+
+
+#[cfg(feature = "orc")]
+pub struct OrcBenchmark {
+    schema: Option<Schema>,
+    output: Option<tempfile::TempDir>
+}
+
+#[cfg(feature = "orc")]
+impl Benchmark for OrcBenchmark {
+    fn name(&self) -> String {
+        "convert:orc".to_owned()
+    }
+
+    fn setup(&mut self, input: &Input) -> Result<(), Error> {
+        self.schema = Some(infer_file_schema(&input.path, "inferred_schema")?);
+        self.output = Some(tempfile::TempDir::new()?);
+        Ok(())
+    }
+
+    fn run(&mut self, input: &Input, timings: &mut RecordTimings) -> Result<usize, Error> {
+        let schema = self.schema.as_ref().ok_or_else(|| format_err!("setup was not run"))?;
+        let dir = self.output.as_ref().ok_or_else(|| format_err!("setup was not run"))?;
+        let path = dir.path().join("output.orc").to_string_lossy().into_owned();
+        let mut writer = crate::orc::OrcWriter::new(schema, &path)?;
+        let records = timed_records(input, timings, |line| writer.write(&json::parse(&line)?).map(|_| 1))?;
+        writer.finish()?;
+        Ok(records)
+    }
+
+    fn teardown(&mut self) -> Result<(), Error> {
+        if let Some(dir) = self.output.take() {
+            dir.close()?;
+        }
+        Ok(())
+    }
+}
+
+
+// Every registered benchmark in its default configuration.
+pub fn benchmarks() -> Vec<Box<dyn Benchmark>> {
+    let mut benchmarks: Vec<Box<dyn Benchmark>> = vec![Box::new(ReadBenchmark)];
+    for decoder in &Decoder::all() {
+        benchmarks.push(Box::new(DecodeBenchmark { decoder: *decoder }));
+    }
+    for size in &[Some(1 << 20), Some(16 << 20), None] {
+        benchmarks.push(Box::new(LinesBenchmark::new(false, *size)));
+        benchmarks.push(Box::new(LinesBenchmark::new(true, *size)));
+    }
+    for parser in parsers() {
+        benchmarks.push(Box::new(ParseBenchmark { parser }));
+    }
+    #[cfg(feature = "yyjson")]
+    benchmarks.push(Box::new(YyjsonBenchmark));
+    #[cfg(feature = "rapidjson")]
+    for sax in &[false, true] {
+        benchmarks.push(Box::new(RapidjsonBenchmark { sax: *sax }));
+    }
+    for array in &[false, true] {
+        for parser in parsers() {
+            benchmarks.push(Box::new(WholeFileBenchmark { parser, array: *array }));
+        }
+    }
+    for backend in &TYPED_BACKENDS {
+        benchmarks.push(Box::new(TypedBenchmark { backend: *backend }));
+    }
+    benchmarks.push(Box::new(LazyTypedBenchmark));
+    for backend in &ZERO_COPY_BACKENDS {
+        benchmarks.push(Box::new(ZeroCopyBenchmark { backend: *backend, borrowed: false }));
+        benchmarks.push(Box::new(ZeroCopyBenchmark { backend: *backend, borrowed: true }));
+    }
+    benchmarks.push(Box::new(StreamBenchmark));
+    benchmarks.push(Box::new(TokensBenchmark));
+    for strategy in &NUMBER_STRATEGIES {
+        benchmarks.push(Box::new(NumbersBenchmark { strategy: *strategy }));
+    }
+    for threads in &[2, 4] {
+        benchmarks.push(Box::new(ParallelParseBenchmark { parser: "json", threads: *threads }));
+    }
+    for api in &SIMD_APIS {
+        benchmarks.push(Box::new(SimdBenchmark { api: *api }));
+    }
+    for backend in &SERIALIZE_BACKENDS {
+        benchmarks.push(Box::new(SerializeBenchmark { backend: *backend, documents: None }));
+    }
+    for backend in &SERIALIZE_BACKENDS {
+        benchmarks.push(Box::new(ReserializeBenchmark { backend: *backend, changed: 0 }));
+    }
+    for compressor in compressors() {
+        let level = compressor.default_level();
+        benchmarks.push(Box::new(CompressBenchmark { compressor, level, block_size: None }));
+    }
+    for compressor in compressors() {
+        let level = compressor.default_level();
+        benchmarks.push(Box::new(CompressBenchmark { compressor, level, block_size: Some(DEFAULT_BLOCK_SIZE) }));
+    }
+    for backend in &["json", "serde", "simd"] {
+        benchmarks.push(Box::new(CanonicalizeBenchmark { backend }));
+    }
+    benchmarks.push(Box::new(WindowBenchmark { window_seconds: 60 }));
+    for backend in &EXTRACT_BACKENDS {
+        benchmarks.push(Box::new(ExtractBenchmark { backend: *backend, field: "id_str".to_owned() }));
+    }
+    for backend in &FIELDS_BACKENDS {
+        benchmarks.push(Box::new(FieldsBenchmark { backend: *backend }));
+    }
+    for selectivity in &[1, 10, 50] {
+        benchmarks.push(Box::new(FilterBenchmark::new(FilterMode::Parse, *selectivity)));
+        benchmarks.push(Box::new(FilterBenchmark::new(FilterMode::Regex, *selectivity)));
+    }
+    benchmarks.push(Box::new(ConvertBenchmark { format: ConvertFormat::Avro, schema: None }));
+    benchmarks.push(Box::new(ConvertBenchmark { format: ConvertFormat::AvroJson, schema: None }));
+    benchmarks.push(Box::new(RoundtripBenchmark { typed: true, schema: None }));
+    benchmarks.push(Box::new(RoundtripBenchmark { typed: false, schema: None }));
+    #[cfg(feature = "orc")]
+    benchmarks.push(Box::new(OrcBenchmark { schema: None, output: None }));
+    benchmarks
+}
+
+// Looks a benchmark up by name. Parameterized names like "compress:zstd:19" pick a non default
+// configuration; "compress:zstd" is the default level and "compress:zstd:19:256k" compresses
+// 256 KiB blocks of records instead of single records.
+pub fn find_benchmark(name: &str) -> Result<Box<dyn Benchmark>, Error> {
+    let parts: Vec<&str> = name.split(':').collect();
+    if let ["compress", codec, rest @ ..] = parts.as_slice() {
+        let compressor = find_compressor(codec).ok_or_else(|| format_err!("unknown codec {}", codec))?;
+        let (level, block_size) = match rest {
+            [] => (compressor.default_level(), None),
+            [level] => (level.parse()?, None),
+            [level, block_size] => (level.parse()?, Some(parse_block_size(block_size)?)),
+            _ => return Err(format_err!("unknown benchmark {}", name))
+        };
+        if !compressor.levels().contains(&level) {
+            return Err(format_err!("{} has no level {}", codec, level));
+        }
+        return Ok(Box::new(CompressBenchmark { compressor, level, block_size }));
+    }
+    if let ["window", seconds] = parts.as_slice() {
+        let window_seconds: i64 = seconds.parse()?;
+        if window_seconds <= 0 {
+            bail!("window length must be positive");
+        }
+        return Ok(Box::new(WindowBenchmark { window_seconds }));
+    }
+    if let ["extract", backend, field] = parts.as_slice() {
+        let backend = EXTRACT_BACKENDS.iter().find(|known| *known == backend).ok_or_else(|| format_err!("unknown extract backend {}", backend))?;
+        return Ok(Box::new(ExtractBenchmark { backend: *backend, field: field.to_string() }));
+    }
+    if let ["filter", mode, selectivity] = parts.as_slice() {
+        let mode = match *mode {
+            "parse" => FilterMode::Parse,
+            "regex" => FilterMode::Regex,
+            _ => bail!("unknown filter mode {}, expected parse or regex", mode)
+        };
+        let selectivity: u32 = selectivity.parse()?;
+        if selectivity == 0 || selectivity > 100 {
+            bail!("selectivity is a percentage from 1 to 100");
+        }
+        return Ok(Box::new(FilterBenchmark::new(mode, selectivity)));
+    }
+    if let ["lines", strategy, size @ ..] = parts.as_slice() {
+        let eager = match *strategy {
+            "lazy" => false,
+            "eager" => true,
+            _ => bail!("unknown line strategy {}, expected lazy or eager", strategy)
+        };
+        let size = match size {
+            [] => None,
+            [size] => Some(parse_size(size)?),
+            _ => bail!("unknown benchmark {}", name)
+        };
+        return Ok(Box::new(LinesBenchmark::new(eager, size)));
+    }
+    if let ["parallel", parser, threads] = parts.as_slice() {
+        let parser = find_parser(parser).ok_or_else(|| format_err!("unknown parser {}", parser))?.name();
+        let threads: usize = threads.parse()?;
+        if threads == 0 {
+            bail!("at least one worker thread");
+        }
+        return Ok(Box::new(ParallelParseBenchmark { parser, threads }));
+    }
+    if let ["parse", parser] = parts.as_slice() {
+        let parser = find_parser(parser).ok_or_else(|| format_err!("unknown parser {}", parser))?;
+        return Ok(Box::new(ParseBenchmark { parser }));
+    }
+    benchmarks()
+        .into_iter()
+        .find(|benchmark| benchmark.name() == name)
+        .ok_or_else(|| format_err!("unknown benchmark {}", name))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Repetitions {
+    // passes run and thrown away before measuring, to warm caches, allocators and branch predictors
+    pub warmup: usize,
+    pub runs: usize,
+    // per record latencies in an HDR histogram, see RecordTimings::percentiles
+    pub latency: bool,
+    // time budget of every measured run, which passes over the input again and again until it is
+    // spent, for a steady state rather than a single pass
+    pub duration: Option<Duration>
+}
+
+impl Default for Repetitions {
+    fn default() -> Self {
+        Repetitions { warmup: 0, runs: 1, latency: false, duration: None }
+    }
+}
+
+pub struct RepeatedRun {
+    pub measurements: Vec<Measurement>,
+    // of the last measured run
+    pub timings: RecordTimings,
+    // over the input in each measured run, more than one with a duration
+    pub passes: usize,
+    pub stats: Stats,
+    // with run_interleaved, the slot of every measured run in its round, 0 for first
+    pub positions: Vec<usize>
+}
+
+impl RepeatedRun {
+    pub fn last(&self) -> &Measurement {
+        self.measurements.last().unwrap()
+    }
+
+    // at the median time, input_bytes being the size of the input file
+    pub fn throughput(&self, input_bytes: u64) -> Throughput {
+        let median = Duration::from_secs_f64(self.stats.median_ms.max(0.001) / 1000.0);
+        Throughput::new(self.last().records as u64, input_bytes * self.passes as u64, self.timings.bytes, median)
+    }
+}
+
+// Setup and teardown run once around all passes. before_each runs untimed ahead of every pass,
+// warmup included, e.g. to evict the input from the page cache again.
+pub fn run_benchmark(benchmark: &mut dyn Benchmark, input: &Input, repetitions: Repetitions,
+                     before_each: &mut dyn FnMut() -> Result<(), Error>) -> Result<RepeatedRun, Error> {
+    if repetitions.runs == 0 {
+        bail!("at least one measured run is needed");
+    }
+    benchmark.setup(input)?;
+    let result = repeat(benchmark, input, repetitions, before_each);
+    benchmark.teardown()?;
+    result
+}
+
+fn repeat(benchmark: &mut dyn Benchmark, input: &Input, repetitions: Repetitions,
+          before_each: &mut dyn FnMut() -> Result<(), Error>) -> Result<RepeatedRun, Error> {
+    for _ in 0..repetitions.warmup {
+        before_each()?;
+        benchmark.run(input, &mut RecordTimings::default())?;
+    }
+    let mut measurements = Vec::with_capacity(repetitions.runs);
+    let mut timings = RecordTimings::default();
+    let mut passes = 0;
+    for _ in 0..repetitions.runs {
+        let (measurement, run_timings, run_passes) = measured_run(benchmark, input, repetitions, before_each)?;
+        measurements.push(measurement);
+        timings = run_timings;
+        passes = run_passes;
+    }
+    let elapsed: Vec<Duration> = measurements.iter().map(|m| m.elapsed).collect();
+    let stats = Stats::of(&elapsed).unwrap();
+    Ok(RepeatedRun { measurements, timings, passes, stats, positions: Vec::new() })
+}
+
+// One measured run, of as many passes as the duration of the repetitions takes.
+fn measured_run(benchmark: &mut dyn Benchmark, input: &Input, repetitions: Repetitions,
+                before_each: &mut dyn FnMut() -> Result<(), Error>) -> Result<(Measurement, RecordTimings, usize), Error> {
+    before_each()?;
+    let mut timings = if repetitions.latency { RecordTimings::with_latency() } else { RecordTimings::default() };
+    let mut passes = 0;
+    let mut result = Ok(0);
+    let measurement = measure(&benchmark.name(), || {
+        let started = Instant::now();
+        let mut records = 0;
+        loop {
+            result = benchmark.run(input, &mut timings);
+            passes += 1;
+            records += *result.as_ref().unwrap_or(&0);
+            if result.is_err() || repetitions.duration.map_or(true, |duration| started.elapsed() >= duration) {
+                break records;
+            }
+        }
+    });
+    result?;
+    Ok((measurement, timings, passes))
+}
+
+struct Interleaved {
+    benchmark: Box<dyn Benchmark>,
+    measurements: Vec<Measurement>,
+    positions: Vec<usize>,
+    timings: RecordTimings,
+    passes: usize,
+    error: Option<Error>
+}
+
+// Runs the benchmarks round by round rather than one after the other, every round in a new random
+// order, so drift over the life of the process (clocks, page cache, heap) and the benchmark that
+// ran just before spread over all of them instead of favouring whichever runs first. Setup and
+// teardown still run once per benchmark; one that fails drops out of the later rounds.
+pub fn run_interleaved<R: Rng>(names: &[String], input: &Input, repetitions: Repetitions, rng: &mut R,
+                               before_each: &mut dyn FnMut() -> Result<(), Error>) -> Vec<(String, Result<RepeatedRun, Error>)> {
+    let mut entries: Vec<(String, Result<Interleaved, Error>)> = names
+        .iter()
+        .map(|name| {
+            let entry = find_benchmark(name).and_then(|mut benchmark| {
+                if repetitions.runs == 0 {
+                    bail!("at least one measured run is needed");
+                }
+                benchmark.setup(input)?;
+                Ok(Interleaved { benchmark, measurements: Vec::new(), positions: Vec::new(), timings: RecordTimings::default(), passes: 0, error: None })
+            });
+            (name.clone(), entry)
+        })
+        .collect();
+    let mut order: Vec<usize> = (0..entries.len()).collect();
+    for round in 0..repetitions.warmup + repetitions.runs {
+        order.shuffle(rng);
+        let mut position = 0;
+        for i in &order {
+            let entry = match &mut entries[*i].1 {
+                Ok(entry) if entry.error.is_none() => entry,
+                _ => continue
+            };
+            let result = if round < repetitions.warmup {
+                before_each().and_then(|_| entry.benchmark.run(input, &mut RecordTimings::default())).map(|_| ())
+            } else {
+                measured_run(entry.benchmark.as_mut(), input, repetitions, before_each).map(|(measurement, timings, passes)| {
+                    entry.measurements.push(measurement);
+                    entry.positions.push(position);
+                    entry.timings = timings;
+                    entry.passes = passes;
+                })
+            };
+            if let Err(e) = result {
+                entry.error = Some(e);
+            }
+            position += 1;
+        }
+    }
+    entries
+        .into_iter()
+        .map(|(name, entry)| {
+            let run = entry.and_then(|mut entry| {
+                let teardown = entry.benchmark.teardown();
+                if let Some(e) = entry.error {
+                    return Err(e);
+                }
+                teardown?;
+                let elapsed: Vec<Duration> = entry.measurements.iter().map(|m| m.elapsed).collect();
+                let stats = Stats::of(&elapsed).unwrap();
+                Ok(RepeatedRun { measurements: entry.measurements, timings: entry.timings, passes: entry.passes, stats, positions: entry.positions })
+            });
+            (name, run)
+        })
+        .collect()
+}
+
+// One benchmark in the `bench --output` file, the fields scripts track over time.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BenchResult {
+    pub name: String,
+    pub kind: String,
+    // the rest of the name, e.g. ["zstd", "19"] for compress:zstd:19
+    pub parameters: Vec<String>,
+    pub records: usize,
+    pub errors: usize,
+    // median of the measured runs
+    pub elapsed_ms: f64,
+    pub records_per_second: f64,
+    // of the input file as stored, compressed or not
+    pub input_bytes_per_second: f64,
+    // of the NDJSON after decompression
+    #[serde(default)]
+    pub decompressed_bytes_per_second: f64,
+    pub joules: Option<f64>,
+    // of the last measured run
+    #[serde(default)]
+    pub allocations: Allocations,
+    #[serde(default)]
+    pub latency: Option<Percentiles>,
+    // over the input in each measured run, see Repetitions::duration
+    #[serde(default = "one_pass")]
+    pub passes: usize,
+    // of the last measured run, see Sink
+    #[serde(default)]
+    pub checksum: u64,
+    // time of every measured run, for the significance tests of `compare`
+    #[serde(default)]
+    pub samples_ms: Vec<f64>,
+    // hardware counters of the last measured run, with --perf
+    #[serde(default)]
+    pub perf: Option<PerfCounters>,
+    // slot in its round of every measured run, with --interleave
+    #[serde(default)]
+    pub positions: Vec<usize>,
+    // uncompressed over compressed bytes, for the compress benchmarks
+    #[serde(default)]
+    pub compression_ratio: Option<f64>,
+    pub stats: Stats
+}
+
+fn one_pass() -> usize {
+    1
+}
+
+impl BenchResult {
+    pub fn new(run: &RepeatedRun, input_bytes: u64) -> Self {
+        let measurement = run.last();
+        let mut parts = measurement.name.split(':').map(|part| part.to_owned());
+        let throughput = run.throughput(input_bytes);
+        BenchResult {
+            name: measurement.name.clone(),
+            kind: parts.next().unwrap_or_default(),
+            parameters: parts.collect(),
+            records: measurement.records,
+            errors: run.timings.errors,
+            elapsed_ms: run.stats.median_ms,
+            records_per_second: throughput.records_per_second,
+            input_bytes_per_second: throughput.compressed_bytes_per_second,
+            decompressed_bytes_per_second: throughput.decompressed_bytes_per_second,
+            joules: measurement.joules,
+            allocations: measurement.allocations,
+            latency: run.timings.percentiles(),
+            passes: run.passes,
+            checksum: run.timings.sink.checksum,
+            samples_ms: run.measurements.iter().map(|m| m.elapsed.as_secs_f64() * 1000.0).collect(),
+            perf: measurement.perf,
+            positions: run.positions.clone(),
+            compression_ratio: run.timings.compression_ratio(),
+            stats: run.stats.clone()
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BenchResults {
+    pub input: String,
+    pub input_bytes: u64,
+    pub decoder: Decoder,
+    pub warmup: usize,
+    pub runs: usize,
+    pub results: Vec<BenchResult>,
+    // benchmark name to error, for the runs that failed
+    pub failed: BTreeMap<String, String>
+}
+
+// Appends one row per benchmark to a CSV file, starting with a header when the file is new, so
+// repeated runs accumulate into one table.
+pub fn append_csv(file_path: &str, results: &BenchResults) -> Result<usize, Error> {
+    let is_new = fs::metadata(file_path).map(|metadata| metadata.len() == 0).unwrap_or(true);
+    let mut writer = csv::Writer::from_writer(OpenOptions::new().create(true).append(true).open(file_path)?);
+    if is_new {
+        writer.write_record(&["timestamp", "benchmark", "dataset", "records", "bytes", "elapsed_ms", "mb_per_second"])?;
+    }
+    let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0).to_string();
+    for result in &results.results {
+        writer.write_record(&[
+            timestamp.clone(),
+            result.name.clone(),
+            results.input.clone(),
+            result.records.to_string(),
+            // of NDJSON the benchmark went through in a run, back from its throughput
+            ((result.decompressed_bytes_per_second * result.elapsed_ms / 1000.0).round() as u64).to_string(),
+            format!("{:.3}", result.elapsed_ms),
+            format!("{:.3}", result.input_bytes_per_second / 1_000_000.0)
+        ])?;
+    }
+    writer.flush()?;
+    Ok(results.results.len())
+}
+
+pub fn run_named(name: &str, input: &Input, repetitions: Repetitions,
+                 before_each: &mut dyn FnMut() -> Result<(), Error>) -> Result<RepeatedRun, Error> {
+    run_benchmark(find_benchmark(name)?.as_mut(), input, repetitions, before_each)
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::seed::Seed;
+
+    #[test]
+    fn test_scaling_names() {
+        assert_eq!(scaling_names("simd", 6), vec!["parallel:simd:1", "parallel:simd:2", "parallel:simd:4", "parallel:simd:6"]);
+        assert_eq!(scaling_names("json", 4), vec!["parallel:json:1", "parallel:json:2", "parallel:json:4"]);
+        assert_eq!(scaling_names("json", 1), vec!["parallel:json:1"]);
+        assert_eq!(find_benchmark("parallel:serde:3").unwrap().name(), "parallel:serde:3");
+        assert!(find_benchmark("parallel:serde:0").is_err());
+    }
+
+    #[test]
+    fn test_level_names() {
+        let names = level_names(&["flate2".to_owned()]).unwrap();
+        assert_eq!(names.len(), 10);
+        assert_eq!((names[0].as_str(), names[9].as_str()), ("compress:flate2:0:64k", "compress:flate2:9:64k"));
+        assert!(names.iter().all(|name| find_benchmark(name).is_ok()));
+        assert!(level_names(&[]).unwrap().len() > names.len());
+        assert!(level_names(&["nope".to_owned()]).is_err());
+    }
+
+    #[test]
+    fn test_roundtrip_paths_agree() {
+        let schema = Schema::parse_str(tweet::SCHEMA).unwrap();
+        let line = r#"{"created_at":"Sat May 26 19:54:01 +0000 2018","id":1000514497163542528,"id_str":"1000514497163542528",
+            "text":"We are the champions","user":{"id":7,"id_str":"7","name":"LFC","screen_name":"LFC","followers_count":10},
+            "entities":{"hashtags":[{"text":"UCLfinal","indices":[0,9]}],"urls":[],"user_mentions":[]},"lang":"en"}"#;
+        let tweet: Tweet = serde_json::from_str(line).unwrap();
+        assert_eq!(tweet.user.followers_count, Some(10));
+        assert_eq!(tweet.coordinates, None);
+        let typed = avro_rs::to_avro_datum(&schema, avro_rs::to_value(tweet).unwrap()).unwrap();
+        let dynamic = avro_rs::to_avro_datum(&schema, json_to_avro(&json::parse(line).unwrap(), &schema).unwrap()).unwrap();
+        assert_eq!(typed, dynamic);
+    }
+
+    #[test]
+    fn test_run_interleaved() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("tweets.json.gz").to_string_lossy().into_owned();
+        let mut encoder = flate2::write::GzEncoder::new(fs::File::create(&path).unwrap(), flate2::Compression::default());
+        encoder.write_all(b"{\"id\":1}\n{\"id\":2}\n").unwrap();
+        encoder.finish().unwrap();
+
+        let names: Vec<String> = ["parse:json", "parse:serde", "parse:nope"].iter().map(|name| name.to_string()).collect();
+        let repetitions = Repetitions { warmup: 1, runs: 4, ..Repetitions::default() };
+        let runs = run_interleaved(&names, &Input::new(&path, Decoder::Flate2), repetitions, &mut Seed(1).rng("test"), &mut || Ok(()));
+        assert_eq!(runs.len(), 3);
+        for (_, run) in &runs[..2] {
+            let run = run.as_ref().unwrap();
+            assert_eq!(run.measurements.len(), 4);
+            assert_eq!(run.last().records, 2);
+            assert!(run.positions.iter().all(|position| *position < 2));
+        }
+        // every round has one benchmark first and one second
+        let firsts = runs[0].1.as_ref().unwrap().positions.iter().zip(&runs[1].1.as_ref().unwrap().positions).filter(|(a, b)| **a + **b == 1).count();
+        assert_eq!(firsts, 4);
+        assert!(runs[2].1.is_err());
+
+        let input = Input::new(&path, Decoder::Flate2);
+        for name in &["lines:lazy", "lines:eager", "lines:eager:9"] {
+            let run = run_named(name, &input, Repetitions::default(), &mut || Ok(())).unwrap();
+            assert_eq!(run.last().records, if name.ends_with('9') { 1 } else { 2 }, "{}", name);
+        }
+        assert_eq!(find_benchmark("lines:lazy:16m").unwrap().name(), "lines:lazy:16M");
+
+        // the same records written back by every backend
+        let lengths: Vec<u64> = SERIALIZE_BACKENDS.iter().map(|backend| {
+            let run = run_named(&format!("serialize:{}", backend), &input, Repetitions::default(), &mut || Ok(())).unwrap();
+            assert_eq!(run.last().records, 2);
+            run.timings.bytes
+        }).collect();
+        assert_eq!(lengths, vec![18, 18, 18]);
+        let checksums: Vec<u64> = ["zerocopy:simd:owned", "zerocopy:simd:borrowed"].iter().map(|name| {
+            run_named(name, &input, Repetitions::default(), &mut || Ok(())).unwrap().timings.sink.checksum
+        }).collect();
+        assert_eq!(checksums[0], checksums[1]);
+        let tokens = run_named("tokens:jiter", &input, Repetitions::default(), &mut || Ok(())).unwrap();
+        assert_eq!(tokens.timings.sink.checksum, checksums[0]);
+        let runs: Vec<RepeatedRun> = SIMD_APIS.iter().map(|api| run_named(&format!("simd:{}", api), &input, Repetitions::default(), &mut || Ok(())).unwrap()).collect();
+        assert!(runs.iter().all(|run| run.last().records == 2 && run.timings.errors == 0));
+        assert_eq!(runs[1].timings.sink.checksum, runs[2].timings.sink.checksum);
+        assert_eq!(runs[2].timings.sink.checksum, checksums[0]);
+        let checksums: Vec<u64> = ["parse:serde", "stream:serde"].iter().map(|name| {
+            let run = run_named(name, &input, Repetitions::default(), &mut || Ok(())).unwrap();
+            assert_eq!((run.last().records, run.timings.bytes), (2, 18), "{}", name);
+            run.timings.sink.checksum
+        }).collect();
+        assert_eq!(checksums[0], checksums[1]);
+    }
+
+    #[test]
+    fn test_whole_file() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("tweets.json").to_string_lossy().into_owned();
+        fs::write(&path, "{\"id\":1,\"text\":\"a\"}\n\n[1,2.5,null]\n{\"id\":3}\n").unwrap();
+        let input = Input::new(&path, Decoder::Flate2);
+        let run = |name: &str| run_named(name, &input, Repetitions::default(), &mut || Ok(()));
+
+        let lines = run("parse:serde").unwrap();
+        let documents = run("wholefile:serde:documents").unwrap();
+        assert_eq!((documents.last().records, documents.timings.errors), (3, 0));
+        // the empty line is an error line by line
+        assert_eq!(lines.timings.errors, 1);
+        let array = run("wholefile:simd:array").unwrap();
+        assert_eq!((array.last().records, array.timings.bytes), (3, documents.timings.bytes));
+        assert_ne!(array.timings.sink.checksum, 0);
+
+        fs::write(&path, "{\"id\":1}\nnot json\n").unwrap();
+        assert_eq!(run("wholefile:json:documents").unwrap().timings.errors, 1);
+        assert!(run("wholefile:json:array").is_err());
+    }
+
+    #[test]
+    fn test_fields() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("tweets.json").to_string_lossy().into_owned();
+        fs::write(&path, concat!(
+            r#"{"retweeted_status":{"text":"inner","user":{"screen_name":"x"},"retweet_count":5},"text":"RT \"caf\u00e9\"","#,
+            r#""user":{"id":1,"screen_name":"fan"},"retweet_count":12}"#, "\n",
+            r#"{"delete":{"status":{"id":1}}}"#, "\n",
+            r#"{"user":{"screen_name":"LFC"},"text":"plain","retweet_count":0}"#, "\n"
+        )).unwrap();
+        let input = Input::new(&path, Decoder::Flate2);
+        let checksums: Vec<u64> = FIELDS_BACKENDS.iter().map(|backend| {
+            let run = run_named(&format!("fields:{}", backend), &input, Repetitions::default(), &mut || Ok(())).unwrap();
+            assert_eq!((run.last().records, run.timings.errors), (3, 1), "{}", backend);
+            run.timings.sink.checksum
+        }).collect();
+        assert!(checksums.iter().all(|checksum| *checksum == checksums[0]), "{:?}", checksums);
+    }
+
+    #[test]
+    fn test_numbers() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("tweets.json").to_string_lossy().into_owned();
+        fs::write(&path, "{\"id\":1000514497163542528,\"counts\":[1,-2]}\n{\"id\":9007199254740993}\n{\"a\":1.0,\"b\":[2.5,3e2]}\n").unwrap();
+        let input = Input::new(&path, Decoder::Flate2);
+        let runs: Vec<(usize, u64)> = NUMBER_STRATEGIES.iter().map(|strategy| {
+            let benchmark = NumbersBenchmark { strategy: *strategy }.name();
+            let run = run_named(&benchmark, &input, Repetitions::default(), &mut || Ok(())).unwrap();
+            (run.timings.errors, run.timings.sink.checksum)
+        }).collect();
+        // the exact strategies agree, f64 loses both ids
+        assert_eq!(runs[0], runs[1]);
+        assert_eq!((runs[0].0, runs[2].0), (0, 2));
+    }
+
+    #[test]
+    fn test_reserialize() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("tweets.json").to_string_lossy().into_owned();
+        fs::write(&path, concat!(
+            r#"{"id":1000514497163542528,"text":"caf\u00e9 \"RT\"","user":{"b":null,"a":[true,1.0,-2.5]}}"#, "\n",
+            "not json\n",
+            r#"{"z":{},"y":[],"x":""}"#, "\n"
+        )).unwrap();
+        let input = Input::new(&path, Decoder::Flate2);
+        for backend in &SERIALIZE_BACKENDS {
+            let run = run_named(&format!("reserialize:{}", backend), &input, Repetitions::default(), &mut || Ok(())).unwrap();
+            assert_eq!((run.last().records, run.timings.errors), (3, 1), "{}", backend);
+        }
+
+        let same = |a: &str, b: &str| same_json(&serde_json::from_str(a).unwrap(), &serde_json::from_str(b).unwrap());
+        assert!(same(r#"{"a":1,"b":[1.0,"x"]}"#, r#"{"b":[1,"x"],"a":1.0}"#));
+        assert!(!same("[1000514497163542528]", "[1000514497163542529]"));
+        assert!(!same("[1000514497163542528]", "[1.000514497163542528e18]"));
+        assert!(!same(r#"{"a":1}"#, r#"{"a":1,"b":null}"#));
+    }
+
+    #[test]
+    fn test_latency_histogram() {
+        let mut timings = RecordTimings::default();
+        timings.record(Duration::from_nanos(1));
+        timings.record(Duration::from_nanos(3));
+        timings.record(Duration::from_nanos(1024));
+        assert_eq!(timings.histogram.len(), 11);
+        assert_eq!(timings.histogram[0], 1);
+        assert_eq!(timings.histogram[1], 1);
+        assert_eq!(timings.histogram[10], 1);
+        assert!(timings.percentiles().is_none());
+
+        let mut timings = RecordTimings::with_latency();
+        for micros in 1..=1000 {
+            timings.record(Duration::from_micros(micros));
+        }
+        let percentiles = timings.percentiles().unwrap();
+        // within the 3 significant digits the histogram keeps
+        assert!((percentiles.p50_ns as f64 - 500_000.0).abs() < 1000.0);
+        assert!((percentiles.p999_ns as f64 - 999_000.0).abs() < 1000.0);
+        assert!((percentiles.max_ns as f64 - 1_000_000.0).abs() < 1000.0);
+    }
+
+    #[test]
+    fn test_compression_ratio() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("tweets.json.gz").to_string_lossy().into_owned();
+        let mut encoder = flate2::write::GzEncoder::new(fs::File::create(&path).unwrap(), flate2::Compression::default());
+        for id in 0..100 {
+            writeln!(encoder, "{{\"id\":{},\"text\":\"the same text in every record\"}}", id).unwrap();
+        }
+        encoder.finish().unwrap();
+        let input = Input::new(&path, Decoder::Flate2);
+
+        let blocks = run_named("compress:zstd:3:64k", &input, Repetitions::default(), &mut || Ok(())).unwrap();
+        assert!(blocks.timings.compression_ratio().unwrap() > 5.0);
+        let lines = run_named("compress:zstd:3", &input, Repetitions::default(), &mut || Ok(())).unwrap();
+        // every record compressed on its own shares nothing with the others
+        assert!(lines.timings.compression_ratio().unwrap() < blocks.timings.compression_ratio().unwrap());
+        assert_eq!(BenchResult::new(&lines, 0).compression_ratio, lines.timings.compression_ratio());
+        assert!(run_named("read", &input, Repetitions::default(), &mut || Ok(())).unwrap().timings.compression_ratio().is_none());
+    }
+
+    #[test]
+    fn test_find_benchmark() {
+        assert_eq!(find_benchmark("parse:serde").unwrap().name(), "parse:serde");
+        assert_eq!(find_benchmark("compress:zstd:19").unwrap().name(), "compress:zstd:19");
+        assert_eq!(find_benchmark("compress:zstd").unwrap().name(), "compress:zstd:3");
+        assert_eq!(find_benchmark("compress:zstd:3:64k").unwrap().name(), "compress:zstd:3:64k");
+        assert_eq!(find_benchmark("compress:zstd:3:1000").unwrap().name(), "compress:zstd:3:1000");
+        assert!(find_benchmark("compress:zstd:3:0").is_err());
+        assert_eq!(find_benchmark("window:3600").unwrap().name(), "window:3600");
+        assert_eq!(find_benchmark("extract:scan:lang").unwrap().name(), "extract:scan:lang");
+        assert!(find_benchmark("extract:regex:lang").is_err());
+        assert!(find_benchmark("compress:zstd:99").is_err());
+        assert!(find_benchmark("parse:nope").is_err());
+        let names: Vec<String> = benchmarks().iter().map(|b| b.name()).collect();
+        assert!(names.contains(&"canonicalize:simd".to_owned()));
+    }
+
+    #[test]
+    fn test_filter() {
+        let lines: Vec<String> = (0..200)
+            .map(|i| format!(r#"{{"id_str":"99{}","retweeted_status":{{"id_str":"1"}}}}"#, 1000 + i))
+            .collect();
+        for selectivity in &[1, 10, 37, 100] {
+            let pattern = selectivity_regex(*selectivity);
+            let parsed = lines.iter().filter(|line| selected(&json::parse(line).unwrap(), *selectivity)).count();
+            let prefiltered = lines.iter()
+                .filter(|line| pattern.is_match(line) && selected(&json::parse(line).unwrap(), *selectivity))
+                .count();
+            assert_eq!(parsed, 2 * *selectivity as usize);
+            assert_eq!(prefiltered, parsed);
+        }
+        assert_eq!(find_benchmark("filter:regex:25").unwrap().name(), "filter:regex:25");
+        assert!(find_benchmark("filter:regex:0").is_err());
+    }
+
+    #[test]
+    fn test_append_csv() {
+        let stats = Stats::of(&[Duration::from_millis(500)]).unwrap();
+        let result = BenchResult {
+            name: "parse:serde".to_owned(),
+            kind: "parse".to_owned(),
+            parameters: vec!["serde".to_owned()],
+            records: 1000,
+            errors: 0,
+            elapsed_ms: 500.0,
+            records_per_second: 2000.0,
+            input_bytes_per_second: 4_000_000.0,
+            decompressed_bytes_per_second: 40_000_000.0,
+            joules: None,
+            allocations: Allocations::default(),
+            latency: None,
+            passes: 1,
+            checksum: 0,
+            samples_ms: Vec::new(),
+            perf: None,
+            positions: Vec::new(),
+            compression_ratio: None,
+            stats
+        };
+        let results = BenchResults {
+            input: "tweets.json.gz".to_owned(),
+            input_bytes: 2_000_000,
+            decoder: Decoder::Flate2,
+            warmup: 0,
+            runs: 1,
+            results: vec![result],
+            failed: BTreeMap::new()
+        };
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("results.csv").to_string_lossy().into_owned();
+        append_csv(&path, &results).unwrap();
+        append_csv(&path, &results).unwrap();
+        let text = fs::read_to_string(&path).unwrap();
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0], "timestamp,benchmark,dataset,records,bytes,elapsed_ms,mb_per_second");
+        assert!(lines[2].ends_with(",parse:serde,tweets.json.gz,1000,20000000,500.000,4.000"));
+    }
+}
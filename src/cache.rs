@@ -0,0 +1,116 @@
+use std::fs::File;
+use std::io::{self, Read};
+use serde::{Deserialize, Serialize};
+
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CacheState {
+    // input read once before the run so it is served from the page cache
+    Warm,
+    // input evicted from the page cache before the run
+    Cold
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PageCache {
+    Warm,
+    Cold,
+    // cold run followed by a warm one, reported separately
+    Both
+}
+
+impl Default for PageCache {
+    fn default() -> Self {
+        PageCache::Warm
+    }
+}
+
+impl PageCache {
+    pub fn states(self) -> Vec<CacheState> {
+        match self {
+            PageCache::Warm => vec![CacheState::Warm],
+            PageCache::Cold => vec![CacheState::Cold],
+            PageCache::Both => vec![CacheState::Cold, CacheState::Warm]
+        }
+    }
+}
+
+
+// Returns the state the run gets: a cold run falls back to a warm one where the cache can't be
+// dropped, unsupported or not permitted, so it is not reported as cold.
+pub fn prepare(file_path: &str, state: CacheState) -> io::Result<CacheState> {
+    prepare_with(file_path, state, evict)
+}
+
+fn prepare_with(file_path: &str, state: CacheState, evict: fn(&str) -> io::Result<()>) -> io::Result<CacheState> {
+    match state {
+        CacheState::Warm => warm(file_path).map(|_| CacheState::Warm),
+        CacheState::Cold => match evict(file_path) {
+            Ok(()) => Ok(CacheState::Cold),
+            Err(e) if e.kind() == io::ErrorKind::PermissionDenied || e.kind() == io::ErrorKind::Unsupported => {
+                warm(file_path).map(|_| CacheState::Warm)
+            },
+            Err(e) => Err(e)
+        }
+    }
+}
+
+pub fn warm(file_path: &str) -> io::Result<()> {
+    let mut file = File::open(file_path)?;
+    let mut buffer = vec![0; 1 << 20];
+    while file.read(&mut buffer)? > 0 {}
+    Ok(())
+}
+
+// Asks the kernel to drop the cached pages of the file. Dirty pages can't be dropped, so they are
+// written back first. Unlike dropping the whole cache this does not need root.
+#[cfg(target_os = "linux")]
+pub fn evict(file_path: &str) -> io::Result<()> {
+    use std::os::unix::io::AsRawFd;
+
+    let file = File::open(file_path)?;
+    file.sync_data()?;
+    let result = unsafe { libc::posix_fadvise(file.as_raw_fd(), 0, 0, libc::POSIX_FADV_DONTNEED) };
+    if result != 0 {
+        return Err(io::Error::from_raw_os_error(result));
+    }
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn evict(_file_path: &str) -> io::Result<()> {
+    Err(io::Error::new(io::ErrorKind::Unsupported, "page cache eviction is only supported on Linux"))
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_states() {
+        assert_eq!(PageCache::default().states(), vec![CacheState::Warm]);
+        assert_eq!(PageCache::Both.states(), vec![CacheState::Cold, CacheState::Warm]);
+        assert_eq!(serde_json::from_str::<PageCache>("\"both\"").unwrap(), PageCache::Both);
+        assert_eq!(serde_json::to_string(&CacheState::Cold).unwrap(), "\"cold\"");
+    }
+
+    #[test]
+    fn test_prepare_fallback() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("input.json").to_string_lossy().into_owned();
+        std::fs::write(&path, "{}\n").unwrap();
+
+        assert_eq!(prepare_with(&path, CacheState::Cold, |_| Ok(())).unwrap(), CacheState::Cold);
+        assert_eq!(prepare_with(&path, CacheState::Warm, |_| panic!("not evicted")).unwrap(), CacheState::Warm);
+        let denied: fn(&str) -> io::Result<()> = |_| Err(io::Error::from(io::ErrorKind::PermissionDenied));
+        assert_eq!(prepare_with(&path, CacheState::Cold, denied).unwrap(), CacheState::Warm);
+        let unsupported: fn(&str) -> io::Result<()> = |_| Err(io::Error::from(io::ErrorKind::Unsupported));
+        assert_eq!(prepare_with(&path, CacheState::Cold, unsupported).unwrap(), CacheState::Warm);
+        let failed: fn(&str) -> io::Result<()> = |_| Err(io::Error::from(io::ErrorKind::InvalidInput));
+        assert!(prepare_with(&path, CacheState::Cold, failed).is_err());
+        assert!(prepare_with(&dir.path().join("missing").to_string_lossy(), CacheState::Warm, evict).is_err());
+    }
+}
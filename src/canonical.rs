@@ -0,0 +1,257 @@
+use std::io::Write;
+use std::fmt::Write as FmtWrite;
+use json::JsonValue;
+use json::number::Number;
+use serde_json::Value;
+use simd_json::StaticNode;
+use simd_json::value::borrowed::Value as BorrowedValue;
+use failure::{Error, bail};
+use crate::convert::RecordWriter;
+
+
+// JSON Canonicalization Scheme (RFC 8785): object members sorted by the UTF-16 code units of their
+// names, no insignificant whitespace, ECMAScript string escaping and number serialization.
+pub fn canonicalize(json: &JsonValue) -> Result<String, Error> {
+    let mut out = String::new();
+    write_json(json, false, &mut out)?;
+    Ok(out)
+}
+
+// Like canonicalize, with integers written from their exact digits instead of rounded to a double,
+// so distinct ids above 2^53 stay distinct. Not RFC 8785, for hashing.
+pub fn canonicalize_exact(json: &JsonValue) -> Result<String, Error> {
+    let mut out = String::new();
+    write_json(json, true, &mut out)?;
+    Ok(out)
+}
+
+pub fn canonicalize_serde(value: &Value) -> Result<String, Error> {
+    let mut out = String::new();
+    write_serde(value, &mut out)?;
+    Ok(out)
+}
+
+pub fn canonicalize_simd(value: &BorrowedValue) -> Result<String, Error> {
+    let mut out = String::new();
+    write_simd(value, &mut out)?;
+    Ok(out)
+}
+
+
+fn write_json(json: &JsonValue, exact: bool, out: &mut String) -> Result<(), Error> {
+    match json {
+        JsonValue::Null => out.push_str("null"),
+        JsonValue::Boolean(b) => out.push_str(if *b { "true" } else { "false" }),
+        JsonValue::Number(number) => match exact_integer(number).filter(|_| exact) {
+            Some(integer) => write!(out, "{}", integer).unwrap(),
+            None => write_number(f64::from(*number), out)?
+        },
+        JsonValue::String(_) | JsonValue::Short(_) => write_string(json.as_str().unwrap(), out),
+        JsonValue::Array(items) => {
+            out.push('[');
+            for (i, item) in items.iter().enumerate() {
+                if i > 0 { out.push(','); }
+                write_json(item, exact, out)?;
+            }
+            out.push(']');
+        },
+        JsonValue::Object(_) => {
+            let mut entries: Vec<(&str, &JsonValue)> = json.entries().collect();
+            entries.sort_by(|a, b| a.0.encode_utf16().cmp(b.0.encode_utf16()));
+            out.push('{');
+            for (i, (key, value)) in entries.into_iter().enumerate() {
+                if i > 0 { out.push(','); }
+                write_string(key, out);
+                out.push(':');
+                write_json(value, exact, out)?;
+            }
+            out.push('}');
+        }
+    }
+    Ok(())
+}
+
+// The value of an integral number, 1.0 and 10e-1 included, None for fractions and ones that don't fit.
+fn exact_integer(number: &Number) -> Option<i128> {
+    if number.is_nan() {
+        return None;
+    }
+    let (positive, mut mantissa, mut exponent) = number.as_parts();
+    while exponent < 0 && mantissa % 10 == 0 {
+        mantissa /= 10;
+        exponent += 1;
+    }
+    if exponent < 0 {
+        return None;
+    }
+    let value = (mantissa as i128).checked_mul(10i128.checked_pow(exponent as u32)?)?;
+    Some(if positive { value } else { -value })
+}
+
+fn write_serde(value: &Value, out: &mut String) -> Result<(), Error> {
+    match value {
+        Value::Null => out.push_str("null"),
+        Value::Bool(b) => out.push_str(if *b { "true" } else { "false" }),
+        Value::Number(number) => write_number(number.as_f64().unwrap_or(std::f64::NAN), out)?,
+        Value::String(s) => write_string(s, out),
+        Value::Array(items) => {
+            out.push('[');
+            for (i, item) in items.iter().enumerate() {
+                if i > 0 { out.push(','); }
+                write_serde(item, out)?;
+            }
+            out.push(']');
+        },
+        Value::Object(map) => {
+            let mut entries: Vec<(&String, &Value)> = map.iter().collect();
+            entries.sort_by(|a, b| a.0.encode_utf16().cmp(b.0.encode_utf16()));
+            out.push('{');
+            for (i, (key, value)) in entries.into_iter().enumerate() {
+                if i > 0 { out.push(','); }
+                write_string(key, out);
+                out.push(':');
+                write_serde(value, out)?;
+            }
+            out.push('}');
+        }
+    }
+    Ok(())
+}
+
+fn write_simd(value: &BorrowedValue, out: &mut String) -> Result<(), Error> {
+    match value {
+        BorrowedValue::Static(StaticNode::Null) => out.push_str("null"),
+        BorrowedValue::Static(StaticNode::Bool(b)) => out.push_str(if *b { "true" } else { "false" }),
+        BorrowedValue::Static(StaticNode::I64(i)) => write_number(*i as f64, out)?,
+        BorrowedValue::Static(StaticNode::U64(u)) => write_number(*u as f64, out)?,
+        BorrowedValue::Static(StaticNode::F64(f)) => write_number(*f, out)?,
+        BorrowedValue::String(s) => write_string(s, out),
+        BorrowedValue::Array(items) => {
+            out.push('[');
+            for (i, item) in items.iter().enumerate() {
+                if i > 0 { out.push(','); }
+                write_simd(item, out)?;
+            }
+            out.push(']');
+        },
+        BorrowedValue::Object(map) => {
+            let mut entries: Vec<(&str, &BorrowedValue)> = map.iter().map(|(k, v)| (k.as_ref(), v)).collect();
+            entries.sort_by(|a, b| a.0.encode_utf16().cmp(b.0.encode_utf16()));
+            out.push('{');
+            for (i, (key, value)) in entries.into_iter().enumerate() {
+                if i > 0 { out.push(','); }
+                write_string(key, out);
+                out.push(':');
+                write_simd(value, out)?;
+            }
+            out.push('}');
+        }
+    }
+    Ok(())
+}
+
+
+fn write_string(s: &str, out: &mut String) {
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\u{08}' => out.push_str("\\b"),
+            '\t' => out.push_str("\\t"),
+            '\n' => out.push_str("\\n"),
+            '\u{0c}' => out.push_str("\\f"),
+            '\r' => out.push_str("\\r"),
+            c if (c as u32) < 0x20 => { write!(out, "\\u{:04x}", c as u32).unwrap(); },
+            c => out.push(c)
+        }
+    }
+    out.push('"');
+}
+
+// ECMAScript Number::toString of the value rounded to an IEEE double.
+fn write_number(value: f64, out: &mut String) -> Result<(), Error> {
+    if !value.is_finite() {
+        bail!("{} can not be canonicalized", value);
+    }
+    if value == 0.0 {
+        out.push('0');
+        return Ok(());
+    }
+    if value < 0.0 {
+        out.push('-');
+    }
+
+    // shortest round-trip digits, e.g. "1.2345e6"
+    let scientific = format!("{:e}", value.abs());
+    let (mantissa, exponent) = scientific.split_at(scientific.find('e').unwrap());
+    let digits: String = mantissa.chars().filter(|c| *c != '.').collect();
+    let k = digits.len() as i32;
+    let n = exponent[1..].parse::<i32>().unwrap() + 1;
+
+    if k <= n && n <= 21 {
+        out.push_str(&digits);
+        out.extend(std::iter::repeat('0').take((n - k) as usize));
+    } else if 0 < n && n <= 21 {
+        out.push_str(&digits[..n as usize]);
+        out.push('.');
+        out.push_str(&digits[n as usize..]);
+    } else if -6 < n && n <= 0 {
+        out.push_str("0.");
+        out.extend(std::iter::repeat('0').take(-n as usize));
+        out.push_str(&digits);
+    } else {
+        out.push_str(&digits[..1]);
+        if k > 1 {
+            out.push('.');
+            out.push_str(&digits[1..]);
+        }
+        write!(out, "e{}{}", if n - 1 < 0 { '-' } else { '+' }, (n - 1).abs()).unwrap();
+    }
+    Ok(())
+}
+
+
+pub struct CanonicalJsonWriter<W: Write> {
+    output: W
+}
+
+impl<W: Write> CanonicalJsonWriter<W> {
+    pub fn new(output: W) -> Self {
+        CanonicalJsonWriter { output }
+    }
+}
+
+impl<W: Write> RecordWriter for CanonicalJsonWriter<W> {
+    fn write(&mut self, record: &JsonValue) -> Result<(), Error> {
+        writeln!(self.output, "{}", canonicalize(record)?)?;
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<(), Error> {
+        self.output.flush()?;
+        Ok(())
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_canonicalize() {
+        let json = json::parse(r#"{"b": [1e2, 0.000001, 1e21, -1.5, 1e-7], "a": "\u001f\"é", "\r": null}"#).unwrap();
+        assert_eq!(canonicalize(&json).unwrap(), r#"{"\r":null,"a":"\u001f\"é","b":[100,0.000001,1e+21,-1.5,1e-7]}"#);
+
+        let serde: Value = serde_json::from_str(r#"{"b": [1e2, 0.000001, 1e21, -1.5, 1e-7], "a": "\u001f\"é", "\r": null}"#).unwrap();
+        assert_eq!(canonicalize_serde(&serde).unwrap(), canonicalize(&json).unwrap());
+    }
+
+    #[test]
+    fn test_canonicalize_exact() {
+        let json = json::parse(r#"[1000514497163542529, 1.0, -20e-1, 1.5, 1e2]"#).unwrap();
+        assert_eq!(canonicalize(&json).unwrap(), "[1000514497163542500,1,-2,1.5,100]");
+        assert_eq!(canonicalize_exact(&json).unwrap(), "[1000514497163542529,1,-2,1.5,100]");
+    }
+}
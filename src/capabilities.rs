@@ -0,0 +1,102 @@
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use json::JsonValue;
+use crate::parser::Parser;
+
+
+pub struct Probe {
+    pub name: &'static str,
+    pub input: String,
+    // describes the outcome of a successful parse
+    pub check: fn(&JsonValue) -> String
+}
+
+fn duplicate_keys(json: &JsonValue) -> String {
+    match json["a"].as_i64() {
+        Some(1) => "first wins".to_owned(),
+        Some(2) => "last wins".to_owned(),
+        _ => format!("kept {}", json.dump())
+    }
+}
+
+fn exact_number(expected: &'static str) -> impl Fn(&JsonValue) -> String {
+    move |json: &JsonValue| {
+        if json["a"].dump() == expected { "exact".to_owned() } else { format!("rounded to {}", json["a"].dump()) }
+    }
+}
+
+fn accepted(_: &JsonValue) -> String {
+    "accepted".to_owned()
+}
+
+pub fn probes() -> Vec<Probe> {
+    vec![
+        Probe { name: "duplicate keys", input: r#"{"a":1,"a":2}"#.to_owned(), check: duplicate_keys },
+        Probe { name: "int > 2^53", input: r#"{"a":9007199254740993}"#.to_owned(), check: |json| exact_number("9007199254740993")(json) },
+        Probe { name: "int > 2^64", input: r#"{"a":18446744073709551617}"#.to_owned(), check: |json| exact_number("18446744073709551617")(json) },
+        Probe { name: "NaN literal", input: r#"{"a":NaN}"#.to_owned(), check: accepted },
+        Probe { name: "trailing data", input: r#"{"a":1} x"#.to_owned(), check: accepted },
+        Probe { name: "trailing comma", input: r#"{"a":[1,2,],}"#.to_owned(), check: accepted },
+        Probe { name: "lone surrogate", input: r#"{"a":"\ud83d"}"#.to_owned(), check: accepted },
+    ]
+}
+
+fn run(parser: &dyn Parser, input: &str) -> Result<JsonValue, String> {
+    let mut bytes = input.as_bytes().to_vec();
+    match catch_unwind(AssertUnwindSafe(|| parser.parse(&mut bytes))) {
+        Ok(Ok(json)) => Ok(json),
+        Ok(Err(_)) => Err("rejected".to_owned()),
+        Err(_) => Err("panicked".to_owned())
+    }
+}
+
+pub fn probe(parser: &dyn Parser, probe: &Probe) -> String {
+    match run(parser, &probe.input) {
+        Ok(json) => (probe.check)(&json),
+        Err(outcome) => outcome
+    }
+}
+
+const DEPTHS: &[usize] = &[16, 32, 64, 127, 128, 256, 512, 1024, 2048, 4096];
+
+// Deepest array nesting from DEPTHS the parser accepts.
+pub fn max_depth(parser: &dyn Parser) -> String {
+    let mut deepest = None;
+    for depth in DEPTHS {
+        let input = format!("{}{}", "[".repeat(*depth), "]".repeat(*depth));
+        match run(parser, &input) {
+            Ok(_) => deepest = Some(*depth),
+            Err(_) => break
+        }
+    }
+    match deepest {
+        Some(depth) if depth == *DEPTHS.last().unwrap() => format!(">= {}", depth),
+        Some(depth) => depth.to_string(),
+        None => format!("< {}", DEPTHS[0])
+    }
+}
+
+
+// Rows are probes, columns are parsers.
+pub fn capability_matrix(parsers: &[Box<dyn Parser>]) -> Vec<(String, Vec<String>)> {
+    let mut rows: Vec<(String, Vec<String>)> =
+        probes()
+            .iter()
+            .map(|p| (p.name.to_owned(), parsers.iter().map(|parser| probe(parser.as_ref(), p)).collect()))
+            .collect();
+    rows.push(("max depth".to_owned(), parsers.iter().map(|parser| max_depth(parser.as_ref())).collect()));
+    rows
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::parser::JsonRustParser;
+
+    #[test]
+    fn test_probe_json_rust() {
+        let probes = probes();
+        assert_eq!(probe(&JsonRustParser, &probes[0]), "last wins");
+        assert_eq!(probe(&JsonRustParser, &probes[4]), "rejected");
+    }
+}
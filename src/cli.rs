@@ -0,0 +1,573 @@
+use structopt::StructOpt;
+use crate::convert::Tolerance;
+use crate::fallback::Fallback;
+use crate::gate::{parse_target, Target};
+use crate::infer::ArrayPolicy;
+use crate::io::Decoder;
+use crate::measure::parse_duration;
+use crate::limit::parse_size;
+use crate::prune::RareFields;
+use std::time::Duration;
+
+
+#[derive(StructOpt, Debug)]
+#[structopt(name = "json-benchmarks")]
+pub struct Opt {
+    /// seed of every random choice (sampling, shuffling, generation), 0 when not given
+    #[structopt(long, global = true)]
+    pub seed: Option<u64>,
+    /// input lines skipped before any are read, by benchmarks, inference and conversion
+    #[structopt(long, global = true, default_value = "0")]
+    pub skip: usize,
+    /// stop after this many input lines
+    #[structopt(long, global = true)]
+    pub limit: Option<usize>,
+    /// keep each input line with this probability, drawn from --seed
+    #[structopt(long, global = true, default_value = "1.0")]
+    pub sample_rate: f64,
+    /// also write the summary printed to stderr at the end of the command as JSON to this file,
+    /// - for stdout
+    #[structopt(long, global = true)]
+    pub summary_json: Option<String>,
+    /// show a progress bar on stderr with records, MB/s and the time left, for benchmarks that
+    /// read the input line by line and for convert
+    #[structopt(long, global = true)]
+    pub progress: bool,
+    #[structopt(subcommand)]
+    pub command: Option<Command>
+}
+
+
+#[derive(StructOpt, Debug)]
+pub enum Command {
+    /// Runs benchmarks from the registry, e.g. `bench --parser serde` or `bench compress:zstd:19`
+    Bench {
+        /// registry names, see --list
+        names: Vec<String>,
+        /// shorthand for parse:<parser> (repeatable)
+        #[structopt(long = "parser")]
+        parsers: Vec<String>,
+        /// shorthand for compress:<codec>[:<level>[:<block size>]] (repeatable)
+        #[structopt(long = "codec")]
+        codecs: Vec<String>,
+        /// defaults to $INPUT_FILE, then TweetsChampions.json.gz
+        #[structopt(long)]
+        input: Option<String>,
+        /// gzip decoder of the input: flate2 (streaming), libdeflater or zune (whole file)
+        #[structopt(long, default_value = "flate2")]
+        decoder: Decoder,
+        /// passes run before measuring, not counted
+        #[structopt(long, default_value = "0")]
+        warmup: usize,
+        /// measured passes, reported as mean, median, stddev, min and max
+        #[structopt(long, default_value = "1")]
+        runs: usize,
+        /// record the time of every record into an HDR histogram and report p50 to p99.9
+        #[structopt(long)]
+        latency: bool,
+        /// loop over the input, reopening it, until this much time is spent in every run, e.g. 30s,
+        /// and report the steady state throughput
+        #[structopt(long, parse(try_from_str = parse_duration))]
+        duration: Option<Duration>,
+        /// also write the results as JSON, e.g. results.json
+        #[structopt(long)]
+        output: Option<String>,
+        /// append one row per benchmark to this CSV file
+        #[structopt(long)]
+        csv: Option<String>,
+        /// write Markdown comparison tables, one per benchmark kind, to this file
+        #[structopt(long)]
+        markdown: Option<String>,
+        /// also record the results in this SQLite database with the git commit and machine, for
+        /// `history` (needs the results-sqlite feature)
+        #[structopt(long)]
+        store: Option<String>,
+        /// also push the results to this Prometheus pushgateway, e.g. http://localhost:9091
+        #[structopt(long)]
+        push_gateway: Option<String>,
+        /// job the results are pushed under, replacing the job's previous results
+        #[structopt(long, default_value = "json_benchmarks")]
+        push_job: String,
+        /// NAME=VALUE label added to every pushed sample, e.g. instance=bench-01 (repeatable)
+        #[structopt(long = "push-label")]
+        push_labels: Vec<String>,
+        /// keep the results as baselines/<name>.json
+        #[structopt(long)]
+        save_baseline: Option<String>,
+        /// compare with baselines/<name>.json, exiting with 3 when a benchmark regressed
+        #[structopt(long)]
+        compare_baseline: Option<String>,
+        /// slowdown of the median time that counts as a regression, 0.1 is 10%
+        #[structopt(long, default_value = "0.1")]
+        regression_threshold: f64,
+        /// NAME=TARGET, exit with 3 when the benchmark is slower than the target, a median time
+        /// like 120ms or a throughput like 50MB/s, a bare TARGET for every benchmark (repeatable)
+        #[structopt(long, parse(try_from_str = parse_target))]
+        fail_if_slower_than: Vec<(String, Target)>,
+        /// TOML file of "benchmark" = "target" lines like --fail-if-slower-than, "*" for every
+        /// benchmark, overridden by the command line
+        #[structopt(long)]
+        targets: Option<String>,
+        /// run every parse and compress benchmark and print them in one comparison table
+        #[structopt(long, conflicts_with_all = &["names", "parsers", "codecs"])]
+        matrix: bool,
+        /// also run the read baseline, decompression and line splitting alone, and print the
+        /// benchmarks that read lines with its time taken out of theirs
+        #[structopt(long)]
+        net: bool,
+        /// parse with this parser on 1, 2, 4, .. worker threads and print the scaling efficiency, e.g. simd
+        #[structopt(long, conflicts_with_all = &["names", "parsers", "codecs", "matrix"])]
+        scaling: Option<String>,
+        /// the most worker threads of --scaling, defaults to the available cores
+        #[structopt(long, requires = "scaling")]
+        max_threads: Option<usize>,
+        /// compress with every level of each codec, or of the --codecs ones, in blocks as Avro
+        /// does, and print speed against compression ratio
+        #[structopt(long, conflicts_with_all = &["names", "parsers", "matrix", "scaling"])]
+        levels: bool,
+        /// run only the benchmarks whose name matches this regex, e.g. 'simd|zstd', --list included
+        #[structopt(long)]
+        filter: Option<String>,
+        /// keep the benchmark thread on these cores, e.g. 2,3, on Linux
+        #[structopt(long, use_delimiter = true)]
+        pin_cores: Vec<usize>,
+        /// raise the process priority, usually needs root
+        #[structopt(long)]
+        high_priority: bool,
+        /// count instructions, cycles, cache and branch misses of each run, on Linux with perf_event_paranoid <= 2
+        #[structopt(long)]
+        perf: bool,
+        /// sample the one selected benchmark and write a flamegraph of it here, e.g. infer.svg (needs the profile feature)
+        #[structopt(long)]
+        profile: Option<String>,
+        /// run the benchmarks round by round, each round in a random order from --seed, and print
+        /// the time and slot of every run
+        #[structopt(long, conflicts_with = "profile")]
+        interleave: bool,
+        /// print the registered benchmarks and exit
+        #[structopt(long)]
+        list: bool,
+        /// run this suite of --suites, its benchmarks over each of its inputs, with what it leaves
+        /// out taken from the command line
+        #[structopt(long)]
+        suite: Option<String>,
+        /// TOML file of [suite.NAME] tables, benchmarks.toml when not given
+        #[structopt(long)]
+        suites: Option<String>
+    },
+    /// Infers an Avro schema from an NDJSON file
+    Infer {
+        input: String,
+        #[structopt(long, default_value = "inferred_schema")]
+        name: String,
+        /// write the schema here instead of stdout
+        #[structopt(long)]
+        output: Option<String>,
+        /// union, promote or stringify: how arrays of items of several kinds and fields that are
+        /// only sometimes arrays are typed
+        #[structopt(long, default_value = "union")]
+        array_policy: ArrayPolicy
+    },
+    /// Schema inference tools
+    Schema(SchemaCommand),
+    /// Generates Rust structs with serde derives from an Avro schema, given or inferred from an
+    /// NDJSON file, for typed parsing of the corpus
+    Codegen {
+        #[structopt(required_unless = "schema")]
+        input: Option<String>,
+        /// Avro schema file to generate from instead of inferring one
+        #[structopt(long)]
+        schema: Option<String>,
+        #[structopt(long, default_value = "inferred_schema")]
+        name: String,
+        /// add the schema as SCHEMA and the attributes avro-rs' serde support needs
+        #[structopt(long)]
+        avro: bool,
+        /// write the code here instead of stdout
+        #[structopt(long)]
+        output: Option<String>
+    },
+    /// Repartitions an input into N shards, round-robin or by the hash of a key field
+    Split {
+        input: String,
+        #[structopt(long)]
+        shards: usize,
+        /// dotted path of the field to hash, round-robin when not given
+        #[structopt(long)]
+        key: Option<String>,
+        #[structopt(long, default_value = "shard")]
+        output_prefix: String,
+        /// gzip the shards
+        #[structopt(long)]
+        gzip: bool
+    },
+    /// Externally sorts an NDJSON file by a field, spilling sorted chunks to temporary files
+    Sort {
+        input: String,
+        #[structopt(long)]
+        output: String,
+        /// dotted path of the sort key
+        #[structopt(long)]
+        key: String,
+        /// records sorted in memory before spilling
+        #[structopt(long, default_value = "100000")]
+        chunk_records: usize,
+        #[structopt(long)]
+        gzip: bool
+    },
+    /// Hashes or redacts field paths, keeping the structure and the value types
+    Anonymize {
+        input: String,
+        #[structopt(long)]
+        output: String,
+        /// dotted field path to replace with a salted hash, "[]" marks arrays (repeatable)
+        #[structopt(long = "hash")]
+        hash: Vec<String>,
+        /// dotted field path to mask (repeatable)
+        #[structopt(long = "redact")]
+        redact: Vec<String>,
+        #[structopt(long, default_value = "")]
+        salt: String,
+        #[structopt(long)]
+        gzip: bool
+    },
+    /// Fabricates NDJSON records matching an Avro schema
+    Generate {
+        #[structopt(long)]
+        from_schema: String,
+        #[structopt(long)]
+        output: String,
+        #[structopt(long, default_value = "10000")]
+        records: usize,
+        #[structopt(long, default_value = "2.0")]
+        mean_array_length: f64,
+        #[structopt(long, default_value = "0.3")]
+        null_probability: f64,
+        #[structopt(long)]
+        gzip: bool
+    },
+    /// Shuffles an NDJSON file, or takes a shuffled reservoir sample of it
+    Shuffle {
+        input: String,
+        #[structopt(long)]
+        output: String,
+        /// reservoir sample size, a full external shuffle when not given
+        #[structopt(long)]
+        sample: Option<usize>,
+        /// bucket files used by the external shuffle
+        #[structopt(long, default_value = "64")]
+        buckets: usize,
+        #[structopt(long)]
+        gzip: bool
+    },
+    /// Randomly interleaves several corpora in the given ratios
+    Interleave {
+        inputs: Vec<String>,
+        #[structopt(long)]
+        output: String,
+        /// relative weight per input, e.g. 3,1
+        #[structopt(long, use_delimiter = true)]
+        ratios: Vec<f64>,
+        #[structopt(long)]
+        gzip: bool
+    },
+    /// Probes every parser backend with a conformance micro-suite and prints a capability matrix
+    Capabilities {
+        /// also time every parser over this file
+        #[structopt(long)]
+        input: Option<String>
+    },
+    /// Sweeps codecs and levels over a sample and recommends one that meets a constraint
+    RecommendCodec {
+        input: String,
+        #[structopt(long, default_value = "10000")]
+        sample_records: usize,
+        /// best ratio among codecs at least this fast (MB/s)
+        #[structopt(long)]
+        min_speed: Option<f64>,
+        /// fastest codec reaching at least this ratio
+        #[structopt(long)]
+        min_ratio: Option<f64>,
+        /// write the whole input to this file with the recommended codec
+        #[structopt(long)]
+        recompress: Option<String>
+    },
+    /// Aggregates records into tumbling time windows: counts per group and the most frequent values
+    Window {
+        input: String,
+        /// epoch milliseconds or a tweet created_at string
+        #[structopt(long, default_value = "timestamp_ms")]
+        time_field: String,
+        #[structopt(long, default_value = "60")]
+        window_seconds: i64,
+        #[structopt(long, default_value = "lang")]
+        group_by: String,
+        /// path of the values to rank, [] steps into arrays
+        #[structopt(long, default_value = "entities.hashtags[].text")]
+        top_of: String,
+        #[structopt(long, default_value = "10")]
+        top_k: usize
+    },
+    /// Shows how many compressed bytes each top level field costs, encoded and compressed as a
+    /// column of its own
+    FieldSizes {
+        input: String,
+        /// Avro schema file, inferred from the sample when not given
+        #[structopt(long)]
+        schema: Option<String>,
+        #[structopt(long, default_value = "10000")]
+        sample_records: usize,
+        #[structopt(long, default_value = "zstd")]
+        codec: String,
+        /// the codec's default level when not given
+        #[structopt(long)]
+        level: Option<i32>
+    },
+    /// Runs a suite config without interactive output, writes report.json to its report_dir and
+    /// exits with 1 on errors, 2 when the error rate is over threshold, 3 on a regression
+    Batch {
+        config: String,
+        /// overrides $INPUT_FILE and the input of the suite config
+        #[structopt(long)]
+        input: Option<String>,
+        /// show live progress, latency histograms and baseline deltas (needs the tui feature)
+        #[structopt(long)]
+        tui: bool,
+        /// report.json of an earlier run to compare against in the TUI
+        #[structopt(long)]
+        baseline: Option<String>,
+        /// keep the benchmark thread on these cores, e.g. 2,3, on Linux
+        #[structopt(long, use_delimiter = true)]
+        pin_cores: Vec<usize>,
+        /// raise the process priority, usually needs root
+        #[structopt(long)]
+        high_priority: bool
+    },
+    /// Checks every compiled in parser and codec backend, schema inference and conversion on a
+    /// small generated corpus
+    SelfTest,
+    /// Prints how a benchmark's times developed over the runs recorded with bench --store
+    History {
+        benchmark: String,
+        #[structopt(long, default_value = "results.db")]
+        db: String,
+        /// runs of this machine, this one when not given
+        #[structopt(long, conflicts_with = "all_machines")]
+        machine: Option<String>,
+        /// runs of every machine
+        #[structopt(long)]
+        all_machines: bool,
+        /// the most recent runs shown
+        #[structopt(long, default_value = "20")]
+        limit: usize
+    },
+    /// Combines batch report.json files into one Parquet table, one row per benchmark run
+    ExportResults {
+        reports: Vec<String>,
+        #[structopt(long, default_value = "results.parquet")]
+        output: String
+    },
+    /// Converts NDJSON to Avro, with a given schema or one inferred from the input
+    Convert {
+        input: String,
+        #[structopt(long, required_unless = "dry-run")]
+        output: Option<String>,
+        /// Avro schema file, inferred from the input when not given
+        #[structopt(long)]
+        schema: Option<String>,
+        /// fill defaults, drop unknown fields and parse numeric strings to fit the schema, skipping
+        /// records that still don't fit instead of failing
+        #[structopt(long, conflicts_with = "strict")]
+        repair: bool,
+        /// fail on any record with fields the schema does not declare
+        #[structopt(long)]
+        strict: bool,
+        /// with --strict, write rejected records to this NDJSON file and keep going, each with the
+        /// source file, line and unknown fields
+        #[structopt(long, requires = "strict")]
+        dead_letter: Option<String>,
+        /// map or raw: when the inferred top level is more heterogeneous than the threshold, write
+        /// a map of the top level fields or the raw JSON text instead of records
+        #[structopt(long, conflicts_with_all = &["schema", "repair", "strict"])]
+        fallback: Option<Fallback>,
+        /// share of irregular top level fields above which --fallback applies
+        #[structopt(long, default_value = "0.5")]
+        heterogeneity_threshold: f64,
+        /// provenance fields added to every record as _<field>: all or a comma separated list of
+        /// ingest_time, source_file, line_number and content_hash
+        #[structopt(long, conflicts_with = "fallback")]
+        envelope: Option<String>,
+        /// parsers tried in order on every line for inference and conversion, e.g.
+        /// simd,serde,lenient, json-rust alone when not given
+        #[structopt(long)]
+        parser_chain: Option<String>,
+        /// stop after writing this many records; limited conversions write <output>.manifest.json
+        #[structopt(long)]
+        max_records: Option<usize>,
+        /// stop once the output reaches this size, e.g. 64M, overshooting by up to an Avro block
+        #[structopt(long, parse(try_from_str = parse_size))]
+        max_output_bytes: Option<u64>,
+        /// union, promote or stringify, applied in inference and conversion alike, see infer
+        #[structopt(long, default_value = "union", conflicts_with = "fallback")]
+        array_policy: ArrayPolicy,
+        /// leave fields present in fewer than this share of the records out of the inferred
+        /// schema, 0.01 being 1%
+        #[structopt(long, conflicts_with_all = &["schema", "fallback"])]
+        min_presence: Option<f64>,
+        /// discard or catch-all: what --min-presence does with the values of the dropped fields,
+        /// catch-all keeps them as JSON text in a _rare_fields map
+        #[structopt(long, default_value = "catch-all")]
+        rare_fields: RareFields,
+        /// fail, skip or repair: what happens to lines that do not parse, repair trying the
+        /// lenient parser on them; skipped and repaired ones are counted in the report
+        #[structopt(long, default_value = "fail")]
+        tolerance: Tolerance,
+        /// convert without writing, scoring how well every record fits the schema: fields coerced,
+        /// missing and extra, and records that would not convert
+        #[structopt(long, conflicts_with_all = &["output", "repair", "strict", "fallback"])]
+        dry_run: bool,
+        /// write the records as RFC 8785 canonical JSON lines instead of Avro, sorted keys and
+        /// canonical numbers, for hashing or signing them; there is no schema to infer
+        #[structopt(long, conflicts_with_all = &["schema", "repair", "strict", "fallback", "envelope", "min-presence", "max-records", "max-output-bytes", "dry-run"])]
+        canonical: bool,
+        /// write "<record number>\t<hash>" lines to this file, the SHA-256 of the canonical form of
+        /// every record written, for dedupe and change detection
+        #[structopt(long, conflicts_with_all = &["dry-run", "canonical"])]
+        hash_sidecar: Option<String>,
+        /// add the hash of --hash-sidecar to every record as a string field of this name instead,
+        /// the output schema gets the field too
+        #[structopt(long, conflicts_with_all = &["hash-sidecar", "dry-run", "canonical"])]
+        hash_field: Option<String>
+    },
+    /// Reshapes records with a chain of transforms, applied in the given order
+    Transform {
+        input: String,
+        #[structopt(long)]
+        output: String,
+        /// rename:PATTERN=REPLACEMENT, flatten[:SEPARATOR], explode:PATH, enrich:PATH=FILE[,KEY_COLUMN],
+        /// encrypt:PATH[,PATH..]=KEY_FILE (AES-256-GCM), decrypt:PATH[,PATH..]=KEY_FILE reversing it or
+        /// hmac:PATH[,PATH..]=KEY_FILE (repeatable); key files hold the key as hex, e.g. from openssl
+        /// rand -hex 32
+        #[structopt(long = "transform")]
+        transforms: Vec<String>,
+        /// write Avro with a schema inferred from the transformed records instead of NDJSON
+        #[structopt(long)]
+        avro: bool,
+        /// gzip the NDJSON output, Avro blocks are always deflated
+        #[structopt(long)]
+        gzip: bool
+    },
+    /// Routes records by the value of a discriminator field into one Avro file per route, each with
+    /// a schema inferred from the records of that route only
+    Route {
+        input: String,
+        /// dotted path of the discriminator, e.g. type
+        #[structopt(long)]
+        key: String,
+        /// routes are written to <prefix>-<route>.avro with the schema in <prefix>-<route>.avsc
+        #[structopt(long, default_value = "route")]
+        output_prefix: String
+    },
+    /// Converts the input once and writes the records in several formats at the same time, to
+    /// compare sizes and write times of the formats without parsing the input per format
+    Archive {
+        input: String,
+        /// every format is written to <prefix>.<extension>, e.g. archive.deflate.avro
+        #[structopt(long, default_value = "archive")]
+        output_prefix: String,
+        /// avro:null, avro:deflate, avro-json, ndjson, ndjson:COMPRESSOR[:LEVEL] or orc, with the orc
+        /// feature (repeatable), by default avro:null, avro:deflate, avro-json, ndjson and ndjson:zstd
+        #[structopt(long = "format")]
+        formats: Vec<String>,
+        /// Avro schema file, inferred from the input when not given
+        #[structopt(long)]
+        schema: Option<String>
+    },
+    /// Recompresses an NDJSON file, e.g. gzip to zstd, then reads the output back and checks that
+    /// it has the same records, exiting with 1 when it does not
+    Transcode {
+        input: String,
+        #[structopt(long)]
+        output: String,
+        /// none, gzip or a compressor, from the input extension when not given (.gz, .zst, .br,
+        /// .lz4, .sz, .deflate)
+        #[structopt(long)]
+        from: Option<String>,
+        /// format of the output, from its extension when not given
+        #[structopt(long)]
+        to: Option<String>,
+        /// compression level of the output, the format's default when not given
+        #[structopt(long)]
+        level: Option<i32>,
+        /// skip reading the output back
+        #[structopt(long)]
+        no_verify: bool
+    },
+    /// Merges NDJSON files that are each already sorted by a field
+    Merge {
+        inputs: Vec<String>,
+        #[structopt(long)]
+        output: String,
+        #[structopt(long)]
+        key: String,
+        #[structopt(long)]
+        gzip: bool
+    },
+    /// Tests per benchmark whether two bench --output files differ by more than noise, with a
+    /// Mann-Whitney test and a bootstrap interval over the run times, exiting with 3 when a
+    /// benchmark got significantly slower
+    Compare {
+        baseline: String,
+        current: String,
+        /// significance level of the Mann-Whitney test
+        #[structopt(long, default_value = "0.05")]
+        alpha: f64,
+        /// bootstrap resamples for the interval of the change
+        #[structopt(long, default_value = "10000")]
+        resamples: usize
+    },
+    /// Loops over the input with one workload for a fixed time, printing throughput and memory
+    /// checkpoints, to catch leaks and slowdowns that only show under sustained load
+    Soak {
+        /// defaults to $INPUT_FILE, then TweetsChampions.json.gz
+        #[structopt(long)]
+        input: Option<String>,
+        /// parse:PARSER, infer or convert
+        #[structopt(long, default_value = "parse:json")]
+        workload: String,
+        /// how long to keep going, e.g. 30m
+        #[structopt(long, default_value = "10m", parse(try_from_str = parse_duration))]
+        duration: Duration,
+        #[structopt(long, default_value = "30s", parse(try_from_str = parse_duration))]
+        checkpoint_every: Duration,
+        /// also write the checkpoints here as NDJSON
+        #[structopt(long)]
+        output: Option<String>,
+        /// exit with 3 when the throughput of the last checkpoint is this much below the first,
+        /// 0.1 is 10%
+        #[structopt(long)]
+        max_slowdown: Option<f64>
+    },
+    /// Serves schema inference, merging, compatibility checks and a conversion job queue over HTTP
+    /// with JSON bodies: POST /infer, /merge, /compatibility and /jobs, GET /jobs/<id>, POST
+    /// /jobs/<id>/cancel and GET /jobs/<id>/manifest
+    Serve {
+        #[structopt(long, default_value = "127.0.0.1:8080")]
+        address: String,
+        /// where jobs and their manifests are kept, queued jobs are resumed on start
+        #[structopt(long, default_value = "jobs")]
+        state_dir: String
+    }
+}
+
+
+#[derive(StructOpt, Debug)]
+pub enum SchemaCommand {
+    /// Infers the schemas of two corpora and reports structural differences and per-field deltas
+    InferDiff {
+        left: String,
+        right: String,
+        #[structopt(long, default_value = "inferred_schema")]
+        name: String
+    }
+}
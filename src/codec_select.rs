@@ -0,0 +1,113 @@
+use std::cell::Cell;
+use std::io::{self, Write};
+use std::rc::Rc;
+use std::time::Instant;
+use failure::Error;
+use crate::compress::Compressor;
+use crate::io::open_lines;
+
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CodecMeasurement {
+    pub codec: &'static str,
+    pub level: i32,
+    pub ratio: f64,
+    // uncompressed megabytes per second
+    pub speed: f64
+}
+
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Constraint {
+    // best ratio among the codecs compressing at least this many MB/s
+    MinSpeed(f64),
+    // fastest codec reaching at least this ratio
+    MinRatio(f64)
+}
+
+
+pub fn sample(file_path: &str, records: usize) -> Result<Vec<u8>, Error> {
+    let mut sample = Vec::new();
+    for line in open_lines(file_path)?.take(records) {
+        sample.extend_from_slice(line?.as_bytes());
+        sample.push(b'\n');
+    }
+    Ok(sample)
+}
+
+
+// counts compressed bytes without keeping them, the stream encoders need an owned 'static writer
+struct ByteCounter(Rc<Cell<usize>>);
+
+impl Write for ByteCounter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.set(self.0.get() + buf.len());
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+pub fn measure(compressor: &dyn Compressor, level: i32, sample: &[u8]) -> Result<CodecMeasurement, Error> {
+    let compressed = Rc::new(Cell::new(0));
+    let now = Instant::now();
+    let mut encoder = compressor.compress_stream(Box::new(ByteCounter(compressed.clone())), level)?;
+    encoder.write_all(sample)?;
+    encoder.finish()?;
+    let seconds = now.elapsed().as_secs_f64().max(1e-9);
+
+    Ok(CodecMeasurement {
+        codec: compressor.name(),
+        level,
+        ratio: sample.len() as f64 / compressed.get().max(1) as f64,
+        speed: sample.len() as f64 / 1_000_000.0 / seconds
+    })
+}
+
+pub fn sweep(compressors: &[Box<dyn Compressor>], sample: &[u8]) -> Result<Vec<CodecMeasurement>, Error> {
+    let mut measurements = Vec::new();
+    for compressor in compressors {
+        for level in compressor.levels() {
+            measurements.push(measure(compressor.as_ref(), level, sample)?);
+        }
+    }
+    Ok(measurements)
+}
+
+pub fn recommend(measurements: &[CodecMeasurement], constraint: Constraint) -> Option<&CodecMeasurement> {
+    match constraint {
+        Constraint::MinSpeed(min_speed) => {
+            measurements
+                .iter()
+                .filter(|m| m.speed >= min_speed)
+                .max_by(|a, b| a.ratio.partial_cmp(&b.ratio).unwrap())
+        },
+        Constraint::MinRatio(min_ratio) => {
+            measurements
+                .iter()
+                .filter(|m| m.ratio >= min_ratio)
+                .max_by(|a, b| a.speed.partial_cmp(&b.speed).unwrap())
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn measurement(codec: &'static str, ratio: f64, speed: f64) -> CodecMeasurement {
+        CodecMeasurement { codec, level: 1, ratio, speed }
+    }
+
+    #[test]
+    fn test_recommend() {
+        let measurements = vec![measurement("a", 3.0, 500.0), measurement("b", 5.0, 250.0), measurement("c", 8.0, 20.0)];
+        assert_eq!(recommend(&measurements, Constraint::MinSpeed(200.0)).unwrap().codec, "b");
+        assert_eq!(recommend(&measurements, Constraint::MinRatio(4.0)).unwrap().codec, "b");
+        assert_eq!(recommend(&measurements, Constraint::MinRatio(2.0)).unwrap().codec, "a");
+        assert!(recommend(&measurements, Constraint::MinSpeed(1000.0)).is_none());
+    }
+}
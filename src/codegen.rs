@@ -0,0 +1,211 @@
+use std::collections::HashMap;
+use std::fmt::Write;
+use avro_rs::Schema;
+use failure::Error;
+
+
+const KEYWORDS: [&str; 39] = [
+    "as", "async", "await", "break", "const", "continue", "crate", "dyn", "else", "enum", "extern", "false", "fn",
+    "for", "if", "impl", "in", "let", "loop", "match", "mod", "move", "mut", "pub", "ref", "return", "static",
+    "self", "struct", "super", "trait", "true", "type", "unsafe", "use", "where", "while", "abstract", "try"
+];
+
+// Rust source with a serde struct per record of the schema and an enum per Avro enum, the top
+// level record first. With avro the schema is included as SCHEMA and fields are annotated for
+// avro-rs' serde support: bytes through serde_bytes and defaults where the schema has them.
+pub fn generate(schema: &Schema, avro: bool) -> Result<String, Error> {
+    let mut generator = Generator { avro, names: HashMap::new(), items: Vec::new() };
+    let root = generator.type_of(schema)?;
+    let mut source = String::new();
+    writeln!(source, "// Generated by json-benchmarks codegen, the top level record is {}.", root)?;
+    writeln!(source, "use serde::{{Deserialize, Serialize}};")?;
+    if avro {
+        writeln!(source, "\npub const SCHEMA: &str = {:?};", serde_json::to_string(schema)?)?;
+    }
+    for item in &generator.items {
+        write!(source, "\n{}", item)?;
+    }
+    Ok(source)
+}
+
+struct Generator<'s> {
+    avro: bool,
+    // Rust type name to the schema it was generated for, to reuse identical records and rename
+    // different ones with the same name
+    names: HashMap<String, &'s Schema>,
+    items: Vec<String>
+}
+
+impl<'s> Generator<'s> {
+    fn type_of(&mut self, schema: &'s Schema) -> Result<String, Error> {
+        Ok(match schema {
+            Schema::Null => "Option<()>".to_owned(),
+            Schema::Boolean => "bool".to_owned(),
+            Schema::Int => "i32".to_owned(),
+            Schema::Long => "i64".to_owned(),
+            Schema::Float => "f32".to_owned(),
+            Schema::Double => "f64".to_owned(),
+            Schema::Bytes | Schema::Fixed { .. } => "Vec<u8>".to_owned(),
+            Schema::String => "String".to_owned(),
+            Schema::Array(items) => format!("Vec<{}>", self.type_of(items)?),
+            Schema::Map(values) => format!("std::collections::HashMap<String, {}>", self.type_of(values)?),
+            Schema::Union(union) => {
+                let variants: Vec<&Schema> = union.variants().iter().filter(|variant| **variant != Schema::Null).collect();
+                let nullable = variants.len() < union.variants().len();
+                match variants.as_slice() {
+                    [] => "Option<()>".to_owned(),
+                    [variant] if nullable => format!("Option<{}>", self.type_of(variant)?),
+                    [variant] => self.type_of(variant)?,
+                    // values of several types stay untyped
+                    _ if nullable => "Option<serde_json::Value>".to_owned(),
+                    _ => "serde_json::Value".to_owned()
+                }
+            },
+            Schema::Record { name, fields, .. } => {
+                let type_name = match self.claim(&name.name, schema) {
+                    Ok(type_name) => type_name,
+                    Err(type_name) => return Ok(type_name)
+                };
+                let mut item = String::new();
+                writeln!(item, "#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]")?;
+                writeln!(item, "pub struct {} {{", type_name)?;
+                for field in fields {
+                    let field_type = self.type_of(&field.schema)?;
+                    let ident = field_ident(&field.name);
+                    let mut attributes = Vec::new();
+                    if ident.trim_start_matches("r#") != field.name {
+                        attributes.push(format!("rename = \"{}\"", field.name.escape_default()));
+                    }
+                    if self.avro && field.default.is_some() {
+                        attributes.push("default".to_owned());
+                    }
+                    if self.avro && field.schema == Schema::Bytes {
+                        attributes.push("with = \"serde_bytes\"".to_owned());
+                    }
+                    if !attributes.is_empty() {
+                        writeln!(item, "    #[serde({})]", attributes.join(", "))?;
+                    }
+                    writeln!(item, "    pub {}: {},", ident, field_type)?;
+                }
+                writeln!(item, "}}")?;
+                self.items.push(item);
+                type_name
+            },
+            Schema::Enum { name, symbols, .. } => {
+                let type_name = match self.claim(&name.name, schema) {
+                    Ok(type_name) => type_name,
+                    Err(type_name) => return Ok(type_name)
+                };
+                let mut item = String::new();
+                writeln!(item, "#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]")?;
+                writeln!(item, "pub enum {} {{", type_name)?;
+                for symbol in symbols {
+                    let variant = camel_case(symbol);
+                    if variant != *symbol {
+                        writeln!(item, "    #[serde(rename = \"{}\")]", symbol.escape_default())?;
+                    }
+                    writeln!(item, "    {},", variant)?;
+                }
+                writeln!(item, "}}")?;
+                self.items.push(item);
+                type_name
+            },
+            // logical types of other Avro versions
+            #[allow(unreachable_patterns)]
+            _ => "serde_json::Value".to_owned()
+        })
+    }
+
+    // A free type name for a named schema, or Err with the name already generated for it.
+    fn claim(&mut self, name: &str, schema: &'s Schema) -> Result<String, String> {
+        let base = camel_case(name);
+        for suffix in 1.. {
+            let candidate = if suffix == 1 { base.clone() } else { format!("{}{}", base, suffix) };
+            match self.names.get(&candidate) {
+                Some(existing) if *existing == schema => return Err(candidate),
+                Some(_) => continue,
+                None => {
+                    self.names.insert(candidate.clone(), schema);
+                    return Ok(candidate);
+                }
+            }
+        }
+        unreachable!()
+    }
+}
+
+// "id_str" -> "IdStr", "user-mentions" -> "UserMentions"
+fn camel_case(name: &str) -> String {
+    let mut camel: String = name
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|part| !part.is_empty())
+        .map(|part| {
+            let mut chars = part.chars();
+            chars.next().map(|first| first.to_ascii_uppercase().to_string() + chars.as_str()).unwrap_or_default()
+        })
+        .collect();
+    if camel.is_empty() || camel.starts_with(|c: char| c.is_ascii_digit()) {
+        camel.insert(0, 'T');
+    }
+    camel
+}
+
+// "idStr" -> "id_str", "type" -> "r#type", "1st" -> "_1st"
+fn field_ident(name: &str) -> String {
+    let mut ident = String::new();
+    for (i, c) in name.chars().enumerate() {
+        if c.is_ascii_uppercase() {
+            if i > 0 && !ident.ends_with('_') {
+                ident.push('_');
+            }
+            ident.push(c.to_ascii_lowercase());
+        } else if c.is_ascii_alphanumeric() {
+            ident.push(c);
+        } else if !ident.ends_with('_') {
+            ident.push('_');
+        }
+    }
+    if ident.is_empty() || ident.starts_with(|c: char| c.is_ascii_digit()) {
+        ident.insert(0, '_');
+    }
+    if KEYWORDS.contains(&ident.as_str()) {
+        // crate, self and super cannot be raw identifiers
+        if ["crate", "self", "super"].contains(&ident.as_str()) { ident.push('_') } else { ident.insert_str(0, "r#") }
+    }
+    ident
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_generate() {
+        let schema = Schema::parse_str(r#"{"name":"tweet","type":"record","fields":[
+            {"name":"id","type":"long"},
+            {"name":"idStr","type":["null","string"]},
+            {"name":"type","type":{"name":"kind","type":"enum","symbols":["tweet","retweet"]}},
+            {"name":"user","type":{"name":"user","type":"record","fields":[{"name":"name","type":"string"}]}},
+            {"name":"quoted","type":["null",{"name":"quoted","type":"record","fields":[
+                {"name":"user","type":{"name":"user","type":"record","fields":[{"name":"name","type":"string"}]}}]}]},
+            {"name":"coordinates","type":["null","double","string"]},
+            {"name":"blob","type":"bytes","default":""}
+        ]}"#).unwrap();
+        let source = generate(&schema, false).unwrap();
+        assert!(source.contains("pub struct Tweet {\n    pub id: i64,\n    #[serde(rename = \"idStr\")]\n    pub id_str: Option<String>,\n"), "{}", source);
+        assert!(source.contains("    pub r#type: Kind,\n"));
+        assert!(source.contains("    pub quoted: Option<Quoted>,\n"));
+        assert!(source.contains("    pub coordinates: Option<serde_json::Value>,\n"));
+        assert!(source.contains("pub enum Kind {\n    #[serde(rename = \"tweet\")]\n    Tweet,\n"));
+        // the same user record is generated once
+        assert_eq!(source.matches("pub struct User {").count(), 1);
+        assert!(!source.contains("SCHEMA"));
+
+        let source = generate(&schema, true).unwrap();
+        assert!(source.contains("pub const SCHEMA: &str"));
+        assert!(source.contains("    #[serde(default, with = \"serde_bytes\")]\n    pub blob: Vec<u8>,\n"));
+        assert_eq!(field_ident("user-mentions"), "user_mentions");
+        assert_eq!(camel_case("1st"), "T1st");
+    }
+}
@@ -0,0 +1,112 @@
+use avro_rs::Schema;
+use avro_rs::schema::SchemaKind;
+use crate::diff::schema_type;
+
+
+// Why data written with the writer schema could not be read with the reader schema under Avro's
+// schema resolution rules, one entry per path. Empty when the schemas are compatible.
+pub fn incompatibilities(reader: &Schema, writer: &Schema) -> Vec<String> {
+    let mut problems = Vec::new();
+    check("", reader, writer, &mut problems);
+    problems
+}
+
+fn check(path: &str, reader: &Schema, writer: &Schema, problems: &mut Vec<String>) {
+    match (reader, writer) {
+        // every branch the writer may have used needs a reader branch
+        (_, Schema::Union(writer_union)) => {
+            for variant in writer_union.variants() {
+                check(path, reader, variant, problems);
+            }
+        },
+        (Schema::Union(reader_union), _) => {
+            let readable = reader_union.variants().iter().any(|variant| {
+                let mut variant_problems = Vec::new();
+                check(path, variant, writer, &mut variant_problems);
+                variant_problems.is_empty()
+            });
+            if !readable {
+                problems.push(format!("{}: {} is not in the reader union {}", display(path), schema_type(writer), schema_type(reader)));
+            }
+        },
+        (Schema::Record { fields: reader_fields, .. }, Schema::Record { fields: writer_fields, lookup, .. }) => {
+            for field in reader_fields {
+                let field_path = join(path, &field.name);
+                match lookup.get(&field.name) {
+                    Some(idx) => check(&field_path, &field.schema, &writer_fields[*idx].schema, problems),
+                    None if field.default.is_none() => problems.push(format!("{}: missing in the writer schema and without a default", field_path)),
+                    None => {}
+                }
+            }
+        },
+        (Schema::Array(reader_items), Schema::Array(writer_items)) => {
+            check(&format!("{}[]", path), reader_items, writer_items, problems);
+        },
+        (Schema::Map(reader_values), Schema::Map(writer_values)) => {
+            check(&format!("{}{{}}", path), reader_values, writer_values, problems);
+        },
+        (Schema::Enum { symbols: reader_symbols, .. }, Schema::Enum { symbols: writer_symbols, .. }) => {
+            for symbol in writer_symbols.iter().filter(|symbol| !reader_symbols.contains(symbol)) {
+                problems.push(format!("{}: enum symbol {} is unknown to the reader", display(path), symbol));
+            }
+        },
+        (Schema::Fixed { size: reader_size, .. }, Schema::Fixed { size: writer_size, .. }) => {
+            if reader_size != writer_size {
+                problems.push(format!("{}: fixed size {} cannot be read as {}", display(path), writer_size, reader_size));
+            }
+        },
+        _ => {
+            if !promotes(SchemaKind::from(writer), SchemaKind::from(reader)) {
+                problems.push(format!("{}: {} cannot be read as {}", display(path), schema_type(writer), schema_type(reader)));
+            }
+        }
+    }
+}
+
+// the primitive promotions of the Avro specification
+fn promotes(writer: SchemaKind, reader: SchemaKind) -> bool {
+    use SchemaKind::*;
+    writer == reader || matches!((writer, reader),
+        (Int, Long) | (Int, Float) | (Int, Double) | (Long, Float) | (Long, Double) | (Float, Double) |
+        (String, Bytes) | (Bytes, String))
+}
+
+fn join(path: &str, name: &str) -> String {
+    if path.is_empty() { name.to_owned() } else { format!("{}.{}", path, name) }
+}
+
+fn display(path: &str) -> &str {
+    if path.is_empty() { "." } else { path }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_incompatibilities() {
+        let writer = Schema::parse_str(r#"{"name":"t","type":"record","fields":[
+            {"name":"id","type":"int"},
+            {"name":"text","type":["null","string"]},
+            {"name":"tags","type":{"type":"array","items":"string"}}
+        ]}"#).unwrap();
+        let reader = Schema::parse_str(r#"{"name":"t","type":"record","fields":[
+            {"name":"id","type":"long"},
+            {"name":"text","type":["null","string"]},
+            {"name":"lang","type":"string","default":"en"}
+        ]}"#).unwrap();
+        assert!(incompatibilities(&reader, &writer).is_empty());
+
+        let reader = Schema::parse_str(r#"{"name":"t","type":"record","fields":[
+            {"name":"id","type":"int"},
+            {"name":"text","type":"string"},
+            {"name":"lang","type":"string"}
+        ]}"#).unwrap();
+        assert_eq!(incompatibilities(&reader, &writer), vec![
+            "text: null cannot be read as string",
+            "lang: missing in the writer schema and without a default"
+        ]);
+        assert_eq!(incompatibilities(&Schema::Int, &Schema::Long), vec![".: long cannot be read as int"]);
+    }
+}
@@ -0,0 +1,316 @@
+use std::io::{self, Read, Write};
+use std::ops::RangeInclusive;
+use failure::{Error, format_err};
+
+
+// Streaming encoder returned by Compressor::compress_stream. finish writes any buffered data and
+// the format trailer, dropping the encoder without it may produce a truncated stream.
+pub trait StreamEncoder: Write {
+    fn finish(self: Box<Self>) -> io::Result<()>;
+}
+
+
+// A compression codec backend, mirroring the parser abstraction: codec benchmarks compress single
+// blocks (one record per call), output sinks compress a stream. decompress_stream reads what
+// compress_stream wrote.
+pub trait Compressor {
+    fn name(&self) -> &'static str;
+    fn levels(&self) -> RangeInclusive<i32>;
+    fn default_level(&self) -> i32;
+    fn compress_block(&self, input: &[u8], level: i32) -> Result<Vec<u8>, Error>;
+    fn compress_stream(&self, output: Box<dyn Write>, level: i32) -> Result<Box<dyn StreamEncoder>, Error>;
+    fn decompress_stream(&self, input: Box<dyn Read>) -> Result<Box<dyn Read>, Error>;
+}
+
+
+pub struct Flate2;
+
+impl StreamEncoder for flate2::write::DeflateEncoder<Box<dyn Write>> {
+    fn finish(self: Box<Self>) -> io::Result<()> {
+        (*self).finish()?.flush()
+    }
+}
+
+impl Compressor for Flate2 {
+    fn name(&self) -> &'static str { "flate2" }
+    fn levels(&self) -> RangeInclusive<i32> { 0..=9 }
+    fn default_level(&self) -> i32 { 6 }
+
+    fn compress_block(&self, input: &[u8], level: i32) -> Result<Vec<u8>, Error> {
+        let mut encoder = flate2::write::DeflateEncoder::new(Vec::new(), flate2::Compression::new(level as u32));
+        encoder.write_all(input)?;
+        Ok(encoder.finish()?)
+    }
+
+    fn compress_stream(&self, output: Box<dyn Write>, level: i32) -> Result<Box<dyn StreamEncoder>, Error> {
+        Ok(Box::new(flate2::write::DeflateEncoder::new(output, flate2::Compression::new(level as u32))))
+    }
+
+    fn decompress_stream(&self, input: Box<dyn Read>) -> Result<Box<dyn Read>, Error> {
+        Ok(Box::new(flate2::read::DeflateDecoder::new(input)))
+    }
+}
+
+
+pub struct Libdeflater;
+
+// libdeflater only compresses whole buffers, the stream is collected and compressed on finish.
+pub struct BufferedEncoder {
+    buffer: Vec<u8>,
+    output: Box<dyn Write>,
+    compress: Box<dyn Fn(&[u8]) -> Result<Vec<u8>, Error>>
+}
+
+impl Write for BufferedEncoder {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.buffer.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl StreamEncoder for BufferedEncoder {
+    fn finish(mut self: Box<Self>) -> io::Result<()> {
+        let compressed = (self.compress)(&self.buffer).map_err(|e| io::Error::new(io::ErrorKind::Other, e.to_string()))?;
+        self.output.write_all(&compressed)?;
+        self.output.flush()
+    }
+}
+
+impl Compressor for Libdeflater {
+    fn name(&self) -> &'static str { "libdeflater" }
+    fn levels(&self) -> RangeInclusive<i32> { 1..=12 }
+    fn default_level(&self) -> i32 { 6 }
+
+    fn compress_block(&self, input: &[u8], level: i32) -> Result<Vec<u8>, Error> {
+        let lvl = libdeflater::CompressionLvl::new(level).map_err(|_| format_err!("invalid libdeflater level {}", level))?;
+        let mut compressor = libdeflater::Compressor::new(lvl);
+        let mut output = vec![0; compressor.deflate_compress_bound(input.len())];
+        let size = compressor.deflate_compress(input, &mut output).map_err(|e| format_err!("{:?}", e))?;
+        output.truncate(size);
+        Ok(output)
+    }
+
+    fn compress_stream(&self, output: Box<dyn Write>, level: i32) -> Result<Box<dyn StreamEncoder>, Error> {
+        Ok(Box::new(BufferedEncoder {
+            buffer: Vec::new(),
+            output,
+            compress: Box::new(move |input: &[u8]| Libdeflater.compress_block(input, level))
+        }))
+    }
+
+    // raw deflate like the other two deflate backends
+    fn decompress_stream(&self, input: Box<dyn Read>) -> Result<Box<dyn Read>, Error> {
+        Ok(Box::new(flate2::read::DeflateDecoder::new(input)))
+    }
+}
+
+
+pub struct Deflate;
+
+// the deflate crate has three presets instead of numeric levels
+fn deflate_options(level: i32) -> deflate::CompressionOptions {
+    match level {
+        1 => deflate::CompressionOptions::fast(),
+        3 => deflate::CompressionOptions::high(),
+        _ => deflate::CompressionOptions::default()
+    }
+}
+
+impl StreamEncoder for deflate::write::DeflateEncoder<Box<dyn Write>> {
+    fn finish(self: Box<Self>) -> io::Result<()> {
+        (*self).finish()?.flush()
+    }
+}
+
+impl Compressor for Deflate {
+    fn name(&self) -> &'static str { "deflate" }
+    fn levels(&self) -> RangeInclusive<i32> { 1..=3 }
+    fn default_level(&self) -> i32 { 2 }
+
+    fn compress_block(&self, input: &[u8], level: i32) -> Result<Vec<u8>, Error> {
+        Ok(deflate::deflate_bytes_conf(input, deflate_options(level)))
+    }
+
+    fn compress_stream(&self, output: Box<dyn Write>, level: i32) -> Result<Box<dyn StreamEncoder>, Error> {
+        Ok(Box::new(deflate::write::DeflateEncoder::new(output, deflate_options(level))))
+    }
+
+    fn decompress_stream(&self, input: Box<dyn Read>) -> Result<Box<dyn Read>, Error> {
+        Ok(Box::new(flate2::read::DeflateDecoder::new(input)))
+    }
+}
+
+
+pub struct Zstd;
+
+impl StreamEncoder for zstd::stream::Encoder<'static, Box<dyn Write>> {
+    fn finish(self: Box<Self>) -> io::Result<()> {
+        (*self).finish()?.flush()
+    }
+}
+
+impl Compressor for Zstd {
+    fn name(&self) -> &'static str { "zstd" }
+    fn levels(&self) -> RangeInclusive<i32> { 1..=22 }
+    fn default_level(&self) -> i32 { 3 }
+
+    fn compress_block(&self, input: &[u8], level: i32) -> Result<Vec<u8>, Error> {
+        Ok(zstd::bulk::compress(input, level)?)
+    }
+
+    fn compress_stream(&self, output: Box<dyn Write>, level: i32) -> Result<Box<dyn StreamEncoder>, Error> {
+        Ok(Box::new(zstd::stream::Encoder::new(output, level)?))
+    }
+
+    fn decompress_stream(&self, input: Box<dyn Read>) -> Result<Box<dyn Read>, Error> {
+        Ok(Box::new(zstd::stream::read::Decoder::new(input)?))
+    }
+}
+
+
+pub struct Brotli;
+
+const BROTLI_BUFFER_SIZE: usize = 4096;
+const BROTLI_WINDOW: u32 = 22;
+
+impl StreamEncoder for brotli::CompressorWriter<Box<dyn Write>> {
+    fn finish(self: Box<Self>) -> io::Result<()> {
+        (*self).into_inner().flush()
+    }
+}
+
+impl Compressor for Brotli {
+    fn name(&self) -> &'static str { "brotli" }
+    fn levels(&self) -> RangeInclusive<i32> { 0..=11 }
+    fn default_level(&self) -> i32 { 6 }
+
+    fn compress_block(&self, input: &[u8], level: i32) -> Result<Vec<u8>, Error> {
+        let mut writer = brotli::CompressorWriter::new(Vec::new(), BROTLI_BUFFER_SIZE, level as u32, BROTLI_WINDOW);
+        writer.write_all(input)?;
+        Ok(writer.into_inner())
+    }
+
+    fn compress_stream(&self, output: Box<dyn Write>, level: i32) -> Result<Box<dyn StreamEncoder>, Error> {
+        Ok(Box::new(brotli::CompressorWriter::new(output, BROTLI_BUFFER_SIZE, level as u32, BROTLI_WINDOW)))
+    }
+
+    fn decompress_stream(&self, input: Box<dyn Read>) -> Result<Box<dyn Read>, Error> {
+        Ok(Box::new(brotli::Decompressor::new(input, BROTLI_BUFFER_SIZE)))
+    }
+}
+
+
+pub struct Lz4;
+
+impl StreamEncoder for lz4::Encoder<Box<dyn Write>> {
+    fn finish(self: Box<Self>) -> io::Result<()> {
+        let (mut output, result) = (*self).finish();
+        result?;
+        output.flush()
+    }
+}
+
+impl Compressor for Lz4 {
+    fn name(&self) -> &'static str { "lz4" }
+    // 0 is the fast mode, higher levels use lz4hc
+    fn levels(&self) -> RangeInclusive<i32> { 0..=12 }
+    fn default_level(&self) -> i32 { 0 }
+
+    fn compress_block(&self, input: &[u8], level: i32) -> Result<Vec<u8>, Error> {
+        let mode = if level == 0 { None } else { Some(lz4::block::CompressionMode::HIGHCOMPRESSION(level)) };
+        Ok(lz4::block::compress(input, mode, false)?)
+    }
+
+    fn compress_stream(&self, output: Box<dyn Write>, level: i32) -> Result<Box<dyn StreamEncoder>, Error> {
+        Ok(Box::new(lz4::EncoderBuilder::new().level(level as u32).build(output)?))
+    }
+
+    fn decompress_stream(&self, input: Box<dyn Read>) -> Result<Box<dyn Read>, Error> {
+        Ok(Box::new(lz4::Decoder::new(input)?))
+    }
+}
+
+
+pub struct Snappy;
+
+impl StreamEncoder for snap::write::FrameEncoder<Box<dyn Write>> {
+    fn finish(self: Box<Self>) -> io::Result<()> {
+        let mut output = (*self).into_inner().map_err(|e| io::Error::new(io::ErrorKind::Other, e.to_string()))?;
+        output.flush()
+    }
+}
+
+impl Compressor for Snappy {
+    fn name(&self) -> &'static str { "snappy" }
+    fn levels(&self) -> RangeInclusive<i32> { 0..=0 }
+    fn default_level(&self) -> i32 { 0 }
+
+    fn compress_block(&self, input: &[u8], _level: i32) -> Result<Vec<u8>, Error> {
+        Ok(snap::raw::Encoder::new().compress_vec(input)?)
+    }
+
+    fn compress_stream(&self, output: Box<dyn Write>, _level: i32) -> Result<Box<dyn StreamEncoder>, Error> {
+        Ok(Box::new(snap::write::FrameEncoder::new(output)))
+    }
+
+    fn decompress_stream(&self, input: Box<dyn Read>) -> Result<Box<dyn Read>, Error> {
+        Ok(Box::new(snap::read::FrameDecoder::new(input)))
+    }
+}
+
+
+pub fn compressors() -> Vec<Box<dyn Compressor>> {
+    vec![
+        Box::new(Flate2),
+        Box::new(Libdeflater),
+        Box::new(Deflate),
+        Box::new(Zstd),
+        Box::new(Brotli),
+        Box::new(Lz4),
+        Box::new(Snappy)
+    ]
+}
+
+pub fn find_compressor(name: &str) -> Option<Box<dyn Compressor>> {
+    compressors().into_iter().find(|compressor| compressor.name() == name)
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    // the stream encoders need an owned 'static writer
+    struct SharedBuffer(std::rc::Rc<std::cell::RefCell<Vec<u8>>>);
+
+    impl Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.borrow_mut().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_compress_block() {
+        let input = r#"{"text":"RT @ThaiLFC: #WeAreLiverpool #UCLfinal","lang":"th","lang":"th","lang":"th"}"#.repeat(10);
+        for compressor in compressors() {
+            let compressed = compressor.compress_block(input.as_bytes(), compressor.default_level()).unwrap();
+            assert!(compressed.len() < input.len(), "{} did not compress", compressor.name());
+
+            let stream = std::rc::Rc::new(std::cell::RefCell::new(Vec::new()));
+            let mut encoder = compressor.compress_stream(Box::new(SharedBuffer(stream.clone())), compressor.default_level()).unwrap();
+            encoder.write_all(input.as_bytes()).unwrap();
+            encoder.finish().unwrap();
+            let compressed = stream.borrow().clone();
+            let mut decompressed = String::new();
+            compressor.decompress_stream(Box::new(io::Cursor::new(compressed))).unwrap().read_to_string(&mut decompressed).unwrap();
+            assert_eq!(decompressed, input, "{} did not round trip", compressor.name());
+        }
+    }
+}
@@ -0,0 +1,236 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use json::JsonValue;
+use avro_rs::Schema;
+use avro_rs::schema::SchemaKind;
+use failure::Error;
+use crate::avro::{json_kind, json_to_avro};
+use crate::convert::{keep_location, Location, RecordWriter};
+
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct FieldConformity {
+    pub coerced: usize,
+    pub missing: usize,
+    pub extra: usize,
+    pub invalid: usize
+}
+
+impl FieldConformity {
+    pub fn issues(&self) -> usize {
+        self.coerced + self.missing + self.extra + self.invalid
+    }
+}
+
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct RecordScore {
+    pub values: usize,
+    pub coerced: usize,
+    pub missing: usize,
+    pub extra: usize,
+    pub invalid: usize
+}
+
+impl RecordScore {
+    // Share of the record's values (including missing and extra fields) that fit the schema as is.
+    pub fn score(&self) -> f64 {
+        let total = self.values + self.missing + self.extra;
+        if total == 0 {
+            return 1.0;
+        }
+        let issues = self.coerced + self.missing + self.extra + self.invalid;
+        1.0 - issues as f64 / total as f64
+    }
+}
+
+
+#[derive(Debug, Default)]
+pub struct ConformityReport {
+    pub records: usize,
+    pub conforming_records: usize,
+    pub failed_records: usize,
+    pub failed_at: Vec<Location>,
+    pub score_sum: f64,
+    pub min_score: Option<f64>,
+    pub fields: BTreeMap<String, FieldConformity>
+}
+
+impl ConformityReport {
+    pub fn add(&mut self, score: &RecordScore, converted: bool) {
+        let s = score.score();
+        self.records += 1;
+        self.score_sum += s;
+        self.min_score = Some(self.min_score.map_or(s, |min| min.min(s)));
+        if s == 1.0 {
+            self.conforming_records += 1;
+        }
+        if !converted {
+            self.failed_records += 1;
+        }
+    }
+
+    pub fn mean_score(&self) -> f64 {
+        if self.records == 0 { 1.0 } else { self.score_sum / self.records as f64 }
+    }
+
+    fn field(&mut self, path: &str) -> &mut FieldConformity {
+        self.fields.entry(path.to_owned()).or_insert_with(FieldConformity::default)
+    }
+}
+
+impl fmt::Display for ConformityReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "Records: {}", self.records)?;
+        writeln!(f, "Fully conforming: {} ({:.2}%)", self.conforming_records, percent(self.conforming_records, self.records))?;
+        writeln!(f, "Failed conversion: {}", self.failed_records)?;
+        for location in &self.failed_at {
+            writeln!(f, "  at {}", location)?;
+        }
+        writeln!(f, "Mean score: {:.4}, min score: {:.4}", self.mean_score(), self.min_score.unwrap_or(1.0))?;
+
+        let mut fields: Vec<(&String, &FieldConformity)> = self.fields.iter().filter(|(_, c)| c.issues() > 0).collect();
+        fields.sort_by(|a, b| b.1.issues().cmp(&a.1.issues()));
+        if !fields.is_empty() {
+            writeln!(f, "{:<60} {:>10} {:>10} {:>10} {:>10}", "field", "coerced", "missing", "extra", "invalid")?;
+        }
+        for (path, c) in fields {
+            writeln!(f, "{:<60} {:>10} {:>10} {:>10} {:>10}", path, c.coerced, c.missing, c.extra, c.invalid)?;
+        }
+        Ok(())
+    }
+}
+
+fn percent(part: usize, total: usize) -> f64 {
+    if total == 0 { 100.0 } else { part as f64 * 100.0 / total as f64 }
+}
+
+
+pub fn score_record(json: &JsonValue, schema: &Schema, report: &mut ConformityReport) -> RecordScore {
+    let mut score = RecordScore::default();
+    score_value(json, schema, "", &mut score, report);
+    score
+}
+
+fn score_value(json: &JsonValue, schema: &Schema, path: &str, score: &mut RecordScore, report: &mut ConformityReport) {
+    match (json, schema) {
+        (_, Schema::Union(union)) => {
+            let sk = json_kind(json);
+            let variant =
+                union
+                    .variants()
+                    .iter()
+                    .find(|variant| SchemaKind::from(*variant) == sk);
+            match variant {
+                Some(variant) => score_value(json, variant, path, score, report),
+                None => score_scalar(json, schema, path, score, report)
+            }
+        },
+        (JsonValue::Object(_), Schema::Record {fields, lookup, ..}) => {
+            for field in fields {
+                let field_path = join(path, &field.name);
+                if json.has_key(&field.name) {
+                    score_value(&json[field.name.as_str()], &field.schema, &field_path, score, report);
+                } else {
+                    score.missing += 1;
+                    report.field(&field_path).missing += 1;
+                }
+            }
+            for (key, _) in json.entries() {
+                if !lookup.contains_key(key) {
+                    score.extra += 1;
+                    report.field(&join(path, key)).extra += 1;
+                }
+            }
+        },
+        (JsonValue::Object(_), Schema::Map(values_schema)) => {
+            let values_path = format!("{}{{}}", path);
+            for (_, value) in json.entries() {
+                score_value(value, values_schema, &values_path, score, report);
+            }
+        },
+        (JsonValue::Array(items), Schema::Array(items_schema)) => {
+            let items_path = format!("{}[]", path);
+            for item in items {
+                score_value(item, items_schema, &items_path, score, report);
+            }
+        },
+        _ => score_scalar(json, schema, path, score, report)
+    }
+}
+
+fn score_scalar(json: &JsonValue, schema: &Schema, path: &str, score: &mut RecordScore, report: &mut ConformityReport) {
+    score.values += 1;
+    if json_kind(json) == SchemaKind::from(schema) {
+        return;
+    }
+    if json_to_avro(json, schema).is_ok() {
+        score.coerced += 1;
+        report.field(path).coerced += 1;
+    } else {
+        score.invalid += 1;
+        report.field(path).invalid += 1;
+    }
+}
+
+fn join(path: &str, name: &str) -> String {
+    if path.is_empty() { name.to_owned() } else { format!("{}.{}", path, name) }
+}
+
+
+// Dry-run sink: scores every record against the schema and checks that it converts, without
+// writing anything.
+pub struct ConformityWriter<'a> {
+    schema: &'a Schema,
+    location: Location,
+    pub report: ConformityReport
+}
+
+impl<'a> ConformityWriter<'a> {
+    pub fn new(schema: &'a Schema) -> Self {
+        ConformityWriter { schema, location: Location::default(), report: ConformityReport::default() }
+    }
+}
+
+impl<'a> RecordWriter for ConformityWriter<'a> {
+    fn write(&mut self, record: &JsonValue) -> Result<(), Error> {
+        let score = score_record(record, self.schema, &mut self.report);
+        let converted = json_to_avro(record, self.schema).is_ok();
+        self.report.add(&score, converted);
+        if !converted {
+            keep_location(&mut self.report.failed_at, &self.location);
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn locate(&mut self, location: &Location) {
+        self.location = location.clone();
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_score_record() {
+        let schema = Schema::parse_str(r#"{"name":"test","type":"record","fields":[{"name":"a","type":"long"},{"name":"b","type":"double"},{"name":"c","type":"string"}]}"#).unwrap();
+        let json = json::parse(r#"{"a": 1, "b": 2, "d": true}"#).unwrap();
+        let mut report = ConformityReport::default();
+        let score = score_record(&json, &schema, &mut report);
+
+        assert_eq!(score.values, 2);
+        assert_eq!(score.coerced, 1);
+        assert_eq!(score.missing, 1);
+        assert_eq!(score.extra, 1);
+        assert_eq!(report.fields["b"].coerced, 1);
+        assert_eq!(report.fields["c"].missing, 1);
+        assert_eq!(report.fields["d"].extra, 1);
+        assert_eq!(score.score(), 0.25);
+    }
+}
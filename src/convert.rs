@@ -0,0 +1,285 @@
+use json;
+use json::JsonValue;
+use avro_rs::{Schema, Writer, Codec};
+use failure::{Error, format_err};
+use std::fmt;
+use std::io::Write;
+use std::str::FromStr;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::io::{open_input_numbered, Decoder};
+#[cfg(not(target_arch = "wasm32"))]
+use crate::parser::{LenientParser, Parser};
+use crate::avro::{json_to_avro, avro_json_encoding};
+#[cfg(not(target_arch = "wasm32"))]
+use crate::infer::SchemaInferrer;
+
+
+// Output backends receive every parsed record of the input in order. They are created from the
+// schema inferred in the first pass, so they can prepare their column/type layout up front.
+pub trait RecordWriter {
+    fn write(&mut self, record: &JsonValue) -> Result<(), Error>;
+    fn finish(&mut self) -> Result<(), Error>;
+
+    // Called by convert ahead of every write with where the record comes from. Writers in front of
+    // another pass it on, writers that reject records keep it for their reports.
+    fn locate(&mut self, _location: &Location) {}
+
+    // True once the writer will not take more records, e.g. LimitedWriter at its limit, which ends
+    // convert early. Writers in front of another pass it on.
+    fn full(&self) -> bool {
+        false
+    }
+}
+
+
+// The input line a record was read from.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Location {
+    pub source: String,
+    // 1 based
+    pub line: usize
+}
+
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}", self.source, self.line)
+    }
+}
+
+// Reports list where the first few rejected records are, not all of them.
+pub const MAX_LOCATIONS: usize = 10;
+
+pub fn keep_location(locations: &mut Vec<Location>, location: &Location) {
+    if locations.len() < MAX_LOCATIONS {
+        locations.push(location.clone());
+    }
+}
+
+
+#[cfg(not(target_arch = "wasm32"))]
+pub fn infer_file_schema(file_path: &str, name: &str) -> Result<Schema, Error> {
+    SchemaInferrer::from_file(file_path, name)?
+        .into_schema()
+        .map_err(|_| format_err!("{} does not contain any records", file_path))
+}
+
+
+// Errors name the file and line of the record they happened on.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn convert(file_path: &str, writer: &mut dyn RecordWriter) -> Result<usize, Error> {
+    convert_with(file_path, writer, &mut |line| Ok(json::parse(line)?))
+}
+
+// With another parser than json-rust, e.g. a ParserChain.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn convert_with(file_path: &str, writer: &mut dyn RecordWriter, parse: &mut dyn FnMut(&str) -> Result<JsonValue, Error>) -> Result<usize, Error> {
+    convert_tolerant(file_path, writer, parse, Tolerance::Fail, &mut BadRecords::default())
+}
+
+
+// What conversion does with a line that does not parse.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Tolerance {
+    // stops with the error
+    Fail,
+    // leaves the line out
+    Skip,
+    // parses it again with the lenient parser, leaving it out when that fails too
+    Repair
+}
+
+impl FromStr for Tolerance {
+    type Err = Error;
+
+    fn from_str(name: &str) -> Result<Self, Error> {
+        match name {
+            "fail" => Ok(Tolerance::Fail),
+            "skip" => Ok(Tolerance::Skip),
+            "repair" => Ok(Tolerance::Repair),
+            _ => Err(format_err!("unknown tolerance {}, expected fail, skip or repair", name))
+        }
+    }
+}
+
+// Lines a tolerant conversion did not parse, and where the first few are.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct BadRecords {
+    pub skipped: usize,
+    // taken by the lenient parser
+    pub repaired: usize,
+    pub locations: Vec<Location>
+}
+
+impl fmt::Display for BadRecords {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Malformed records: {} skipped, {} repaired", self.skipped, self.repaired)?;
+        for location in &self.locations {
+            write!(f, "\n  {}", location)?;
+        }
+        Ok(())
+    }
+}
+
+// convert_with going on past the lines that do not parse, counted in bad. Lines that cannot be
+// read, e.g. a truncated gzip stream, and records the writer rejects still stop it.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn convert_tolerant(file_path: &str, writer: &mut dyn RecordWriter, parse: &mut dyn FnMut(&str) -> Result<JsonValue, Error>,
+                        tolerance: Tolerance, bad: &mut BadRecords) -> Result<usize, Error> {
+    let mut records = 0;
+    let mut location = Location { source: file_path.to_owned(), line: 0 };
+    let lines = open_input_numbered(file_path, Decoder::Flate2).map_err(|e| format_err!("{}: {}", file_path, e))?;
+    for (number, line) in lines {
+        if writer.full() {
+            break;
+        }
+        location.line = number;
+        let line = line.map_err(|e| format_err!("{}: {}", location, e))?;
+        let json = match (parse(&line), tolerance) {
+            (Ok(json), _) => json,
+            (Err(e), Tolerance::Fail) => return Err(format_err!("{}: {}", location, e)),
+            (Err(_), tolerance) => {
+                keep_location(&mut bad.locations, &location);
+                let repaired = match tolerance {
+                    Tolerance::Repair => LenientParser.parse(&mut line.into_bytes()).ok(),
+                    _ => None
+                };
+                match repaired {
+                    Some(json) => {
+                        bad.repaired += 1;
+                        json
+                    },
+                    None => {
+                        bad.skipped += 1;
+                        continue;
+                    }
+                }
+            }
+        };
+        writer.locate(&location);
+        writer.write(&json).map_err(|e| format_err!("{}: {}", location, e))?;
+        records += 1;
+    }
+    writer.finish()?;
+    Ok(records)
+}
+
+
+pub struct AvroWriter<'a, W: Write> {
+    schema: &'a Schema,
+    writer: Writer<'a, W>
+}
+
+impl<'a, W: Write> AvroWriter<'a, W> {
+    pub fn new(schema: &'a Schema, output: W, codec: Codec) -> Self {
+        AvroWriter { schema, writer: Writer::with_codec(schema, output, codec) }
+    }
+}
+
+impl<'a, W: Write> RecordWriter for AvroWriter<'a, W> {
+    fn write(&mut self, record: &JsonValue) -> Result<(), Error> {
+        let avro = json_to_avro(record, self.schema)?;
+        self.writer.append(avro)?;
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<(), Error> {
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+
+// Writes one Avro JSON encoded record per line. Records go through the same conversion as the
+// binary writer, so a record that fails here would also fail the binary output.
+pub struct AvroJsonWriter<'a, W: Write> {
+    schema: &'a Schema,
+    output: W
+}
+
+impl<'a, W: Write> AvroJsonWriter<'a, W> {
+    pub fn new(schema: &'a Schema, output: W) -> Self {
+        AvroJsonWriter { schema, output }
+    }
+}
+
+impl<'a, W: Write> RecordWriter for AvroJsonWriter<'a, W> {
+    fn write(&mut self, record: &JsonValue) -> Result<(), Error> {
+        let avro = json_to_avro(record, self.schema)?;
+        let encoded = avro_json_encoding(&avro, self.schema)?;
+        writeln!(self.output, "{}", encoded)?;
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<(), Error> {
+        self.output.flush()?;
+        Ok(())
+    }
+}
+
+
+pub struct JsonLinesWriter<W: Write> {
+    output: W
+}
+
+impl<W: Write> JsonLinesWriter<W> {
+    pub fn new(output: W) -> Self {
+        JsonLinesWriter { output }
+    }
+
+    pub fn into_inner(self) -> W {
+        self.output
+    }
+}
+
+impl<W: Write> RecordWriter for JsonLinesWriter<W> {
+    fn write(&mut self, record: &JsonValue) -> Result<(), Error> {
+        writeln!(self.output, "{}", record.dump())?;
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<(), Error> {
+        self.output.flush()?;
+        Ok(())
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+
+    #[test]
+    fn test_convert_error_location() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("lines.json.gz").to_string_lossy().into_owned();
+        let mut encoder = GzEncoder::new(std::fs::File::create(&path).unwrap(), Compression::default());
+        encoder.write_all(b"{\"a\":1}\n{\"a\":2}\n{\"a\":\n").unwrap();
+        encoder.finish().unwrap();
+        let error = convert(&path, &mut JsonLinesWriter::new(Vec::new())).unwrap_err();
+        assert!(error.to_string().starts_with(&format!("{}:3: ", path)), "{}", error);
+    }
+
+    #[test]
+    fn test_convert_tolerant() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("lines.json").to_string_lossy().into_owned();
+        std::fs::write(&path, "{\"a\":1}\n{\"a\":[2,],}\n{\"a\":\n{\"a\":4}\n").unwrap();
+        let mut parse = |line: &str| Ok(json::parse(line)?);
+        assert!("lenient".parse::<Tolerance>().is_err());
+
+        let mut bad = BadRecords::default();
+        let mut output = Vec::new();
+        let records = convert_tolerant(&path, &mut JsonLinesWriter::new(&mut output), &mut parse, Tolerance::Skip, &mut bad).unwrap();
+        assert_eq!((records, bad.skipped, bad.repaired), (2, 2, 0));
+        assert_eq!(bad.locations.iter().map(|location| location.line).collect::<Vec<_>>(), vec![2, 3]);
+
+        let mut bad = BadRecords::default();
+        let mut output = Vec::new();
+        let records = convert_tolerant(&path, &mut JsonLinesWriter::new(&mut output), &mut parse, "repair".parse().unwrap(), &mut bad).unwrap();
+        assert_eq!((records, bad.skipped, bad.repaired), (3, 1, 1));
+        assert_eq!(String::from_utf8(output).unwrap().lines().nth(1), Some("{\"a\":[2]}"));
+        assert!(bad.to_string().starts_with("Malformed records: 1 skipped, 1 repaired\n"));
+    }
+}
@@ -0,0 +1,175 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use avro_rs::Schema;
+use avro_rs::schema::SchemaKind;
+use crate::infer::SchemaInferrer;
+
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SchemaDifference {
+    OnlyInLeft { path: String, schema: String },
+    OnlyInRight { path: String, schema: String },
+    TypeChanged { path: String, left: String, right: String }
+}
+
+impl fmt::Display for SchemaDifference {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SchemaDifference::OnlyInLeft { path, schema } => write!(f, "- {}: {}", path, schema),
+            SchemaDifference::OnlyInRight { path, schema } => write!(f, "+ {}: {}", path, schema),
+            SchemaDifference::TypeChanged { path, left, right } => write!(f, "~ {}: {} -> {}", path, left, right)
+        }
+    }
+}
+
+
+pub fn diff_schemas(left: &Schema, right: &Schema) -> Vec<SchemaDifference> {
+    let mut differences = Vec::new();
+    diff_at("", left, right, &mut differences);
+    differences
+}
+
+fn diff_at(path: &str, left: &Schema, right: &Schema, differences: &mut Vec<SchemaDifference>) {
+    match (left, right) {
+        (Schema::Record { fields: left_fields, lookup: left_lookup, .. }, Schema::Record { fields: right_fields, lookup: right_lookup, .. }) => {
+            for field in left_fields {
+                let field_path = join(path, &field.name);
+                match right_lookup.get(&field.name) {
+                    Some(idx) => diff_at(&field_path, &field.schema, &right_fields[*idx].schema, differences),
+                    None => differences.push(SchemaDifference::OnlyInLeft { path: field_path, schema: schema_type(&field.schema) })
+                }
+            }
+            for field in right_fields {
+                if !left_lookup.contains_key(&field.name) {
+                    differences.push(SchemaDifference::OnlyInRight { path: join(path, &field.name), schema: schema_type(&field.schema) });
+                }
+            }
+        },
+        (Schema::Array(left_items), Schema::Array(right_items)) => {
+            diff_at(&format!("{}[]", path), left_items, right_items, differences);
+        },
+        (Schema::Map(left_values), Schema::Map(right_values)) => {
+            diff_at(&format!("{}{{}}", path), left_values, right_values, differences);
+        },
+        _ => {
+            if variant_kinds(left) != variant_kinds(right) {
+                differences.push(SchemaDifference::TypeChanged { path: path.to_owned(), left: schema_type(left), right: schema_type(right) });
+            }
+            // nested types present on both sides are still compared structurally
+            for left_variant in variants(left) {
+                if let Some(right_variant) = variants(right).into_iter().find(|v| SchemaKind::from(*v) == SchemaKind::from(left_variant)) {
+                    match left_variant {
+                        Schema::Record { .. } | Schema::Array(_) | Schema::Map(_) => diff_at(path, left_variant, right_variant, differences),
+                        _ => {}
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn variants(schema: &Schema) -> Vec<&Schema> {
+    match schema {
+        Schema::Union(union) => union.variants().iter().collect(),
+        _ => vec![schema]
+    }
+}
+
+fn variant_kinds(schema: &Schema) -> BTreeSet<String> {
+    variants(schema).into_iter().map(schema_type).collect()
+}
+
+// Short type description, nested types are not expanded.
+pub fn schema_type(schema: &Schema) -> String {
+    match schema {
+        Schema::Union(union) => {
+            let names: Vec<String> = union.variants().iter().map(schema_type).collect();
+            format!("[{}]", names.join(", "))
+        },
+        Schema::Array(_) => "array".to_owned(),
+        Schema::Map(_) => "map".to_owned(),
+        Schema::Record { .. } => "record".to_owned(),
+        other => format!("{:?}", SchemaKind::from(other)).to_lowercase()
+    }
+}
+
+fn join(path: &str, name: &str) -> String {
+    if path.is_empty() { name.to_owned() } else { format!("{}.{}", path, name) }
+}
+
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FieldDelta {
+    pub path: String,
+    pub left_presence: f64,
+    pub right_presence: f64,
+    pub left_types: BTreeMap<&'static str, usize>,
+    pub right_types: BTreeMap<&'static str, usize>
+}
+
+impl fmt::Display for FieldDelta {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:<60} {:>7.2}% {:>7.2}% {:>+8.2}%  {} | {}",
+               self.path,
+               self.left_presence * 100.0,
+               self.right_presence * 100.0,
+               (self.right_presence - self.left_presence) * 100.0,
+               type_shares(&self.left_types),
+               type_shares(&self.right_types))
+    }
+}
+
+fn type_shares(types: &BTreeMap<&'static str, usize>) -> String {
+    let total: usize = types.values().sum();
+    let shares: Vec<String> =
+        types
+            .iter()
+            .map(|(name, count)| format!("{} {:.1}%", name, *count as f64 * 100.0 / total as f64))
+            .collect();
+    if shares.is_empty() { "-".to_owned() } else { shares.join(", ") }
+}
+
+// Per-field presence (share of records containing the path) and type counts on both sides, only
+// for paths where the presence or the set of observed types differs.
+pub fn field_deltas(left: &SchemaInferrer, right: &SchemaInferrer) -> Vec<FieldDelta> {
+    let paths: BTreeSet<&String> = left.fields().keys().chain(right.fields().keys()).collect();
+    let mut deltas = Vec::new();
+    for path in paths {
+        let (left_presence, left_types) = presence(left, path);
+        let (right_presence, right_types) = presence(right, path);
+        let same_types = left_types.keys().eq(right_types.keys());
+        if (left_presence - right_presence).abs() > 1e-9 || !same_types {
+            deltas.push(FieldDelta { path: path.clone(), left_presence, right_presence, left_types, right_types });
+        }
+    }
+    deltas
+}
+
+fn presence(inferrer: &SchemaInferrer, path: &str) -> (f64, BTreeMap<&'static str, usize>) {
+    match inferrer.fields().get(path) {
+        Some(stats) if inferrer.records() > 0 => {
+            (stats.present as f64 / inferrer.records() as f64, stats.types.clone())
+        },
+        _ => (0.0, BTreeMap::new())
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_diff_schemas() {
+        let left = Schema::parse_str(r#"{"name":"t","type":"record","fields":[{"name":"a","type":"long"},{"name":"b","type":"string"},{"name":"r","type":{"name":"r","type":"record","fields":[{"name":"x","type":"long"}]}}]}"#).unwrap();
+        let right = Schema::parse_str(r#"{"name":"t","type":"record","fields":[{"name":"a","type":["null","long"]},{"name":"c","type":"string"},{"name":"r","type":{"name":"r","type":"record","fields":[{"name":"x","type":"double"}]}}]}"#).unwrap();
+
+        let differences = diff_schemas(&left, &right);
+        assert_eq!(differences, vec![
+            SchemaDifference::TypeChanged { path: "a".to_owned(), left: "long".to_owned(), right: "[null, long]".to_owned() },
+            SchemaDifference::OnlyInLeft { path: "b".to_owned(), schema: "string".to_owned() },
+            SchemaDifference::TypeChanged { path: "r.x".to_owned(), left: "long".to_owned(), right: "double".to_owned() },
+            SchemaDifference::OnlyInRight { path: "c".to_owned(), schema: "string".to_owned() },
+        ]);
+    }
+}
@@ -0,0 +1,106 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const POWERCAP: &str = "/sys/class/powercap";
+
+
+// Package energy counters of Intel RAPL on Linux (also exposed by recent AMD kernels under the same
+// name). Only the top level intel-rapl:N zones are read, their subzones are already included.
+struct Zone {
+    energy: PathBuf,
+    max_range: u64
+}
+
+pub struct EnergyMeter {
+    zones: Vec<Zone>,
+    start: Vec<u64>
+}
+
+fn read_u64(path: &Path) -> Option<u64> {
+    fs::read_to_string(path).ok()?.trim().parse().ok()
+}
+
+impl EnergyMeter {
+    // None when RAPL is not available or not readable (the counters are root only on newer kernels).
+    pub fn start() -> Option<Self> {
+        EnergyMeter::start_in(Path::new(POWERCAP))
+    }
+
+    // the zones under a powercap directory, the fixture one in tests
+    fn start_in(powercap: &Path) -> Option<Self> {
+        let mut zones = Vec::new();
+        for entry in fs::read_dir(powercap).ok()? {
+            let path = entry.ok()?.path();
+            let name = path.file_name()?.to_string_lossy().into_owned();
+            if name.starts_with("intel-rapl:") && name.matches(':').count() == 1 {
+                let max_range = read_u64(&path.join("max_energy_range_uj")).unwrap_or(std::u64::MAX);
+                zones.push(Zone { energy: path.join("energy_uj"), max_range });
+            }
+        }
+
+        let start = zones.iter().map(|zone| read_u64(&zone.energy)).collect::<Option<Vec<u64>>>()?;
+        if zones.is_empty() {
+            return None;
+        }
+        Some(EnergyMeter { zones, start })
+    }
+
+    pub fn joules(&self) -> Option<f64> {
+        let mut microjoules = 0;
+        for (zone, start) in self.zones.iter().zip(&self.start) {
+            let now = read_u64(&zone.energy)?;
+            // the counter wraps around at max_energy_range_uj
+            microjoules += if now >= *start { now - start } else { zone.max_range - start + now };
+        }
+        Some(microjoules as f64 / 1_000_000.0)
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::allocation::Allocations;
+    use crate::measure::Measurement;
+
+    fn write_zone(powercap: &Path, name: &str, energy: u64, max_range: u64) {
+        let zone = powercap.join(name);
+        fs::create_dir_all(&zone).unwrap();
+        fs::write(zone.join("energy_uj"), format!("{}\n", energy)).unwrap();
+        fs::write(zone.join("max_energy_range_uj"), format!("{}\n", max_range)).unwrap();
+    }
+
+    #[test]
+    fn test_energy_meter() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let powercap = dir.path();
+        assert!(EnergyMeter::start_in(powercap).is_none(), "no zones");
+
+        write_zone(powercap, "intel-rapl:0", 1_000_000, 10_000_000);
+        // a subzone, counted in its package already
+        write_zone(powercap, "intel-rapl:0:0", 500_000, 10_000_000);
+        write_zone(powercap, "intel-rapl:1", 9_500_000, 10_000_000);
+        let meter = EnergyMeter::start_in(powercap).unwrap();
+        assert_eq!(meter.zones.len(), 2);
+        assert_eq!(meter.joules(), Some(0.0));
+
+        write_zone(powercap, "intel-rapl:0", 3_000_000, 10_000_000);
+        write_zone(powercap, "intel-rapl:0:0", 9_000_000, 10_000_000);
+        // wrapped around at the max range
+        write_zone(powercap, "intel-rapl:1", 500_000, 10_000_000);
+        let joules = meter.joules().unwrap();
+        assert!((joules - 3.0).abs() < 1e-9, "{}", joules);
+
+        let measurement = Measurement {
+            name: "parse:json".to_owned(),
+            elapsed: std::time::Duration::from_secs(1),
+            records: 600,
+            joules: Some(joules),
+            allocations: Allocations::default(),
+            perf: None
+        };
+        assert_eq!(measurement.records_per_joule().map(|per_joule| per_joule.round()), Some(200.0));
+        assert_eq!(Measurement { joules: Some(0.0), ..measurement.clone() }.records_per_joule(), None);
+        assert_eq!(Measurement { joules: None, ..measurement }.records_per_joule(), None);
+    }
+}
@@ -0,0 +1,155 @@
+use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
+use json::JsonValue;
+use avro_rs::Schema;
+use failure::{Error, format_err};
+use crate::avro::with_extra_field;
+use crate::convert::{Location, RecordWriter};
+use crate::hash::content_hash;
+
+
+// Provenance carried into the output next to the record's own fields.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EnvelopeField {
+    // when the conversion started, epoch milliseconds, the same for every record of a run
+    IngestTime,
+    SourceFile,
+    // 1 based line of the record in the input
+    LineNumber,
+    ContentHash
+}
+
+impl EnvelopeField {
+    pub fn all() -> [EnvelopeField; 4] {
+        [EnvelopeField::IngestTime, EnvelopeField::SourceFile, EnvelopeField::LineNumber, EnvelopeField::ContentHash]
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            EnvelopeField::IngestTime => "ingest_time",
+            EnvelopeField::SourceFile => "source_file",
+            EnvelopeField::LineNumber => "line_number",
+            EnvelopeField::ContentHash => "content_hash"
+        }
+    }
+
+    // prefixed so it does not collide with a field of the record
+    pub fn field_name(self) -> String {
+        format!("_{}", self.name())
+    }
+
+    fn schema(self) -> Schema {
+        match self {
+            EnvelopeField::IngestTime | EnvelopeField::LineNumber => Schema::Long,
+            EnvelopeField::SourceFile | EnvelopeField::ContentHash => Schema::String
+        }
+    }
+}
+
+impl FromStr for EnvelopeField {
+    type Err = Error;
+
+    fn from_str(name: &str) -> Result<Self, Error> {
+        EnvelopeField::all()
+            .iter()
+            .find(|field| field.name() == name)
+            .cloned()
+            .ok_or_else(|| format_err!("unknown envelope field {}, expected ingest_time, source_file, line_number or content_hash", name))
+    }
+}
+
+// "all" or a comma separated list of envelope fields
+pub fn parse_envelope(spec: &str) -> Result<Vec<EnvelopeField>, Error> {
+    if spec == "all" {
+        return Ok(EnvelopeField::all().to_vec());
+    }
+    spec.split(',').map(|name| name.trim().parse()).collect()
+}
+
+
+pub struct Envelope {
+    fields: Vec<EnvelopeField>,
+    source_file: String,
+    ingest_ms: i64
+}
+
+impl Envelope {
+    pub fn new(fields: Vec<EnvelopeField>, source_file: &str) -> Self {
+        let ingest_ms = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_millis() as i64).unwrap_or(0);
+        Envelope { fields, source_file: source_file.to_owned(), ingest_ms }
+    }
+
+    // The record schema with the envelope fields appended.
+    pub fn schema(&self, schema: Schema) -> Result<Schema, Error> {
+        self.fields.iter().try_fold(schema, |schema, field| with_extra_field(schema, &field.field_name(), field.schema()))
+    }
+
+    pub fn wrap(&self, record: &JsonValue, line_number: usize) -> Result<JsonValue, Error> {
+        let mut wrapped = record.clone();
+        for field in &self.fields {
+            wrapped[field.field_name().as_str()] = match field {
+                EnvelopeField::IngestTime => JsonValue::from(self.ingest_ms),
+                EnvelopeField::SourceFile => JsonValue::from(self.source_file.as_str()),
+                EnvelopeField::LineNumber => JsonValue::from(line_number),
+                EnvelopeField::ContentHash => JsonValue::from(content_hash(record)?)
+            };
+        }
+        Ok(wrapped)
+    }
+}
+
+
+// Adds the envelope to every record in front of a writer built on Envelope::schema, with the line
+// number convert locates each record at.
+pub struct EnvelopeWriter<'w> {
+    inner: &'w mut dyn RecordWriter,
+    envelope: &'w Envelope,
+    line: usize
+}
+
+impl<'w> EnvelopeWriter<'w> {
+    pub fn new(inner: &'w mut dyn RecordWriter, envelope: &'w Envelope) -> Self {
+        EnvelopeWriter { inner, envelope, line: 0 }
+    }
+}
+
+impl<'w> RecordWriter for EnvelopeWriter<'w> {
+    fn write(&mut self, record: &JsonValue) -> Result<(), Error> {
+        self.inner.write(&self.envelope.wrap(record, self.line)?)
+    }
+
+    fn finish(&mut self) -> Result<(), Error> {
+        self.inner.finish()
+    }
+
+    fn locate(&mut self, location: &Location) {
+        self.line = location.line;
+        self.inner.locate(location);
+    }
+
+    fn full(&self) -> bool {
+        self.inner.full()
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::avro::{infer_schema, json_to_avro};
+
+    #[test]
+    fn test_envelope() {
+        let record = json::parse(r#"{"id": 1, "text": "hi"}"#).unwrap();
+        let envelope = Envelope::new(parse_envelope("all").unwrap(), "tweets.json.gz");
+        let schema = envelope.schema(infer_schema(&record, "t").unwrap()).unwrap();
+        let wrapped = envelope.wrap(&record, 7).unwrap();
+        assert!(json_to_avro(&wrapped, &schema).is_ok());
+        assert_eq!(wrapped["_line_number"], 7);
+        assert_eq!(wrapped["_source_file"], "tweets.json.gz");
+        assert_eq!(wrapped["_content_hash"].as_str().unwrap(), content_hash(&record).unwrap());
+
+        assert_eq!(parse_envelope("line_number, source_file").unwrap(), vec![EnvelopeField::LineNumber, EnvelopeField::SourceFile]);
+        assert!(parse_envelope("offset").is_err());
+    }
+}
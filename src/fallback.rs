@@ -0,0 +1,136 @@
+use std::str::FromStr;
+use json::JsonValue;
+use avro_rs::Schema;
+use failure::{Error, format_err};
+use crate::convert::{Location, RecordWriter};
+use crate::infer::SchemaInferrer;
+
+
+// Output shapes for corpora too irregular for one inferred record schema.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Fallback {
+    // a map of the top level fields, nested objects and arrays as JSON text
+    Map,
+    // a record with the whole input line as a single JSON string field
+    Raw
+}
+
+impl FromStr for Fallback {
+    type Err = Error;
+
+    fn from_str(name: &str) -> Result<Self, Error> {
+        match name {
+            "map" => Ok(Fallback::Map),
+            "raw" => Ok(Fallback::Raw),
+            _ => Err(format_err!("unknown fallback {}, expected map or raw", name))
+        }
+    }
+}
+
+impl Fallback {
+    pub fn schema(self) -> Schema {
+        let schema = match self {
+            Fallback::Map => r#"{"type":"map","values":["null","boolean","long","double","string"]}"#,
+            Fallback::Raw => r#"{"name":"raw_record","type":"record","fields":[{"name":"json","type":"string"}]}"#
+        };
+        Schema::parse_str(schema).unwrap()
+    }
+
+    pub fn reshape(self, record: &JsonValue) -> JsonValue {
+        match self {
+            Fallback::Map => {
+                let mut map = JsonValue::new_object();
+                for (key, value) in record.entries() {
+                    map[key] = match value {
+                        JsonValue::Object(_) | JsonValue::Array(_) => JsonValue::from(value.dump()),
+                        other => other.clone()
+                    };
+                }
+                map
+            },
+            Fallback::Raw => json::object! { "json" => record.dump() }
+        }
+    }
+}
+
+
+// Share of the top level fields that are missing from some records or carry more than one non
+// null type, 0.0 for records that all have the same shape.
+pub fn heterogeneity(inferrer: &SchemaInferrer) -> f64 {
+    let top_level: Vec<_> = inferrer.fields()
+        .iter()
+        .filter(|(path, _)| !path.contains('.') && !path.contains('['))
+        .collect();
+    if top_level.is_empty() {
+        return 0.0;
+    }
+    let irregular = top_level
+        .iter()
+        .filter(|(_, stats)| {
+            let types = stats.types.keys().filter(|kind| **kind != "null").count();
+            stats.present < inferrer.records() || types > 1
+        })
+        .count();
+    irregular as f64 / top_level.len() as f64
+}
+
+
+// Reshapes every record into the fallback shape in front of a writer built on Fallback::schema.
+pub struct FallbackWriter<'w> {
+    inner: &'w mut dyn RecordWriter,
+    fallback: Fallback
+}
+
+impl<'w> FallbackWriter<'w> {
+    pub fn new(inner: &'w mut dyn RecordWriter, fallback: Fallback) -> Self {
+        FallbackWriter { inner, fallback }
+    }
+}
+
+impl<'w> RecordWriter for FallbackWriter<'w> {
+    fn write(&mut self, record: &JsonValue) -> Result<(), Error> {
+        self.inner.write(&self.fallback.reshape(record))
+    }
+
+    fn finish(&mut self) -> Result<(), Error> {
+        self.inner.finish()
+    }
+
+    fn locate(&mut self, location: &Location) {
+        self.inner.locate(location);
+    }
+
+    fn full(&self) -> bool {
+        self.inner.full()
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::avro::json_to_avro;
+
+    #[test]
+    fn test_fallback() {
+        let records: Vec<JsonValue> = vec![r#"{"id":1,"user":{"name":"a"}}"#, r#"{"id":"x","tags":[1]}"#]
+            .into_iter()
+            .map(|record| json::parse(record).unwrap())
+            .collect();
+        let mut inferrer = SchemaInferrer::new("t");
+        for record in &records {
+            inferrer.update(record).unwrap();
+        }
+        // id has two types, user and tags are each missing once
+        assert_eq!(heterogeneity(&inferrer), 1.0);
+
+        for fallback in &[Fallback::Map, Fallback::Raw] {
+            let schema = fallback.schema();
+            for record in &records {
+                assert!(json_to_avro(&fallback.reshape(record), &schema).is_ok());
+            }
+        }
+        assert_eq!(Fallback::Map.reshape(&records[0]).dump(), r#"{"id":1,"user":"{\"name\":\"a\"}"}"#);
+        assert!("wide".parse::<Fallback>().is_err());
+    }
+}
@@ -0,0 +1,135 @@
+// C entry points of the cdylib, see include/json_benchmarks.h. Strings returned to the caller are
+// owned by Rust and given back with free_string. On failure functions return null or -1 and the
+// message is kept for last_error until the next call on the same thread. Pointer arguments
+// are null or NUL terminated strings, which is all the safety contract there is.
+#![allow(clippy::missing_safety_doc)]
+use std::cell::RefCell;
+use std::ffi::{CStr, CString};
+use std::os::raw::c_char;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::ptr;
+use avro_rs::{Codec, Schema};
+use failure::{Error, format_err};
+use crate::convert::{convert, infer_file_schema, AvroWriter};
+use crate::io::OutputFile;
+
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = RefCell::new(None);
+}
+
+fn set_last_error(error: &Error) {
+    let message = CString::new(error.to_string().replace('\0', " ")).unwrap();
+    LAST_ERROR.with(|last| *last.borrow_mut() = Some(message));
+}
+
+fn clear_last_error() {
+    LAST_ERROR.with(|last| *last.borrow_mut() = None);
+}
+
+unsafe fn to_str<'a>(s: *const c_char, what: &str) -> Result<&'a str, Error> {
+    if s.is_null() {
+        return Err(format_err!("{} is null", what));
+    }
+    CStr::from_ptr(s).to_str().map_err(|e| format_err!("{} is not UTF-8: {}", what, e))
+}
+
+// panics must not unwind into the caller, GzipFile for one panics on unreadable files
+fn guard<T>(f: impl FnOnce() -> Result<T, Error>) -> Result<T, Error> {
+    catch_unwind(AssertUnwindSafe(f)).unwrap_or_else(|_| Err(format_err!("panicked, see stderr")))
+}
+
+fn infer(path: &str) -> Result<CString, Error> {
+    std::fs::File::open(path).map_err(|e| format_err!("{}: {}", path, e))?;
+    let schema = infer_file_schema(path, "inferred_schema")?;
+    Ok(CString::new(serde_json::to_string(&schema)?)?)
+}
+
+fn convert_to_avro(input: &str, schema: Option<&str>, output: &str) -> Result<usize, Error> {
+    std::fs::File::open(input).map_err(|e| format_err!("{}: {}", input, e))?;
+    let schema = match schema {
+        Some(schema) => Schema::parse_str(schema)?,
+        None => infer_file_schema(input, "inferred_schema")?
+    };
+    let mut writer = AvroWriter::new(&schema, OutputFile::create(output, false)?, Codec::Deflate);
+    convert(input, &mut writer)
+}
+
+
+// Avro schema inferred from a gzipped NDJSON file, as JSON text.
+#[no_mangle]
+pub unsafe extern "C" fn infer_schema_from_ndjson(path: *const c_char) -> *mut c_char {
+    clear_last_error();
+    match guard(|| infer(to_str(path, "path")?)) {
+        Ok(schema) => schema.into_raw(),
+        Err(e) => {
+            set_last_error(&e);
+            ptr::null_mut()
+        }
+    }
+}
+
+// Streams a gzipped NDJSON file into a deflated Avro file, a record at a time. The schema is JSON
+// text, or null to infer it from the input first. Returns the number of records written.
+#[no_mangle]
+pub unsafe extern "C" fn convert_ndjson_to_avro(input: *const c_char, schema: *const c_char, output: *const c_char) -> i64 {
+    clear_last_error();
+    let result = guard(|| {
+        let schema = if schema.is_null() { None } else { Some(to_str(schema, "schema")?) };
+        convert_to_avro(to_str(input, "input")?, schema, to_str(output, "output")?)
+    });
+    match result {
+        Ok(records) => records as i64,
+        Err(e) => {
+            set_last_error(&e);
+            -1
+        }
+    }
+}
+
+// Message of the last failure on this thread, null when the last call succeeded. Valid until the
+// next call.
+#[no_mangle]
+pub extern "C" fn last_error() -> *const c_char {
+    LAST_ERROR.with(|last| last.borrow().as_ref().map_or(ptr::null(), |message| message.as_ptr()))
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn free_string(s: *mut c_char) {
+    if !s.is_null() {
+        drop(CString::from_raw(s));
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::io::Write;
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+
+    #[test]
+    fn test_ffi() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let input = dir.path().join("lines.json.gz").to_string_lossy().into_owned();
+        let mut encoder = GzEncoder::new(std::fs::File::create(&input).unwrap(), Compression::default());
+        encoder.write_all(b"{\"a\":1}\n{\"a\":2}\n").unwrap();
+        encoder.finish().unwrap();
+        let input = CString::new(input).unwrap();
+        let output = CString::new(dir.path().join("lines.avro").to_string_lossy().into_owned()).unwrap();
+
+        unsafe {
+            let schema = infer_schema_from_ndjson(input.as_ptr());
+            assert!(!schema.is_null());
+            assert!(Schema::parse_str(CStr::from_ptr(schema).to_str().unwrap()).is_ok());
+            assert_eq!(convert_ndjson_to_avro(input.as_ptr(), schema, output.as_ptr()), 2);
+            assert!(last_error().is_null());
+            free_string(schema);
+
+            let missing = CString::new(dir.path().join("missing.json.gz").to_string_lossy().into_owned()).unwrap();
+            assert_eq!(convert_ndjson_to_avro(missing.as_ptr(), ptr::null(), output.as_ptr()), -1);
+            assert!(!last_error().is_null());
+        }
+    }
+}
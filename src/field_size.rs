@@ -0,0 +1,107 @@
+use std::fmt;
+use json::JsonValue;
+use avro_rs::Schema;
+use avro_rs::types::Value as AvroValue;
+use failure::{Error, bail};
+use crate::avro::json_to_avro;
+use crate::compress::Compressor;
+
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FieldSize {
+    pub field: String,
+    // Avro binary encoding of the field over all records
+    pub encoded_bytes: usize,
+    // the encoded column compressed on its own
+    pub compressed_bytes: usize
+}
+
+
+#[derive(Debug)]
+pub struct FieldSizes {
+    pub records: usize,
+    // records the schema can not convert, left out of every column
+    pub skipped_records: usize,
+    pub fields: Vec<FieldSize>
+}
+
+impl FieldSizes {
+    pub fn compressed_bytes(&self) -> usize {
+        self.fields.iter().map(|field| field.compressed_bytes).sum()
+    }
+}
+
+impl fmt::Display for FieldSizes {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "Records: {} ({} skipped)", self.records, self.skipped_records)?;
+        let total = self.compressed_bytes().max(1) as f64;
+        writeln!(f, "{:<40} {:>12} {:>12} {:>8} {:>7}", "field", "encoded", "compressed", "ratio", "share")?;
+        for field in &self.fields {
+            writeln!(f, "{:<40} {:>12} {:>12} {:>8.2} {:>6.1}%",
+                     field.field, field.encoded_bytes, field.compressed_bytes,
+                     field.encoded_bytes as f64 / field.compressed_bytes.max(1) as f64,
+                     field.compressed_bytes as f64 / total * 100.0)?;
+        }
+        Ok(())
+    }
+}
+
+
+// Storage each top level field costs: every field is encoded into a column of its own and the
+// column compressed separately, the way a columnar format would store it. Sorted by compressed
+// size, largest first, so the fields worth dropping come up top.
+pub fn field_sizes(records: &[JsonValue], schema: &Schema, compressor: &dyn Compressor, level: i32) -> Result<FieldSizes, Error> {
+    let fields = match schema {
+        Schema::Record { fields, .. } => fields,
+        _ => bail!("per field sizes need a record schema")
+    };
+    let mut columns: Vec<Vec<u8>> = vec![Vec::new(); fields.len()];
+    let mut skipped_records = 0;
+    for record in records {
+        let values = match json_to_avro(record, schema) {
+            Ok(AvroValue::Record(values)) => values,
+            _ => {
+                skipped_records += 1;
+                continue;
+            }
+        };
+        for ((column, field), (_, value)) in columns.iter_mut().zip(fields).zip(values) {
+            column.extend(avro_rs::to_avro_datum(&field.schema, value)?);
+        }
+    }
+
+    let mut sizes = Vec::with_capacity(fields.len());
+    for (column, field) in columns.iter().zip(fields) {
+        sizes.push(FieldSize {
+            field: field.name.clone(),
+            encoded_bytes: column.len(),
+            compressed_bytes: compressor.compress_block(column, level)?.len()
+        });
+    }
+    sizes.sort_by(|a, b| b.compressed_bytes.cmp(&a.compressed_bytes).then_with(|| a.field.cmp(&b.field)));
+    Ok(FieldSizes { records: records.len(), skipped_records, fields: sizes })
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::avro::infer_schema;
+    use crate::compress::find_compressor;
+
+    #[test]
+    fn test_field_sizes() {
+        let records: Vec<JsonValue> = (0..500)
+            .map(|i| json::object! { "id" => i, "lang" => "en", "text" => format!("tweet number {} with some words {}", i, i * 7919 % 1000) })
+            .collect();
+        let schema = infer_schema(&records[0], "t").unwrap();
+        let zstd = find_compressor("zstd").unwrap();
+        let sizes = field_sizes(&records, &schema, zstd.as_ref(), 3).unwrap();
+        assert_eq!(sizes.skipped_records, 0);
+        assert_eq!(sizes.fields[0].field, "text");
+        let lang = sizes.fields.iter().find(|field| field.field == "lang").unwrap();
+        // one length byte and two characters per record
+        assert_eq!(lang.encoded_bytes, 1500);
+        assert!(lang.compressed_bytes < 100);
+    }
+}
@@ -0,0 +1,178 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::str::FromStr;
+use failure::{Error, bail, format_err};
+use crate::bench::BenchResults;
+
+
+// What a benchmark has to reach for bench to exit with 0, e.g. 120ms or 50MB/s.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Target {
+    // median time
+    Millis(f64),
+    // of the NDJSON after decompression, at the median time
+    MegabytesPerSecond(f64)
+}
+
+impl FromStr for Target {
+    type Err = Error;
+
+    fn from_str(target: &str) -> Result<Self, Error> {
+        let (number, target): (&str, fn(f64) -> Target) = match target.strip_suffix("MB/s") {
+            Some(number) => (number, Target::MegabytesPerSecond),
+            None => match target.strip_suffix("ms") {
+                Some(number) => (number, Target::Millis),
+                None => bail!("unknown target {}, expected a time like 120ms or a throughput like 50MB/s", target)
+            }
+        };
+        let number: f64 = number.trim().parse()?;
+        if number.is_nan() || number <= 0.0 {
+            bail!("target must be positive");
+        }
+        Ok(target(number))
+    }
+}
+
+impl fmt::Display for Target {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Target::Millis(ms) => write!(f, "{}ms", ms),
+            Target::MegabytesPerSecond(mb) => write!(f, "{}MB/s", mb)
+        }
+    }
+}
+
+// Benchmark name to target, the empty name for every benchmark without one of its own.
+pub type Targets = BTreeMap<String, Target>;
+
+// NAME=TARGET or a bare TARGET for every benchmark.
+pub fn parse_target(spec: &str) -> Result<(String, Target), Error> {
+    match spec.rsplit_once('=') {
+        Some((name, target)) => Ok((name.to_owned(), target.parse()?)),
+        None => Ok((String::new(), spec.parse()?))
+    }
+}
+
+// A TOML file of "benchmark" = "target" lines, e.g. "parse:simd" = "120ms", with "*" for every
+// benchmark without one of its own.
+pub fn load(path: &str) -> Result<Targets, Error> {
+    let text = fs::read_to_string(path).map_err(|e| format_err!("targets {}: {}", path, e))?;
+    let table: BTreeMap<String, String> = toml::from_str(&text)?;
+    table.into_iter()
+        .map(|(name, target)| {
+            let name = if name == "*" { String::new() } else { name };
+            Ok((name, target.parse().map_err(|e| format_err!("targets {}: {}", path, e))?))
+        })
+        .collect()
+}
+
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Check {
+    pub name: String,
+    pub target: Target,
+    // in the unit of the target, None when the benchmark failed or did not run
+    pub actual: Option<f64>,
+    pub missed: bool
+}
+
+impl fmt::Display for Check {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let actual = match (self.actual, self.target) {
+            (Some(ms), Target::Millis(_)) => format!("{:.1}ms", ms),
+            (Some(mb), Target::MegabytesPerSecond(_)) => format!("{:.1}MB/s", mb),
+            (None, _) => "not run".to_owned()
+        };
+        let target = self.target.to_string();
+        write!(f, "{:<32} {:>12} {:>12}{}", self.name, target, actual, if self.missed { "  MISSED" } else { "" })
+    }
+}
+
+// Every benchmark of the run with a target, then the named ones that did not produce a result,
+// which miss theirs.
+pub fn check(targets: &Targets, results: &BenchResults) -> Vec<Check> {
+    let mut checks: Vec<Check> = results.results
+        .iter()
+        .filter_map(|result| {
+            let target = *targets.get(&result.name).or_else(|| targets.get(""))?;
+            let (actual, missed) = match target {
+                Target::Millis(ms) => (result.elapsed_ms, result.elapsed_ms > ms),
+                Target::MegabytesPerSecond(mb) => {
+                    let actual = result.decompressed_bytes_per_second / 1_000_000.0;
+                    (actual, actual < mb)
+                }
+            };
+            Some(Check { name: result.name.clone(), target, actual: Some(actual), missed })
+        })
+        .collect();
+    for (name, target) in targets {
+        if !name.is_empty() && !results.results.iter().any(|result| &result.name == name) {
+            checks.push(Check { name: name.clone(), target: *target, actual: None, missed: true });
+        }
+    }
+    checks
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::time::Duration;
+    use crate::bench::BenchResult;
+    use crate::io::Decoder;
+    use crate::measure::Stats;
+
+    #[test]
+    fn test_check() {
+        assert_eq!("120ms".parse::<Target>().unwrap(), Target::Millis(120.0));
+        assert_eq!("2.5MB/s".parse::<Target>().unwrap(), Target::MegabytesPerSecond(2.5));
+        assert!("120s".parse::<Target>().is_err());
+        assert!("0ms".parse::<Target>().is_err());
+        assert_eq!(parse_target("compress:zstd:3=50MB/s").unwrap(), ("compress:zstd:3".to_owned(), Target::MegabytesPerSecond(50.0)));
+        assert_eq!(parse_target("80ms").unwrap(), (String::new(), Target::Millis(80.0)));
+
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("targets.toml").to_string_lossy().into_owned();
+        fs::write(&path, "\"parse:simd\" = \"100ms\"\n\"parse:serde\" = \"20MB/s\"\n\"parse:nope\" = \"1ms\"\n\"*\" = \"1000ms\"\n").unwrap();
+        let targets = load(&path).unwrap();
+        fs::write(&path, "\"parse:simd\" = \"fast\"\n").unwrap();
+        assert!(load(&path).is_err());
+
+        let result = |name: &str, elapsed_ms: f64, megabytes_per_second: f64| BenchResult {
+            name: name.to_owned(),
+            kind: "parse".to_owned(),
+            parameters: Vec::new(),
+            records: 100,
+            errors: 0,
+            elapsed_ms,
+            records_per_second: 0.0,
+            input_bytes_per_second: 0.0,
+            decompressed_bytes_per_second: megabytes_per_second * 1_000_000.0,
+            joules: None,
+            allocations: Default::default(),
+            latency: None,
+            passes: 1,
+            checksum: 0,
+            samples_ms: Vec::new(),
+            perf: None,
+            positions: Vec::new(),
+            compression_ratio: None,
+            stats: Stats::of(&[Duration::from_millis(elapsed_ms as u64)]).unwrap()
+        };
+        let results = BenchResults {
+            input: "tweets.json.gz".to_owned(),
+            input_bytes: 0,
+            decoder: Decoder::Flate2,
+            warmup: 0,
+            runs: 1,
+            results: vec![result("parse:simd", 120.0, 50.0), result("parse:serde", 300.0, 25.0), result("parse:json", 900.0, 8.0)],
+            failed: BTreeMap::new()
+        };
+        let checks = check(&targets, &results);
+        let missed: Vec<(&str, bool)> = checks.iter().map(|check| (check.name.as_str(), check.missed)).collect();
+        assert_eq!(missed, vec![("parse:simd", true), ("parse:serde", false), ("parse:json", false), ("parse:nope", true)]);
+        assert_eq!(checks[1].actual, Some(25.0));
+        assert!(checks[3].to_string().contains("not run"));
+    }
+}
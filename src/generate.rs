@@ -0,0 +1,189 @@
+use std::io::Write;
+use json::JsonValue;
+use avro_rs::Schema;
+use rand::Rng;
+use failure::Error;
+
+
+pub struct GeneratorConfig {
+    pub mean_array_length: f64,
+    pub max_array_length: usize,
+    // chance of picking the null branch of a union that has one
+    pub null_probability: f64
+}
+
+impl Default for GeneratorConfig {
+    fn default() -> Self {
+        GeneratorConfig { mean_array_length: 2.0, max_array_length: 50, null_probability: 0.3 }
+    }
+}
+
+
+// Avro schemas carry no logical type for most inferred string fields, so the pattern is picked
+// from the field name, using the conventions of the Twitter corpus.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StringPattern {
+    TwitterDate,
+    Url,
+    Digits,
+    Language,
+    Color,
+    Text
+}
+
+pub fn detect_pattern(field_name: &str) -> StringPattern {
+    let name = field_name.to_lowercase();
+    if name == "created_at" || name.ends_with("_date") {
+        StringPattern::TwitterDate
+    } else if name == "url" || name.ends_with("_url") || name.ends_with("_url_https") || name.ends_with("_https") {
+        StringPattern::Url
+    } else if name.ends_with("_str") || name == "timestamp_ms" {
+        StringPattern::Digits
+    } else if name == "lang" {
+        StringPattern::Language
+    } else if name.ends_with("_color") {
+        StringPattern::Color
+    } else {
+        StringPattern::Text
+    }
+}
+
+const WORDS: &[&str] = &["the", "final", "champions", "league", "goal", "match", "liverpool", "madrid", "kiev", "we", "are", "win", "today", "fans", "team"];
+const LANGUAGES: &[&str] = &["en", "es", "th", "fr", "de", "ja", "pt", "und"];
+const DAYS: &[&str] = &["Mon", "Tue", "Wed", "Thu", "Fri", "Sat", "Sun"];
+const MONTHS: &[&str] = &["Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec"];
+
+
+pub struct Generator<R: Rng> {
+    rng: R,
+    config: GeneratorConfig
+}
+
+impl<R: Rng> Generator<R> {
+    pub fn new(rng: R, config: GeneratorConfig) -> Self {
+        Generator { rng, config }
+    }
+
+    pub fn record(&mut self, schema: &Schema) -> JsonValue {
+        self.value(schema, "")
+    }
+
+    fn value(&mut self, schema: &Schema, field_name: &str) -> JsonValue {
+        match schema {
+            Schema::Null => JsonValue::Null,
+            Schema::Boolean => JsonValue::Boolean(self.rng.gen_bool(0.5)),
+            Schema::Int => JsonValue::from(self.rng.gen_range(0, 1_000_000) as i32),
+            Schema::Long => {
+                if field_name == "id" || field_name.ends_with("_id") {
+                    JsonValue::from(self.rng.gen_range(100_000_000_000_000_000i64, 1_100_000_000_000_000_000i64))
+                } else {
+                    JsonValue::from(self.rng.gen_range(0i64, 100_000))
+                }
+            },
+            Schema::Float | Schema::Double => JsonValue::from(self.rng.gen_range(-180.0, 180.0)),
+            Schema::Bytes | Schema::Fixed { .. } => JsonValue::from(self.word()),
+            Schema::String => JsonValue::from(self.string(detect_pattern(field_name))),
+            Schema::Enum { symbols, .. } => JsonValue::from(symbols[self.rng.gen_range(0, symbols.len())].clone()),
+            Schema::Array(items) => {
+                let length = self.array_length();
+                let items: Vec<JsonValue> = (0..length).map(|_| self.value(items, field_name)).collect();
+                JsonValue::Array(items)
+            },
+            Schema::Map(values) => {
+                let mut object = JsonValue::new_object();
+                for i in 0..self.array_length() {
+                    object[format!("key{}", i).as_str()] = self.value(values, field_name);
+                }
+                object
+            },
+            Schema::Record { fields, .. } => {
+                let mut object = JsonValue::new_object();
+                for field in fields {
+                    object[field.name.as_str()] = self.value(&field.schema, &field.name);
+                }
+                object
+            },
+            Schema::Union(union) => {
+                let variants = union.variants();
+                let non_null: Vec<&Schema> = variants.iter().filter(|v| **v != Schema::Null).collect();
+                let has_null = non_null.len() < variants.len();
+                if non_null.is_empty() || (has_null && self.rng.gen_bool(self.config.null_probability)) {
+                    JsonValue::Null
+                } else {
+                    let variant = non_null[self.rng.gen_range(0, non_null.len())];
+                    self.value(variant, field_name)
+                }
+            }
+        }
+    }
+
+    // geometric with the configured mean, capped
+    fn array_length(&mut self) -> usize {
+        let p = self.config.mean_array_length / (self.config.mean_array_length + 1.0);
+        let mut length = 0;
+        while length < self.config.max_array_length && self.rng.gen_bool(p) {
+            length += 1;
+        }
+        length
+    }
+
+    fn word(&mut self) -> &'static str {
+        WORDS[self.rng.gen_range(0, WORDS.len())]
+    }
+
+    fn string(&mut self, pattern: StringPattern) -> String {
+        match pattern {
+            StringPattern::TwitterDate => {
+                format!("{} {} {:02} {:02}:{:02}:{:02} +0000 {}",
+                        DAYS[self.rng.gen_range(0, DAYS.len())],
+                        MONTHS[self.rng.gen_range(0, MONTHS.len())],
+                        self.rng.gen_range(1, 29),
+                        self.rng.gen_range(0, 24),
+                        self.rng.gen_range(0, 60),
+                        self.rng.gen_range(0, 60),
+                        self.rng.gen_range(2008, 2021))
+            },
+            StringPattern::Url => format!("https://t.co/{}{}", self.word(), self.rng.gen_range(1000, 100_000)),
+            StringPattern::Digits => {
+                let length = self.rng.gen_range(9, 20);
+                (0..length).map(|i| std::char::from_digit(self.rng.gen_range(if i == 0 { 1 } else { 0 }, 10), 10).unwrap()).collect()
+            },
+            StringPattern::Language => LANGUAGES[self.rng.gen_range(0, LANGUAGES.len())].to_owned(),
+            StringPattern::Color => format!("{:06X}", self.rng.gen_range(0, 0x100_0000)),
+            StringPattern::Text => {
+                let words = self.rng.gen_range(1, 15);
+                (0..words).map(|_| self.word()).collect::<Vec<_>>().join(" ")
+            }
+        }
+    }
+}
+
+
+pub fn generate<R: Rng, W: Write>(generator: &mut Generator<R>, schema: &Schema, records: usize, output: &mut W) -> Result<(), Error> {
+    for _ in 0..records {
+        writeln!(output, "{}", generator.record(schema).dump())?;
+    }
+    Ok(())
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rand::SeedableRng;
+    use rand::rngs::StdRng;
+    use crate::avro::infer_schema;
+    use crate::conformity::{score_record, ConformityReport};
+
+    #[test]
+    fn test_generated_records_match_schema() {
+        let schema = Schema::parse_str(r#"{"name":"t","type":"record","fields":[{"name":"id","type":"long"},{"name":"created_at","type":"string"},{"name":"user","type":["null",{"name":"user","type":"record","fields":[{"name":"lang","type":"string"}]}]},{"name":"indices","type":{"type":"array","items":"long"}}]}"#).unwrap();
+        let mut generator = Generator::new(StdRng::seed_from_u64(42), GeneratorConfig::default());
+        let mut report = ConformityReport::default();
+        for _ in 0..100 {
+            let record = generator.record(&schema);
+            assert!(infer_schema(&record, "t").is_ok());
+            assert_eq!(score_record(&record, &schema, &mut report).score(), 1.0);
+        }
+    }
+}
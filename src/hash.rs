@@ -0,0 +1,125 @@
+use std::io::Write;
+use json::JsonValue;
+use sha2::{Sha256, Digest};
+use failure::Error;
+use crate::canonical::canonicalize_exact;
+use crate::convert::{Location, RecordWriter};
+
+
+// SHA-256 over the canonical form, so key order and number formatting don't change the hash.
+// Integers are hashed from their exact digits, ids above 2^53 would collide as doubles.
+pub fn content_hash(json: &JsonValue) -> Result<String, Error> {
+    let canonical = canonicalize_exact(json)?;
+    Ok(format!("{:x}", Sha256::digest(canonical.as_bytes())))
+}
+
+
+pub enum HashOutput<S: Write> {
+    // adds the hash to every record as a string field, the schema needs it too (see with_extra_field)
+    Field(String),
+    // writes "<record number>\t<hash>" lines next to the output, numbered from 0
+    Sidecar(S)
+}
+
+
+// Hashes the records written through it.
+pub struct HashingWriter<'w, S: Write> {
+    inner: &'w mut dyn RecordWriter,
+    output: HashOutput<S>,
+    records: usize
+}
+
+impl<'w, S: Write> HashingWriter<'w, S> {
+    pub fn new(inner: &'w mut dyn RecordWriter, output: HashOutput<S>) -> Self {
+        HashingWriter { inner, output, records: 0 }
+    }
+}
+
+impl<'w, S: Write> RecordWriter for HashingWriter<'w, S> {
+    fn write(&mut self, record: &JsonValue) -> Result<(), Error> {
+        let hash = content_hash(record)?;
+        match &mut self.output {
+            HashOutput::Field(field_name) => {
+                let mut record = record.clone();
+                record[field_name.as_str()] = JsonValue::from(hash);
+                self.inner.write(&record)?;
+            },
+            HashOutput::Sidecar(sidecar) => {
+                self.inner.write(record)?;
+                writeln!(sidecar, "{}\t{}", self.records, hash)?;
+            }
+        }
+        self.records += 1;
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<(), Error> {
+        if let HashOutput::Sidecar(sidecar) = &mut self.output {
+            sidecar.flush()?;
+        }
+        self.inner.finish()
+    }
+
+    fn locate(&mut self, location: &Location) {
+        self.inner.locate(location);
+    }
+
+    fn full(&self) -> bool {
+        self.inner.full()
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_content_hash_ignores_key_order() {
+        let a = json::parse(r#"{"a": 1, "b": [true, null]}"#).unwrap();
+        let b = json::parse(r#"{"b": [true, null], "a": 1.0}"#).unwrap();
+        assert_eq!(content_hash(&a).unwrap(), content_hash(&b).unwrap());
+    }
+
+    #[test]
+    fn test_content_hash_of_large_ids() {
+        let a = json::parse(r#"{"id": 1000514497163542528}"#).unwrap();
+        let b = json::parse(r#"{"id": 1000514497163542529}"#).unwrap();
+        assert_ne!(content_hash(&a).unwrap(), content_hash(&b).unwrap());
+        assert_eq!(content_hash(&a).unwrap(), content_hash(&json::parse(r#"{"id": 10005144971635425280e-1}"#).unwrap()).unwrap());
+    }
+
+    struct Records(Vec<JsonValue>);
+
+    impl RecordWriter for Records {
+        fn write(&mut self, record: &JsonValue) -> Result<(), Error> {
+            self.0.push(record.clone());
+            Ok(())
+        }
+
+        fn finish(&mut self) -> Result<(), Error> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_hashing_writer() {
+        let record = json::parse(r#"{"id": 1, "text": "a"}"#).unwrap();
+        let mut records = Records(Vec::new());
+        let mut writer = HashingWriter::new(&mut records, HashOutput::<Vec<u8>>::Field("content_hash".to_owned()));
+        writer.write(&record).unwrap();
+        writer.finish().unwrap();
+        assert_eq!(records.0[0]["content_hash"].as_str(), Some(content_hash(&record).unwrap().as_str()));
+        assert_eq!(records.0[0]["id"], record["id"]);
+
+        let mut records = Records(Vec::new());
+        let mut sidecar = Vec::new();
+        let mut writer = HashingWriter::new(&mut records, HashOutput::Sidecar(&mut sidecar));
+        writer.write(&record).unwrap();
+        writer.write(&record).unwrap();
+        writer.finish().unwrap();
+        assert_eq!(records.0, vec![record.clone(), record.clone()]);
+        let hash = content_hash(&record).unwrap();
+        assert_eq!(String::from_utf8(sidecar).unwrap(), format!("0\t{}\n1\t{}\n", hash, hash));
+    }
+}
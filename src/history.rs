@@ -0,0 +1,190 @@
+use std::fmt::Write;
+use std::process::Command;
+use std::time::{SystemTime, UNIX_EPOCH};
+use rusqlite::{params, Connection};
+use failure::Error;
+use crate::bench::BenchResults;
+use crate::window::civil_from_days;
+
+
+// One row per bench run and one per benchmark in it, the run keyed by commit, time and machine.
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS runs (
+        id INTEGER PRIMARY KEY,
+        commit_hash TEXT,
+        recorded_at INTEGER NOT NULL,
+        machine TEXT NOT NULL,
+        input TEXT NOT NULL,
+        decoder TEXT NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS results (
+        run_id INTEGER NOT NULL REFERENCES runs(id),
+        benchmark TEXT NOT NULL,
+        records INTEGER NOT NULL,
+        errors INTEGER NOT NULL,
+        elapsed_ms REAL NOT NULL,
+        stddev_ms REAL NOT NULL,
+        records_per_second REAL NOT NULL,
+        input_bytes_per_second REAL NOT NULL
+    );
+    CREATE INDEX IF NOT EXISTS results_benchmark ON results (benchmark);
+";
+
+pub fn open(path: &str) -> Result<Connection, Error> {
+    let connection = Connection::open(path)?;
+    connection.execute_batch(SCHEMA)?;
+    Ok(connection)
+}
+
+// The commit checked out in the working directory, None outside a git repository.
+pub fn git_commit() -> Option<String> {
+    let output = Command::new("git").args(&["rev-parse", "--short=12", "HEAD"]).output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    Some(String::from_utf8_lossy(&output.stdout).trim().to_owned()).filter(|commit| !commit.is_empty())
+}
+
+pub fn machine() -> String {
+    dns_lookup::get_hostname().unwrap_or_else(|_| "unknown".to_owned())
+}
+
+// Returns the id of the new run.
+pub fn save(connection: &mut Connection, results: &BenchResults, commit: Option<&str>, machine: &str) -> Result<i64, Error> {
+    let recorded_at = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0) as i64;
+    let transaction = connection.transaction()?;
+    transaction.execute(
+        "INSERT INTO runs (commit_hash, recorded_at, machine, input, decoder) VALUES (?1, ?2, ?3, ?4, ?5)",
+        params![commit, recorded_at, machine, results.input, results.decoder.name()]
+    )?;
+    let run_id = transaction.last_insert_rowid();
+    for result in &results.results {
+        transaction.execute(
+            "INSERT INTO results (run_id, benchmark, records, errors, elapsed_ms, stddev_ms, records_per_second, input_bytes_per_second)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+            params![run_id, result.name, result.records as i64, result.errors as i64, result.elapsed_ms, result.stats.stddev_ms,
+                    result.records_per_second, result.input_bytes_per_second]
+        )?;
+    }
+    transaction.commit()?;
+    Ok(run_id)
+}
+
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct HistoryRow {
+    pub commit: Option<String>,
+    pub recorded_at: i64,
+    pub machine: String,
+    pub elapsed_ms: f64,
+    pub stddev_ms: f64,
+    pub records_per_second: f64
+}
+
+// The runs of a benchmark, oldest first, the last `limit` of them; on one machine only when given,
+// times of different machines not being comparable.
+pub fn history(connection: &Connection, benchmark: &str, machine: Option<&str>, limit: usize) -> Result<Vec<HistoryRow>, Error> {
+    let mut statement = connection.prepare(
+        "SELECT commit_hash, recorded_at, machine, elapsed_ms, stddev_ms, records_per_second
+         FROM results JOIN runs ON runs.id = results.run_id
+         WHERE benchmark = ?1 AND (?2 IS NULL OR machine = ?2)
+         ORDER BY recorded_at DESC, runs.id DESC LIMIT ?3"
+    )?;
+    let rows = statement.query_map(params![benchmark, machine, limit as i64], |row| {
+        Ok(HistoryRow {
+            commit: row.get(0)?,
+            recorded_at: row.get(1)?,
+            machine: row.get(2)?,
+            elapsed_ms: row.get(3)?,
+            stddev_ms: row.get(4)?,
+            records_per_second: row.get(5)?
+        })
+    })?;
+    let mut rows = rows.collect::<Result<Vec<_>, _>>()?;
+    rows.reverse();
+    Ok(rows)
+}
+
+// A table of the rows with the change from the run before and from the first one shown.
+pub fn trend(rows: &[HistoryRow]) -> String {
+    let mut text = String::new();
+    writeln!(text, "{:<20} {:<12} {:<16} {:>10} {:>8} {:>12} {:>9} {:>9}",
+             "date", "commit", "machine", "time (ms)", "stddev", "records/s", "previous", "first").unwrap();
+    for (i, row) in rows.iter().enumerate() {
+        let change = |base: &HistoryRow| format!("{:+.1}%", (row.elapsed_ms / base.elapsed_ms.max(0.001) - 1.0) * 100.0);
+        let previous = if i > 0 { change(&rows[i - 1]) } else { "-".to_owned() };
+        let first = if i > 0 { change(&rows[0]) } else { "-".to_owned() };
+        writeln!(text, "{:<20} {:<12} {:<16} {:>10.1} {:>8.1} {:>12.0} {:>9} {:>9}",
+                 date(row.recorded_at), row.commit.as_deref().unwrap_or("-"), row.machine, row.elapsed_ms, row.stddev_ms,
+                 row.records_per_second, previous, first).unwrap();
+    }
+    text
+}
+
+fn date(seconds: i64) -> String {
+    let (year, month, day) = civil_from_days(seconds.div_euclid(86400));
+    let time = seconds.rem_euclid(86400);
+    format!("{:04}-{:02}-{:02} {:02}:{:02}", year, month, day, time / 3600, time / 60 % 60)
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::collections::BTreeMap;
+    use std::time::Duration;
+    use crate::bench::BenchResult;
+    use crate::io::Decoder;
+    use crate::measure::Stats;
+
+    fn results(elapsed_ms: u64) -> BenchResults {
+        let result = BenchResult {
+            name: "parse:simd".to_owned(),
+            kind: "parse".to_owned(),
+            parameters: vec!["simd".to_owned()],
+            records: 1000,
+            errors: 0,
+            elapsed_ms: elapsed_ms as f64,
+            records_per_second: 1e6 / elapsed_ms as f64,
+            input_bytes_per_second: 1e9 / elapsed_ms as f64,
+            decompressed_bytes_per_second: 1e10 / elapsed_ms as f64,
+            joules: None,
+            allocations: Default::default(),
+            latency: None,
+            passes: 1,
+            checksum: 0,
+            samples_ms: Vec::new(),
+            perf: None,
+            positions: Vec::new(),
+            compression_ratio: None,
+            stats: Stats::of(&[Duration::from_millis(elapsed_ms)]).unwrap()
+        };
+        BenchResults {
+            input: "tweets.json.gz".to_owned(),
+            input_bytes: 1_000_000,
+            decoder: Decoder::Flate2,
+            warmup: 0,
+            runs: 1,
+            results: vec![result],
+            failed: BTreeMap::new()
+        }
+    }
+
+    #[test]
+    fn test_history() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("results.db").to_string_lossy().into_owned();
+        let mut connection = open(&path).unwrap();
+        save(&mut connection, &results(200), Some("aaaa"), "laptop").unwrap();
+        save(&mut connection, &results(100), Some("bbbb"), "laptop").unwrap();
+        save(&mut connection, &results(50), None, "ci").unwrap();
+
+        let rows = history(&open(&path).unwrap(), "parse:simd", Some("laptop"), 10).unwrap();
+        assert_eq!(rows.iter().map(|row| row.commit.as_deref()).collect::<Vec<_>>(), vec![Some("aaaa"), Some("bbbb")]);
+        let text = trend(&rows);
+        assert!(text.lines().nth(2).unwrap().ends_with("-50.0%    -50.0%"), "{}", text);
+        assert_eq!(history(&connection, "parse:simd", None, 2).unwrap().len(), 2);
+        assert!(history(&connection, "parse:nope", None, 10).unwrap().is_empty());
+        assert_eq!(date(1527364441), "2018-05-26 19:54");
+    }
+}
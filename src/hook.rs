@@ -0,0 +1,121 @@
+use std::collections::BTreeMap;
+use avro_rs::Schema;
+use avro_rs::schema::{Name, UnionSchema};
+use failure::Error;
+
+
+// Rewrites the schema a SchemaInferrer emits before it is written or used for conversion, so
+// applications can apply their house rules to inferred schemas: naming, namespaces, nullability.
+// Closures of Schema -> Result<Schema, Error> are hooks too.
+pub trait SchemaHook: Send + Sync {
+    fn apply(&self, schema: Schema) -> Result<Schema, Error>;
+}
+
+impl<F> SchemaHook for F where F: Fn(Schema) -> Result<Schema, Error> + Send + Sync {
+    fn apply(&self, schema: Schema) -> Result<Schema, Error> {
+        self(schema)
+    }
+}
+
+
+// Calls rename on the name of every record, enum and fixed type in the schema, nested ones
+// included.
+pub fn map_names(schema: Schema, rename: &mut dyn FnMut(&mut Name)) -> Result<Schema, Error> {
+    Ok(match schema {
+        Schema::Record { mut name, doc, mut fields, lookup } => {
+            rename(&mut name);
+            for field in &mut fields {
+                let schema = std::mem::replace(&mut field.schema, Schema::Null);
+                field.schema = map_names(schema, rename)?;
+            }
+            Schema::Record { name, doc, fields, lookup }
+        },
+        Schema::Enum { mut name, doc, symbols } => {
+            rename(&mut name);
+            Schema::Enum { name, doc, symbols }
+        },
+        Schema::Fixed { mut name, size } => {
+            rename(&mut name);
+            Schema::Fixed { name, size }
+        },
+        Schema::Array(items) => Schema::Array(Box::new(map_names(*items, rename)?)),
+        Schema::Map(values) => Schema::Map(Box::new(map_names(*values, rename)?)),
+        Schema::Union(union) => {
+            let variants = union.variants()
+                .iter()
+                .map(|variant| map_names(variant.clone(), rename))
+                .collect::<Result<Vec<_>, Error>>()?;
+            Schema::Union(UnionSchema::new(variants)?)
+        },
+        schema => schema
+    })
+}
+
+// Puts every named type without a namespace of its own into this one.
+pub struct Namespace(pub String);
+
+impl SchemaHook for Namespace {
+    fn apply(&self, schema: Schema) -> Result<Schema, Error> {
+        map_names(schema, &mut |name| {
+            if name.namespace.is_none() {
+                name.namespace = Some(self.0.clone());
+            }
+        })
+    }
+}
+
+// Renames types by their inferred name, which is the name of the field they were found in.
+pub struct RenameTypes(pub BTreeMap<String, String>);
+
+impl SchemaHook for RenameTypes {
+    fn apply(&self, schema: Schema) -> Result<Schema, Error> {
+        map_names(schema, &mut |name| {
+            if let Some(renamed) = self.0.get(&name.name) {
+                name.name = renamed.clone();
+            }
+        })
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::infer::SchemaInferrer;
+
+    #[test]
+    fn test_hooks() {
+        let renames: BTreeMap<String, String> = vec![("user".to_owned(), "User".to_owned())].into_iter().collect();
+        let mut inferrer = SchemaInferrer::new("tweet")
+            .with_hook(RenameTypes(renames))
+            .with_hook(Namespace("com.example".to_owned()))
+            // every top level field nullable, as a closure
+            .with_hook(|schema: Schema| -> Result<Schema, Error> { match schema {
+                Schema::Record { name, doc, mut fields, lookup } => {
+                    for field in &mut fields {
+                        if let Schema::Union(_) = field.schema {
+                            continue;
+                        }
+                        let schema = std::mem::replace(&mut field.schema, Schema::Null);
+                        field.schema = Schema::Union(UnionSchema::new(vec![Schema::Null, schema])?);
+                    }
+                    Ok(Schema::Record { name, doc, fields, lookup })
+                },
+                schema => Ok(schema)
+            }});
+        inferrer.update(&json::parse(r#"{"id":1,"user":{"name":"u"}}"#).unwrap()).unwrap();
+        let schema = inferrer.into_schema().unwrap();
+        let text = serde_json::to_string(&schema).unwrap();
+        assert!(text.contains("\"User\"") && !text.contains("\"user\",\"fields\""), "{}", text);
+        match &schema {
+            Schema::Record { name, fields, .. } => {
+                assert_eq!((name.name.as_str(), name.namespace.as_deref()), ("tweet", Some("com.example")));
+                assert!(fields.iter().all(|field| match &field.schema {
+                    Schema::Union(union) => union.variants()[0] == Schema::Null,
+                    _ => false
+                }));
+            },
+            _ => panic!("not a record: {}", text)
+        }
+    }
+}
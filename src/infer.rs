@@ -0,0 +1,530 @@
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashSet};
+use std::str::FromStr;
+use std::sync::{Arc, Mutex, RwLock};
+use json;
+use json::JsonValue;
+use avro_rs::Schema;
+use avro_rs::schema::UnionSchema;
+use failure::{Error, format_err};
+use crate::avro::{infer_schema, merge_schemas};
+use crate::convert::{Location, RecordWriter};
+use crate::hook::SchemaHook;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::convert::convert_with;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::parser::serde_to_json;
+
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct FieldStats {
+    // number of records the path appeared in
+    pub present: usize,
+    // number of values per inferred type, arrays can contribute several values per record
+    pub types: BTreeMap<&'static str, usize>
+}
+
+
+// How inference types arrays whose items are of several kinds, like [1, {"a": 1}], and fields that
+// are arrays in some records and single values in others.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ArrayPolicy {
+    // the items are a union of their types, a field that is an array in some records only is a
+    // union of the array and the other types
+    Union,
+    // a field that is an array anywhere is an array everywhere, single values becoming arrays of
+    // one item of the union of both types
+    Promote,
+    // arrays of items of several kinds hold the JSON text of the items that are not strings
+    Stringify
+}
+
+impl Default for ArrayPolicy {
+    fn default() -> Self {
+        ArrayPolicy::Union
+    }
+}
+
+impl FromStr for ArrayPolicy {
+    type Err = Error;
+
+    fn from_str(name: &str) -> Result<Self, Error> {
+        match name {
+            "union" => Ok(ArrayPolicy::Union),
+            "promote" => Ok(ArrayPolicy::Promote),
+            "stringify" => Ok(ArrayPolicy::Stringify),
+            _ => Err(format_err!("unknown array policy {}, expected union, promote or stringify", name))
+        }
+    }
+}
+
+impl ArrayPolicy {
+    // The record the way the policy has inference see it; conversion has to see the same, see
+    // ArrayPolicyWriter.
+    pub fn apply<'j>(self, json: &'j JsonValue, schema: Option<&Schema>) -> Cow<'j, JsonValue> {
+        match (self, schema) {
+            (ArrayPolicy::Stringify, _) => Cow::Owned(stringify_mixed_arrays(json)),
+            (ArrayPolicy::Promote, Some(schema)) => Cow::Owned(promote_values(json, schema)),
+            _ => Cow::Borrowed(json)
+        }
+    }
+}
+
+// numbers of both kinds are one kind here, a union of long and double is not messy
+fn item_kind(json: &JsonValue) -> &'static str {
+    match kind_name(json) {
+        "long" | "double" => "number",
+        kind => kind
+    }
+}
+
+fn stringify_mixed_arrays(json: &JsonValue) -> JsonValue {
+    match json {
+        JsonValue::Object(_) => {
+            let mut object = JsonValue::new_object();
+            for (name, value) in json.entries() {
+                object[name] = stringify_mixed_arrays(value);
+            }
+            object
+        },
+        JsonValue::Array(items) => {
+            let kinds: HashSet<&str> = items.iter().filter(|item| !item.is_null()).map(item_kind).collect();
+            if kinds.len() > 1 {
+                JsonValue::Array(items.iter().map(|item| match item {
+                    JsonValue::Null | JsonValue::String(_) | JsonValue::Short(_) => item.clone(),
+                    _ => JsonValue::from(item.dump())
+                }).collect())
+            } else {
+                JsonValue::Array(items.iter().map(stringify_mixed_arrays).collect())
+            }
+        },
+        _ => json.clone()
+    }
+}
+
+// Single values where the schema has an array, alone or next to null, as one item arrays.
+fn promote_values(json: &JsonValue, schema: &Schema) -> JsonValue {
+    let array_items = match schema {
+        Schema::Array(items) => Some(items.as_ref()),
+        Schema::Union(union) => union.variants().iter().find_map(|variant| match variant {
+            Schema::Array(items) => Some(items.as_ref()),
+            _ => None
+        }),
+        _ => None
+    };
+    match (json, schema, array_items) {
+        (JsonValue::Null, _, _) => JsonValue::Null,
+        (JsonValue::Array(items), _, Some(items_schema)) => JsonValue::Array(items.iter().map(|item| promote_values(item, items_schema)).collect()),
+        (_, _, Some(items_schema)) => JsonValue::Array(vec![promote_values(json, items_schema)]),
+        (JsonValue::Object(_), Schema::Record { fields, .. }, None) => {
+            let mut object = json.clone();
+            for field in fields {
+                if object.has_key(&field.name) {
+                    object[field.name.as_str()] = promote_values(&json[field.name.as_str()], &field.schema);
+                }
+            }
+            object
+        },
+        (JsonValue::Object(_), Schema::Map(values), None) => {
+            let mut object = JsonValue::new_object();
+            for (name, value) in json.entries() {
+                object[name] = promote_values(value, values);
+            }
+            object
+        },
+        _ => json.clone()
+    }
+}
+
+// Rewrites unions of an array and other types into an array of the union of the items and the
+// other types, keeping null next to it.
+fn promote_arrays(schema: Schema) -> Result<Schema, Error> {
+    Ok(match schema {
+        Schema::Record { name, doc, mut fields, lookup } => {
+            for field in &mut fields {
+                field.schema = promote_arrays(std::mem::replace(&mut field.schema, Schema::Null))?;
+            }
+            Schema::Record { name, doc, fields, lookup }
+        },
+        Schema::Array(items) => Schema::Array(Box::new(promote_arrays(*items)?)),
+        Schema::Map(values) => Schema::Map(Box::new(promote_arrays(*values)?)),
+        Schema::Union(union) => {
+            let variants = union.variants().to_vec();
+            if !variants.iter().any(|variant| matches!(variant, Schema::Array(_))) {
+                return Ok(Schema::Union(union));
+            }
+            let nullable = variants.contains(&Schema::Null);
+            let mut items: Option<Schema> = None;
+            for variant in variants.into_iter().filter(|variant| *variant != Schema::Null) {
+                let variant = match variant {
+                    Schema::Array(variant_items) => *variant_items,
+                    single => single
+                };
+                items = Some(match items {
+                    Some(items) => merge_schemas(items, variant)?,
+                    None => variant
+                });
+            }
+            let array = Schema::Array(Box::new(promote_arrays(items.unwrap_or(Schema::Null))?));
+            if nullable { Schema::Union(UnionSchema::new(vec![Schema::Null, array])?) } else { array }
+        },
+        schema => schema
+    })
+}
+
+
+// Incremental schema inference: records are fed one at a time and merged into the running schema,
+// while per-field presence and type counts are collected along the way.
+pub struct SchemaInferrer {
+    name: String,
+    schema: Option<Schema>,
+    records: usize,
+    fields: BTreeMap<String, FieldStats>,
+    array_policy: ArrayPolicy,
+    // applied in order by into_schema
+    hooks: Vec<Box<dyn SchemaHook>>
+}
+
+impl SchemaInferrer {
+    pub fn new(name: &str) -> Self {
+        SchemaInferrer {
+            name: name.to_owned(),
+            schema: None,
+            records: 0,
+            fields: BTreeMap::new(),
+            array_policy: ArrayPolicy::default(),
+            hooks: Vec::new()
+        }
+    }
+
+    pub fn with_array_policy(mut self, array_policy: ArrayPolicy) -> Self {
+        self.array_policy = array_policy;
+        self
+    }
+
+    pub fn with_hook(mut self, hook: impl SchemaHook + 'static) -> Self {
+        self.hooks.push(Box::new(hook));
+        self
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn from_file(file_path: &str, name: &str) -> Result<Self, Error> {
+        SchemaInferrer::from_file_with(file_path, name, &mut |line| Ok(json::parse(line)?))
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn from_file_with(file_path: &str, name: &str, parse: &mut dyn FnMut(&str) -> Result<JsonValue, Error>) -> Result<Self, Error> {
+        let mut inferrer = SchemaInferrer::new(name);
+        convert_with(file_path, &mut inferrer, parse)?;
+        Ok(inferrer)
+    }
+
+    pub fn update(&mut self, json: &JsonValue) -> Result<(), Error> {
+        let record_schema = infer_schema(&self.array_policy.apply(json, None), &self.name)?;
+        let schema = match self.schema.take() {
+            Some(base) => merge_schemas(base, record_schema)?,
+            None => record_schema
+        };
+        self.schema = Some(if self.array_policy == ArrayPolicy::Promote { promote_arrays(schema)? } else { schema });
+        self.records += 1;
+
+        let mut seen = HashSet::new();
+        collect_stats(json, "", &mut self.fields, &mut seen);
+        for path in seen {
+            self.fields.get_mut(&path).unwrap().present += 1;
+        }
+        Ok(())
+    }
+
+    pub fn records(&self) -> usize {
+        self.records
+    }
+
+    // the schema merged so far, without the hooks
+    pub fn schema(&self) -> Option<&Schema> {
+        self.schema.as_ref()
+    }
+
+    pub fn fields(&self) -> &BTreeMap<String, FieldStats> {
+        &self.fields
+    }
+
+    pub fn into_schema(self) -> Result<Schema, Error> {
+        let schema = self.schema.ok_or_else(|| format_err!("no records were seen"))?;
+        self.hooks.iter().try_fold(schema, |schema, hook| hook.apply(schema))
+    }
+}
+
+// lets inference run at the end of a writer chain, e.g. behind transforms
+impl RecordWriter for SchemaInferrer {
+    fn write(&mut self, record: &JsonValue) -> Result<(), Error> {
+        self.update(record)
+    }
+
+    fn finish(&mut self) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+
+// Applies the array policy inference ran with to the records going into writer, so that they fit
+// the schema it inferred. Nothing to do for ArrayPolicy::Union.
+pub struct ArrayPolicyWriter<'w> {
+    inner: &'w mut dyn RecordWriter,
+    schema: &'w Schema,
+    policy: ArrayPolicy
+}
+
+impl<'w> ArrayPolicyWriter<'w> {
+    pub fn new(inner: &'w mut dyn RecordWriter, schema: &'w Schema, policy: ArrayPolicy) -> Self {
+        ArrayPolicyWriter { inner, schema, policy }
+    }
+}
+
+impl<'w> RecordWriter for ArrayPolicyWriter<'w> {
+    fn write(&mut self, record: &JsonValue) -> Result<(), Error> {
+        self.inner.write(&self.policy.apply(record, Some(self.schema)))
+    }
+
+    fn finish(&mut self) -> Result<(), Error> {
+        self.inner.finish()
+    }
+
+    fn locate(&mut self, location: &Location) {
+        self.inner.locate(location)
+    }
+
+    fn full(&self) -> bool {
+        self.inner.full()
+    }
+}
+
+
+// What a SchemaInferrer had seen at one point.
+#[derive(Debug, Clone, Default)]
+pub struct InferenceSnapshot {
+    pub records: usize,
+    pub schema: Option<Schema>,
+    pub fields: BTreeMap<String, FieldStats>
+}
+
+// A SchemaInferrer to share between threads: ingestion updates it while readers take snapshots,
+// which are republished every publish_every records and cost readers an Arc clone, so serving the
+// current schema never waits for a merge.
+pub struct SharedInferrer {
+    inferrer: Mutex<SchemaInferrer>,
+    snapshot: RwLock<Arc<InferenceSnapshot>>,
+    publish_every: usize
+}
+
+impl SharedInferrer {
+    pub fn new(name: &str, publish_every: usize) -> Self {
+        SharedInferrer {
+            inferrer: Mutex::new(SchemaInferrer::new(name)),
+            snapshot: RwLock::new(Arc::new(InferenceSnapshot::default())),
+            publish_every: publish_every.max(1)
+        }
+    }
+
+    pub fn update(&self, json: &JsonValue) -> Result<(), Error> {
+        let mut inferrer = self.inferrer.lock().unwrap();
+        inferrer.update(json)?;
+        if inferrer.records() % self.publish_every == 0 {
+            self.publish_from(&inferrer);
+        }
+        Ok(())
+    }
+
+    // Makes everything seen so far visible, e.g. at the end of an input.
+    pub fn publish(&self) {
+        self.publish_from(&self.inferrer.lock().unwrap());
+    }
+
+    fn publish_from(&self, inferrer: &SchemaInferrer) {
+        let snapshot = InferenceSnapshot {
+            records: inferrer.records(),
+            schema: inferrer.schema().cloned(),
+            fields: inferrer.fields().clone()
+        };
+        *self.snapshot.write().unwrap() = Arc::new(snapshot);
+    }
+
+    pub fn snapshot(&self) -> Arc<InferenceSnapshot> {
+        self.snapshot.read().unwrap().clone()
+    }
+}
+
+
+// Schema of values the caller already holds, merged the same way records read from a file are.
+pub fn infer_json_batch(values: &[JsonValue], name: &str) -> Result<Schema, Error> {
+    let mut inferrer = SchemaInferrer::new(name);
+    for value in values {
+        inferrer.update(value)?;
+    }
+    inferrer.into_schema()
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub fn infer_schema_batch(values: &[serde_json::Value], name: &str) -> Result<Schema, Error> {
+    let mut inferrer = SchemaInferrer::new(name);
+    for value in values {
+        inferrer.update(&serde_to_json(value.clone()))?;
+    }
+    inferrer.into_schema()
+}
+
+
+pub fn kind_name(json: &JsonValue) -> &'static str {
+    match json {
+        JsonValue::Null => "null",
+        JsonValue::Boolean(_) => "boolean",
+        JsonValue::Number(number) => {
+            let (_, _, exponent) = number.as_parts();
+            if exponent == 0 { "long" } else { "double" }
+        },
+        JsonValue::String(_) | JsonValue::Short(_) => "string",
+        JsonValue::Array(_) => "array",
+        JsonValue::Object(_) => "record"
+    }
+}
+
+fn collect_stats(json: &JsonValue, path: &str, fields: &mut BTreeMap<String, FieldStats>, seen: &mut HashSet<String>) {
+    match json {
+        JsonValue::Object(_) => {
+            for (name, value) in json.entries() {
+                let field_path = if path.is_empty() { name.to_owned() } else { format!("{}.{}", path, name) };
+                record_value(value, &field_path, fields, seen);
+                collect_stats(value, &field_path, fields, seen);
+            }
+        },
+        JsonValue::Array(items) => {
+            let items_path = format!("{}[]", path);
+            for item in items {
+                record_value(item, &items_path, fields, seen);
+                collect_stats(item, &items_path, fields, seen);
+            }
+        },
+        _ => {}
+    }
+}
+
+fn record_value(json: &JsonValue, path: &str, fields: &mut BTreeMap<String, FieldStats>, seen: &mut HashSet<String>) {
+    let stats = fields.entry(path.to_owned()).or_insert_with(FieldStats::default);
+    *stats.types.entry(kind_name(json)).or_insert(0) += 1;
+    if !seen.contains(path) {
+        seen.insert(path.to_owned());
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_field_stats() {
+        let mut inferrer = SchemaInferrer::new("test");
+        inferrer.update(&json::parse(r#"{"a": 1, "b": [{"c": "x"}, {"c": 2}]}"#).unwrap()).unwrap();
+        inferrer.update(&json::parse(r#"{"a": null}"#).unwrap()).unwrap();
+
+        assert_eq!(inferrer.records(), 2);
+        assert_eq!(inferrer.fields()["a"].present, 2);
+        assert_eq!(inferrer.fields()["a"].types["long"], 1);
+        assert_eq!(inferrer.fields()["a"].types["null"], 1);
+        assert_eq!(inferrer.fields()["b[].c"].present, 1);
+        assert_eq!(inferrer.fields()["b[].c"].types.len(), 2);
+    }
+
+    #[test]
+    fn test_array_policies() {
+        // indices are arrays of numbers and media arrays of objects in every tweet, mixed is both
+        let tweets = [
+            r#"{"entities":{"hashtags":[{"text":"UCLfinal","indices":[0,9]}],"media":[{"id":1,"indices":[10,33]}]},"mixed":[1,{"a":1}],"urls":"x"}"#,
+            r#"{"entities":{"hashtags":[],"media":[{"id":2,"indices":[0,23]}]},"mixed":["b",2.5],"urls":["y","z"]}"#
+        ];
+        let records: Vec<JsonValue> = tweets.iter().map(|tweet| json::parse(tweet).unwrap()).collect();
+        let infer = |policy: ArrayPolicy| -> Schema {
+            let mut inferrer = SchemaInferrer::new("tweet").with_array_policy(policy);
+            for record in &records {
+                inferrer.update(record).unwrap();
+            }
+            inferrer.into_schema().unwrap()
+        };
+        let field = |schema: &serde_json::Value, name: &str| -> serde_json::Value {
+            schema["fields"].as_array().unwrap().iter().find(|field| field["name"] == name).unwrap()["type"].clone()
+        };
+        let entities = |schema: &serde_json::Value| field(schema, "entities");
+
+        let union = serde_json::to_value(infer(ArrayPolicy::Union)).unwrap();
+        let stringify = serde_json::to_value(infer(ArrayPolicy::Stringify)).unwrap();
+        let promote = serde_json::to_value(infer(ArrayPolicy::Promote)).unwrap();
+        for schema in &[&union, &stringify, &promote] {
+            // the regular arrays are the same under every policy
+            assert_eq!(entities(schema), entities(&union));
+            let media = field(&entities(schema), "media");
+            assert_eq!(media["type"], "array");
+            assert_eq!(field(&media["items"], "indices"), serde_json::json!({"type": "array", "items": "long"}));
+        }
+        let kinds = |items: &serde_json::Value| -> Vec<String> {
+            items.as_array().unwrap().iter().map(|item| item.as_str().map_or("record".to_owned(), str::to_owned)).collect()
+        };
+        let mut mixed = kinds(&field(&union, "mixed")["items"]);
+        mixed.sort();
+        assert_eq!(mixed, vec!["double", "long", "record", "string"]);
+        // the string "b" and the text of 2.5, of 1 and of {"a":1}
+        assert_eq!(field(&stringify, "mixed"), serde_json::json!({"type": "array", "items": "string"}));
+        assert_eq!(kinds(&field(&union, "urls")).len(), 2);
+        assert_eq!(field(&promote, "urls"), serde_json::json!({"type": "array", "items": "string"}));
+
+        let schema = infer(ArrayPolicy::Promote);
+        let promoted = ArrayPolicy::Promote.apply(&records[0], Some(&schema));
+        assert_eq!(promoted["urls"], json::array!["x"]);
+        assert!(crate::avro::json_to_avro(&promoted, &schema).is_ok());
+        assert!(crate::avro::json_to_avro(&records[0], &schema).is_err());
+        assert_eq!(ArrayPolicy::Stringify.apply(&records[0], None)["mixed"], json::array!["1", "{\"a\":1}"]);
+        assert!("flatten".parse::<ArrayPolicy>().is_err());
+    }
+
+    #[test]
+    fn test_infer_batch() {
+        let lines = [r#"{"a": 1, "b": "x"}"#, r#"{"a": 2, "b": null}"#];
+        let json_values: Vec<JsonValue> = lines.iter().map(|line| json::parse(line).unwrap()).collect();
+        let serde_values: Vec<serde_json::Value> = lines.iter().map(|line| serde_json::from_str(line).unwrap()).collect();
+
+        let mut inferrer = SchemaInferrer::new("test");
+        for value in &json_values {
+            inferrer.update(value).unwrap();
+        }
+        let expected = inferrer.into_schema().unwrap();
+        assert_eq!(infer_json_batch(&json_values, "test").unwrap(), expected);
+        assert_eq!(infer_schema_batch(&serde_values, "test").unwrap(), expected);
+        assert!(infer_schema_batch(&[], "test").is_err());
+    }
+
+    #[test]
+    fn test_shared_inferrer() {
+        fn shareable<T: Send + Sync>(_: &T) {}
+        let shared = Arc::new(SharedInferrer::new("test", 10));
+        shareable(&shared);
+        let writers: Vec<_> = (0..4).map(|writer| {
+            let shared = shared.clone();
+            std::thread::spawn(move || {
+                for i in 0..25 {
+                    shared.update(&json::parse(&format!(r#"{{"w{}": {}}}"#, writer, i)).unwrap()).unwrap();
+                    let snapshot = shared.snapshot();
+                    assert_eq!(snapshot.records % 10, 0);
+                }
+            })
+        }).collect();
+        writers.into_iter().for_each(|writer| writer.join().unwrap());
+        assert_eq!(shared.snapshot().records, 100);
+
+        shared.update(&json::parse(r#"{"w0": 1}"#).unwrap()).unwrap();
+        assert_eq!(shared.snapshot().records, 100);
+        shared.publish();
+        let snapshot = shared.snapshot();
+        assert_eq!(snapshot.records, 101);
+        assert_eq!(snapshot.fields["w0"].present, 26);
+        assert!(snapshot.schema.is_some());
+    }
+}
@@ -1,18 +1,150 @@
+use std::cell::Cell;
 use std::fs::File;
-use flate2::read::{GzDecoder, GzEncoder};
-use std::io::{BufReader, BufRead, Lines, Read};
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use std::io::{BufReader, BufRead, BufWriter, Cursor, Read, Write};
+use std::rc::Rc;
+use std::str::FromStr;
+use std::sync::RwLock;
+use std::time::Duration;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use crate::compress::{Compressor, StreamEncoder};
+use crate::measure::Throughput;
+use crate::seed::Seed;
+
+pub const DEFAULT_INPUT: &str = "TweetsChampions.json.gz";
+pub const INPUT_ENV: &str = "INPUT_FILE";
+
+// The input of a benchmark or conversion: --input when given, then $INPUT_FILE, then the fallback.
+pub fn input_file(explicit: Option<&str>, fallback: &str) -> String {
+    explicit
+        .map(|path| path.to_owned())
+        .or_else(|| std::env::var(INPUT_ENV).ok())
+        .unwrap_or_else(|| fallback.to_owned())
+}
+
+// The part of an input that is read: skip lines first, then keep each with the sample rate, then
+// stop after limit kept lines. Applies to GzipFile and open_input, the readers of benchmark and
+// conversion inputs, not to lookup tables or the temporary files of sort and shuffle.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Subset {
+    pub skip: usize,
+    pub limit: Option<usize>,
+    pub sample_rate: f64,
+    pub seed: Seed
+}
+
+impl Default for Subset {
+    fn default() -> Self {
+        Subset { skip: 0, limit: None, sample_rate: 1.0, seed: Seed(0) }
+    }
+}
+
+impl Subset {
+    pub fn apply<'a, T: 'a, I: Iterator<Item=T> + 'a>(self, lines: I) -> Box<dyn Iterator<Item=T> + 'a> {
+        let mut lines: Box<dyn Iterator<Item=T> + 'a> = Box::new(lines.skip(self.skip));
+        if self.sample_rate < 1.0 {
+            let (mut rng, rate) = (self.seed.rng("sample"), self.sample_rate.max(0.0));
+            lines = Box::new(lines.filter(move |_| rng.gen_bool(rate)));
+        }
+        match self.limit {
+            Some(limit) => Box::new(lines.take(limit)),
+            None => lines
+        }
+    }
+}
+
+lazy_static! {
+    static ref SUBSET: RwLock<Subset> = RwLock::new(Subset::default());
+}
+
+// Set once from the command line, before any input is opened.
+pub fn set_subset(subset: Subset) {
+    *SUBSET.write().unwrap() = subset;
+}
+
+pub fn subset() -> Subset {
+    *SUBSET.read().unwrap()
+}
+
+
+// What a GzipFile has read so far. Shared with its lines, so the counts are still there after the
+// lines are moved out and consumed.
+#[derive(Debug, Clone, Default)]
+pub struct ReadCounts {
+    // read from the file, which runs ahead of the lines by up to one decoder buffer
+    compressed_bytes: Rc<Cell<u64>>,
+    // of the lines returned, newlines included
+    decompressed_bytes: Rc<Cell<u64>>,
+    records: Rc<Cell<u64>>
+}
+
+impl ReadCounts {
+    pub fn compressed_bytes(&self) -> u64 {
+        self.compressed_bytes.get()
+    }
+
+    pub fn decompressed_bytes(&self) -> u64 {
+        self.decompressed_bytes.get()
+    }
+
+    pub fn records(&self) -> u64 {
+        self.records.get()
+    }
+
+    pub fn throughput(&self, elapsed: Duration) -> Throughput {
+        Throughput::new(self.records(), self.compressed_bytes(), self.decompressed_bytes(), elapsed)
+    }
+}
+
+fn add(counter: &Cell<u64>, n: usize) {
+    counter.set(counter.get() + n as u64);
+}
+
+struct CountingReader<R> {
+    inner: R,
+    bytes: Rc<Cell<u64>>
+}
+
+impl<R: Read> Read for CountingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        add(&self.bytes, n);
+        Ok(n)
+    }
+}
+
+pub struct CountedLines {
+    lines: Box<dyn Iterator<Item=std::io::Result<String>>>,
+    counts: ReadCounts
+}
+
+impl Iterator for CountedLines {
+    type Item = std::io::Result<String>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let line = self.lines.next()?;
+        if let Ok(line) = &line {
+            add(&self.counts.decompressed_bytes, line.len() + 1);
+            add(&self.counts.records, 1);
+        }
+        Some(line)
+    }
+}
 
 pub struct GzipFile {
-    pub lines: Lines<BufReader<GzDecoder<File>>>
+    pub lines: CountedLines,
+    pub counts: ReadCounts
 }
 
 impl GzipFile {
     pub fn new(file_path: &str) -> Self {
-        let file = File::open(file_path).unwrap();
-        let lines = GzDecoder::new(file);
-        let buf_reader = BufReader::new(lines);
-        let lines: Lines<BufReader<GzDecoder<File>>> = buf_reader.lines();
-        GzipFile{lines}
+        let counts = ReadCounts::default();
+        let file = CountingReader { inner: File::open(file_path).unwrap(), bytes: counts.compressed_bytes.clone() };
+        let lines = subset().apply(BufReader::new(GzDecoder::new(file)).lines());
+        GzipFile { lines: CountedLines { lines, counts: counts.clone() }, counts }
     }
 
     pub fn new_reader(file_path: &str) -> BufReader<GzDecoder<File>> {
@@ -21,3 +153,271 @@ impl GzipFile {
         BufReader::new(lines)
     }
 }
+
+
+// How gzip input is decompressed. flate2 streams, the others decode the whole file into memory
+// first and only read single member gzip files.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Decoder {
+    Flate2,
+    Libdeflater,
+    Zune
+}
+
+impl Default for Decoder {
+    fn default() -> Self {
+        Decoder::Flate2
+    }
+}
+
+impl FromStr for Decoder {
+    type Err = failure::Error;
+
+    fn from_str(name: &str) -> Result<Self, failure::Error> {
+        Decoder::all()
+            .iter()
+            .find(|decoder| decoder.name() == name)
+            .cloned()
+            .ok_or_else(|| failure::format_err!("unknown decoder {}, expected flate2, libdeflater or zune", name))
+    }
+}
+
+impl Decoder {
+    pub fn all() -> [Decoder; 3] {
+        [Decoder::Flate2, Decoder::Libdeflater, Decoder::Zune]
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Decoder::Flate2 => "flate2",
+            Decoder::Libdeflater => "libdeflater",
+            Decoder::Zune => "zune"
+        }
+    }
+}
+
+fn invalid_data<E: std::fmt::Debug>(error: E) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::InvalidData, format!("{:?}", error))
+}
+
+// Decompresses a whole gzip buffer.
+pub fn gunzip(data: &[u8], decoder: Decoder) -> std::io::Result<Vec<u8>> {
+    match decoder {
+        Decoder::Flate2 => {
+            let mut output = Vec::new();
+            GzDecoder::new(data).read_to_end(&mut output)?;
+            Ok(output)
+        },
+        Decoder::Libdeflater => {
+            // the trailer has the uncompressed size modulo 2^32, a first guess that is grown when short
+            let size = if data.len() >= 4 {
+                let trailer = &data[data.len() - 4..];
+                u32::from_le_bytes([trailer[0], trailer[1], trailer[2], trailer[3]]) as usize
+            } else {
+                0
+            };
+            let mut output = vec![0; size.max(data.len())];
+            let mut decompressor = libdeflater::Decompressor::new();
+            loop {
+                match decompressor.gzip_decompress(data, &mut output) {
+                    Ok(written) => {
+                        output.truncate(written);
+                        return Ok(output);
+                    },
+                    Err(libdeflater::DecompressionError::InsufficientSpace) => {
+                        let size = output.len() * 2;
+                        output.resize(size, 0);
+                    },
+                    Err(e) => return Err(invalid_data(e))
+                }
+            }
+        },
+        Decoder::Zune => zune_inflate::DeflateDecoder::new(data).decode_gzip().map_err(invalid_data)
+    }
+}
+
+// Lines of an NDJSON file, decompressed when the path ends with .gz.
+pub fn open_lines(file_path: &str) -> std::io::Result<Box<dyn Iterator<Item=std::io::Result<String>>>> {
+    open_lines_with(file_path, Decoder::Flate2)
+}
+
+// Lines of a benchmark or conversion input, the current subset of them.
+pub fn open_input(file_path: &str, decoder: Decoder) -> std::io::Result<Box<dyn Iterator<Item=std::io::Result<String>>>> {
+    Ok(subset().apply(open_lines_with(file_path, decoder)?))
+}
+
+// Like open_input, each line with its number in the file. The lines are numbered before the subset
+// is applied, so errors point at the line of the file and not at the position in the subset.
+pub fn open_input_numbered(file_path: &str, decoder: Decoder) -> std::io::Result<Box<dyn Iterator<Item=(usize, std::io::Result<String>)>>> {
+    Ok(subset().apply(number_lines(open_lines_with(file_path, decoder)?)))
+}
+
+fn number_lines<T, I: Iterator<Item=T>>(lines: I) -> impl Iterator<Item=(usize, T)> {
+    lines.enumerate().map(|(index, line)| (index + 1, line))
+}
+
+pub fn open_lines_with(file_path: &str, decoder: Decoder) -> std::io::Result<Box<dyn Iterator<Item=std::io::Result<String>>>> {
+    let mut file = File::open(file_path)?;
+    if !file_path.ends_with(".gz") {
+        return Ok(Box::new(BufReader::new(file).lines()));
+    }
+    match decoder {
+        Decoder::Flate2 => Ok(Box::new(BufReader::new(GzDecoder::new(file)).lines())),
+        _ => {
+            let mut data = Vec::new();
+            file.read_to_end(&mut data)?;
+            Ok(Box::new(Cursor::new(gunzip(&data, decoder)?).lines()))
+        }
+    }
+}
+
+// Groups lines into blocks of at most block_size bytes that end on a record boundary, each line
+// keeping its newline. A record longer than block_size gets a block of its own.
+pub fn lines_to_blocks<I>(lines: I, block_size: usize) -> Blocks<I>
+    where I: Iterator<Item=std::io::Result<String>> {
+    Blocks { lines, block_size, carry: None }
+}
+
+pub struct Blocks<I> {
+    lines: I,
+    block_size: usize,
+    // the line that did not fit into the previous block
+    carry: Option<Vec<u8>>
+}
+
+impl<I: Iterator<Item=std::io::Result<String>>> Iterator for Blocks<I> {
+    type Item = std::io::Result<Vec<u8>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut block = self.carry.take().unwrap_or_default();
+        loop {
+            match self.lines.next() {
+                Some(Ok(line)) => {
+                    let mut record = line.into_bytes();
+                    record.push(b'\n');
+                    if !block.is_empty() && block.len() + record.len() > self.block_size {
+                        self.carry = Some(record);
+                        return Some(Ok(block));
+                    }
+                    block.extend_from_slice(&record);
+                },
+                Some(Err(e)) => return Some(Err(e)),
+                None => return if block.is_empty() { None } else { Some(Ok(block)) }
+            }
+        }
+    }
+}
+
+// The records of a block, without their newlines.
+pub fn block_to_lines(block: &[u8]) -> impl Iterator<Item=&[u8]> {
+    let block = block.strip_suffix(b"\n").unwrap_or(block);
+    block.split(|byte| *byte == b'\n').filter(move |_| !block.is_empty())
+}
+
+
+// Output file, gzip compressed when requested. Call finish so the gzip trailer is written and
+// errors are not lost on drop.
+pub enum OutputFile {
+    Plain(BufWriter<File>),
+    Gzip(GzEncoder<BufWriter<File>>),
+    Compressed(Box<dyn StreamEncoder>)
+}
+
+impl OutputFile {
+    pub fn create(file_path: &str, gzip: bool) -> std::io::Result<Self> {
+        let writer = BufWriter::new(File::create(file_path)?);
+        if gzip {
+            Ok(OutputFile::Gzip(GzEncoder::new(writer, Compression::default())))
+        } else {
+            Ok(OutputFile::Plain(writer))
+        }
+    }
+
+    pub fn with_compressor(file_path: &str, compressor: &dyn Compressor, level: i32) -> Result<Self, failure::Error> {
+        let writer = BufWriter::new(File::create(file_path)?);
+        Ok(OutputFile::Compressed(compressor.compress_stream(Box::new(writer), level)?))
+    }
+
+    pub fn finish(self) -> std::io::Result<()> {
+        match self {
+            OutputFile::Plain(mut writer) => writer.flush(),
+            OutputFile::Gzip(encoder) => encoder.finish()?.flush(),
+            OutputFile::Compressed(encoder) => encoder.finish()
+        }
+    }
+}
+
+impl Write for OutputFile {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        match self {
+            OutputFile::Plain(writer) => writer.write(buf),
+            OutputFile::Gzip(encoder) => encoder.write(buf),
+            OutputFile::Compressed(encoder) => encoder.write(buf)
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        match self {
+            OutputFile::Plain(writer) => writer.flush(),
+            OutputFile::Gzip(encoder) => encoder.flush(),
+            OutputFile::Compressed(encoder) => encoder.flush()
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_gunzip() {
+        let text = "{\"a\":1}\n".repeat(1000);
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(text.as_bytes()).unwrap();
+        let data = encoder.finish().unwrap();
+        for decoder in &Decoder::all() {
+            assert_eq!(gunzip(&data, *decoder).unwrap(), text.as_bytes(), "{}", decoder.name());
+        }
+        assert!(gunzip(b"not gzip", Decoder::Libdeflater).is_err());
+        assert_eq!("zune".parse::<Decoder>().unwrap(), Decoder::Zune);
+    }
+
+    #[test]
+    fn test_read_counts() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("lines.json.gz").to_string_lossy().into_owned();
+        let mut encoder = GzEncoder::new(File::create(&path).unwrap(), Compression::default());
+        encoder.write_all("{\"a\":1}\n".repeat(100).as_bytes()).unwrap();
+        encoder.finish().unwrap();
+        let file = GzipFile::new(&path);
+        let counts = file.counts.clone();
+        assert_eq!(file.lines.count(), 100);
+        assert_eq!((counts.records(), counts.decompressed_bytes()), (100, 800));
+        assert_eq!(counts.compressed_bytes(), std::fs::metadata(&path).unwrap().len());
+        assert_eq!(counts.throughput(Duration::from_secs(2)).records_per_second, 50.0);
+    }
+
+    #[test]
+    fn test_subset() {
+        let kept = |subset: Subset| subset.apply(0..1000).collect::<Vec<i32>>();
+        assert_eq!(kept(Subset { skip: 10, limit: Some(3), ..Subset::default() }), vec![10, 11, 12]);
+        let sampled = kept(Subset { sample_rate: 0.1, seed: Seed(7), ..Subset::default() });
+        assert!(sampled.len() > 50 && sampled.len() < 150);
+        assert_eq!(sampled, kept(Subset { sample_rate: 0.1, seed: Seed(7), ..Subset::default() }));
+        assert_eq!(kept(Subset::default()).len(), 1000);
+        let numbered: Vec<(usize, i32)> = Subset { skip: 10, limit: Some(2), ..Subset::default() }.apply(number_lines(0..1000)).collect();
+        assert_eq!(numbered, vec![(11, 10), (12, 11)]);
+    }
+
+    #[test]
+    fn test_blocks() {
+        let lines = vec!["aaaa", "bb", "cccccccccc", "d"].into_iter().map(|line| Ok(line.to_owned()));
+        let blocks: Vec<Vec<u8>> = lines_to_blocks(lines, 8).map(|block| block.unwrap()).collect();
+        assert_eq!(blocks, vec![b"aaaa\nbb\n".to_vec(), b"cccccccccc\n".to_vec(), b"d\n".to_vec()]);
+        let records: Vec<&[u8]> = blocks.iter().flat_map(|block| block_to_lines(block)).collect();
+        assert_eq!(records, vec![&b"aaaa"[..], b"bb", b"cccccccccc", b"d"]);
+        assert_eq!(block_to_lines(b"").count(), 0);
+    }
+}
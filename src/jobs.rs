@@ -0,0 +1,340 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{channel, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+use avro_rs::{Codec, Schema};
+use failure::{Error, bail, format_err};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use crate::convert::{infer_file_schema, AvroWriter, Location, RecordWriter};
+use crate::io::{GzipFile, OutputFile};
+use crate::manifest::{digest, InputDigest, Manifest};
+
+
+// how often running jobs report progress, persist it and look for a cancellation
+const PROGRESS_RECORDS: usize = 10_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum JobStatus {
+    Queued,
+    Running,
+    Done,
+    Failed,
+    Cancelled
+}
+
+// A conversion of a gzipped NDJSON file on the server to Avro, as kept in <state dir>/<id>.json.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Job {
+    pub id: u64,
+    pub input: String,
+    pub output: String,
+    // inferred from the input first when not given
+    pub schema: Option<Value>,
+    pub status: JobStatus,
+    // of the input file read so far
+    pub progress_percent: f64,
+    pub records: usize,
+    pub error: Option<String>,
+    // <id>.manifest.json in the state directory, once done
+    pub manifest: Option<String>,
+    pub submitted_at: u64,
+    pub finished_at: Option<u64>
+}
+
+// What a finished job read and wrote, to check and rerun it.
+#[derive(Debug, Serialize)]
+pub struct JobManifest {
+    pub job: u64,
+    pub records: usize,
+    pub elapsed_ms: u128,
+    pub schema: Value,
+    pub output: InputDigest,
+    pub run: Manifest
+}
+
+fn now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
+
+
+// The jobs of a state directory, written through to it on every change so they survive restarts.
+#[derive(Debug, Clone)]
+pub struct JobStore {
+    dir: PathBuf,
+    jobs: Arc<Mutex<BTreeMap<u64, Job>>>,
+    cancelled: Arc<Mutex<BTreeMap<u64, Arc<AtomicBool>>>>
+}
+
+impl JobStore {
+    // Jobs that were running when the last server stopped have failed, queued ones are queued still.
+    pub fn open(dir: &Path) -> Result<Self, Error> {
+        fs::create_dir_all(dir)?;
+        let store = JobStore { dir: dir.to_owned(), jobs: Arc::default(), cancelled: Arc::default() };
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+            let is_job = path.file_name().and_then(|name| name.to_str())
+                .and_then(|name| name.strip_suffix(".json"))
+                .map_or(false, |id| id.parse::<u64>().is_ok());
+            if !is_job {
+                continue;
+            }
+            let mut job: Job = serde_json::from_str(&fs::read_to_string(&path)?)
+                .map_err(|e| format_err!("{}: {}", path.display(), e))?;
+            if job.status == JobStatus::Running {
+                job.status = JobStatus::Failed;
+                job.error = Some("interrupted by a server restart".to_owned());
+                store.save(&job)?;
+            }
+            store.cancelled.lock().unwrap().insert(job.id, Arc::default());
+            store.jobs.lock().unwrap().insert(job.id, job);
+        }
+        Ok(store)
+    }
+
+    fn save(&self, job: &Job) -> Result<(), Error> {
+        // never leave a half written job behind
+        let path = self.dir.join(format!("{}.json", job.id));
+        let temporary = path.with_extension("json.tmp");
+        fs::write(&temporary, serde_json::to_string_pretty(job)?)?;
+        fs::rename(&temporary, &path)?;
+        Ok(())
+    }
+
+    fn update(&self, id: u64, change: impl FnOnce(&mut Job)) -> Result<Job, Error> {
+        let job = {
+            let mut jobs = self.jobs.lock().unwrap();
+            let job = jobs.get_mut(&id).ok_or_else(|| format_err!("no job {}", id))?;
+            change(job);
+            job.clone()
+        };
+        self.save(&job)?;
+        Ok(job)
+    }
+
+    fn submit(&self, input: String, output: String, schema: Option<Value>) -> Result<Job, Error> {
+        if let Some(schema) = &schema {
+            Schema::parse(schema)?;
+        }
+        let job = {
+            let mut jobs = self.jobs.lock().unwrap();
+            let id = jobs.keys().next_back().map_or(1, |id| id + 1);
+            let job = Job {
+                id,
+                input,
+                output,
+                schema,
+                status: JobStatus::Queued,
+                progress_percent: 0.0,
+                records: 0,
+                error: None,
+                manifest: None,
+                submitted_at: now(),
+                finished_at: None
+            };
+            jobs.insert(id, job.clone());
+            job
+        };
+        self.cancelled.lock().unwrap().insert(job.id, Arc::default());
+        self.save(&job)?;
+        Ok(job)
+    }
+
+    pub fn get(&self, id: u64) -> Option<Job> {
+        self.jobs.lock().unwrap().get(&id).cloned()
+    }
+
+    pub fn list(&self) -> Vec<Job> {
+        self.jobs.lock().unwrap().values().cloned().collect()
+    }
+
+    // Queued jobs are cancelled right away, running ones at their next progress report.
+    pub fn cancel(&self, id: u64) -> Result<Job, Error> {
+        let job = self.get(id).ok_or_else(|| format_err!("no job {}", id))?;
+        match job.status {
+            JobStatus::Queued | JobStatus::Running => {
+                self.cancelled.lock().unwrap()[&id].store(true, Ordering::SeqCst);
+            },
+            _ => bail!("job {} is {:?} already", id, job.status)
+        }
+        if job.status == JobStatus::Queued {
+            return self.update(id, |job| {
+                job.status = JobStatus::Cancelled;
+                job.finished_at = Some(now());
+            });
+        }
+        Ok(job)
+    }
+
+    pub fn manifest(&self, id: u64) -> Result<Value, Error> {
+        let job = self.get(id).ok_or_else(|| format_err!("no job {}", id))?;
+        let file = job.manifest.clone().ok_or_else(|| format_err!("job {} is {:?}, not done", id, job.status))?;
+        Ok(serde_json::from_str(&fs::read_to_string(self.dir.join(file))?)?)
+    }
+
+    fn run(&self, id: u64) {
+        let cancelled = self.cancelled.lock().unwrap()[&id].clone();
+        let job = match self.get(id) {
+            Some(job) if job.status == JobStatus::Queued && !cancelled.load(Ordering::SeqCst) => job,
+            _ => return
+        };
+        let save_error = |e: Error| eprintln!("job {}: {}", id, e);
+        self.update(id, |job| job.status = JobStatus::Running).map_err(save_error).ok();
+
+        let started = Instant::now();
+        let result = catch_unwind(AssertUnwindSafe(|| -> Result<Option<(usize, String)>, Error> {
+            fs::metadata(&job.input).map_err(|e| format_err!("{}: {}", job.input, e))?;
+            let schema = match &job.schema {
+                Some(schema) => Schema::parse(schema)?,
+                None => infer_file_schema(&job.input, "inferred_schema")?
+            };
+            match self.convert(&job, &schema, &cancelled)? {
+                Some(records) => Ok(Some((records, self.write_manifest(&job, records, &schema, started)?))),
+                None => Ok(None)
+            }
+        })).unwrap_or_else(|_| Err(format_err!("conversion panicked, see the server log")));
+        self.update(id, |job| {
+            match result {
+                Ok(Some((records, manifest))) => {
+                    job.status = JobStatus::Done;
+                    job.progress_percent = 100.0;
+                    job.records = records;
+                    job.manifest = Some(manifest);
+                },
+                Ok(None) => job.status = JobStatus::Cancelled,
+                Err(e) => {
+                    job.status = JobStatus::Failed;
+                    job.error = Some(e.to_string());
+                }
+            }
+            job.finished_at = Some(now());
+        }).map_err(save_error).ok();
+    }
+
+    // The number of records converted, None when the job was cancelled on the way.
+    fn convert(&self, job: &Job, schema: &Schema, cancelled: &AtomicBool) -> Result<Option<usize>, Error> {
+        let input_bytes = fs::metadata(&job.input)?.len().max(1);
+        let mut writer = AvroWriter::new(schema, OutputFile::create(&job.output, false)?, Codec::Deflate);
+        let file = GzipFile::new(&job.input);
+        let counts = file.counts.clone();
+        let mut location = Location { source: job.input.clone(), line: 0 };
+        let mut records = 0;
+        for line in file.lines {
+            location.line += 1;
+            let line = line.map_err(|e| format_err!("{}: {}", location, e))?;
+            let json = json::parse(&line).map_err(|e| format_err!("{}: {}", location, e))?;
+            writer.locate(&location);
+            writer.write(&json).map_err(|e| format_err!("{}: {}", location, e))?;
+            records += 1;
+            if records % PROGRESS_RECORDS == 0 {
+                if cancelled.load(Ordering::SeqCst) {
+                    return Ok(None);
+                }
+                let progress_percent = (counts.compressed_bytes() as f64 * 100.0 / input_bytes as f64).min(100.0);
+                self.update(job.id, |job| {
+                    job.progress_percent = progress_percent;
+                    job.records = records;
+                })?;
+            }
+        }
+        writer.finish()?;
+        Ok(Some(records))
+    }
+
+    fn write_manifest(&self, job: &Job, records: usize, schema: &Schema, started: Instant) -> Result<String, Error> {
+        let manifest = JobManifest {
+            job: job.id,
+            records,
+            elapsed_ms: started.elapsed().as_millis(),
+            schema: serde_json::to_value(schema)?,
+            output: digest(&job.output)?,
+            run: Manifest::new(None, &[&job.input], 0)?
+        };
+        let file = format!("{}.manifest.json", job.id);
+        fs::write(self.dir.join(&file), serde_json::to_string_pretty(&manifest)?)?;
+        Ok(file)
+    }
+}
+
+
+// Runs submitted jobs one after the other on a thread of its own, oldest first.
+pub struct JobQueue {
+    pub store: JobStore,
+    sender: Sender<u64>
+}
+
+impl JobQueue {
+    // Starts with the jobs the state directory still has queued.
+    pub fn start(dir: &Path) -> Result<Self, Error> {
+        let store = JobStore::open(dir)?;
+        let (sender, receiver) = channel();
+        let worker = store.clone();
+        thread::spawn(move || {
+            for id in receiver {
+                worker.run(id);
+            }
+        });
+        for job in store.list().into_iter().filter(|job| job.status == JobStatus::Queued) {
+            sender.send(job.id)?;
+        }
+        Ok(JobQueue { store, sender })
+    }
+
+    pub fn submit(&self, input: String, output: String, schema: Option<Value>) -> Result<Job, Error> {
+        let job = self.store.submit(input, output, schema)?;
+        self.sender.send(job.id)?;
+        Ok(job)
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::io::Write;
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+
+    fn wait(store: &JobStore, id: u64) -> Job {
+        loop {
+            let job = store.get(id).unwrap();
+            if job.finished_at.is_some() {
+                return job;
+            }
+            thread::sleep(std::time::Duration::from_millis(10));
+        }
+    }
+
+    #[test]
+    fn test_job_queue() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let input = dir.path().join("lines.json.gz").to_string_lossy().into_owned();
+        let mut encoder = GzEncoder::new(fs::File::create(&input).unwrap(), Compression::default());
+        encoder.write_all(b"{\"a\":1}\n{\"a\":2}\n").unwrap();
+        encoder.finish().unwrap();
+        let output = dir.path().join("lines.avro").to_string_lossy().into_owned();
+        let state = dir.path().join("jobs");
+
+        let queue = JobQueue::start(&state).unwrap();
+        let job = queue.submit(input.clone(), output, None).unwrap();
+        let done = wait(&queue.store, job.id);
+        assert_eq!((done.status, done.records, done.progress_percent), (JobStatus::Done, 2, 100.0));
+        assert_eq!(queue.store.manifest(job.id).unwrap()["records"], 2);
+        assert!(queue.store.cancel(job.id).is_err());
+
+        let missing = queue.submit("missing.json.gz".to_owned(), "missing.avro".to_owned(), None).unwrap();
+        let failed = wait(&queue.store, missing.id);
+        assert_eq!(failed.status, JobStatus::Failed);
+        assert!(queue.store.manifest(missing.id).is_err());
+
+        let reopened = JobStore::open(&state).unwrap();
+        assert_eq!(reopened.list().len(), 2);
+        assert_eq!(reopened.get(job.id).unwrap(), done);
+    }
+}
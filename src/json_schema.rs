@@ -0,0 +1,74 @@
+use avro_rs::Schema;
+use serde_json::{json, Map, Value};
+
+
+// The JSON Schema (draft 7) of the JSON that an inferred Avro schema was inferred from. Fields
+// whose schema is a union with null are optional, all others required.
+pub fn to_json_schema(schema: &Schema) -> Value {
+    let mut json_schema = convert(schema);
+    if let Value::Object(object) = &mut json_schema {
+        object.insert("$schema".to_owned(), json!("http://json-schema.org/draft-07/schema#"));
+    }
+    json_schema
+}
+
+fn convert(schema: &Schema) -> Value {
+    match schema {
+        Schema::Null => json!({"type": "null"}),
+        Schema::Boolean => json!({"type": "boolean"}),
+        Schema::Int | Schema::Long => json!({"type": "integer"}),
+        Schema::Float | Schema::Double => json!({"type": "number"}),
+        Schema::String | Schema::Bytes | Schema::Fixed { .. } => json!({"type": "string"}),
+        Schema::Enum { symbols, .. } => json!({"enum": symbols}),
+        Schema::Array(items) => json!({"type": "array", "items": convert(items)}),
+        Schema::Map(values) => json!({"type": "object", "additionalProperties": convert(values)}),
+        Schema::Union(union) => json!({"anyOf": union.variants().iter().map(convert).collect::<Vec<_>>()}),
+        Schema::Record { fields, .. } => {
+            let mut properties = Map::new();
+            let mut required = Vec::new();
+            for field in fields {
+                properties.insert(field.name.clone(), convert(&field.schema));
+                if !is_nullable(&field.schema) {
+                    required.push(field.name.clone());
+                }
+            }
+            json!({"type": "object", "properties": properties, "required": required})
+        },
+        // logical types of other Avro versions, anything goes
+        #[allow(unreachable_patterns)]
+        _ => json!({})
+    }
+}
+
+fn is_nullable(schema: &Schema) -> bool {
+    match schema {
+        Schema::Null => true,
+        Schema::Union(union) => union.variants().iter().any(|variant| *variant == Schema::Null),
+        _ => false
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_to_json_schema() {
+        let schema = Schema::parse_str(r#"{"name":"t","type":"record","fields":[
+            {"name":"id","type":"long"},
+            {"name":"text","type":["null","string"]},
+            {"name":"tags","type":{"type":"array","items":"string"}}
+        ]}"#).unwrap();
+        assert_eq!(to_json_schema(&schema), json!({
+            "$schema": "http://json-schema.org/draft-07/schema#",
+            "type": "object",
+            "properties": {
+                "id": {"type": "integer"},
+                "text": {"anyOf": [{"type": "null"}, {"type": "string"}]},
+                "tags": {"type": "array", "items": {"type": "string"}}
+            },
+            "required": ["id", "tags"]
+        }));
+    }
+}
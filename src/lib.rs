@@ -0,0 +1,34 @@
+// The reading, parsing, inference, conversion and compression core of json-benchmarks, for
+// applications that embed it. The json-benchmarks binary is built on top of it. On wasm32 only
+// inference and conversion of in-memory records are left, files, parsers other than json-rust and
+// codecs being native only.
+#[macro_use] extern crate lazy_static;
+
+#[cfg(not(target_arch = "wasm32"))]
+pub mod io;
+pub mod avro;
+pub mod convert;
+pub mod infer;
+pub mod hook;
+pub mod json_schema;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod parser;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod compress;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod energy;
+pub mod allocation;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod measure;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod perf;
+pub mod seed;
+pub mod sink;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+#[cfg(target_arch = "wasm32")]
+pub mod wasm;
+
+pub use crate::infer::infer_json_batch;
+#[cfg(not(target_arch = "wasm32"))]
+pub use crate::infer::infer_schema_batch;
@@ -0,0 +1,186 @@
+use std::cell::Cell;
+use std::io::{self, Write};
+use std::rc::Rc;
+use json::JsonValue;
+use serde::Serialize;
+use failure::{Error, format_err};
+use crate::convert::{Location, RecordWriter};
+use crate::manifest::{digest, InputDigest, Manifest};
+
+
+// Caps on a conversion, for bounded samples of corpora too big to convert whole.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Limits {
+    pub max_records: Option<usize>,
+    pub max_output_bytes: Option<u64>
+}
+
+impl Limits {
+    pub fn is_some(&self) -> bool {
+        self.max_records.is_some() || self.max_output_bytes.is_some()
+    }
+}
+
+// "500000", "64K", "64M" or "2G", in powers of 1024
+pub fn parse_size(s: &str) -> Result<u64, Error> {
+    let s = s.trim();
+    let (number, multiplier) = match s.chars().last().map(|c| c.to_ascii_uppercase()) {
+        Some('K') => (&s[..s.len() - 1], 1 << 10),
+        Some('M') => (&s[..s.len() - 1], 1 << 20),
+        Some('G') => (&s[..s.len() - 1], 1 << 30),
+        _ => (s, 1)
+    };
+    let number: u64 = number.parse().map_err(|_| format_err!("{} is not a size, e.g. 64M", s))?;
+    Ok(number * multiplier)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Truncation {
+    MaxRecords,
+    MaxOutputBytes
+}
+
+
+// Counts the bytes that reach the output for a LimitedWriter in front of the encoder writing there.
+pub struct CountingOutput<W: Write> {
+    inner: W,
+    written: Rc<Cell<u64>>
+}
+
+impl<W: Write> CountingOutput<W> {
+    pub fn new(inner: W) -> Self {
+        CountingOutput { inner, written: Rc::new(Cell::new(0)) }
+    }
+
+    pub fn counter(&self) -> Rc<Cell<u64>> {
+        self.written.clone()
+    }
+}
+
+impl<W: Write> Write for CountingOutput<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.written.set(self.written.get() + written as u64);
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+
+// Stops taking records once a limit is hit, which ends convert after the record that hit it; finish
+// still flushes and closes the output, so what was written is a complete file. The byte limit is
+// checked against what the encoder handed to the output so far: a buffering encoder like Avro's
+// overshoots it by up to a block.
+pub struct LimitedWriter<'w> {
+    inner: &'w mut dyn RecordWriter,
+    limits: Limits,
+    output_bytes: Option<Rc<Cell<u64>>>,
+    pub written: usize,
+    pub truncated: Option<Truncation>
+}
+
+impl<'w> LimitedWriter<'w> {
+    // output_bytes from the CountingOutput of the inner writer, needed for max_output_bytes
+    pub fn new(inner: &'w mut dyn RecordWriter, limits: Limits, output_bytes: Option<Rc<Cell<u64>>>) -> Self {
+        LimitedWriter { inner, limits, output_bytes, written: 0, truncated: None }
+    }
+
+    pub fn output_bytes(&self) -> u64 {
+        self.output_bytes.as_ref().map_or(0, |bytes| bytes.get())
+    }
+}
+
+impl<'w> RecordWriter for LimitedWriter<'w> {
+    fn write(&mut self, record: &JsonValue) -> Result<(), Error> {
+        // records a transform fans out after the limit
+        if self.truncated.is_some() {
+            return Ok(());
+        }
+        self.inner.write(record)?;
+        self.written += 1;
+        if self.limits.max_records.map_or(false, |max| self.written >= max) {
+            self.truncated = Some(Truncation::MaxRecords);
+        } else if self.limits.max_output_bytes.map_or(false, |max| self.output_bytes() >= max) {
+            self.truncated = Some(Truncation::MaxOutputBytes);
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<(), Error> {
+        self.inner.finish()
+    }
+
+    fn locate(&mut self, location: &Location) {
+        self.inner.locate(location);
+    }
+
+    fn full(&self) -> bool {
+        self.truncated.is_some()
+    }
+}
+
+
+// <output>.manifest.json of a limited conversion: where it stopped and why.
+#[derive(Debug, Serialize)]
+pub struct LimitManifest {
+    pub records: usize,
+    pub max_records: Option<usize>,
+    pub max_output_bytes: Option<u64>,
+    // the limit that ended the conversion, None when the input ran out first
+    pub truncated: Option<Truncation>,
+    pub output: InputDigest,
+    pub run: Manifest
+}
+
+pub fn write_manifest(input: &str, output: &str, limits: Limits, writer: &LimitedWriter) -> Result<String, Error> {
+    let manifest = LimitManifest {
+        records: writer.written,
+        max_records: limits.max_records,
+        max_output_bytes: limits.max_output_bytes,
+        truncated: writer.truncated,
+        output: digest(output)?,
+        run: Manifest::new(None, &[input], 0)?
+    };
+    let path = format!("{}.manifest.json", output);
+    std::fs::write(&path, serde_json::to_string_pretty(&manifest)?)?;
+    Ok(path)
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::convert::JsonLinesWriter;
+
+    #[test]
+    fn test_limited_writer() {
+        let record = json::parse(r#"{"a":1}"#).unwrap();
+        let mut inner = JsonLinesWriter::new(Vec::new());
+        let mut writer = LimitedWriter::new(&mut inner, Limits { max_records: Some(2), max_output_bytes: None }, None);
+        writer.write(&record).unwrap();
+        assert!(!writer.full());
+        writer.write(&record).unwrap();
+        writer.write(&record).unwrap();
+        assert_eq!(writer.truncated, Some(Truncation::MaxRecords));
+        assert_eq!(writer.written, 2);
+        assert_eq!(inner.into_inner().len(), 16);
+
+        let output = CountingOutput::new(Vec::new());
+        let counter = output.counter();
+        let mut inner = JsonLinesWriter::new(output);
+        let mut writer = LimitedWriter::new(&mut inner, Limits { max_records: None, max_output_bytes: Some(20) }, Some(counter));
+        while !writer.full() {
+            writer.write(&record).unwrap();
+        }
+        assert_eq!((writer.written, writer.output_bytes()), (3, 24));
+        assert_eq!(writer.truncated, Some(Truncation::MaxOutputBytes));
+
+        assert_eq!(parse_size("64M").unwrap(), 64 << 20);
+        assert_eq!(parse_size("1000").unwrap(), 1000);
+        assert!(parse_size("1T").is_err());
+    }
+}
@@ -1,111 +1,1129 @@
-mod io;
-mod avro;
+use learningrust::{io, avro, convert, infer, parser, compress, allocation, measure, perf, seed, sink};
+
+mod conformity;
+mod diff;
+mod codegen;
+mod compat;
+mod cli;
+mod canonical;
+mod hash;
+mod path;
+mod split;
+mod sort;
+mod anonymize;
+mod generate;
+mod shuffle;
+mod capabilities;
+mod codec_select;
+mod tweet;
+mod models;
+mod bench;
+mod report;
+mod baseline;
+mod gate;
+mod significance;
+mod transform;
+mod limit;
+mod protect;
+mod repair;
+mod strict;
+mod window;
+mod scan;
+mod field_size;
+mod fallback;
+mod envelope;
+mod prune;
+mod summary;
+mod suite;
+mod progress;
+mod route;
+mod push;
+mod tee;
+mod soak;
+mod transcode;
+mod cache;
+mod affinity;
+mod manifest;
+mod jobs;
+mod batch;
+mod selftest;
+mod serve;
+#[cfg(feature = "orc")]
+mod orc;
+#[cfg(feature = "tui")]
+mod tui;
+#[cfg(feature = "results-parquet")]
+mod results;
+#[cfg(feature = "results-sqlite")]
+mod history;
+#[cfg(feature = "profile")]
+mod profile;
+#[cfg(feature = "yyjson")]
+mod yyjson;
+#[cfg(feature = "rapidjson")]
+mod rapidjson;
 
-use json;
-use std::ptr::null;
 use crate::io::GzipFile;
 use std::time::{Instant, Duration};
-use std::borrow::Borrow;
-use serde_json;
-use serde_json::{Value, Map, Deserializer};
-use simd_json;
 use std::thread;
-use dns_lookup;
-use std::net::{IpAddr, Ipv4Addr};
-use std::str::FromStr;
-use simd_json::value::{Value as SimdValue};
-use flate2::{write::DeflateEncoder, Compression};
-use std::io::{Write, BufRead};
-use libdeflater::{Compressor, CompressionLvl};
-use deflate::deflate_bytes;
+use std::io::Write;
 use json::JsonValue;
-use json::number::Number;
+use structopt::StructOpt;
+use crate::cli::{Opt, Command, SchemaCommand};
+use crate::infer::SchemaInferrer;
+
+#[global_allocator]
+static ALLOCATOR: allocation::CountingAllocator = allocation::CountingAllocator;
+
+
+// The bench arguments with the suite merged in, for one input.
+#[derive(Clone)]
+struct BenchOptions {
+    names: Vec<String>,
+    parsers: Vec<String>,
+    codecs: Vec<String>,
+    input: Option<String>,
+    decoder: io::Decoder,
+    repetitions: bench::Repetitions,
+    output: Option<String>,
+    csv: Option<String>,
+    markdown: Option<String>,
+    store: Option<String>,
+    push: Option<push::Gateway>,
+    save_baseline: Option<String>,
+    compare_baseline: Option<String>,
+    regression_threshold: f64,
+    targets: gate::Targets,
+    matrix: bool,
+    net: bool,
+    scaling: Option<(String, usize)>,
+    levels: bool,
+    filter: Option<String>,
+    profile: Option<String>,
+    interleave: Option<seed::Seed>,
+    list: bool
+}
+
+fn bench_command(options: BenchOptions) -> i32 {
+    let BenchOptions { mut names, parsers, codecs, input, decoder, repetitions, output, csv, markdown, store, push, save_baseline,
+                       compare_baseline, regression_threshold, targets, matrix, net, scaling, levels, filter, profile, interleave, list } = options;
+    let filter = match filter.map(|filter| regex::Regex::new(&filter)).transpose() {
+        Ok(filter) => filter,
+        Err(e) => {
+            eprintln!("--filter: {}", e);
+            return batch::EXIT_ERROR;
+        }
+    };
+    let matches = |name: &str| filter.as_ref().map_or(true, |filter| filter.is_match(name));
+    if list {
+        for benchmark in bench::benchmarks() {
+            if matches(&benchmark.name()) {
+                println!("{}", benchmark.name());
+            }
+        }
+        return 0;
+    }
+    // fail before the run rather than after it
+    if store.is_some() && !cfg!(feature = "results-sqlite") {
+        eprintln!("--store needs a build with the results-sqlite feature");
+        return batch::EXIT_ERROR;
+    }
+    let previous = match compare_baseline.map(|name| baseline::load(&name)).transpose() {
+        Ok(previous) => previous,
+        Err(e) => {
+            eprintln!("--compare-baseline: {}", e);
+            return batch::EXIT_ERROR;
+        }
+    };
+    names.extend(parsers.iter().map(|parser| format!("parse:{}", parser)));
+    names.extend(codecs.iter().map(|codec| format!("compress:{}", codec)));
+    if matrix {
+        names = bench::benchmarks()
+            .iter()
+            .map(|benchmark| benchmark.name())
+            .filter(|name| name.starts_with("parse:") || name.starts_with("compress:"))
+            .collect();
+    } else if let Some((parser, max_threads)) = &scaling {
+        names = bench::scaling_names(parser, *max_threads);
+    } else if levels {
+        names = match bench::level_names(&codecs) {
+            Ok(names) => names,
+            Err(e) => {
+                eprintln!("{}", e);
+                return batch::EXIT_ERROR;
+            }
+        };
+    } else if names.is_empty() {
+        names = bench::benchmarks().iter().map(|benchmark| benchmark.name()).collect();
+    }
+    names.retain(|name| matches(name));
+    if net && !names.iter().any(|name| name == "read") {
+        names.insert(0, "read".to_owned());
+    }
+    if profile.is_some() && names.len() != 1 {
+        eprintln!("--profile takes a single benchmark, {} selected", names.len());
+        return batch::EXIT_ERROR;
+    }
+    let input = bench::Input::new(&io::input_file(input.as_deref(), io::DEFAULT_INPUT), decoder);
+    let input_bytes = std::fs::metadata(&input.path).map(|metadata| metadata.len()).unwrap_or(0);
+    let mut results = bench::BenchResults {
+        input: input.path.clone(),
+        input_bytes,
+        decoder,
+        warmup: repetitions.warmup,
+        runs: repetitions.runs,
+        results: Vec::new(),
+        failed: std::collections::BTreeMap::new()
+    };
+    // sequential runs print as each benchmark finishes, interleaved ones all at the end
+    let runs: Box<dyn Iterator<Item = (String, Result<bench::RepeatedRun, failure::Error>)>> = match interleave {
+        Some(seed) => Box::new(bench::run_interleaved(&names, &input, repetitions, &mut seed.rng("interleave"), &mut || Ok(())).into_iter()),
+        None => Box::new(names.into_iter().map(|name| {
+            let run = profiled(profile.as_deref(), || bench::run_named(&name, &input, repetitions, &mut || Ok(())));
+            (name, run)
+        }))
+    };
+    for (name, run) in runs {
+        match run {
+            Ok(run) => {
+                println!("{}", run.last());
+                if !run.positions.is_empty() {
+                    let runs: Vec<String> = run.measurements
+                        .iter()
+                        .zip(&run.positions)
+                        .map(|(measurement, position)| format!("{} ms (#{})", measurement.elapsed.as_millis(), position + 1))
+                        .collect();
+                    println!("Runs: {}", runs.join(", "));
+                }
+                println!("Throughput: {}", run.throughput(input_bytes));
+                if let Some(latency) = run.timings.percentiles() {
+                    println!("Latency: {}", latency);
+                }
+                if run.passes > 1 {
+                    println!("Passes per run: {}", run.passes);
+                }
+                if repetitions.runs > 1 {
+                    println!("{}", run.stats);
+                }
+                let timings = &run.timings;
+                println!("Errors: {}, read: {} ms, process: {} ms", timings.errors, timings.read.as_millis(), timings.process.as_millis());
+                println!("Checksum: {}", timings.sink);
+                if let Some(ratio) = timings.compression_ratio() {
+                    println!("Ratio: {} bytes to {} bytes, {:.2}x", timings.bytes, timings.compressed_bytes, ratio);
+                }
+                if let Some(counters) = run.last().perf {
+                    println!("Counters: {}", counters);
+                } else if perf::enabled() {
+                    println!("Counters: not available, see /proc/sys/kernel/perf_event_paranoid");
+                }
+                results.results.push(bench::BenchResult::new(&run, input_bytes));
+            },
+            Err(e) => {
+                println!("{}: {}", name, e);
+                results.failed.insert(name, e.to_string());
+            }
+        }
+    }
+    if let Some(output) = output {
+        std::fs::write(&output, serde_json::to_string_pretty(&results).unwrap()).unwrap();
+        println!("Results written to {}", output);
+    }
+    if let Some(csv) = csv {
+        bench::append_csv(&csv, &results).unwrap();
+        println!("Results appended to {}", csv);
+    }
+    if let Some(store) = store {
+        store_results(&store, &results);
+    }
+    if let Some(gateway) = push {
+        // a dashboard missing a run is no reason to fail the benchmark
+        match gateway.push(&results) {
+            Ok(()) => println!("Results pushed to the pushgateway as job {}", gateway.job),
+            Err(e) => eprintln!("Could not push the results: {}", e)
+        }
+    }
+    if let Some(markdown) = markdown {
+        std::fs::write(&markdown, report::markdown(&results)).unwrap();
+        println!("Tables written to {}", markdown);
+    }
+    if matrix {
+        print!("{}", report::matrix(&results));
+    }
+    if scaling.is_some() {
+        print!("{}", report::scaling(&results));
+    }
+    if levels {
+        print!("{}", report::levels(&results));
+    }
+    if net {
+        match report::net_of_read(&results) {
+            Some(table) => print!("{}", table),
+            None => eprintln!("No read result to subtract, it failed")
+        }
+    }
+    let mut exit_code = 0;
+    if !targets.is_empty() {
+        println!("{:<32} {:>12} {:>12}", "benchmark", "target", "actual");
+        for check in gate::check(&targets, &results) {
+            println!("{}", check);
+            if check.missed {
+                exit_code = batch::EXIT_REGRESSION;
+            }
+        }
+    }
+    if let Some(previous) = previous {
+        println!("{:<32} {:>10} {:>10} {:>9}", "benchmark", "baseline", "current", "change");
+        for comparison in baseline::compare(&previous, &results, regression_threshold) {
+            println!("{}", comparison);
+            if comparison.regressed {
+                exit_code = batch::EXIT_REGRESSION;
+            }
+        }
+    }
+    if let Some(name) = save_baseline {
+        println!("Baseline saved to {}", baseline::save(&name, &results).unwrap().display());
+    }
+    exit_code
+}
+
+#[cfg(feature = "results-sqlite")]
+fn store_results(db: &str, results: &bench::BenchResults) {
+    let mut connection = history::open(db).unwrap();
+    let run = history::save(&mut connection, results, history::git_commit().as_deref(), &history::machine()).unwrap();
+    println!("Results stored in {} as run {}", db, run);
+}
+
+// not reached, bench_command refuses --store up front without the feature
+#[cfg(not(feature = "results-sqlite"))]
+fn store_results(_db: &str, _results: &bench::BenchResults) {}
 
-#[macro_use] extern crate lazy_static;
+#[cfg(feature = "results-sqlite")]
+fn history_command(db: &str, benchmark: &str, machine: Option<String>, all_machines: bool, limit: usize) {
+    let machine = if all_machines { None } else { Some(machine.unwrap_or_else(history::machine)) };
+    let rows = history::history(&history::open(db).unwrap(), benchmark, machine.as_deref(), limit).unwrap();
+    if rows.is_empty() {
+        println!("No runs of {} in {}", benchmark, db);
+        return;
+    }
+    print!("{}", history::trend(&rows));
+}
+
+#[cfg(not(feature = "results-sqlite"))]
+fn history_command(_db: &str, _benchmark: &str, _machine: Option<String>, _all_machines: bool, _limit: usize) {
+    eprintln!("history needs a build with the results-sqlite feature");
+    std::process::exit(batch::EXIT_ERROR);
+}
+
+#[cfg(feature = "profile")]
+fn profiled<T, F: FnOnce() -> T>(svg: Option<&str>, f: F) -> T {
+    match svg {
+        Some(svg) => {
+            let result = profile::flamegraph(svg, f).unwrap();
+            println!("Flamegraph written to {}", svg);
+            result
+        },
+        None => f()
+    }
+}
+
+#[cfg(not(feature = "profile"))]
+fn profiled<T, F: FnOnce() -> T>(svg: Option<&str>, f: F) -> T {
+    if svg.is_some() {
+        eprintln!("--profile needs a build with the profile feature");
+        std::process::exit(batch::EXIT_ERROR);
+    }
+    f()
+}
+
+fn compare_command(baseline: &str, current: &str, alpha: f64, resamples: usize, seed: seed::Seed) -> i32 {
+    let load = |path: &str| -> bench::BenchResults { serde_json::from_str(&std::fs::read_to_string(path).unwrap()).unwrap() };
+    let comparisons = significance::compare(&load(baseline), &load(current), alpha, resamples, seed);
+    println!("{:<32} {:>10} {:>10} {:>9} {:>8} 95% interval", "benchmark", "baseline", "current", "change", "p");
+    for comparison in &comparisons {
+        println!("{}", comparison);
+    }
+    if comparisons.iter().any(|comparison| comparison.regressed()) { batch::EXIT_REGRESSION } else { 0 }
+}
+
+fn infer_command(input: &str, name: &str, output: Option<String>, array_policy: infer::ArrayPolicy) {
+    let now = Instant::now();
+    let mut inferrer = SchemaInferrer::new(name).with_array_policy(array_policy);
+    let records = summary::timed("inference", || convert::convert(input, &mut inferrer)).unwrap();
+    summary::read_and_parsed(records);
+    let schema = inferrer.into_schema().unwrap();
+    let json = serde_json::to_string_pretty(&schema).unwrap();
+    summary::update(|summary| summary.bytes_in = summary::file_size(input));
+    match output {
+        Some(output) => {
+            std::fs::write(&output, json).unwrap();
+            summary::update(|summary| summary.bytes_out = summary::file_size(&output));
+        },
+        None => println!("{}", json)
+    }
+    eprintln!("Execution time: {:?}", now.elapsed().as_millis());
+}
+
+// A schema file from the command line, exits when it can not be read or parsed.
+fn read_schema(path: &str) -> avro_rs::Schema {
+    let schema = std::fs::read_to_string(path)
+        .map_err(failure::Error::from)
+        .and_then(|text| Ok(avro_rs::Schema::parse_str(&text)?));
+    match schema {
+        Ok(schema) => schema,
+        Err(e) => {
+            eprintln!("{}: {}", path, e);
+            std::process::exit(batch::EXIT_ERROR);
+        }
+    }
+}
+
+fn codegen_command(input: Option<String>, schema: Option<String>, name: &str, avro: bool, output: Option<String>) {
+    let schema = match (schema, input) {
+        (Some(path), _) => read_schema(&path),
+        (None, Some(input)) => convert::infer_file_schema(&input, name).unwrap(),
+        (None, None) => unreachable!("structopt requires one of them")
+    };
+    let source = codegen::generate(&schema, avro).unwrap();
+    match output {
+        Some(output) => std::fs::write(output, source).unwrap(),
+        None => print!("{}", source)
+    }
+}
+
+fn schema_infer_diff(left: &str, right: &str, name: &str) {
+    let left_inferrer = SchemaInferrer::from_file(left, name).unwrap();
+    let right_inferrer = SchemaInferrer::from_file(right, name).unwrap();
+    println!("Records: {} vs {}", left_inferrer.records(), right_inferrer.records());
+
+    if let (Some(left_schema), Some(right_schema)) = (left_inferrer.schema(), right_inferrer.schema()) {
+        let differences = diff::diff_schemas(left_schema, right_schema);
+        println!("Schema differences: {}", differences.len());
+        differences.iter().for_each(|d| println!("{}", d));
+    }
 
+    let deltas = diff::field_deltas(&left_inferrer, &right_inferrer);
+    println!("Field deltas: {}", deltas.len());
+    println!("{:<60} {:>8} {:>8} {:>9}  types", "field", "left", "right", "delta");
+    deltas.iter().for_each(|d| println!("{}", d));
+}
+
+fn split_command(input: &str, shards: usize, key: Option<String>, output_prefix: &str, gzip: bool) {
+    let partitioning = match key {
+        Some(key) => split::Partitioning::Key(key),
+        None => split::Partitioning::RoundRobin
+    };
+    let now = Instant::now();
+    let stats = split::split(input, output_prefix, shards, &partitioning, gzip).unwrap();
+    summary::update(|summary| {
+        summary.records_converted = Some(stats.iter().map(|shard| shard.records as u64).sum());
+        summary.bytes_in = summary::file_size(input);
+        summary.bytes_out = Some(stats.iter().map(|shard| shard.file_bytes).sum());
+    });
+    for shard in &stats {
+        println!("{}: {} records, {} bytes, {} bytes on disk", shard.path, shard.records, shard.bytes, shard.file_bytes);
+    }
+    println!("Imbalance (largest/smallest): {:.3}", split::imbalance(&stats));
+    println!("Execution time: {:?}", now.elapsed().as_millis());
+}
+
+fn anonymize_command(input: &str, output: &str, hash: Vec<String>, redact: Vec<String>, salt: &str, gzip: bool) {
+    let mut anonymizer = anonymize::Anonymizer::new(salt);
+    for path in hash {
+        anonymizer = anonymizer.rule(&path, anonymize::Redaction::Hash);
+    }
+    for path in redact {
+        anonymizer = anonymizer.rule(&path, anonymize::Redaction::Redact);
+    }
 
-fn json_benchmark() {
-    let json_file = GzipFile::new("TweetsChampions.json.gz");
     let now = Instant::now();
-    json_file
-        .lines
-        .map(|x| x.unwrap())
-        .for_each(|x| {json::parse(x.as_str());});
+    let mut json_writer = convert::JsonLinesWriter::new(io::OutputFile::create(output, gzip).unwrap());
+    let mut writer = anonymize::AnonymizingWriter::new(&mut json_writer, anonymizer);
+    let records = convert::convert(input, &mut writer).unwrap();
+    json_writer.into_inner().finish().unwrap();
+    println!("Anonymized {} records", records);
     println!("Execution time: {:?}", now.elapsed().as_millis());
 }
 
+// The convert arguments besides the input and output.
+struct ConvertOptions {
+    schema: Option<String>,
+    repair: bool,
+    strict: bool,
+    dead_letter: Option<String>,
+    fallback: Option<fallback::Fallback>,
+    heterogeneity_threshold: f64,
+    envelope: Option<String>,
+    parser_chain: Option<String>,
+    limits: limit::Limits,
+    array_policy: infer::ArrayPolicy,
+    min_presence: Option<f64>,
+    rare_fields: prune::RareFields,
+    tolerance: convert::Tolerance,
+    dry_run: bool,
+    canonical: bool,
+    hash_sidecar: Option<String>,
+    hash_field: Option<String>
+}
 
-fn serde_benchmark() {
-    let mut json_file = GzipFile::new("TweetsChampions.json.gz");
+// The output is only None for a dry run.
+fn convert_command(input: &str, output: Option<&str>, options: ConvertOptions) {
+    let ConvertOptions { schema, repair, strict, dead_letter, fallback, heterogeneity_threshold, envelope, parser_chain, limits, array_policy,
+                         min_presence, rare_fields, tolerance, dry_run, canonical, hash_sidecar, hash_field } = options;
+    let mut chain = parser_chain.map(|spec| parser::ParserChain::parse_spec(&spec).unwrap());
+    // one pass over the input for inference, one for conversion
+    let progress = progress::Progress::new(input, "inference");
+    let mut parse = |line: &str| {
+        progress.record(line.len());
+        match &mut chain {
+            Some(chain) => chain.parse(line),
+            None => Ok(json::parse(line)?)
+        }
+    };
+    if canonical {
+        // RFC 8785 NDJSON has no schema to infer
+        let now = Instant::now();
+        let output = output.unwrap();
+        let mut writer = canonical::CanonicalJsonWriter::new(io::OutputFile::create(output, false).unwrap());
+        let mut bad = convert::BadRecords::default();
+        progress.restart("conversion");
+        let records = summary::timed("conversion", || convert::convert_tolerant(input, &mut writer, &mut parse, tolerance, &mut bad)).unwrap();
+        summary::read_and_parsed(records);
+        println!("Converted {} records", records);
+        if tolerance != convert::Tolerance::Fail {
+            println!("{}", bad);
+        }
+        summary::update(|summary| {
+            summary.records_converted = Some(records as u64);
+            summary.bytes_in = summary::file_size(input);
+            summary.bytes_out = summary::file_size(output);
+        });
+        println!("Execution time: {:?}", now.elapsed().as_millis());
+        return;
+    }
+    let mut use_fallback = None;
+    let mut pruned = None;
+    let schema = match schema {
+        Some(path) => read_schema(&path),
+        None => {
+            let mut inferrer = SchemaInferrer::new("inferred_schema").with_array_policy(array_policy);
+            // the malformed lines are counted in the conversion pass
+            summary::timed("inference", || convert::convert_tolerant(input, &mut inferrer, &mut parse, tolerance, &mut convert::BadRecords::default())).unwrap();
+            let heterogeneity = fallback::heterogeneity(&inferrer);
+            match fallback {
+                Some(fallback) if heterogeneity > heterogeneity_threshold => {
+                    println!("Heterogeneity {:.2} is over {:.2}, writing the {:?} fallback", heterogeneity, heterogeneity_threshold, fallback);
+                    use_fallback = Some(fallback);
+                    fallback.schema()
+                },
+                _ => match min_presence {
+                    Some(min_presence) => {
+                        let (fields, records) = (inferrer.fields().clone(), inferrer.records());
+                        let (schema, paths) = prune::prune(inferrer.into_schema().unwrap(), &fields, records, min_presence, rare_fields).unwrap();
+                        println!("Pruned {} fields present in fewer than {:.2}% of the records", paths.len(), min_presence * 100.0);
+                        for path in &paths {
+                            println!("  {}", path);
+                        }
+                        pruned = Some(paths);
+                        schema
+                    },
+                    None => inferrer.into_schema().unwrap()
+                }
+            }
+        }
+    };
+    let envelope = envelope.map(|spec| envelope::Envelope::new(envelope::parse_envelope(&spec).unwrap(), input));
+    let schema = match &envelope {
+        Some(envelope) => envelope.schema(schema).unwrap(),
+        None => schema
+    };
+    // the envelope goes on first, so repair and strict see the fields the schema has for it, the
+    // array policy right after, so they see the arrays inference saw, then pruning, so they do not
+    // see the pruned fields
+    let mut bad = convert::BadRecords::default();
+    let mut run = |writer: &mut dyn convert::RecordWriter| {
+        let mut pruning;
+        let writer: &mut dyn convert::RecordWriter = match &pruned {
+            Some(paths) => {
+                pruning = prune::PruningWriter::new(writer, paths, rare_fields);
+                &mut pruning
+            },
+            None => writer
+        };
+        let mut writer = infer::ArrayPolicyWriter::new(writer, &schema, array_policy);
+        match &envelope {
+            Some(envelope) => convert::convert_tolerant(input, &mut envelope::EnvelopeWriter::new(&mut writer, envelope), &mut parse, tolerance, &mut bad),
+            None => convert::convert_tolerant(input, &mut writer, &mut parse, tolerance, &mut bad)
+        }
+    };
     let now = Instant::now();
-    for line_result in json_file.lines.into_iter() {
-        if let Ok(line) = line_result {
-            let x: Value = serde_json::from_str(line.as_ref()).unwrap();
+    if dry_run {
+        let mut writer = conformity::ConformityWriter::new(&schema);
+        progress.restart("conversion");
+        summary::read_and_parsed(summary::timed("conversion", || run(&mut writer)).unwrap());
+        print!("{}", writer.report);
+        if tolerance != convert::Tolerance::Fail {
+            println!("{}", bad);
+        }
+        return;
+    }
+    let output = output.unwrap();
+    let output_file = limit::CountingOutput::new(io::OutputFile::create(output, false).unwrap());
+    let output_bytes = output_file.counter();
+    // repair and strict check the records before the hash field is added, only the output has it
+    let output_schema = match &hash_field {
+        Some(name) => avro::with_extra_field(schema.clone(), name, avro_rs::Schema::String).unwrap(),
+        None => schema.clone()
+    };
+    let mut avro_writer = convert::AvroWriter::new(&output_schema, output_file, avro_rs::Codec::Deflate);
+    // in front of the encoder, so the records it counts are the ones written
+    let mut limited = limit::LimitedWriter::new(&mut avro_writer, limits, Some(output_bytes));
+    let conversion = Instant::now();
+    progress.restart("conversion");
+    // the hashes are of the records as written, after the fallback, repair and strict
+    {
+        let mut hashing;
+        let hash_output = match (hash_sidecar, hash_field) {
+            (Some(path), _) => Some(hash::HashOutput::Sidecar(io::OutputFile::create(&path, false).unwrap())),
+            (None, Some(name)) => Some(hash::HashOutput::Field(name)),
+            (None, None) => None
+        };
+        let writer: &mut dyn convert::RecordWriter = match hash_output {
+            Some(output) => {
+                hashing = hash::HashingWriter::new(&mut limited, output);
+                &mut hashing
+            },
+            None => &mut limited
+        };
+        if let Some(fallback) = use_fallback {
+            let records = run(&mut fallback::FallbackWriter::new(writer, fallback)).unwrap();
+            summary::read_and_parsed(records);
+            println!("Converted {} records", records);
+        } else if repair {
+            let mut writer = repair::RepairingWriter::new(writer, &schema);
+            summary::read_and_parsed(run(&mut writer).unwrap());
+            summary::update(|summary| summary.records_skipped = Some(writer.report.unrepairable_records as u64));
+            print!("{}", writer.report);
+        } else if strict {
+            let mut dead_letter = dead_letter.map(|path| io::OutputFile::create(&path, false).unwrap());
+            let mut writer = strict::StrictWriter::new(writer, &schema, dead_letter.as_mut().map(|output| output as &mut dyn Write));
+            let result = run(&mut writer);
+            print!("{}", writer.report);
+            summary::read_and_parsed(result.unwrap());
+            let rejected_records = writer.report.rejected_records as u64;
+            if let Some(output) = dead_letter {
+                output.finish().unwrap();
+                summary::update(|summary| summary.records_dead_lettered = Some(rejected_records));
+            }
+        } else {
+            let records = run(writer).unwrap();
+            summary::read_and_parsed(records);
+            println!("Converted {} records", records);
         }
     }
+    if tolerance != convert::Tolerance::Fail {
+        println!("{}", bad);
+        summary::update(|summary| {
+            summary.records_read = summary.records_read.map(|read| read + bad.skipped as u64);
+            summary.records_skipped = Some(summary.records_skipped.unwrap_or(0) + bad.skipped as u64);
+        });
+    }
+    summary::stage("conversion", conversion.elapsed());
+    summary::update(|summary| {
+        summary.records_converted = Some(limited.written as u64);
+        summary.bytes_in = summary::file_size(input);
+    });
+    if limits.is_some() {
+        if let Some(truncation) = limited.truncated {
+            println!("Stopped at {:?} after {} records, {} bytes", truncation, limited.written, limited.output_bytes());
+        }
+        println!("Manifest written to {}", limit::write_manifest(input, output, limits, &limited).unwrap());
+    }
+    if let Some(chain) = chain {
+        // inference and conversion passes together
+        println!("{}", chain);
+    }
+    summary::update(|summary| summary.bytes_out = summary::file_size(output));
+    println!("Execution time: {:?}", now.elapsed().as_millis());
+}
+
+fn transform_command(input: &str, output: &str, specs: &[String], avro: bool, gzip: bool) {
+    let transforms: Vec<Box<dyn transform::RecordTransform>> = specs.iter().map(|spec| transform::parse_transform(spec).unwrap()).collect();
+    let now = Instant::now();
+    let records = if avro {
+        let mut inferrer = infer::SchemaInferrer::new("transformed");
+        convert::convert(input, &mut transform::TransformingWriter::new(&mut inferrer, &transforms)).unwrap();
+        let schema = transforms.iter().try_fold(inferrer.into_schema().unwrap(), |schema, transform| transform.annotate(schema)).unwrap();
+        let mut avro_writer = convert::AvroWriter::new(&schema, io::OutputFile::create(output, false).unwrap(), avro_rs::Codec::Deflate);
+        let mut writer = transform::TransformingWriter::new(&mut avro_writer, &transforms);
+        convert::convert(input, &mut writer).unwrap();
+        writer.written
+    } else {
+        let mut json_writer = convert::JsonLinesWriter::new(io::OutputFile::create(output, gzip).unwrap());
+        let mut writer = transform::TransformingWriter::new(&mut json_writer, &transforms);
+        convert::convert(input, &mut writer).unwrap();
+        let written = writer.written;
+        json_writer.into_inner().finish().unwrap();
+        written
+    };
+    summary::update(|summary| {
+        summary.records_converted = Some(records as u64);
+        summary.bytes_in = summary::file_size(input);
+        summary.bytes_out = summary::file_size(output);
+    });
+    println!("Wrote {} records", records);
+    println!("Execution time: {:?}", now.elapsed().as_millis());
+}
+
+fn route_command(input: &str, key: &str, output_prefix: &str) {
+    let now = Instant::now();
+    let mut inferrer = route::RoutingInferrer::new(key, "inferred_schema");
+    summary::timed("inference", || convert::convert(input, &mut inferrer)).unwrap();
+    let schemas = inferrer.into_schemas().unwrap();
+
+    let mut routes: std::collections::BTreeMap<String, Box<dyn convert::RecordWriter + '_>> = std::collections::BTreeMap::new();
+    for (route, schema) in &schemas {
+        std::fs::write(route::route_path(output_prefix, route, "avsc"), serde_json::to_string_pretty(schema).unwrap()).unwrap();
+        let output = io::OutputFile::create(&route::route_path(output_prefix, route, "avro"), false).unwrap();
+        routes.insert(route.clone(), Box::new(convert::AvroWriter::new(schema, output, avro_rs::Codec::Deflate)));
+    }
+    let mut writer = route::RoutingWriter::new(key, routes);
+    let records = summary::timed("conversion", || convert::convert(input, &mut writer)).unwrap();
+    summary::read_and_parsed(records);
+    let bytes_out: Option<u64> = writer.records.keys().map(|route| summary::file_size(&route::route_path(output_prefix, route, "avro"))).sum();
+    summary::update(|summary| {
+        summary.records_converted = Some(writer.records.values().map(|records| *records as u64).sum());
+        summary.bytes_in = summary::file_size(input);
+        summary.bytes_out = bytes_out;
+    });
+    for (route, records) in &writer.records {
+        println!("{}: {} records", route::route_path(output_prefix, route, "avro"), records);
+    }
     println!("Execution time: {:?}", now.elapsed().as_millis());
 }
 
-fn simd_benchmark() {
-    let json_file = GzipFile::new("TweetsChampions.json.gz");
+fn archive_command(input: &str, output_prefix: &str, formats: &[String], schema: Option<String>) {
+    let specs: Vec<&str> = if formats.is_empty() { tee::DEFAULT_FORMATS.to_vec() } else { formats.iter().map(String::as_str).collect() };
+    let formats: Vec<tee::Format> = specs.iter().map(|spec| tee::parse_format(spec).unwrap()).collect();
     let now = Instant::now();
-    for line_result in json_file.lines {
-        if let Ok(mut line) = line_result {
-//            let v = unsafe { simd_json::to_borrowed_value(line.as_bytes_mut()).unwrap() };
-            let tape = unsafe { simd_json::to_tape(line.as_bytes_mut()).unwrap()};
-            println!("{:?}", tape);
-            break
+    let schema = match schema {
+        Some(path) => read_schema(&path),
+        None => convert::infer_file_schema(input, "inferred_schema").unwrap()
+    };
+    let mut writer = tee::TeeWriter::new();
+    let mut paths = Vec::new();
+    for format in &formats {
+        let path = format!("{}.{}", output_prefix, format.extension());
+        writer.add(&path, format.create(&schema, &path).unwrap());
+        paths.push(path);
+    }
+    let records = summary::timed("conversion", || convert::convert(input, &mut writer)).unwrap();
+    summary::read_and_parsed(records);
+    let input_bytes = std::fs::metadata(input).unwrap().len();
+    let bytes_out: Option<u64> = paths.iter().map(|path| summary::file_size(path)).sum();
+    summary::update(|summary| {
+        summary.records_converted = Some(records as u64);
+        summary.bytes_in = Some(input_bytes);
+        summary.bytes_out = bytes_out;
+    });
+    println!("Wrote {} records in {} formats", records, paths.len());
+    println!("{:<40} {:>14} {:>8} {:>10}", "output", "bytes", "vs input", "write ms");
+    for (sink, path) in writer.sinks.iter().zip(&paths) {
+        let bytes = std::fs::metadata(path).unwrap().len();
+        println!("{:<40} {:>14} {:>8.2} {:>10}", path, bytes, input_bytes as f64 / bytes.max(1) as f64, sink.elapsed.as_millis());
+    }
+    println!("Execution time: {:?}", now.elapsed().as_millis());
+}
+
+fn transcode_command(input: &str, output: &str, from: Option<String>, to: Option<String>, level: Option<i32>, verify: bool) -> i32 {
+    let from = from.map_or_else(|| transcode::format_of(input), |name| transcode::parse_format(&name).unwrap());
+    let to = to.map_or_else(|| transcode::format_of(output), |name| transcode::parse_format(&name).unwrap());
+    println!("Transcoding {} ({}) to {} ({})", input, from.name(), output, to.name());
+    let transcoded = transcode::transcode(input, &from, output, &to, level, verify).unwrap();
+    summary::stage("transcode", transcoded.elapsed);
+    if verify {
+        summary::stage("verify", transcoded.verify_elapsed);
+    }
+    summary::update(|summary| {
+        summary.records_read = Some(transcoded.written.records);
+        summary.records_converted = Some(transcoded.written.records);
+        summary.bytes_in = Some(transcoded.input_bytes);
+        summary.bytes_out = Some(transcoded.output_bytes);
+    });
+    print!("{}", transcoded);
+    if transcoded.verified() { batch::EXIT_OK } else { batch::EXIT_ERROR }
+}
+
+fn soak_command(input: &str, workload: &str, duration: Duration, every: Duration, output: Option<String>, max_slowdown: Option<f64>) -> i32 {
+    let workload = soak::parse_workload(workload).unwrap();
+    let mut output = output.map(|path| io::OutputFile::create(&path, false).unwrap());
+    println!("{}", soak::HEADER);
+    let checkpoints = soak::soak(input, &workload, duration, every, &mut |checkpoint| {
+        println!("{}", checkpoint);
+        if let Some(output) = &mut output {
+            writeln!(output, "{}", serde_json::to_string(checkpoint).unwrap()).unwrap();
         }
+    }).unwrap();
+    if let Some(output) = output {
+        output.finish().unwrap();
+    }
+    let last = checkpoints.last().unwrap();
+    summary::update(|summary| {
+        summary.records_read = Some(last.records);
+        summary.records_parsed = Some(last.records - last.errors);
+    });
+    match soak::drift(&checkpoints) {
+        Some(drift) => {
+            println!("{}", drift);
+            if max_slowdown.map_or(false, |max_slowdown| drift.throughput_change < -max_slowdown) {
+                return batch::EXIT_REGRESSION;
+            }
+        },
+        None => println!("A single checkpoint, nothing to compare")
     }
+    batch::EXIT_OK
+}
+
+fn generate_command(from_schema: &str, output: &str, records: usize, seed: seed::Seed, mean_array_length: f64, null_probability: f64, gzip: bool) {
+    let schema = read_schema(from_schema);
+    let config = generate::GeneratorConfig { mean_array_length, null_probability, ..Default::default() };
+    let mut generator = generate::Generator::new(seed.rng("generate"), config);
+    let now = Instant::now();
+    let mut output = io::OutputFile::create(output, gzip).unwrap();
+    generate::generate(&mut generator, &schema, records, &mut output).unwrap();
+    output.finish().unwrap();
+    println!("Generated {} records", records);
     println!("Execution time: {:?}", now.elapsed().as_millis());
 }
 
-fn flate2_benchmark() {
-    let json_file = GzipFile::new("TweetsChampions.json.gz");
+fn shuffle_command(input: &str, output: &str, sample: Option<usize>, buckets: usize, seed: seed::Seed, gzip: bool) {
+    let mut rng = seed.rng("shuffle");
     let now = Instant::now();
-    json_file
-        .lines
-        .for_each(|line| {
-            let mut encoder = DeflateEncoder::new(Vec::new(), Compression::new(6));
-            encoder.write_all(line.unwrap().as_bytes());
-            let v = encoder.finish().unwrap();
-        });
+    let mut output = io::OutputFile::create(output, gzip).unwrap();
+    let records = match sample {
+        Some(sample) => shuffle::reservoir(input, &mut output, sample, &mut rng).unwrap(),
+        None => shuffle::external_shuffle(input, &mut output, buckets, &mut rng).unwrap()
+    };
+    output.finish().unwrap();
+    println!("Shuffled {} records", records);
     println!("Execution time: {:?}", now.elapsed().as_millis());
 }
 
-fn libflater_benchmark() {
-    let json_file = GzipFile::new("TweetsChampions.json.gz");
+fn interleave_command(inputs: &[String], output: &str, ratios: &[f64], seed: seed::Seed, gzip: bool) {
+    let mut rng = seed.rng("interleave");
+    let mut output = io::OutputFile::create(output, gzip).unwrap();
+    let counts = shuffle::interleave(inputs, ratios, &mut output, &mut rng).unwrap();
+    output.finish().unwrap();
+    for (input, count) in inputs.iter().zip(counts) {
+        println!("{}: {} records", input, count);
+    }
+}
+
+#[cfg(feature = "tui")]
+fn batch_command(config: &str, overrides: batch::Overrides, tui: bool, baseline: Option<String>) -> i32 {
+    if tui {
+        batch::exit_code(tui::run(config, &overrides, baseline.as_deref()))
+    } else {
+        batch::run(config, &overrides)
+    }
+}
+
+#[cfg(not(feature = "tui"))]
+fn batch_command(config: &str, overrides: batch::Overrides, tui: bool, _baseline: Option<String>) -> i32 {
+    if tui {
+        eprintln!("--tui needs a build with the tui feature");
+        return batch::EXIT_ERROR;
+    }
+    batch::run(config, &overrides)
+}
+
+#[cfg(feature = "results-parquet")]
+fn export_results_command(reports: &[String], output: &str) {
+    let reports: Vec<(String, batch::BatchReport)> = reports
+        .iter()
+        .map(|path| (path.clone(), batch::BatchReport::load(path).unwrap()))
+        .collect();
+    let rows = results::write_parquet(&reports, output).unwrap();
+    println!("Exported {} runs to {}", rows, output);
+}
+
+#[cfg(not(feature = "results-parquet"))]
+fn export_results_command(_reports: &[String], _output: &str) {
+    eprintln!("export-results needs a build with the results-parquet feature");
+    std::process::exit(batch::EXIT_ERROR);
+}
+
+fn capabilities_command(input: Option<String>) {
+    let parsers = parser::parsers();
+    // deep nesting probes recurse in the parsers and in the DOM conversion
+    let rows = thread::Builder::new()
+        .stack_size(256 * 1024 * 1024)
+        .spawn(|| capabilities::capability_matrix(&parser::parsers()))
+        .unwrap()
+        .join()
+        .unwrap();
+
+    print!("{:<20}", "");
+    parsers.iter().for_each(|p| print!(" {:>22}", p.name()));
+    println!();
+    for (probe, outcomes) in rows {
+        print!("{:<20}", probe);
+        outcomes.iter().for_each(|o| print!(" {:>22}", o));
+        println!();
+    }
+
+    if let Some(input) = input {
+        print!("{:<20}", "parse time (ms)");
+        for parser in &parsers {
+            let now = Instant::now();
+            for line in GzipFile::new(&input).lines {
+                let mut bytes = line.unwrap().into_bytes();
+                parser.parse(&mut bytes).unwrap();
+            }
+            print!(" {:>22}", now.elapsed().as_millis());
+        }
+        println!();
+    }
+}
+
+fn window_command(input: &str, spec: window::WindowSpec) {
     let now = Instant::now();
-    let mut dc = libdeflater::Compressor::new(CompressionLvl::new(6).unwrap());
-    json_file
-        .lines
-        .for_each(|line| {
-            let l = line.unwrap();
-            let bytes = l.as_bytes();
-            let mut v = Vec::new();
-            v.resize(dc.deflate_compress_bound(bytes.len()), 0);
-            dc.deflate_compress(bytes, &mut v).unwrap();
-        });
+    let mut aggregator = window::WindowAggregator::new(spec);
+    let records = convert::convert(input, &mut aggregator).unwrap();
+    for summary in aggregator.summaries() {
+        println!("{}", summary);
+    }
+    println!("Records: {}, without a time: {}", records, aggregator.untimed);
     println!("Execution time: {:?}", now.elapsed().as_millis());
 }
 
-fn deflate_benchmark() {
-    let json_file = GzipFile::new("TweetsChampions.json.gz");
+fn field_sizes_command(input: &str, schema: Option<String>, sample_records: usize, codec: &str, level: Option<i32>) {
+    let compressor = match compress::find_compressor(codec) {
+        Some(compressor) => compressor,
+        None => {
+            eprintln!("unknown codec {}", codec);
+            std::process::exit(batch::EXIT_ERROR);
+        }
+    };
+    let level = level.unwrap_or_else(|| compressor.default_level());
     let now = Instant::now();
-    json_file
-        .lines
-        .for_each(|line| {
-            let b = deflate_bytes(line.unwrap().as_bytes());
-        });
+    let records: Vec<JsonValue> = io::open_lines(input).unwrap()
+        .take(sample_records)
+        .map(|line| json::parse(&line.unwrap()).unwrap())
+        .collect();
+    let schema = match schema {
+        Some(path) => read_schema(&path),
+        None => {
+            let mut inferrer = SchemaInferrer::new("inferred_schema");
+            records.iter().for_each(|record| inferrer.update(record).unwrap());
+            inferrer.into_schema().unwrap()
+        }
+    };
+    let sizes = field_size::field_sizes(&records, &schema, compressor.as_ref(), level).unwrap();
+    print!("{}", sizes);
     println!("Execution time: {:?}", now.elapsed().as_millis());
 }
 
+fn recommend_codec_command(input: &str, sample_records: usize, min_speed: Option<f64>, min_ratio: Option<f64>, recompress: Option<String>) {
+    let constraint = match (min_speed, min_ratio) {
+        (_, Some(min_ratio)) => codec_select::Constraint::MinRatio(min_ratio),
+        (Some(min_speed), None) => codec_select::Constraint::MinSpeed(min_speed),
+        (None, None) => codec_select::Constraint::MinSpeed(0.0)
+    };
+
+    let sample = codec_select::sample(input, sample_records).unwrap();
+    let compressors = compress::compressors();
+    let measurements = codec_select::sweep(&compressors, &sample).unwrap();
+    println!("{:<12} {:>6} {:>8} {:>10}", "codec", "level", "ratio", "MB/s");
+    for m in &measurements {
+        println!("{:<12} {:>6} {:>8.3} {:>10.1}", m.codec, m.level, m.ratio, m.speed);
+    }
+
+    match codec_select::recommend(&measurements, constraint) {
+        Some(best) => {
+            println!("Recommended: {} level {} (ratio {:.3}, {:.1} MB/s)", best.codec, best.level, best.ratio, best.speed);
+            if let Some(output) = recompress {
+                let compressor = compress::find_compressor(best.codec).unwrap();
+                let mut output = io::OutputFile::with_compressor(&output, compressor.as_ref(), best.level).unwrap();
+                for line in io::open_lines(input).unwrap() {
+                    writeln!(output, "{}", line.unwrap()).unwrap();
+                }
+                output.finish().unwrap();
+            }
+        },
+        None => println!("No codec meets {:?}", constraint)
+    }
+}
+
 fn main() {
-//    json_benchmark();
-//    serde_benchmark();
-//    simd_benchmark();
-//    flate2_benchmark();
-//    libflater_benchmark();
-//    deflate_benchmark();
-    println!("{:?}", JsonValue::Number(Number::from(123)).as_fixed_point_i64(0));
+    let matches = Opt::clap().get_matches();
+    let opt = Opt::from_clap(&matches);
+    let seed = seed::Seed(opt.seed.unwrap_or(0));
+    io::set_subset(io::Subset { skip: opt.skip, limit: opt.limit, sample_rate: opt.sample_rate, seed });
+    progress::enable(opt.progress);
+    let now = Instant::now();
+    let exit_code = match opt.command {
+        Some(Command::Bench { names, parsers, codecs, input, decoder, warmup, runs, latency, duration, output, csv, markdown, store,
+                              push_gateway, push_job, push_labels, save_baseline, compare_baseline, regression_threshold, fail_if_slower_than, targets: targets_file, matrix, net, scaling, max_threads, levels, filter, pin_cores, high_priority, perf, profile, interleave, list,
+                              suite, suites }) => {
+            affinity::apply(&pin_cores, high_priority);
+            perf::enable(perf);
+            let max_threads = max_threads.unwrap_or_else(|| thread::available_parallelism().map_or(1, |cores| cores.get()));
+            let scaling = scaling.map(|parser| (parser, max_threads));
+            let push = push_gateway.map(|url| push::Gateway::new(&url, &push_job, &push_labels).unwrap());
+            let suites = suites.as_deref().unwrap_or(suite::DEFAULT_SUITES);
+            let suite = suite.map(|name| suite::load(suites, &name).unwrap()).unwrap_or_default();
+            let mut targets = suite.targets().unwrap();
+            targets.extend(targets_file.map(|path| gate::load(&path).unwrap()).unwrap_or_default());
+            targets.extend(fail_if_slower_than);
+            let (mut names, mut parsers, mut codecs) = (names, parsers, codecs);
+            names.extend(suite.benchmarks.iter().cloned());
+            parsers.extend(suite.parsers.iter().cloned());
+            codecs.extend(suite.codecs.iter().cloned());
+            let repetitions = suite.repetitions(bench::Repetitions { warmup, runs, latency, duration }).unwrap();
+            io::set_subset(suite.subset(io::subset()));
+            let inputs = if suite.inputs.is_empty() { vec![input] } else { suite.inputs.iter().cloned().map(Some).collect() };
+            // each input would overwrite the files of the one before
+            if inputs.len() > 1 && (output.is_some() || markdown.is_some() || save_baseline.is_some() || compare_baseline.is_some()) {
+                eprintln!("--output, --markdown and the baselines take a suite with a single input, {} given", inputs.len());
+                batch::EXIT_ERROR
+            } else {
+                let options = BenchOptions {
+                    names, parsers, codecs, input: None, decoder: suite.decoder.unwrap_or(decoder), repetitions, output, csv, markdown, store, push,
+                    save_baseline, compare_baseline, regression_threshold, targets, matrix, net, scaling, levels,
+                    filter: filter.or_else(|| suite.filter.clone()), profile, interleave: if interleave { Some(seed) } else { None }, list
+                };
+                inputs.into_iter().fold(batch::EXIT_OK, |exit_code, input| exit_code.max(bench_command(BenchOptions { input, ..options.clone() })))
+            }
+        },
+        Some(Command::History { benchmark, db, machine, all_machines, limit }) => {
+            history_command(&db, &benchmark, machine, all_machines, limit);
+            batch::EXIT_OK
+        },
+        Some(Command::Infer { input, name, output, array_policy }) => {
+            infer_command(&input, &name, output, array_policy);
+            batch::EXIT_OK
+        },
+        Some(Command::Codegen { input, schema, name, avro, output }) => {
+            codegen_command(input, schema, &name, avro, output);
+            batch::EXIT_OK
+        },
+        Some(Command::Schema(SchemaCommand::InferDiff { left, right, name })) => {
+            schema_infer_diff(&left, &right, &name);
+            batch::EXIT_OK
+        },
+        Some(Command::Split { input, shards, key, output_prefix, gzip }) => {
+            split_command(&input, shards, key, &output_prefix, gzip);
+            batch::EXIT_OK
+        },
+        Some(Command::Sort { input, output, key, chunk_records, gzip }) => {
+            let now = Instant::now();
+            let records = sort::sort(&input, &output, &key, chunk_records, gzip).unwrap();
+            summary::read_and_parsed(records);
+            summary::update(|summary| summary.bytes_in = summary::file_size(&input));
+            summary::update(|summary| summary.bytes_out = summary::file_size(&output));
+            println!("Sorted {} records", records);
+            println!("Execution time: {:?}", now.elapsed().as_millis());
+            batch::EXIT_OK
+        },
+        Some(Command::Anonymize { input, output, hash, redact, salt, gzip }) => {
+            anonymize_command(&input, &output, hash, redact, &salt, gzip);
+            batch::EXIT_OK
+        },
+        Some(Command::Generate { from_schema, output, records, mean_array_length, null_probability, gzip }) => {
+            generate_command(&from_schema, &output, records, seed, mean_array_length, null_probability, gzip);
+            batch::EXIT_OK
+        },
+        Some(Command::Shuffle { input, output, sample, buckets, gzip }) => {
+            shuffle_command(&input, &output, sample, buckets, seed, gzip);
+            batch::EXIT_OK
+        },
+        Some(Command::Interleave { inputs, output, ratios, gzip }) => {
+            interleave_command(&inputs, &output, &ratios, seed, gzip);
+            batch::EXIT_OK
+        },
+        Some(Command::Capabilities { input }) => {
+            capabilities_command(input);
+            batch::EXIT_OK
+        },
+        Some(Command::RecommendCodec { input, sample_records, min_speed, min_ratio, recompress }) => {
+            recommend_codec_command(&input, sample_records, min_speed, min_ratio, recompress);
+            batch::EXIT_OK
+        },
+        Some(Command::Window { input, time_field, window_seconds, group_by, top_of, top_k }) => {
+            let spec = window::WindowSpec { time_path: time_field, window_ms: window_seconds * 1000, group_path: group_by, top_path: top_of, top_k };
+            window_command(&input, spec);
+            batch::EXIT_OK
+        },
+        Some(Command::FieldSizes { input, schema, sample_records, codec, level }) => {
+            field_sizes_command(&input, schema, sample_records, &codec, level);
+            batch::EXIT_OK
+        },
+        Some(Command::Batch { config, input, tui, baseline, pin_cores, high_priority }) => {
+            affinity::apply(&pin_cores, high_priority);
+            batch_command(&config, batch::Overrides { seed: opt.seed, input }, tui, baseline)
+        },
+        Some(Command::SelfTest) => {
+            if selftest::self_test(seed).unwrap() { batch::EXIT_OK } else { batch::EXIT_ERROR }
+        },
+        Some(Command::ExportResults { reports, output }) => {
+            export_results_command(&reports, &output);
+            batch::EXIT_OK
+        },
+        Some(Command::Convert { input, output, schema, repair, strict, dead_letter, fallback, heterogeneity_threshold, envelope, parser_chain, max_records, max_output_bytes, array_policy, min_presence, rare_fields, tolerance,
+                                dry_run, canonical, hash_sidecar, hash_field }) => {
+            let limits = limit::Limits { max_records, max_output_bytes };
+            convert_command(&input, output.as_deref(), ConvertOptions { schema, repair, strict, dead_letter, fallback, heterogeneity_threshold,
+                                                                        envelope, parser_chain, limits, array_policy, min_presence, rare_fields,
+                                                                        tolerance, dry_run, canonical, hash_sidecar, hash_field });
+            batch::EXIT_OK
+        },
+        Some(Command::Transform { input, output, transforms, avro, gzip }) => {
+            transform_command(&input, &output, &transforms, avro, gzip);
+            batch::EXIT_OK
+        },
+        Some(Command::Route { input, key, output_prefix }) => {
+            route_command(&input, &key, &output_prefix);
+            batch::EXIT_OK
+        },
+        Some(Command::Archive { input, output_prefix, formats, schema }) => {
+            archive_command(&input, &output_prefix, &formats, schema);
+            batch::EXIT_OK
+        },
+        Some(Command::Transcode { input, output, from, to, level, no_verify }) => {
+            transcode_command(&input, &output, from, to, level, !no_verify)
+        },
+        Some(Command::Merge { inputs, output, key, gzip }) => {
+            let now = Instant::now();
+            let records = sort::merge(&inputs, &output, &key, gzip).unwrap();
+            summary::read_and_parsed(records);
+            summary::update(|summary| {
+                summary.bytes_in = inputs.iter().map(|input| summary::file_size(input)).sum();
+                summary.bytes_out = summary::file_size(&output);
+            });
+            println!("Merged {} records", records);
+            println!("Execution time: {:?}", now.elapsed().as_millis());
+            batch::EXIT_OK
+        },
+        Some(Command::Compare { baseline, current, alpha, resamples }) => {
+            compare_command(&baseline, &current, alpha, resamples, seed)
+        },
+        Some(Command::Soak { input, workload, duration, checkpoint_every, output, max_slowdown }) => {
+            soak_command(&io::input_file(input.as_deref(), io::DEFAULT_INPUT), &workload, duration, checkpoint_every, output, max_slowdown)
+        },
+        Some(Command::Serve { address, state_dir }) => {
+            serve::serve(&address, std::path::Path::new(&state_dir)).unwrap();
+            batch::EXIT_OK
+        },
+        None => {
+            Opt::clap().print_help().unwrap();
+            println!();
+            return;
+        }
+    };
+    let summary = summary::finish(matches.subcommand_name().unwrap_or_default(), exit_code, now.elapsed());
+    eprint!("{}", summary);
+    if let Some(path) = opt.summary_json {
+        summary::write_json(&summary, &path).unwrap();
+    }
+    std::process::exit(exit_code);
 }
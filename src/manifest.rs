@@ -0,0 +1,129 @@
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+use serde::Serialize;
+use sha2::{Sha256, Digest};
+use failure::Error;
+
+
+#[derive(Debug, Serialize)]
+pub struct InputDigest {
+    pub path: String,
+    pub bytes: u64,
+    // of the file as stored, before decompression
+    pub sha256: String
+}
+
+#[derive(Debug, Serialize)]
+pub struct Environment {
+    pub os: &'static str,
+    pub arch: &'static str,
+    pub hostname: Option<String>,
+    pub cpu: Option<String>,
+    // RUST* variables, they change allocator, backtrace and thread pool behavior
+    pub variables: BTreeMap<String, String>
+}
+
+// Everything needed to rerun a report: written as manifest.json next to it.
+#[derive(Debug, Serialize)]
+pub struct Manifest {
+    pub args: Vec<String>,
+    pub config: Option<String>,
+    pub inputs: Vec<InputDigest>,
+    pub crate_version: &'static str,
+    pub features: Vec<&'static str>,
+    pub seed: u64,
+    pub environment: Environment,
+    pub started_at: u64
+}
+
+impl Manifest {
+    pub fn new(config: Option<String>, inputs: &[&str], seed: u64) -> Result<Self, Error> {
+        Ok(Manifest {
+            args: std::env::args().collect(),
+            config,
+            inputs: inputs.iter().map(|path| digest(path)).collect::<io::Result<_>>()?,
+            crate_version: env!("CARGO_PKG_VERSION"),
+            features: features(),
+            seed,
+            environment: Environment {
+                os: std::env::consts::OS,
+                arch: std::env::consts::ARCH,
+                hostname: dns_lookup::get_hostname().ok(),
+                cpu: cpu_model(),
+                variables: std::env::vars().filter(|(key, _)| key.starts_with("RUST")).collect()
+            },
+            started_at: SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+        })
+    }
+
+    pub fn write(&self, report_dir: &Path) -> Result<(), Error> {
+        fs::write(report_dir.join("manifest.json"), serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+}
+
+
+pub fn features() -> Vec<&'static str> {
+    let mut features = Vec::new();
+    if cfg!(feature = "orc") {
+        features.push("orc");
+    }
+    if cfg!(feature = "tui") {
+        features.push("tui");
+    }
+    if cfg!(feature = "results-parquet") {
+        features.push("results-parquet");
+    }
+    if cfg!(feature = "results-sqlite") {
+        features.push("results-sqlite");
+    }
+    if cfg!(feature = "profile") {
+        features.push("profile");
+    }
+    features
+}
+
+pub fn digest(file_path: &str) -> io::Result<InputDigest> {
+    let mut file = File::open(file_path)?;
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0; 1 << 20];
+    let mut bytes = 0;
+    loop {
+        let read = file.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        hasher.input(&buffer[..read]);
+        bytes += read as u64;
+    }
+    let sha256 = hasher.result().iter().map(|b| format!("{:02x}", b)).collect();
+    Ok(InputDigest { path: file_path.to_owned(), bytes, sha256 })
+}
+
+fn cpu_model() -> Option<String> {
+    fs::read_to_string("/proc/cpuinfo")
+        .ok()?
+        .lines()
+        .find(|line| line.starts_with("model name"))
+        .and_then(|line| line.splitn(2, ':').nth(1))
+        .map(|model| model.trim().to_owned())
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::io::Write;
+
+    #[test]
+    fn test_digest() {
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        file.write_all(b"abc").unwrap();
+        let digest = digest(file.path().to_str().unwrap()).unwrap();
+        assert_eq!(digest.bytes, 3);
+        assert_eq!(digest.sha256, "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad");
+    }
+}
@@ -0,0 +1,165 @@
+use std::fmt;
+use std::time::{Duration, Instant};
+use serde::{Deserialize, Serialize};
+use failure::{Error, format_err};
+use crate::allocation::{AllocationMeter, Allocations};
+use crate::energy::EnergyMeter;
+use crate::perf::{PerfCounters, PerfMeter};
+
+
+#[derive(Debug, Clone)]
+pub struct Measurement {
+    pub name: String,
+    pub elapsed: Duration,
+    pub records: usize,
+    // whole package energy, so it includes anything else running on the machine
+    pub joules: Option<f64>,
+    pub allocations: Allocations,
+    // with perf::enable, where the kernel lets us open the counters
+    pub perf: Option<PerfCounters>
+}
+
+impl Measurement {
+    pub fn records_per_joule(&self) -> Option<f64> {
+        self.joules.filter(|j| *j > 0.0).map(|j| self.records as f64 / j)
+    }
+}
+
+impl fmt::Display for Measurement {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}, Execution time: {:?}", self.name, self.elapsed.as_millis())?;
+        if let (Some(joules), Some(per_joule)) = (self.joules, self.records_per_joule()) {
+            write!(f, ", Energy: {:.2} J, {:.0} records/J", joules, per_joule)?;
+        }
+        write!(f, ", {}", self.allocations)
+    }
+}
+
+
+// Rates over one pass of an input, in bytes per second of the gzip file and of the NDJSON in it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Throughput {
+    pub records_per_second: f64,
+    pub compressed_bytes_per_second: f64,
+    pub decompressed_bytes_per_second: f64
+}
+
+impl Throughput {
+    pub fn new(records: u64, compressed_bytes: u64, decompressed_bytes: u64, elapsed: Duration) -> Self {
+        let seconds = elapsed.as_secs_f64().max(1e-6);
+        Throughput {
+            records_per_second: records as f64 / seconds,
+            compressed_bytes_per_second: compressed_bytes as f64 / seconds,
+            decompressed_bytes_per_second: decompressed_bytes as f64 / seconds
+        }
+    }
+}
+
+impl fmt::Display for Throughput {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:.0} records/s, {:.1} MB/s compressed, {:.1} MB/s decompressed", self.records_per_second,
+               self.compressed_bytes_per_second / 1_000_000.0, self.decompressed_bytes_per_second / 1_000_000.0)
+    }
+}
+
+
+// Summary of the wall clock times of repeated runs of one benchmark.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Stats {
+    pub runs: usize,
+    pub mean_ms: f64,
+    pub median_ms: f64,
+    // sample standard deviation, 0 for a single run
+    pub stddev_ms: f64,
+    pub min_ms: f64,
+    pub max_ms: f64
+}
+
+impl Stats {
+    pub fn of(durations: &[Duration]) -> Option<Stats> {
+        if durations.is_empty() {
+            return None;
+        }
+        let mut ms: Vec<f64> = durations.iter().map(|d| d.as_secs_f64() * 1000.0).collect();
+        ms.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        let n = ms.len();
+        let mean = ms.iter().sum::<f64>() / n as f64;
+        let median = if n % 2 == 1 { ms[n / 2] } else { (ms[n / 2 - 1] + ms[n / 2]) / 2.0 };
+        let variance = if n > 1 { ms.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / (n - 1) as f64 } else { 0.0 };
+        Some(Stats { runs: n, mean_ms: mean, median_ms: median, stddev_ms: variance.sqrt(), min_ms: ms[0], max_ms: ms[n - 1] })
+    }
+}
+
+impl fmt::Display for Stats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Runs: {}, mean: {:.1} ms, median: {:.1} ms, stddev: {:.1} ms, min: {:.1} ms, max: {:.1} ms",
+               self.runs, self.mean_ms, self.median_ms, self.stddev_ms, self.min_ms, self.max_ms)
+    }
+}
+
+
+// "30s", "500ms", "2m" or "1h", plain numbers being seconds.
+pub fn parse_duration(s: &str) -> Result<Duration, Error> {
+    let s = s.trim();
+    let split = s.find(|c: char| !(c.is_ascii_digit() || c == '.')).unwrap_or_else(|| s.len());
+    let (number, unit) = s.split_at(split);
+    let number: f64 = number.parse().map_err(|_| format_err!("{} is not a duration, e.g. 30s", s))?;
+    let seconds = match unit {
+        "" | "s" => number,
+        "ms" => number / 1000.0,
+        "m" => number * 60.0,
+        "h" => number * 3600.0,
+        _ => return Err(format_err!("unknown unit {} in {}, use ms, s, m or h", unit, s))
+    };
+    Ok(Duration::from_secs_f64(seconds))
+}
+
+
+// Runs a benchmark body, which returns the number of records it processed.
+pub fn measure<F: FnOnce() -> usize>(name: &str, f: F) -> Measurement {
+    let meter = EnergyMeter::start();
+    let allocation_meter = AllocationMeter::start();
+    let perf_meter = PerfMeter::start();
+    let now = Instant::now();
+    let records = f();
+    let elapsed = now.elapsed();
+    let perf = perf_meter.map(PerfMeter::counters);
+    Measurement {
+        name: name.to_owned(),
+        elapsed,
+        records,
+        joules: meter.and_then(|m| m.joules()),
+        allocations: allocation_meter.allocations(),
+        perf
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_stats() {
+        let durations: Vec<Duration> = [4, 1, 3, 2].iter().map(|ms| Duration::from_millis(*ms)).collect();
+        let stats = Stats::of(&durations).unwrap();
+        assert_eq!(stats.runs, 4);
+        assert_eq!(stats.median_ms, 2.5);
+        assert_eq!(stats.mean_ms, 2.5);
+        assert_eq!((stats.min_ms, stats.max_ms), (1.0, 4.0));
+        assert!((stats.stddev_ms - 1.291).abs() < 0.001);
+        assert_eq!(Stats::of(&durations[..1]).unwrap().stddev_ms, 0.0);
+        assert!(Stats::of(&[]).is_none());
+    }
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("30s").unwrap(), Duration::from_secs(30));
+        assert_eq!(parse_duration("45").unwrap(), Duration::from_secs(45));
+        assert_eq!(parse_duration("250ms").unwrap(), Duration::from_millis(250));
+        assert_eq!(parse_duration("1.5m").unwrap(), Duration::from_secs(90));
+        assert_eq!(parse_duration("2h").unwrap(), Duration::from_secs(7200));
+        assert!(parse_duration("30d").is_err());
+        assert!(parse_duration("s").is_err());
+    }
+}
@@ -0,0 +1,293 @@
+use std::borrow::Cow;
+use serde::{Deserialize, Serialize};
+use serde_json::value::RawValue;
+
+
+// A hand written model of the Twitter v1.1 statuses in the corpus, the way an application would
+// type them: only the fields it reads, unknown ones skipped by serde, and retweets and quotes
+// nested. Unlike src/tweet.rs it does not follow an Avro schema.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Tweet {
+    pub id: u64,
+    pub id_str: String,
+    pub created_at: String,
+    pub text: String,
+    #[serde(default)]
+    pub truncated: bool,
+    pub in_reply_to_status_id: Option<u64>,
+    pub in_reply_to_screen_name: Option<String>,
+    pub user: User,
+    pub entities: Entities,
+    pub extended_entities: Option<ExtendedEntities>,
+    pub retweeted_status: Option<Box<Tweet>>,
+    pub quoted_status: Option<Box<Tweet>>,
+    #[serde(default)]
+    pub retweet_count: u64,
+    #[serde(default)]
+    pub favorite_count: u64,
+    pub lang: Option<String>,
+    pub timestamp_ms: Option<String>
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct User {
+    pub id: u64,
+    pub id_str: String,
+    pub name: String,
+    pub screen_name: String,
+    pub location: Option<String>,
+    pub description: Option<String>,
+    #[serde(default)]
+    pub verified: bool,
+    #[serde(default)]
+    pub followers_count: u64,
+    #[serde(default)]
+    pub friends_count: u64,
+    pub lang: Option<String>
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Entities {
+    #[serde(default)]
+    pub hashtags: Vec<Hashtag>,
+    #[serde(default)]
+    pub urls: Vec<Url>,
+    #[serde(default)]
+    pub user_mentions: Vec<UserMention>,
+    #[serde(default)]
+    pub media: Vec<Media>
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ExtendedEntities {
+    #[serde(default)]
+    pub media: Vec<Media>
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Hashtag {
+    pub text: String,
+    pub indices: (u32, u32)
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Url {
+    pub url: String,
+    pub expanded_url: Option<String>,
+    pub display_url: Option<String>,
+    pub indices: (u32, u32)
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct UserMention {
+    pub id: u64,
+    pub screen_name: String,
+    pub name: String,
+    pub indices: (u32, u32)
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Media {
+    pub id: u64,
+    pub media_url_https: String,
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub indices: (u32, u32)
+}
+
+impl Tweet {
+    // hashtags of the tweet and of what it retweets or quotes, what the typed benchmarks sink
+    // besides the id so that the nested structs are not skipped unread
+    pub fn hashtag_count(&self) -> usize {
+        self.entities.hashtags.len()
+            + self.retweeted_status.as_ref().map_or(0, |tweet| tweet.hashtag_count())
+            + self.quoted_status.as_ref().map_or(0, |tweet| tweet.hashtag_count())
+    }
+}
+
+
+// The model with its big subtrees left as JSON text, for the benchmark of deferred parsing: the
+// fields an application reads on every record are deserialized, entities and the nested tweets
+// only checked for syntax, for the caller to parse when it needs them. Serializing writes the kept
+// text back verbatim.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct LazyTweet {
+    pub id: u64,
+    pub id_str: String,
+    pub created_at: String,
+    pub text: String,
+    #[serde(default)]
+    pub truncated: bool,
+    pub in_reply_to_status_id: Option<u64>,
+    pub in_reply_to_screen_name: Option<String>,
+    pub user: User,
+    pub entities: Box<RawValue>,
+    pub extended_entities: Option<Box<RawValue>>,
+    pub retweeted_status: Option<Box<RawValue>>,
+    pub quoted_status: Option<Box<RawValue>>,
+    #[serde(default)]
+    pub retweet_count: u64,
+    #[serde(default)]
+    pub favorite_count: u64,
+    pub lang: Option<String>,
+    pub timestamp_ms: Option<String>
+}
+
+
+// The same model borrowing from the line being parsed, for the zero-copy benchmarks. Fields that
+// never hold escapes are &str, text that may is Cow, borrowed unless serde had to unescape it.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct BorrowedTweet<'a> {
+    pub id: u64,
+    pub id_str: &'a str,
+    pub created_at: &'a str,
+    #[serde(borrow)]
+    pub text: Cow<'a, str>,
+    #[serde(default)]
+    pub truncated: bool,
+    pub in_reply_to_status_id: Option<u64>,
+    pub in_reply_to_screen_name: Option<&'a str>,
+    #[serde(borrow)]
+    pub user: BorrowedUser<'a>,
+    #[serde(borrow)]
+    pub entities: BorrowedEntities<'a>,
+    #[serde(borrow)]
+    pub extended_entities: Option<BorrowedExtendedEntities<'a>>,
+    #[serde(borrow)]
+    pub retweeted_status: Option<Box<BorrowedTweet<'a>>>,
+    #[serde(borrow)]
+    pub quoted_status: Option<Box<BorrowedTweet<'a>>>,
+    #[serde(default)]
+    pub retweet_count: u64,
+    #[serde(default)]
+    pub favorite_count: u64,
+    pub lang: Option<&'a str>,
+    pub timestamp_ms: Option<&'a str>
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct BorrowedUser<'a> {
+    pub id: u64,
+    pub id_str: &'a str,
+    #[serde(borrow)]
+    pub name: Cow<'a, str>,
+    pub screen_name: &'a str,
+    #[serde(borrow)]
+    pub location: Option<Cow<'a, str>>,
+    #[serde(borrow)]
+    pub description: Option<Cow<'a, str>>,
+    #[serde(default)]
+    pub verified: bool,
+    #[serde(default)]
+    pub followers_count: u64,
+    #[serde(default)]
+    pub friends_count: u64,
+    pub lang: Option<&'a str>
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+pub struct BorrowedEntities<'a> {
+    #[serde(default, borrow)]
+    pub hashtags: Vec<BorrowedHashtag<'a>>,
+    #[serde(default, borrow)]
+    pub urls: Vec<BorrowedUrl<'a>>,
+    #[serde(default, borrow)]
+    pub user_mentions: Vec<BorrowedUserMention<'a>>,
+    #[serde(default, borrow)]
+    pub media: Vec<BorrowedMedia<'a>>
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+pub struct BorrowedExtendedEntities<'a> {
+    #[serde(default, borrow)]
+    pub media: Vec<BorrowedMedia<'a>>
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct BorrowedHashtag<'a> {
+    #[serde(borrow)]
+    pub text: Cow<'a, str>,
+    pub indices: (u32, u32)
+}
+
+// URLs are escaped as https:\/\/ in the corpus, so they are rarely borrowed
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct BorrowedUrl<'a> {
+    #[serde(borrow)]
+    pub url: Cow<'a, str>,
+    #[serde(borrow)]
+    pub expanded_url: Option<Cow<'a, str>>,
+    #[serde(borrow)]
+    pub display_url: Option<Cow<'a, str>>,
+    pub indices: (u32, u32)
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct BorrowedUserMention<'a> {
+    pub id: u64,
+    pub screen_name: &'a str,
+    #[serde(borrow)]
+    pub name: Cow<'a, str>,
+    pub indices: (u32, u32)
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct BorrowedMedia<'a> {
+    pub id: u64,
+    #[serde(borrow)]
+    pub media_url_https: Cow<'a, str>,
+    #[serde(rename = "type")]
+    pub kind: &'a str,
+    pub indices: (u32, u32)
+}
+
+impl<'a> BorrowedTweet<'a> {
+    pub fn hashtag_count(&self) -> usize {
+        self.entities.hashtags.len()
+            + self.retweeted_status.as_ref().map_or(0, |tweet| tweet.hashtag_count())
+            + self.quoted_status.as_ref().map_or(0, |tweet| tweet.hashtag_count())
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_tweet_model() {
+        let line = r##"{"created_at":"Sat May 26 19:54:01 +0000 2018","id":1000514497163542528,"id_str":"1000514497163542528",
+            "text":"RT @LFC: #UCLfinal","truncated":false,"in_reply_to_status_id":null,"in_reply_to_screen_name":null,
+            "user":{"id":7,"id_str":"7","name":"Fan","screen_name":"fan","location":null,"followers_count":10,"unknown":[1]},
+            "entities":{"hashtags":[],"urls":[],"user_mentions":[{"id":1,"screen_name":"LFC","name":"LFC","indices":[3,7]}]},
+            "retweeted_status":{"created_at":"Sat May 26 19:50:00 +0000 2018","id":1,"id_str":"1","text":"#UCLfinal",
+                "user":{"id":1,"id_str":"1","name":"LFC","screen_name":"LFC","verified":true},
+                "entities":{"hashtags":[{"text":"UCLfinal","indices":[0,9]}],"media":[{"id":5,"media_url_https":"https://p",
+                    "type":"photo","indices":[10,33]}]}},
+            "lang":"en","timestamp_ms":"1527364441000"}"##;
+        let tweet: Tweet = serde_json::from_str(line).unwrap();
+        assert_eq!(tweet.id, 1000514497163542528);
+        assert_eq!(tweet.entities.user_mentions[0].indices, (3, 7));
+        assert_eq!(tweet.hashtag_count(), 1);
+        let retweeted = tweet.retweeted_status.as_ref().unwrap();
+        assert!(retweeted.user.verified);
+        assert_eq!(retweeted.entities.media[0].kind, "photo");
+
+        let mut bytes = line.as_bytes().to_vec();
+        let simd: Tweet = simd_json::serde::from_slice(&mut bytes).unwrap();
+        assert_eq!(simd, tweet);
+
+        let borrowed: BorrowedTweet = serde_json::from_str(line).unwrap();
+        assert_eq!((borrowed.id, borrowed.hashtag_count()), (tweet.id, 1));
+        assert!(matches!(borrowed.text, Cow::Borrowed("RT @LFC: #UCLfinal")));
+        let lazy: LazyTweet = serde_json::from_str(line).unwrap();
+        assert_eq!((lazy.id, lazy.user.screen_name.as_str()), (tweet.id, "fan"));
+        assert!(lazy.retweeted_status.as_ref().unwrap().get().starts_with("{\"created_at\""));
+        assert_eq!(serde_json::from_str::<Entities>(lazy.entities.get()).unwrap(), tweet.entities);
+        let retweeted: Tweet = serde_json::from_str(lazy.retweeted_status.as_ref().unwrap().get()).unwrap();
+        assert_eq!(Some(&retweeted), tweet.retweeted_status.as_deref());
+        assert!(lazy.quoted_status.is_none());
+        let escaped: BorrowedHashtag = serde_json::from_str(r#"{"text":"caf\u00e9","indices":[0,5]}"#).unwrap();
+        assert!(matches!(escaped.text, Cow::Owned(ref text) if text == "café"));
+    }
+}
@@ -0,0 +1,135 @@
+use std::fs::File;
+use std::sync::Arc;
+use avro_rs::Schema;
+use arrow::datatypes::{DataType, Field, Fields, Schema as ArrowSchema, SchemaRef};
+use arrow::json::reader::{Decoder, ReaderBuilder};
+use orc_rust::{ArrowWriter, ArrowWriterBuilder};
+use failure::{Error, bail, format_err};
+use json::JsonValue;
+use crate::convert::RecordWriter;
+
+const BATCH_SIZE: usize = 8192;
+
+
+// Every column is nullable: an inferred field can be absent from any record.
+pub fn to_arrow_schema(schema: &Schema) -> Result<ArrowSchema, Error> {
+    match schema {
+        Schema::Record { fields, .. } => Ok(ArrowSchema::new(to_arrow_fields(fields)?)),
+        _ => Err(format_err!("top level schema must be a record"))
+    }
+}
+
+fn to_arrow_fields(fields: &[avro_rs::schema::RecordField]) -> Result<Fields, Error> {
+    let fields = fields
+        .iter()
+        .map(|field| Ok(Field::new(&field.name, to_arrow_type(&field.schema)?, true)))
+        .collect::<Result<Vec<Field>, Error>>()?;
+    Ok(Fields::from(fields))
+}
+
+fn to_arrow_type(schema: &Schema) -> Result<DataType, Error> {
+    let data_type = match schema {
+        // ORC has no null type, a column that was always null is kept as an empty string column
+        Schema::Null => DataType::Utf8,
+        Schema::Boolean => DataType::Boolean,
+        Schema::Int => DataType::Int32,
+        Schema::Long => DataType::Int64,
+        Schema::Float => DataType::Float32,
+        Schema::Double => DataType::Float64,
+        Schema::Bytes => DataType::Binary,
+        Schema::String => DataType::Utf8,
+        Schema::Array(items) => DataType::List(Arc::new(Field::new("item", to_arrow_type(items)?, true))),
+        Schema::Record { fields, .. } => DataType::Struct(to_arrow_fields(fields)?),
+        Schema::Union(union) => {
+            let variants: Vec<&Schema> =
+                union
+                    .variants()
+                    .iter()
+                    .filter(|variant| **variant != Schema::Null)
+                    .collect();
+
+            match variants.as_slice() {
+                [] => DataType::Utf8,
+                [variant] => to_arrow_type(variant)?,
+                // mixed scalar unions are written in their string form
+                variants if variants.iter().all(|v| is_scalar(v)) => DataType::Utf8,
+                _ => bail!("union of complex types can not be written to ORC: {:?}", schema)
+            }
+        },
+        _ => bail!("unsupported schema for ORC output: {:?}", schema)
+    };
+    Ok(data_type)
+}
+
+fn is_scalar(schema: &Schema) -> bool {
+    match schema {
+        Schema::Array(_) | Schema::Map(_) | Schema::Record { .. } | Schema::Union(_) => false,
+        _ => true
+    }
+}
+
+
+pub struct OrcWriter {
+    decoder: Decoder,
+    writer: Option<ArrowWriter<File>>,
+    buffered: usize
+}
+
+impl OrcWriter {
+    pub fn new(schema: &Schema, file_path: &str) -> Result<Self, Error> {
+        let arrow_schema: SchemaRef = Arc::new(to_arrow_schema(schema)?);
+        let decoder =
+            ReaderBuilder::new(arrow_schema.clone())
+                .with_batch_size(BATCH_SIZE)
+                .with_coerce_primitive(true)
+                .build_decoder()?;
+        let file = File::create(file_path)?;
+        let writer = ArrowWriterBuilder::new(file, arrow_schema).try_build()?;
+        Ok(OrcWriter { decoder, writer: Some(writer), buffered: 0 })
+    }
+
+    fn flush_batch(&mut self) -> Result<(), Error> {
+        if let Some(batch) = self.decoder.flush()? {
+            self.writer
+                .as_mut()
+                .ok_or_else(|| format_err!("ORC writer is already finished"))?
+                .write(&batch)?;
+        }
+        self.buffered = 0;
+        Ok(())
+    }
+}
+
+impl RecordWriter for OrcWriter {
+    fn write(&mut self, record: &JsonValue) -> Result<(), Error> {
+        self.decoder.decode(record.dump().as_bytes())?;
+        self.buffered += 1;
+        if self.buffered == BATCH_SIZE {
+            self.flush_batch()?;
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<(), Error> {
+        self.flush_batch()?;
+        if let Some(writer) = self.writer.take() {
+            writer.close()?;
+        }
+        Ok(())
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_to_arrow_schema() {
+        let schema = Schema::parse_str(r#"{"name":"tweet","type":"record","fields":[{"name":"id","type":"long"},{"name":"lang","type":["null","string"]},{"name":"indices","type":{"type":"array","items":"long"}}]}"#).unwrap();
+        let arrow_schema = to_arrow_schema(&schema).unwrap();
+        assert_eq!(arrow_schema.field(0).data_type(), &DataType::Int64);
+        assert_eq!(arrow_schema.field(1).data_type(), &DataType::Utf8);
+        assert_eq!(arrow_schema.field(2).data_type(), &DataType::List(Arc::new(Field::new("item", DataType::Int64, true))));
+    }
+}
@@ -0,0 +1,259 @@
+use json;
+use json::JsonValue;
+use serde_json::Value;
+use simd_json::StaticNode;
+use simd_json::value::borrowed::Value as BorrowedValue;
+use std::fmt;
+use failure::{Error, format_err};
+use crate::sink::{json_size, serde_size, simd_size};
+
+
+// A JSON parser backend. Results are normalized to the json-rust DOM that inference and conversion
+// work on. Input is mutable because simd-json parses in place.
+pub trait Parser {
+    fn name(&self) -> &'static str;
+    fn parse(&self, input: &mut [u8]) -> Result<JsonValue, Error>;
+
+    // Parses into the backend's own DOM, what the parse benchmarks time, returning its number of
+    // keys and items for the benchmark's sink.
+    fn parse_native(&self, input: &mut [u8]) -> Result<u64, Error> {
+        self.parse(input).map(|value| json_size(&value))
+    }
+}
+
+
+pub struct JsonRustParser;
+
+impl Parser for JsonRustParser {
+    fn name(&self) -> &'static str {
+        "json"
+    }
+
+    fn parse(&self, input: &mut [u8]) -> Result<JsonValue, Error> {
+        Ok(json::parse(std::str::from_utf8(input)?)?)
+    }
+}
+
+
+pub struct SerdeParser;
+
+impl Parser for SerdeParser {
+    fn name(&self) -> &'static str {
+        "serde"
+    }
+
+    fn parse(&self, input: &mut [u8]) -> Result<JsonValue, Error> {
+        let value: Value = serde_json::from_slice(input)?;
+        Ok(serde_to_json(value))
+    }
+
+    fn parse_native(&self, input: &mut [u8]) -> Result<u64, Error> {
+        let value: Value = serde_json::from_slice(input)?;
+        Ok(serde_size(&value))
+    }
+}
+
+pub fn serde_to_json(value: Value) -> JsonValue {
+    match value {
+        Value::Null => JsonValue::Null,
+        Value::Bool(b) => JsonValue::Boolean(b),
+        Value::Number(number) => {
+            if let Some(u) = number.as_u64() {
+                JsonValue::from(u)
+            } else if let Some(i) = number.as_i64() {
+                JsonValue::from(i)
+            } else {
+                JsonValue::from(number.as_f64().unwrap_or(std::f64::NAN))
+            }
+        },
+        Value::String(s) => JsonValue::String(s),
+        Value::Array(items) => JsonValue::Array(items.into_iter().map(serde_to_json).collect()),
+        Value::Object(map) => {
+            let mut object = JsonValue::new_object();
+            for (key, value) in map {
+                object[key.as_str()] = serde_to_json(value);
+            }
+            object
+        }
+    }
+}
+
+
+pub struct SimdParser;
+
+impl Parser for SimdParser {
+    fn name(&self) -> &'static str {
+        "simd"
+    }
+
+    fn parse(&self, input: &mut [u8]) -> Result<JsonValue, Error> {
+        let value = simd_json::to_borrowed_value(input)?;
+        Ok(simd_to_json(&value))
+    }
+
+    fn parse_native(&self, input: &mut [u8]) -> Result<u64, Error> {
+        Ok(simd_size(&simd_json::to_borrowed_value(input)?))
+    }
+}
+
+pub fn simd_to_json(value: &BorrowedValue) -> JsonValue {
+    match value {
+        BorrowedValue::Static(StaticNode::Null) => JsonValue::Null,
+        BorrowedValue::Static(StaticNode::Bool(b)) => JsonValue::Boolean(*b),
+        BorrowedValue::Static(StaticNode::I64(i)) => JsonValue::from(*i),
+        BorrowedValue::Static(StaticNode::U64(u)) => JsonValue::from(*u),
+        BorrowedValue::Static(StaticNode::F64(f)) => JsonValue::from(*f),
+        BorrowedValue::String(s) => JsonValue::from(s.as_ref()),
+        BorrowedValue::Array(items) => JsonValue::Array(items.iter().map(simd_to_json).collect()),
+        BorrowedValue::Object(map) => {
+            let mut object = JsonValue::new_object();
+            for (key, value) in map.iter() {
+                object[key.as_ref()] = simd_to_json(value);
+            }
+            object
+        }
+    }
+}
+
+
+// Takes what tools and hand edits tend to get wrong: trailing commas, raw control characters in
+// strings and invalid UTF-8, which is replaced. The same DOM as json-rust for valid input.
+pub struct LenientParser;
+
+impl Parser for LenientParser {
+    fn name(&self) -> &'static str {
+        "lenient"
+    }
+
+    fn parse(&self, input: &mut [u8]) -> Result<JsonValue, Error> {
+        Ok(json::parse(&relax(&String::from_utf8_lossy(input)))?)
+    }
+}
+
+fn relax(text: &str) -> String {
+    let mut relaxed = String::with_capacity(text.len());
+    let (mut in_string, mut escaped) = (false, false);
+    for c in text.chars() {
+        if in_string {
+            match c {
+                _ if escaped => escaped = false,
+                '\\' => escaped = true,
+                '"' => in_string = false,
+                _ if (c as u32) < 0x20 => {
+                    relaxed.push_str(&format!("\\u{:04x}", c as u32));
+                    continue;
+                },
+                _ => {}
+            }
+        } else if c == '"' {
+            in_string = true;
+        } else if c == '}' || c == ']' {
+            let end = relaxed.trim_end().len();
+            if relaxed[..end].ends_with(',') {
+                relaxed.truncate(end - 1);
+            }
+        }
+        relaxed.push(c);
+    }
+    relaxed
+}
+
+
+// JSON5 through serde, a superset much like what hjson and hand written config files are:
+// comments, unquoted keys, single quoted strings, trailing commas, hex numbers.
+pub struct Json5Parser;
+
+impl Parser for Json5Parser {
+    fn name(&self) -> &'static str {
+        "json5"
+    }
+
+    fn parse(&self, input: &mut [u8]) -> Result<JsonValue, Error> {
+        let value: Value = json5::from_str(std::str::from_utf8(input)?)?;
+        Ok(serde_to_json(value))
+    }
+
+    fn parse_native(&self, input: &mut [u8]) -> Result<u64, Error> {
+        let value: Value = json5::from_str(std::str::from_utf8(input)?)?;
+        Ok(serde_size(&value))
+    }
+}
+
+
+pub fn parsers() -> Vec<Box<dyn Parser>> {
+    vec![Box::new(JsonRustParser), Box::new(SerdeParser), Box::new(SimdParser), Box::new(LenientParser), Box::new(Json5Parser)]
+}
+
+pub fn find_parser(name: &str) -> Option<Box<dyn Parser>> {
+    parsers().into_iter().find(|parser| parser.name() == name)
+}
+
+
+// Tries the parsers in order until one accepts the line, so the fast path stays fast and odd
+// records still get through, e.g. "simd,serde,lenient". Counts which backend took each record.
+pub struct ParserChain {
+    parsers: Vec<Box<dyn Parser>>,
+    pub parsed: Vec<usize>,
+    pub failed: usize
+}
+
+impl ParserChain {
+    pub fn parse_spec(spec: &str) -> Result<Self, Error> {
+        let parsers = spec
+            .split(',')
+            .map(|name| find_parser(name.trim()).ok_or_else(|| format_err!("unknown parser {}", name)))
+            .collect::<Result<Vec<_>, Error>>()?;
+        Ok(ParserChain { parsed: vec![0; parsers.len()], parsers, failed: 0 })
+    }
+
+    // The error is the last parser's.
+    pub fn parse(&mut self, line: &str) -> Result<JsonValue, Error> {
+        let mut error = format_err!("no parsers in the chain");
+        for (parser, parsed) in self.parsers.iter().zip(self.parsed.iter_mut()) {
+            // simd-json parses in place, every attempt needs the original bytes
+            match parser.parse(&mut line.as_bytes().to_vec()) {
+                Ok(json) => {
+                    *parsed += 1;
+                    return Ok(json);
+                },
+                Err(e) => error = e
+            }
+        }
+        self.failed += 1;
+        Err(error)
+    }
+
+    // records that needed more than the first parser
+    pub fn fallbacks(&self) -> usize {
+        self.parsed.iter().skip(1).sum()
+    }
+}
+
+impl fmt::Display for ParserChain {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let counts: Vec<String> = self.parsers.iter().zip(&self.parsed).map(|(parser, parsed)| format!("{} {}", parser.name(), parsed)).collect();
+        write!(f, "Parsed by {}, fallbacks: {}, failed: {}", counts.join(", "), self.fallbacks(), self.failed)
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parser_chain() {
+        assert_eq!(relax("{\"a\":[1,2, ],\"b\":\"x\ty,}\",}"), "{\"a\":[1,2],\"b\":\"x\\u0009y,}\"}");
+        let mut chain = ParserChain::parse_spec("simd,serde,lenient").unwrap();
+        assert!(chain.parse(r#"{"a":1}"#).is_ok());
+        assert_eq!(chain.parse(r#"{"a":[1,],}"#).unwrap()["a"][0], 1);
+        assert!(chain.parse("{").is_err());
+        assert_eq!((chain.parsed.clone(), chain.fallbacks(), chain.failed), (vec![1, 0, 1], 1, 1));
+        assert!(ParserChain::parse_spec("simd,yaml").is_err());
+        let mut chain = ParserChain::parse_spec("simd,json5").unwrap();
+        let json = chain.parse("{id: 0x10, text: 'it\\'s', /* comment */ tags: [1,],}").unwrap();
+        assert_eq!((json["id"].as_u64(), json["text"].as_str(), json["tags"].len()), (Some(16), Some("it's"), 1));
+        // the member a, its two items and the member b
+        assert_eq!(Json5Parser.parse_native(&mut b"{a: [1, {b: null}]}".to_vec()).unwrap(), 4);
+    }
+}
@@ -0,0 +1,59 @@
+use json::JsonValue;
+
+
+// Dotted field paths like "user.screen_name". A missing segment yields null, same as indexing a
+// JsonValue with a missing key.
+pub fn lookup<'a>(json: &'a JsonValue, path: &str) -> &'a JsonValue {
+    path.split('.').fold(json, |value, segment| &value[segment])
+}
+
+// Every value at a path where "a[].b" steps into each item of the array at a, for fields that
+// repeat like "entities.hashtags[].text". Missing and null values are left out.
+pub fn lookup_all<'a>(json: &'a JsonValue, path: &str) -> Vec<&'a JsonValue> {
+    let mut values = vec![json];
+    for segment in path.split('.') {
+        let (key, each) = match segment.strip_suffix("[]") {
+            Some(key) => (key, true),
+            None => (segment, false)
+        };
+        values = values
+            .into_iter()
+            .map(|value| if key.is_empty() { value } else { &value[key] })
+            .flat_map(|value| if each { value.members().collect() } else { vec![value] })
+            .collect();
+    }
+    values.retain(|value| !value.is_null());
+    values
+}
+
+pub fn lookup_mut<'a>(json: &'a mut JsonValue, path: &str) -> Option<&'a mut JsonValue> {
+    let mut value = json;
+    for segment in path.split('.') {
+        if !value.has_key(segment) {
+            return None;
+        }
+        value = &mut value[segment];
+    }
+    Some(value)
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_lookup() {
+        let mut json = json::parse(r#"{"user": {"screen_name": "ThaiLFC"}}"#).unwrap();
+        assert_eq!(lookup(&json, "user.screen_name").as_str(), Some("ThaiLFC"));
+        assert!(lookup(&json, "user.id.value").is_null());
+        assert!(lookup_mut(&mut json, "user.id").is_none());
+        *lookup_mut(&mut json, "user.screen_name").unwrap() = JsonValue::Null;
+        assert!(json["user"]["screen_name"].is_null());
+
+        let json = json::parse(r#"{"entities": {"hashtags": [{"text": "a"}, {"text": "b"}, {}]}}"#).unwrap();
+        let texts: Vec<&str> = lookup_all(&json, "entities.hashtags[].text").iter().filter_map(|value| value.as_str()).collect();
+        assert_eq!(texts, vec!["a", "b"]);
+        assert!(lookup_all(&json, "user.lang").is_empty());
+    }
+}
@@ -0,0 +1,224 @@
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use serde::{Deserialize, Serialize};
+
+
+// Off unless asked for with bench --perf, opening counters costs a few syscalls per measurement.
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+pub fn enable(enabled: bool) {
+    ENABLED.store(enabled, Ordering::SeqCst);
+}
+
+pub fn enabled() -> bool {
+    ENABLED.load(Ordering::SeqCst)
+}
+
+
+// Hardware counters of the process in user space, threads started during the measurement
+// included. Each counter is None when the CPU or the kernel does not provide it.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct PerfCounters {
+    pub cycles: Option<u64>,
+    pub instructions: Option<u64>,
+    pub cache_references: Option<u64>,
+    pub cache_misses: Option<u64>,
+    pub branches: Option<u64>,
+    pub branch_misses: Option<u64>
+}
+
+fn ratio(numerator: Option<u64>, denominator: Option<u64>) -> Option<f64> {
+    match (numerator, denominator) {
+        (Some(numerator), Some(denominator)) if denominator > 0 => Some(numerator as f64 / denominator as f64),
+        _ => None
+    }
+}
+
+impl PerfCounters {
+    // instructions per cycle
+    pub fn ipc(&self) -> Option<f64> {
+        ratio(self.instructions, self.cycles)
+    }
+
+    pub fn cache_miss_rate(&self) -> Option<f64> {
+        ratio(self.cache_misses, self.cache_references)
+    }
+
+    pub fn branch_miss_rate(&self) -> Option<f64> {
+        ratio(self.branch_misses, self.branches)
+    }
+}
+
+impl fmt::Display for PerfCounters {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let count = |value: Option<u64>| value.map_or("-".to_owned(), |value| value.to_string());
+        let percent = |value: Option<f64>| value.map_or("-".to_owned(), |value| format!("{:.2}%", value * 100.0));
+        write!(f, "instructions: {}, cycles: {}, IPC: {}, cache misses: {} ({}), branch misses: {} ({})",
+               count(self.instructions), count(self.cycles), self.ipc().map_or("-".to_owned(), |ipc| format!("{:.2}", ipc)),
+               count(self.cache_misses), percent(self.cache_miss_rate()),
+               count(self.branch_misses), percent(self.branch_miss_rate()))
+    }
+}
+
+
+#[cfg(target_os = "linux")]
+mod sys {
+    use std::os::raw::c_ulong;
+
+    pub const PERF_TYPE_HARDWARE: u32 = 0;
+    pub const PERF_COUNT_HW_CPU_CYCLES: u64 = 0;
+    pub const PERF_COUNT_HW_INSTRUCTIONS: u64 = 1;
+    pub const PERF_COUNT_HW_CACHE_REFERENCES: u64 = 2;
+    pub const PERF_COUNT_HW_CACHE_MISSES: u64 = 3;
+    pub const PERF_COUNT_HW_BRANCH_INSTRUCTIONS: u64 = 4;
+    pub const PERF_COUNT_HW_BRANCH_MISSES: u64 = 5;
+
+    const FLAG_DISABLED: u64 = 1 << 0;
+    const FLAG_INHERIT: u64 = 1 << 1;
+    const FLAG_EXCLUDE_KERNEL: u64 = 1 << 5;
+    const FLAG_EXCLUDE_HV: u64 = 1 << 6;
+
+    pub const PERF_EVENT_IOC_ENABLE: c_ulong = 0x2400;
+    pub const PERF_EVENT_IOC_DISABLE: c_ulong = 0x2401;
+    pub const PERF_EVENT_IOC_RESET: c_ulong = 0x2403;
+
+    // the first version of struct perf_event_attr, which every kernel since 2.6.31 accepts
+    #[repr(C)]
+    pub struct PerfEventAttr {
+        pub kind: u32,
+        pub size: u32,
+        pub config: u64,
+        pub sample_period: u64,
+        pub sample_type: u64,
+        pub read_format: u64,
+        pub flags: u64,
+        pub wakeup_events: u32,
+        pub bp_type: u32,
+        pub config1: u64
+    }
+
+    impl PerfEventAttr {
+        pub fn counter(config: u64) -> Self {
+            PerfEventAttr {
+                kind: PERF_TYPE_HARDWARE,
+                size: std::mem::size_of::<PerfEventAttr>() as u32,
+                config,
+                sample_period: 0,
+                sample_type: 0,
+                read_format: 0,
+                flags: FLAG_DISABLED | FLAG_INHERIT | FLAG_EXCLUDE_KERNEL | FLAG_EXCLUDE_HV,
+                wakeup_events: 0,
+                bp_type: 0,
+                config1: 0
+            }
+        }
+    }
+}
+
+// Counters opened for the calling process on any CPU. Usually needs perf_event_paranoid at 2 or
+// lower; inside containers often not available at all.
+#[cfg(target_os = "linux")]
+pub struct PerfMeter {
+    // file descriptor per counter of PerfCounters, in its field order
+    counters: Vec<Option<i32>>
+}
+
+#[cfg(target_os = "linux")]
+impl PerfMeter {
+    // None when not enabled or when no counter could be opened.
+    pub fn start() -> Option<Self> {
+        if !enabled() {
+            return None;
+        }
+        let configs = [
+            sys::PERF_COUNT_HW_CPU_CYCLES,
+            sys::PERF_COUNT_HW_INSTRUCTIONS,
+            sys::PERF_COUNT_HW_CACHE_REFERENCES,
+            sys::PERF_COUNT_HW_CACHE_MISSES,
+            sys::PERF_COUNT_HW_BRANCH_INSTRUCTIONS,
+            sys::PERF_COUNT_HW_BRANCH_MISSES
+        ];
+        let counters: Vec<Option<i32>> = configs.iter().map(|config| open(*config)).collect();
+        if counters.iter().all(Option::is_none) {
+            return None;
+        }
+        for fd in counters.iter().flatten() {
+            unsafe {
+                libc::ioctl(*fd, sys::PERF_EVENT_IOC_RESET, 0);
+                libc::ioctl(*fd, sys::PERF_EVENT_IOC_ENABLE, 0);
+            }
+        }
+        Some(PerfMeter { counters })
+    }
+
+    pub fn counters(self) -> PerfCounters {
+        let values: Vec<Option<u64>> = self.counters.iter().map(|fd| fd.and_then(read)).collect();
+        PerfCounters {
+            cycles: values[0],
+            instructions: values[1],
+            cache_references: values[2],
+            cache_misses: values[3],
+            branches: values[4],
+            branch_misses: values[5]
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+impl Drop for PerfMeter {
+    fn drop(&mut self) {
+        for fd in self.counters.iter().flatten() {
+            unsafe { libc::close(*fd) };
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn open(config: u64) -> Option<i32> {
+    let attr = sys::PerfEventAttr::counter(config);
+    // this process, any CPU, no group, no flags
+    let fd = unsafe { libc::syscall(libc::SYS_perf_event_open, &attr as *const sys::PerfEventAttr, 0, -1, -1, 0) };
+    if fd < 0 { None } else { Some(fd as i32) }
+}
+
+#[cfg(target_os = "linux")]
+fn read(fd: i32) -> Option<u64> {
+    let mut value: u64 = 0;
+    unsafe {
+        libc::ioctl(fd, sys::PERF_EVENT_IOC_DISABLE, 0);
+        let read = libc::read(fd, &mut value as *mut u64 as *mut libc::c_void, std::mem::size_of::<u64>());
+        if read != std::mem::size_of::<u64>() as isize {
+            return None;
+        }
+    }
+    Some(value)
+}
+
+#[cfg(not(target_os = "linux"))]
+pub struct PerfMeter;
+
+#[cfg(not(target_os = "linux"))]
+impl PerfMeter {
+    pub fn start() -> Option<Self> {
+        None
+    }
+
+    pub fn counters(self) -> PerfCounters {
+        PerfCounters::default()
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_perf_counters() {
+        let counters = PerfCounters { cycles: Some(200), instructions: Some(500), cache_references: Some(0), ..PerfCounters::default() };
+        assert_eq!(counters.ipc(), Some(2.5));
+        assert_eq!(counters.cache_miss_rate(), None);
+        assert_eq!(counters.branch_miss_rate(), None);
+        assert!(PerfMeter::start().is_none(), "disabled by default");
+    }
+}
@@ -0,0 +1,16 @@
+use std::fs::File;
+use failure::Error;
+use pprof::ProfilerGuard;
+
+
+// Samples the stacks of every thread while f runs and writes them as a flamegraph SVG. 997 Hz
+// rather than 1000 so the sampling does not fall into step with periodic work; frames of inlined
+// functions are only as good as the debug info, so profile a release
+// build with CARGO_PROFILE_RELEASE_DEBUG=true.
+pub fn flamegraph<T, F: FnOnce() -> T>(svg: &str, f: F) -> Result<T, Error> {
+    let guard = ProfilerGuard::new(997)?;
+    let result = f();
+    let report = guard.report().build()?;
+    report.flamegraph(File::create(svg)?)?;
+    Ok(result)
+}
@@ -0,0 +1,120 @@
+use std::cell::Cell;
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::sync::atomic::{AtomicBool, Ordering};
+use indicatif::{ProgressBar, ProgressStyle};
+
+
+// Off unless asked for with --progress, drawing on stderr costs a little of every record.
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+pub fn enable(enabled: bool) {
+    ENABLED.store(enabled, Ordering::SeqCst);
+}
+
+fn enabled() -> bool {
+    ENABLED.load(Ordering::SeqCst)
+}
+
+// how often the record count next to the bar is redrawn
+const MESSAGE_RECORDS: u64 = 10_000;
+
+// Size of the NDJSON in a file. For gzip, the trailer keeps it modulo 4 GiB, which is taken to be
+// at least the compressed size to guess the wraps; concatenated members only count the last one.
+pub fn expected_bytes(path: &str) -> std::io::Result<u64> {
+    let mut file = File::open(path)?;
+    let size = file.metadata()?.len();
+    if !path.ends_with(".gz") || size < 18 {
+        return Ok(size);
+    }
+    file.seek(SeekFrom::End(-4))?;
+    let mut trailer = [0; 4];
+    file.read_exact(&mut trailer)?;
+    let mut bytes = u32::from_le_bytes(trailer) as u64;
+    while bytes < size {
+        bytes += 1 << 32;
+    }
+    Ok(bytes)
+}
+
+// A bar on stderr over the lines of one input: records, MB/s and the time left. Does nothing
+// unless enabled, and clears itself when dropped so it does not mix with what is printed after.
+pub struct Progress {
+    bar: Option<ProgressBar>,
+    records: Cell<u64>
+}
+
+impl Progress {
+    pub fn new(path: &str, prefix: &str) -> Self {
+        let bar = match expected_bytes(path) {
+            Ok(bytes) if enabled() => {
+                let bar = ProgressBar::new(bytes);
+                bar.set_style(ProgressStyle::with_template("{prefix} [{elapsed_precise}] {wide_bar} {bytes}/{total_bytes} {bytes_per_sec} ETA {eta} {msg}")
+                    .unwrap());
+                bar.set_prefix(prefix.to_owned());
+                Some(bar)
+            },
+            _ => None
+        };
+        Progress { bar, records: Cell::new(0) }
+    }
+
+    // A line of this many bytes, without its newline, was read.
+    pub fn record(&self, bytes: usize) {
+        if let Some(bar) = &self.bar {
+            let records = self.records.get() + 1;
+            self.records.set(records);
+            bar.inc(bytes as u64 + 1);
+            if records % MESSAGE_RECORDS == 0 {
+                bar.set_message(format!("{} records", records));
+            }
+        }
+    }
+
+    // From the start of the input again, for the next pass over it.
+    pub fn restart(&self, prefix: &str) {
+        if let Some(bar) = &self.bar {
+            self.records.set(0);
+            bar.reset();
+            bar.set_prefix(prefix.to_owned());
+            bar.set_message("");
+        }
+    }
+}
+
+impl Drop for Progress {
+    fn drop(&mut self) {
+        if let Some(bar) = &self.bar {
+            bar.finish_and_clear();
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::io::Write;
+
+    #[test]
+    fn test_expected_bytes() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let lines = "{\"id\":1}\n".repeat(1000);
+        let plain = dir.path().join("tweets.json").to_string_lossy().into_owned();
+        std::fs::write(&plain, &lines).unwrap();
+        assert_eq!(expected_bytes(&plain).unwrap(), lines.len() as u64);
+
+        let gzip = dir.path().join("tweets.json.gz").to_string_lossy().into_owned();
+        let mut encoder = flate2::write::GzEncoder::new(File::create(&gzip).unwrap(), flate2::Compression::default());
+        encoder.write_all(lines.as_bytes()).unwrap();
+        encoder.finish().unwrap();
+        assert_eq!(expected_bytes(&gzip).unwrap(), lines.len() as u64);
+        assert!(expected_bytes(&dir.path().join("missing.json").to_string_lossy()).is_err());
+
+        // disabled, nothing is drawn or counted
+        let progress = Progress::new(&gzip, "convert");
+        progress.record(8);
+        assert!(progress.bar.is_none());
+        assert_eq!(progress.records.get(), 0);
+    }
+}
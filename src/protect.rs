@@ -0,0 +1,232 @@
+use std::fs;
+use aes_gcm::aead::{Aead, NewAead, Payload};
+use aes_gcm::Aes256Gcm;
+use avro_rs::Schema;
+use failure::{Error, bail, format_err};
+use hmac::{Hmac, Mac};
+use json::JsonValue;
+use rand::RngCore;
+use sha2::Sha256;
+use crate::anonymize::apply_at;
+use crate::transform::RecordTransform;
+
+
+const NONCE_BYTES: usize = 12;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Protection {
+    // AES-256-GCM of the JSON text of the value under a random nonce, stored as base64 of nonce and
+    // ciphertext; the field path is the associated data, so a value cannot be moved to another field
+    Encrypt,
+    // HMAC-SHA256 of the JSON text as hex, irreversible but equal for equal values under one key,
+    // so joins and group-bys still work
+    Hmac
+}
+
+impl Protection {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Protection::Encrypt => "aes-256-gcm",
+            Protection::Hmac => "hmac-sha256"
+        }
+    }
+}
+
+// Replaces the values at the paths, "a[].b" stepping into every item of the array at a, with
+// strings, leaving nulls so optional fields stay optional. Objects and arrays are protected whole.
+pub struct Protect {
+    protection: Protection,
+    paths: Vec<String>,
+    key: Vec<u8>
+}
+
+impl Protect {
+    pub fn new(protection: Protection, paths: &[&str], key: &[u8]) -> Result<Self, Error> {
+        if protection == Protection::Encrypt && key.len() != 32 {
+            bail!("AES-256-GCM takes a 32 byte key, got {} bytes", key.len());
+        }
+        if key.is_empty() {
+            bail!("empty key");
+        }
+        if paths.is_empty() {
+            bail!("no fields to protect");
+        }
+        Ok(Protect { protection, paths: paths.iter().map(|path| path.to_string()).collect(), key: key.to_vec() })
+    }
+
+    // The key file holds the key as hex, e.g. from openssl rand -hex 32.
+    pub fn from_key_file(protection: Protection, paths: &[&str], key_file: &str) -> Result<Self, Error> {
+        let hex = fs::read_to_string(key_file).map_err(|e| format_err!("{}: {}", key_file, e))?;
+        Protect::new(protection, paths, &decode_hex(hex.trim()).map_err(|e| format_err!("{}: {}", key_file, e))?)
+    }
+
+    fn cipher(&self) -> Result<Aes256Gcm, Error> {
+        Aes256Gcm::new_varkey(&self.key).map_err(|_| format_err!("AES-256-GCM takes a 32 byte key, got {} bytes", self.key.len()))
+    }
+
+    fn protect(&self, path: &str, value: &JsonValue) -> Result<JsonValue, Error> {
+        let plaintext = value.dump();
+        Ok(JsonValue::from(match self.protection {
+            Protection::Encrypt => {
+                let mut nonce = [0u8; NONCE_BYTES];
+                rand::thread_rng().fill_bytes(&mut nonce);
+                let ciphertext = self.cipher()?
+                    .encrypt((&nonce[..]).into(), Payload { msg: plaintext.as_bytes(), aad: path.as_bytes() })
+                    .map_err(|_| format_err!("{}: encryption failed", path))?;
+                base64::encode([&nonce[..], &ciphertext].concat())
+            },
+            Protection::Hmac => {
+                let mut mac = Hmac::<Sha256>::new_varkey(&self.key).map_err(|_| format_err!("invalid HMAC key"))?;
+                mac.input(plaintext.as_bytes());
+                mac.result().code().iter().map(|b| format!("{:02x}", b)).collect::<String>()
+            }
+        }))
+    }
+
+    // The value encrypt wrote at the path.
+    pub fn decrypt(&self, path: &str, value: &str) -> Result<JsonValue, Error> {
+        if self.protection != Protection::Encrypt {
+            bail!("{} values can not be decrypted", self.protection.name());
+        }
+        let bytes = base64::decode(value)?;
+        if bytes.len() < NONCE_BYTES {
+            bail!("{}: too short for an encrypted value", path);
+        }
+        let (nonce, ciphertext) = bytes.split_at(NONCE_BYTES);
+        let plaintext = self.cipher()?
+            .decrypt(nonce.into(), Payload { msg: ciphertext, aad: path.as_bytes() })
+            .map_err(|_| format_err!("{}: wrong key or tampered value", path))?;
+        Ok(json::parse(std::str::from_utf8(&plaintext)?)?)
+    }
+}
+
+impl RecordTransform for Protect {
+    fn name(&self) -> &'static str {
+        match self.protection {
+            Protection::Encrypt => "encrypt",
+            Protection::Hmac => "hmac"
+        }
+    }
+
+    fn apply(&self, mut record: JsonValue) -> Result<Vec<JsonValue>, Error> {
+        for path in &self.paths {
+            replace_at(&mut record, path, |value| self.protect(path, value))?;
+        }
+        Ok(vec![record])
+    }
+
+    // lists the protected fields in the doc of the top level record, which Avro files carry in their
+    // header (avro-rs does not write the docs of fields)
+    fn annotate(&self, schema: Schema) -> Result<Schema, Error> {
+        match schema {
+            Schema::Record { name, doc, fields, lookup } => {
+                let protected: Vec<String> = self.paths.iter().map(|path| format!("{} ({})", path, self.protection.name())).collect();
+                let protected = format!("protected: {}", protected.join(", "));
+                let doc = Some(match doc {
+                    Some(doc) => format!("{}; {}", doc, protected),
+                    None => protected
+                });
+                Ok(Schema::Record { name, doc, fields, lookup })
+            },
+            _ => bail!("protected fields need a record schema")
+        }
+    }
+}
+
+
+// Decrypts the fields encrypt wrote under the same key, for the consumers allowed to read them.
+pub struct Unprotect(Protect);
+
+impl Unprotect {
+    pub fn from_key_file(paths: &[&str], key_file: &str) -> Result<Self, Error> {
+        Ok(Unprotect(Protect::from_key_file(Protection::Encrypt, paths, key_file)?))
+    }
+}
+
+impl RecordTransform for Unprotect {
+    fn name(&self) -> &'static str {
+        "decrypt"
+    }
+
+    fn apply(&self, mut record: JsonValue) -> Result<Vec<JsonValue>, Error> {
+        for path in &self.0.paths {
+            replace_at(&mut record, path, |value| match value.as_str() {
+                Some(encrypted) => self.0.decrypt(path, encrypted),
+                None => bail!("{}: not an encrypted value", path)
+            })?;
+        }
+        Ok(vec![record])
+    }
+}
+
+// Replaces the non-null values at the path, stopping at the first error.
+fn replace_at(record: &mut JsonValue, path: &str, replace: impl Fn(&JsonValue) -> Result<JsonValue, Error>) -> Result<(), Error> {
+    let segments: Vec<String> = path.split('.').map(|s| s.to_owned()).collect();
+    let mut result = Ok(());
+    apply_at(record, &segments, &mut |value| {
+        if result.is_ok() && !value.is_null() {
+            match replace(value) {
+                Ok(replaced) => *value = replaced,
+                Err(e) => result = Err(e)
+            }
+        }
+    });
+    result
+}
+
+fn decode_hex(hex: &str) -> Result<Vec<u8>, Error> {
+    if hex.len() % 2 != 0 || !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+        bail!("expected the key as hex digits");
+    }
+    (0..hex.len()).step_by(2).map(|i| Ok(u8::from_str_radix(&hex[i..i + 2], 16)?)).collect()
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::avro::infer_schema;
+
+    #[test]
+    fn test_protect() {
+        let key = [7u8; 32];
+        let encrypt = Protect::new(Protection::Encrypt, &["user.id", "entities.user_mentions[].screen_name"], &key).unwrap();
+        let hmac = Protect::new(Protection::Hmac, &["user.name"], &key).unwrap();
+        let record = json::parse(r#"{"user":{"id":904718268,"name":"LFC"},"entities":{"user_mentions":[{"screen_name":"ThaiLFC"},{"screen_name":null}]}}"#).unwrap();
+
+        let protected = hmac.apply(encrypt.apply(record.clone()).unwrap().remove(0)).unwrap().remove(0);
+        let id = protected["user"]["id"].as_str().unwrap();
+        assert_eq!(encrypt.decrypt("user.id", id).unwrap(), 904718268);
+        assert!(encrypt.decrypt("user.name", id).is_err(), "bound to its path");
+        let mention = protected["entities"]["user_mentions"][0]["screen_name"].as_str().unwrap();
+        assert_eq!(encrypt.decrypt("entities.user_mentions[].screen_name", mention).unwrap(), "ThaiLFC");
+        assert!(protected["entities"]["user_mentions"][1]["screen_name"].is_null());
+        assert_eq!(protected["user"]["name"].as_str().unwrap().len(), 64);
+        assert_eq!(hmac.apply(record.clone()).unwrap()[0]["user"]["name"], protected["user"]["name"]);
+        assert_ne!(encrypt.apply(record).unwrap()[0]["user"]["id"], protected["user"]["id"], "random nonces");
+
+        let schema = hmac.annotate(encrypt.annotate(infer_schema(&protected, "t").unwrap()).unwrap()).unwrap();
+        let schema = serde_json::to_string(&schema).unwrap();
+        assert!(schema.contains("\"doc\":\"protected: user.id (aes-256-gcm), entities.user_mentions[].screen_name (aes-256-gcm); protected: user.name (hmac-sha256)\""), "{}", schema);
+
+        assert!(Protect::new(Protection::Encrypt, &["a"], &[1, 2]).is_err());
+        assert_eq!(decode_hex("00ff").unwrap(), vec![0, 255]);
+    }
+
+    #[test]
+    fn test_unprotect() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let key_file = dir.path().join("key.hex").to_string_lossy().into_owned();
+        fs::write(&key_file, format!("{}\n", "07".repeat(32))).unwrap();
+        let paths = ["user.id", "entities.user_mentions[].screen_name"];
+        let encrypt = Protect::from_key_file(Protection::Encrypt, &paths, &key_file).unwrap();
+        let decrypt = Unprotect::from_key_file(&paths, &key_file).unwrap();
+        let record = json::parse(r#"{"user":{"id":904718268,"name":"LFC"},"entities":{"user_mentions":[{"screen_name":"ThaiLFC"},{"screen_name":null}]}}"#).unwrap();
+
+        let protected = encrypt.apply(record.clone()).unwrap().remove(0);
+        assert_eq!(decrypt.apply(protected.clone()).unwrap(), vec![record.clone()]);
+        assert!(decrypt.apply(record).is_err(), "not encrypted");
+        let other_key = Unprotect(Protect::new(Protection::Encrypt, &paths, &[8u8; 32]).unwrap());
+        assert!(other_key.apply(protected).is_err());
+    }
+}
@@ -0,0 +1,209 @@
+use std::collections::{BTreeMap, HashMap};
+use std::str::FromStr;
+use json::JsonValue;
+use avro_rs::Schema;
+use avro_rs::schema::UnionSchema;
+use failure::{Error, format_err};
+use crate::avro::with_extra_field;
+use crate::convert::{Location, RecordWriter};
+use crate::infer::FieldStats;
+
+
+// map of the dropped paths to the JSON text of their values, in records that had any
+pub const CATCH_ALL_FIELD: &str = "_rare_fields";
+
+// What happens to the values of fields pruned from the schema.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RareFields {
+    Discard,
+    // kept in CATCH_ALL_FIELD
+    CatchAll
+}
+
+impl FromStr for RareFields {
+    type Err = Error;
+
+    fn from_str(name: &str) -> Result<Self, Error> {
+        match name {
+            "discard" => Ok(RareFields::Discard),
+            "catch-all" => Ok(RareFields::CatchAll),
+            _ => Err(format_err!("unknown rare field handling {}, expected discard or catch-all", name))
+        }
+    }
+}
+
+
+// The schema without the fields present in fewer than min_presence of the records, 0.01 being
+// 1%, and the paths of the dropped fields. Fields under a dropped one are not listed separately.
+pub fn prune(schema: Schema, fields: &BTreeMap<String, FieldStats>, records: usize, min_presence: f64,
+             rare_fields: RareFields) -> Result<(Schema, Vec<String>), Error> {
+    let mut pruned = Vec::new();
+    let presence = |path: &str| fields.get(path).map_or(0, |stats| stats.present) as f64 / records.max(1) as f64;
+    let schema = prune_at(schema, "", &presence, min_presence, &mut pruned)?;
+    let schema = match rare_fields {
+        RareFields::CatchAll => with_extra_field(schema, CATCH_ALL_FIELD, Schema::Map(Box::new(Schema::String)))?,
+        RareFields::Discard => schema
+    };
+    Ok((schema, pruned))
+}
+
+fn prune_at(schema: Schema, path: &str, presence: &dyn Fn(&str) -> f64, min_presence: f64, pruned: &mut Vec<String>) -> Result<Schema, Error> {
+    Ok(match schema {
+        Schema::Record { name, doc, fields, .. } => {
+            let mut kept = Vec::with_capacity(fields.len());
+            for mut field in fields {
+                let field_path = if path.is_empty() { field.name.clone() } else { format!("{}.{}", path, field.name) };
+                if presence(&field_path) < min_presence {
+                    pruned.push(field_path);
+                    continue;
+                }
+                field.schema = prune_at(field.schema, &field_path, presence, min_presence, pruned)?;
+                field.position = kept.len();
+                kept.push(field);
+            }
+            let lookup: HashMap<String, usize> = kept.iter().map(|field| (field.name.clone(), field.position)).collect();
+            Schema::Record { name, doc, fields: kept, lookup }
+        },
+        Schema::Array(items) => Schema::Array(Box::new(prune_at(*items, &format!("{}[]", path), presence, min_presence, pruned)?)),
+        Schema::Union(union) => {
+            let variants = union.variants()
+                .iter()
+                .map(|variant| prune_at(variant.clone(), path, presence, min_presence, pruned))
+                .collect::<Result<Vec<_>, Error>>()?;
+            Schema::Union(UnionSchema::new(variants)?)
+        },
+        schema => schema
+    })
+}
+
+// Removes the values at a path like "entities.media[].sizes" from the record, every item of the
+// arrays on the way included.
+fn take_all(json: &mut JsonValue, path: &str, taken: &mut Vec<JsonValue>) {
+    let (segment, rest) = match path.find('.') {
+        Some(dot) => (&path[..dot], Some(&path[dot + 1..])),
+        None => (path, None)
+    };
+    let key = segment.strip_suffix("[]").unwrap_or(segment);
+    // indexing a missing key would add it
+    if !json.has_key(key) {
+        return;
+    }
+    match rest {
+        Some(rest) if key != segment => {
+            if let JsonValue::Array(items) = &mut json[key] {
+                for item in items {
+                    take_all(item, rest, taken);
+                }
+            }
+        },
+        Some(rest) => take_all(&mut json[key], rest, taken),
+        None => {
+            let value = json.remove(key);
+            if !value.is_null() {
+                taken.push(value);
+            }
+        }
+    }
+}
+
+
+// Takes the pruned fields out of the records before they reach writer, so strict mode does not
+// see them as unknown fields, and with RareFields::CatchAll keeps their values as JSON text.
+pub struct PruningWriter<'w> {
+    inner: &'w mut dyn RecordWriter,
+    paths: &'w [String],
+    rare_fields: RareFields,
+    // with values in a pruned field
+    pub records_with_rare_fields: usize
+}
+
+impl<'w> PruningWriter<'w> {
+    pub fn new(inner: &'w mut dyn RecordWriter, paths: &'w [String], rare_fields: RareFields) -> Self {
+        PruningWriter { inner, paths, rare_fields, records_with_rare_fields: 0 }
+    }
+}
+
+impl<'w> RecordWriter for PruningWriter<'w> {
+    fn write(&mut self, record: &JsonValue) -> Result<(), Error> {
+        let mut record = record.clone();
+        let mut catch_all = JsonValue::new_object();
+        for path in self.paths {
+            let mut taken = Vec::new();
+            take_all(&mut record, path, &mut taken);
+            if taken.is_empty() {
+                continue;
+            }
+            // a single value is kept as it is unless the path steps through arrays
+            let value = if taken.len() == 1 && !path.contains("[]") { taken.pop().unwrap() } else { JsonValue::Array(taken) };
+            catch_all[path.as_str()] = JsonValue::from(value.dump());
+        }
+        if !catch_all.is_empty() {
+            self.records_with_rare_fields += 1;
+        }
+        if self.rare_fields == RareFields::CatchAll {
+            record[CATCH_ALL_FIELD] = catch_all;
+        }
+        self.inner.write(&record)
+    }
+
+    fn finish(&mut self) -> Result<(), Error> {
+        self.inner.finish()
+    }
+
+    fn locate(&mut self, location: &Location) {
+        self.inner.locate(location)
+    }
+
+    fn full(&self) -> bool {
+        self.inner.full()
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::avro::json_to_avro;
+    use crate::convert::JsonLinesWriter;
+    use crate::infer::SchemaInferrer;
+
+    #[test]
+    fn test_prune() {
+        let mut records: Vec<JsonValue> = (0..19)
+            .map(|i| json::parse(&format!(r#"{{"id":{},"user":{{"name":"u"}},"entities":{{"media":[{{"url":"x"}}]}}}}"#, i)).unwrap())
+            .collect();
+        records.push(json::parse(r#"{"id":19,"user":{"name":"u","banner":"b"},"entities":{"media":[{"url":"x","sizes":{"w":1}}]},"geo":{"lat":1.5}}"#).unwrap());
+        let mut inferrer = SchemaInferrer::new("tweet");
+        for record in &records {
+            inferrer.update(record).unwrap();
+        }
+        let (fields, count) = (inferrer.fields().clone(), inferrer.records());
+        let schema = inferrer.into_schema().unwrap();
+
+        // each rare field is in 5% of the records
+        let (kept, pruned) = prune(schema.clone(), &fields, count, 0.05, RareFields::Discard).unwrap();
+        assert!(pruned.is_empty());
+        assert_eq!(kept, schema);
+        let (pruned_schema, pruned) = prune(schema, &fields, count, 0.1, RareFields::CatchAll).unwrap();
+        assert_eq!(pruned, vec!["user.banner", "entities.media[].sizes", "geo"]);
+        let text = serde_json::to_string(&pruned_schema).unwrap();
+        assert!(!text.contains("banner") && !text.contains("sizes") && !text.contains("\"geo\""), "{}", text);
+
+        let mut output = Vec::new();
+        {
+            let mut lines = JsonLinesWriter::new(&mut output);
+            let mut writer = PruningWriter::new(&mut lines, &pruned, RareFields::CatchAll);
+            writer.write(&records[0]).unwrap();
+            writer.write(&records[19]).unwrap();
+            assert_eq!(writer.records_with_rare_fields, 1);
+        }
+        let written: Vec<JsonValue> = String::from_utf8(output).unwrap().lines().map(|line| json::parse(line).unwrap()).collect();
+        assert_eq!(written[0][CATCH_ALL_FIELD].len(), 0);
+        let rare = &written[1][CATCH_ALL_FIELD];
+        assert_eq!(rare["user.banner"], "\"b\"");
+        assert_eq!(rare["entities.media[].sizes"], "[{\"w\":1}]");
+        assert_eq!(rare["geo"], "{\"lat\":1.5}");
+        assert!(written[1]["user"]["banner"].is_null());
+        assert!(json_to_avro(&written[1], &pruned_schema).is_ok());
+    }
+}
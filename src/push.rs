@@ -0,0 +1,184 @@
+use std::fmt::Write as _;
+use std::io::{Read, Write};
+use std::net::TcpStream;
+use std::path::Path;
+use std::time::Duration;
+use failure::{Error, bail, format_err};
+use crate::bench::{BenchResult, BenchResults};
+
+
+// A Prometheus pushgateway and the grouping key the results are pushed under. Every push replaces
+// the metrics of the previous one in the group, dashboards see the latest run of each machine.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Gateway {
+    host: String,
+    port: u16,
+    path: String,
+    pub job: String,
+    // added to every sample, e.g. instance=bench-01 from --push-label
+    pub labels: Vec<(String, String)>
+}
+
+impl Gateway {
+    // http://HOST[:PORT][/PATH], only plain HTTP
+    pub fn new(url: &str, job: &str, labels: &[String]) -> Result<Self, Error> {
+        let rest = url.strip_prefix("http://").ok_or_else(|| format_err!("{} is not an http:// URL", url))?;
+        let (authority, path) = match rest.find('/') {
+            Some(slash) => (&rest[..slash], rest[slash..].trim_end_matches('/').to_owned()),
+            None => (rest, String::new())
+        };
+        let (host, port) = match authority.rsplit_once(':') {
+            Some((host, port)) => (host, port.parse().map_err(|_| format_err!("invalid port in {}", url))?),
+            None => (authority, 9091)
+        };
+        if host.is_empty() {
+            bail!("no host in {}", url);
+        }
+        let labels = labels
+            .iter()
+            .map(|label| match label.split_once('=') {
+                Some((name, value)) if valid_name(name) => Ok((name.to_owned(), value.to_owned())),
+                _ => Err(format_err!("expected NAME=VALUE with a Prometheus label name, got {}", label))
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+        Ok(Gateway { host: host.to_owned(), port, path, job: job.to_owned(), labels })
+    }
+
+    // PUT of the text exposition format to /metrics/job/<job>
+    pub fn push(&self, results: &BenchResults) -> Result<(), Error> {
+        let body = exposition(results, &self.labels);
+        let mut stream = TcpStream::connect((self.host.as_str(), self.port))?;
+        stream.set_read_timeout(Some(Duration::from_secs(30)))?;
+        write!(stream, "PUT {}/metrics/job/{} HTTP/1.1\r\nHost: {}:{}\r\nContent-Type: text/plain; version=0.0.4\r\n\
+                        Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+               self.path, percent_encode(&self.job), self.host, self.port, body.len(), body)?;
+        let mut response = String::new();
+        stream.read_to_string(&mut response)?;
+        let status = response.lines().next().unwrap_or_default();
+        match status.split_whitespace().nth(1) {
+            Some(code) if code.starts_with('2') => Ok(()),
+            _ => bail!("pushgateway answered {}", if status.is_empty() { "nothing" } else { status })
+        }
+    }
+}
+
+fn valid_name(name: &str) -> bool {
+    !name.is_empty()
+        && !name.starts_with(|c: char| c.is_ascii_digit())
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+fn percent_encode(segment: &str) -> String {
+    segment.bytes().map(|byte| match byte {
+        b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' => (byte as char).to_string(),
+        _ => format!("%{:02X}", byte)
+    }).collect()
+}
+
+fn escape(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+// Labels of a benchmark: its name and kind, the parser or codec it measures and the input file as
+// the dataset, e.g. compress:zstd:19 on tweets.json.gz has codec="zstd", dataset="tweets".
+fn labels(result: &BenchResult, dataset: &str) -> Vec<(&'static str, String)> {
+    let mut labels = vec![("benchmark", result.name.clone()), ("kind", result.kind.clone()), ("dataset", dataset.to_owned())];
+    let first = result.parameters.first().cloned().unwrap_or_default();
+    match result.kind.as_str() {
+        "parse" | "parallel" | "extract" | "canonicalize" => labels.push(("parser", first)),
+        "compress" | "decode" => labels.push(("codec", first)),
+        _ => {}
+    }
+    labels
+}
+
+// Prometheus text exposition of the results, one gauge family per metric with a sample per
+// benchmark. Failed benchmarks have no results and are left out.
+pub fn exposition(results: &BenchResults, extra: &[(String, String)]) -> String {
+    let file_name = Path::new(&results.input).file_name().map_or(results.input.clone(), |name| name.to_string_lossy().into_owned());
+    let dataset = file_name.split('.').next().unwrap_or_default().to_owned();
+    let metrics: [(&str, &str, fn(&BenchResult) -> Option<f64>); 8] = [
+        ("elapsed_seconds", "median time of the measured runs", |result| Some(result.elapsed_ms / 1000.0)),
+        ("stddev_seconds", "standard deviation of the measured runs", |result| Some(result.stats.stddev_ms / 1000.0)),
+        ("records_per_second", "records per second of the median run", |result| Some(result.records_per_second)),
+        ("input_bytes_per_second", "input file bytes per second of the median run", |result| Some(result.input_bytes_per_second)),
+        ("errors", "records that failed", |result| Some(result.errors as f64)),
+        ("peak_heap_bytes", "highest heap use of the last measured run", |result| Some(result.allocations.peak_bytes as f64)),
+        ("allocations", "allocations of the last measured run", |result| Some(result.allocations.allocations as f64)),
+        ("joules", "energy of the median run, where measured", |result| result.joules)
+    ];
+    let mut text = String::new();
+    for (name, help, value) in metrics.iter() {
+        let samples: Vec<(&BenchResult, f64)> = results.results.iter().filter_map(|result| Some((result, value(result)?))).collect();
+        if samples.is_empty() {
+            continue;
+        }
+        writeln!(text, "# HELP json_benchmarks_{} {}", name, help).unwrap();
+        writeln!(text, "# TYPE json_benchmarks_{} gauge", name).unwrap();
+        for (result, value) in samples {
+            let labels: Vec<String> = labels(result, &dataset)
+                .into_iter()
+                .map(|(name, value)| (name.to_owned(), value))
+                .chain(extra.iter().cloned())
+                .map(|(name, value)| format!("{}=\"{}\"", name, escape(&value)))
+                .collect();
+            writeln!(text, "json_benchmarks_{}{{{}}} {}", name, labels.join(","), value).unwrap();
+        }
+    }
+    text
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::time::Duration;
+    use crate::io::Decoder;
+    use crate::measure::Stats;
+
+    #[test]
+    fn test_exposition() {
+        let gateway = Gateway::new("http://localhost/prefix/", "bench", &["instance=ci-1".to_owned()]).unwrap();
+        assert_eq!((gateway.host.as_str(), gateway.port, gateway.path.as_str()), ("localhost", 9091, "/prefix"));
+        assert_eq!(Gateway::new("http://gateway:8080", "bench", &[]).unwrap().port, 8080);
+        assert!(Gateway::new("https://gateway", "bench", &[]).is_err());
+        assert!(Gateway::new("http://gateway", "bench", &["1st=a".to_owned()]).is_err());
+        assert_eq!(percent_encode("json benchmarks"), "json%20benchmarks");
+
+        let result = BenchResult {
+            name: "compress:zstd:19".to_owned(),
+            kind: "compress".to_owned(),
+            parameters: vec!["zstd".to_owned(), "19".to_owned()],
+            records: 10,
+            errors: 0,
+            elapsed_ms: 1500.0,
+            records_per_second: 6.5,
+            input_bytes_per_second: 100.0,
+            decompressed_bytes_per_second: 400.0,
+            joules: None,
+            allocations: Default::default(),
+            latency: None,
+            passes: 1,
+            checksum: 0,
+            samples_ms: Vec::new(),
+            perf: None,
+            positions: Vec::new(),
+            compression_ratio: None,
+            stats: Stats::of(&[Duration::from_millis(1500)]).unwrap()
+        };
+        let results = BenchResults {
+            input: "data/tweets.json.gz".to_owned(),
+            input_bytes: 1000,
+            decoder: Decoder::Flate2,
+            warmup: 0,
+            runs: 1,
+            results: vec![result],
+            failed: Default::default()
+        };
+        let text = exposition(&results, &gateway.labels);
+        assert!(text.contains("# TYPE json_benchmarks_elapsed_seconds gauge\n"), "{}", text);
+        assert!(text.contains("json_benchmarks_elapsed_seconds{benchmark=\"compress:zstd:19\",kind=\"compress\",dataset=\"tweets\",codec=\"zstd\",instance=\"ci-1\"} 1.5\n"), "{}", text);
+        assert!(!text.contains("joules"));
+        assert_eq!(escape("a\"b\\"), "a\\\"b\\\\");
+    }
+}
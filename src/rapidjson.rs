@@ -0,0 +1,35 @@
+use std::os::raw::c_char;
+use failure::{Error, bail};
+
+
+// RapidJSON through the shim of shim/rapidjson.cpp, built by build.rs with the rapidjson feature.
+extern "C" {
+    fn rapidjson_shim_dom_size(data: *const c_char, len: usize) -> i64;
+    fn rapidjson_shim_sax_size(data: *const c_char, len: usize) -> i64;
+}
+
+// Parses a document with RapidJSON, into a Document or as SAX events only, returning its size as
+// sink::json_size counts it, so its checksum matches the one of the Rust parsers.
+pub fn parse_size(input: &[u8], sax: bool) -> Result<u64, Error> {
+    let (data, len) = (input.as_ptr() as *const c_char, input.len());
+    let size = unsafe { if sax { rapidjson_shim_sax_size(data, len) } else { rapidjson_shim_dom_size(data, len) } };
+    if size < 0 {
+        bail!("RapidJSON could not parse the document");
+    }
+    Ok(size as u64)
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_rapidjson() {
+        for sax in &[false, true] {
+            assert_eq!(parse_size(br#"{"a": 1, "b": [true, {"c": null}], "d": {}}"#, *sax).unwrap(), 6);
+            assert_eq!(parse_size(b"[]", *sax).unwrap(), 0);
+            assert!(parse_size(b"{\"a\":", *sax).is_err());
+        }
+    }
+}
@@ -0,0 +1,250 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use json::JsonValue;
+use avro_rs::Schema;
+use avro_rs::schema::SchemaKind;
+use failure::Error;
+use crate::avro::{json_kind, json_to_avro};
+use crate::convert::{keep_location, Location, RecordWriter};
+use crate::parser::serde_to_json;
+
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct FieldRepairs {
+    // missing field filled from the schema default, or with null for nullable fields
+    pub defaulted: usize,
+    // field not in the schema, removed
+    pub dropped: usize,
+    // numeric string parsed into the number the schema asks for
+    pub coerced: usize
+}
+
+impl FieldRepairs {
+    pub fn total(&self) -> usize {
+        self.defaulted + self.dropped + self.coerced
+    }
+}
+
+
+#[derive(Debug, Default)]
+pub struct RepairReport {
+    pub records: usize,
+    pub repaired_records: usize,
+    // still not convertible after the repairs, skipped
+    pub unrepairable_records: usize,
+    pub unrepairable_at: Vec<Location>,
+    pub fields: BTreeMap<String, FieldRepairs>
+}
+
+impl RepairReport {
+    fn field(&mut self, path: &str) -> &mut FieldRepairs {
+        self.fields.entry(path.to_owned()).or_insert_with(FieldRepairs::default)
+    }
+
+    fn merge(&mut self, other: RepairReport) {
+        for (path, repairs) in other.fields {
+            let field = self.field(&path);
+            field.defaulted += repairs.defaulted;
+            field.dropped += repairs.dropped;
+            field.coerced += repairs.coerced;
+        }
+    }
+
+    fn repairs(&self) -> usize {
+        self.fields.values().map(FieldRepairs::total).sum()
+    }
+}
+
+impl fmt::Display for RepairReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "Records: {}", self.records)?;
+        writeln!(f, "Repaired: {}", self.repaired_records)?;
+        writeln!(f, "Unrepairable: {}", self.unrepairable_records)?;
+        for location in &self.unrepairable_at {
+            writeln!(f, "  at {}", location)?;
+        }
+
+        let mut fields: Vec<(&String, &FieldRepairs)> = self.fields.iter().filter(|(_, r)| r.total() > 0).collect();
+        fields.sort_by(|a, b| b.1.total().cmp(&a.1.total()));
+        if !fields.is_empty() {
+            writeln!(f, "{:<60} {:>10} {:>10} {:>10}", "field", "defaulted", "dropped", "coerced")?;
+        }
+        for (path, r) in fields {
+            writeln!(f, "{:<60} {:>10} {:>10} {:>10}", path, r.defaulted, r.dropped, r.coerced)?;
+        }
+        Ok(())
+    }
+}
+
+
+// Returns the record changed only where that is safe: defaults for missing fields, unknown fields
+// dropped, numeric strings parsed. Anything else is left for the conversion to reject.
+pub fn repair(json: &JsonValue, schema: &Schema, report: &mut RepairReport) -> JsonValue {
+    repair_value(json, schema, "", report)
+}
+
+fn repair_value(json: &JsonValue, schema: &Schema, path: &str, report: &mut RepairReport) -> JsonValue {
+    match (json, schema) {
+        (_, Schema::Union(union)) => {
+            let sk = json_kind(json);
+            if let Some(variant) = union.variants().iter().find(|variant| SchemaKind::from(*variant) == sk) {
+                return repair_value(json, variant, path, report);
+            }
+            // first variant the repaired value converts to, with only that variant's repairs counted
+            for variant in union.variants() {
+                let mut attempt = RepairReport::default();
+                let repaired = repair_value(json, variant, path, &mut attempt);
+                if json_to_avro(&repaired, variant).is_ok() {
+                    report.merge(attempt);
+                    return repaired;
+                }
+            }
+            json.clone()
+        },
+        (JsonValue::Object(_), Schema::Record {fields, lookup, ..}) => {
+            let mut repaired = JsonValue::new_object();
+            for field in fields {
+                let field_path = join(path, &field.name);
+                if json.has_key(&field.name) {
+                    repaired[field.name.as_str()] = repair_value(&json[field.name.as_str()], &field.schema, &field_path, report);
+                } else if let Some(default) = &field.default {
+                    repaired[field.name.as_str()] = serde_to_json(default.clone());
+                    report.field(&field_path).defaulted += 1;
+                } else if is_nullable(&field.schema) {
+                    repaired[field.name.as_str()] = JsonValue::Null;
+                    report.field(&field_path).defaulted += 1;
+                }
+            }
+            for (key, _) in json.entries() {
+                if !lookup.contains_key(key) {
+                    report.field(&join(path, key)).dropped += 1;
+                }
+            }
+            repaired
+        },
+        (JsonValue::Object(_), Schema::Map(values_schema)) => {
+            let values_path = format!("{}{{}}", path);
+            let mut repaired = JsonValue::new_object();
+            for (key, value) in json.entries() {
+                repaired[key] = repair_value(value, values_schema, &values_path, report);
+            }
+            repaired
+        },
+        (JsonValue::Array(items), Schema::Array(items_schema)) => {
+            let items_path = format!("{}[]", path);
+            JsonValue::Array(items.iter().map(|item| repair_value(item, items_schema, &items_path, report)).collect())
+        },
+        (JsonValue::String(_), Schema::Long) | (JsonValue::Short(_), Schema::Long) |
+        (JsonValue::String(_), Schema::Int) | (JsonValue::Short(_), Schema::Int) => {
+            match json.as_str().unwrap().trim().parse::<i64>() {
+                Ok(number) => {
+                    report.field(path).coerced += 1;
+                    JsonValue::from(number)
+                },
+                Err(_) => json.clone()
+            }
+        },
+        (JsonValue::String(_), Schema::Double) | (JsonValue::Short(_), Schema::Double) |
+        (JsonValue::String(_), Schema::Float) | (JsonValue::Short(_), Schema::Float) => {
+            match json.as_str().unwrap().trim().parse::<f64>() {
+                Ok(number) if number.is_finite() => {
+                    report.field(path).coerced += 1;
+                    JsonValue::from(number)
+                },
+                _ => json.clone()
+            }
+        },
+        _ => json.clone()
+    }
+}
+
+fn is_nullable(schema: &Schema) -> bool {
+    match schema {
+        Schema::Null => true,
+        Schema::Union(union) => union.variants().iter().any(|variant| *variant == Schema::Null),
+        _ => false
+    }
+}
+
+fn join(path: &str, name: &str) -> String {
+    if path.is_empty() { name.to_owned() } else { format!("{}.{}", path, name) }
+}
+
+
+// Repairs every record in front of another writer. Records the inner writer still rejects are
+// counted and skipped instead of failing the conversion.
+pub struct RepairingWriter<'w, 's> {
+    inner: &'w mut dyn RecordWriter,
+    schema: &'s Schema,
+    location: Location,
+    pub report: RepairReport
+}
+
+impl<'w, 's> RepairingWriter<'w, 's> {
+    pub fn new(inner: &'w mut dyn RecordWriter, schema: &'s Schema) -> Self {
+        RepairingWriter { inner, schema, location: Location::default(), report: RepairReport::default() }
+    }
+}
+
+impl<'w, 's> RecordWriter for RepairingWriter<'w, 's> {
+    fn write(&mut self, record: &JsonValue) -> Result<(), Error> {
+        let mut repairs = RepairReport::default();
+        let repaired = repair(record, self.schema, &mut repairs);
+        self.report.records += 1;
+        if repairs.repairs() > 0 {
+            self.report.repaired_records += 1;
+        }
+        self.report.merge(repairs);
+        // checked up front, a writer failing half way through a record may leave partial output
+        if json_to_avro(&repaired, self.schema).is_err() {
+            self.report.unrepairable_records += 1;
+            keep_location(&mut self.report.unrepairable_at, &self.location);
+            return Ok(());
+        }
+        self.inner.write(&repaired)
+    }
+
+    fn locate(&mut self, location: &Location) {
+        self.location = location.clone();
+        self.inner.locate(location);
+    }
+
+    fn full(&self) -> bool {
+        self.inner.full()
+    }
+
+    fn finish(&mut self) -> Result<(), Error> {
+        self.inner.finish()
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_repair() {
+        let schema = Schema::parse_str(r#"{"name":"t","type":"record","fields":[
+            {"name":"id","type":"long"},
+            {"name":"lang","type":"string","default":"en"},
+            {"name":"score","type":["null","double"]},
+            {"name":"tags","type":{"type":"array","items":"long"}}
+        ]}"#).unwrap();
+        let mut report = RepairReport::default();
+        let record = json::parse(r#"{"id":"42","score":"1.5","tags":["1",2],"extra":true}"#).unwrap();
+        let repaired = repair(&record, &schema, &mut report);
+        assert_eq!(repaired.dump(), r#"{"id":42,"lang":"en","score":1.5,"tags":[1,2]}"#);
+        assert!(json_to_avro(&repaired, &schema).is_ok());
+        assert_eq!(report.fields["id"].coerced, 1);
+        assert_eq!(report.fields["lang"].defaulted, 1);
+        assert_eq!(report.fields["score"].coerced, 1);
+        assert_eq!(report.fields["tags[]"].coerced, 1);
+        assert_eq!(report.fields["extra"].dropped, 1);
+
+        let unfixable = json::parse(r#"{"id":"abc","tags":[]}"#).unwrap();
+        let repaired = repair(&unfixable, &schema, &mut report);
+        assert!(json_to_avro(&repaired, &schema).is_err());
+        assert_eq!(repaired["score"], JsonValue::Null);
+    }
+}
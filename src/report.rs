@@ -0,0 +1,251 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use crate::bench::{BenchResult, BenchResults, LINE_KINDS};
+
+
+// Markdown tables for the README, one per benchmark kind so parsers are compared with parsers and
+// codecs with codecs. Rows are sorted fastest first, speedup is the fastest time over the row time.
+pub fn markdown(results: &BenchResults) -> String {
+    let mut kinds: BTreeMap<&str, Vec<&BenchResult>> = BTreeMap::new();
+    for result in &results.results {
+        kinds.entry(result.kind.as_str()).or_insert_with(Vec::new).push(result);
+    }
+
+    let mut text = String::new();
+    writeln!(text, "Input `{}` ({} bytes, {} decoder), median of {} runs after {} warmup.",
+             results.input, results.input_bytes, results.decoder.name(), results.runs, results.warmup).unwrap();
+    for (kind, mut rows) in kinds {
+        rows.sort_by(|a, b| a.elapsed_ms.partial_cmp(&b.elapsed_ms).unwrap());
+        let fastest = rows[0].elapsed_ms.max(0.001);
+        writeln!(text).unwrap();
+        writeln!(text, "### {}", kind).unwrap();
+        writeln!(text).unwrap();
+        // speed without the ratio is half the story for codecs
+        let ratios = kind == "compress";
+        writeln!(text, "| benchmark | records | time (ms) | stddev (ms) | records/s | MB/s | speedup |{}",
+                 if ratios { " ratio |" } else { "" }).unwrap();
+        writeln!(text, "|---|---:|---:|---:|---:|---:|---:|{}", if ratios { "---:|" } else { "" }).unwrap();
+        for row in rows {
+            write!(text, "| {} | {} | {:.1} | {:.1} | {:.0} | {:.1} | {:.2}x |",
+                   row.name, row.records, row.elapsed_ms, row.stats.stddev_ms, row.records_per_second,
+                   row.input_bytes_per_second / 1_000_000.0, fastest / row.elapsed_ms.max(0.001)).unwrap();
+            if ratios {
+                write!(text, " {} |", row.compression_ratio.map_or("-".to_owned(), |ratio| format!("{:.2}x", ratio))).unwrap();
+            }
+            writeln!(text).unwrap();
+        }
+    }
+    if !results.failed.is_empty() {
+        writeln!(text).unwrap();
+        for (name, error) in &results.failed {
+            writeln!(text, "- {} failed: {}", name, error).unwrap();
+        }
+    }
+    text
+}
+
+
+// Plain text counterpart for the terminal: every benchmark in one table, grouped by kind and
+// fastest first within a kind, the speedup relative to the fastest of the same kind.
+pub fn matrix(results: &BenchResults) -> String {
+    let mut rows: Vec<&BenchResult> = results.results.iter().collect();
+    rows.sort_by(|a, b| a.kind.cmp(&b.kind).then(a.elapsed_ms.partial_cmp(&b.elapsed_ms).unwrap()));
+    let mut fastest: BTreeMap<&str, f64> = BTreeMap::new();
+    for row in &rows {
+        fastest.entry(row.kind.as_str()).or_insert_with(|| row.elapsed_ms.max(0.001));
+    }
+
+    let mut text = String::new();
+    writeln!(text, "{:<28} {:>10} {:>10} {:>12} {:>9} {:>10} {:>8} {:>8}",
+             "benchmark", "records", "time (ms)", "records/s", "MB/s", "heap (MiB)", "errors", "speedup").unwrap();
+    for row in rows {
+        writeln!(text, "{:<28} {:>10} {:>10.1} {:>12.0} {:>9.1} {:>10.1} {:>8} {:>7.2}x",
+                 row.name, row.records, row.elapsed_ms, row.records_per_second, row.decompressed_bytes_per_second / 1_000_000.0,
+                 row.allocations.peak_bytes as f64 / 1048576.0, row.errors, fastest[row.kind.as_str()] / row.elapsed_ms.max(0.001)).unwrap();
+    }
+    for (name, error) in &results.failed {
+        writeln!(text, "{:<28} failed: {}", name, error).unwrap();
+    }
+    text
+}
+
+
+// The parallel:PARSER:N results of bench --scaling against the single threaded one: speedup is the
+// time of one thread over the time of N, efficiency the speedup per thread, 100% for linear scaling.
+pub fn scaling(results: &BenchResults) -> String {
+    let mut rows: Vec<(&BenchResult, usize)> = results.results
+        .iter()
+        .filter(|row| row.kind == "parallel")
+        .filter_map(|row| row.parameters.get(1).and_then(|threads| threads.parse().ok()).map(|threads| (row, threads)))
+        .collect();
+    rows.sort_by_key(|(row, threads)| (row.parameters[0].clone(), *threads));
+
+    let mut text = String::new();
+    writeln!(text, "{:<28} {:>8} {:>10} {:>12} {:>8} {:>10}", "benchmark", "threads", "time (ms)", "records/s", "speedup", "efficiency").unwrap();
+    for (row, threads) in &rows {
+        let single = rows.iter().find(|(other, threads)| *threads == 1 && other.parameters[0] == row.parameters[0]);
+        let speedup = single.map(|(single, _)| single.elapsed_ms / row.elapsed_ms.max(0.001));
+        match speedup {
+            Some(speedup) => writeln!(text, "{:<28} {:>8} {:>10.1} {:>12.0} {:>7.2}x {:>9.0}%", row.name, threads, row.elapsed_ms,
+                                      row.records_per_second, speedup, speedup / *threads as f64 * 100.0).unwrap(),
+            None => writeln!(text, "{:<28} {:>8} {:>10.1} {:>12.0} {:>8} {:>10}", row.name, threads, row.elapsed_ms, row.records_per_second, "-", "-").unwrap()
+        }
+    }
+    text
+}
+
+
+// The compress:CODEC:LEVEL results of bench --levels, by codec and level, for picking the level of
+// an output codec: MB/s of uncompressed input against the ratio it compressed to.
+pub fn levels(results: &BenchResults) -> String {
+    let mut rows: Vec<(&BenchResult, i32)> = results.results
+        .iter()
+        .filter(|row| row.kind == "compress")
+        .filter_map(|row| row.parameters.get(1).and_then(|level| level.parse().ok()).map(|level| (row, level)))
+        .collect();
+    rows.sort_by_key(|(row, level)| (row.parameters[0].clone(), *level));
+
+    let mut text = String::new();
+    writeln!(text, "{:<10} {:>6} {:>10} {:>9} {:>8}", "codec", "level", "time (ms)", "MB/s", "ratio").unwrap();
+    for (row, level) in rows {
+        writeln!(text, "{:<10} {:>6} {:>10.1} {:>9.1} {:>8}", row.parameters[0], level, row.elapsed_ms,
+                 row.decompressed_bytes_per_second / 1_000_000.0, row.compression_ratio.map_or("-".to_owned(), |ratio| format!("{:.2}x", ratio))).unwrap();
+    }
+    text
+}
+
+
+// The benchmarks that read the input line by line net of the read benchmark, which does only
+// that: what is left of their median time is parsing and the rest of their own work. None without
+// a read result to subtract.
+pub fn net_of_read(results: &BenchResults) -> Option<String> {
+    let read = results.results.iter().find(|row| row.name == "read")?;
+    let mut text = String::new();
+    writeln!(text, "{:<32} {:>10} {:>10} {:>8} {:>14}", "benchmark", "time (ms)", "net (ms)", "read", "net records/s").unwrap();
+    for row in results.results.iter().filter(|row| LINE_KINDS.contains(&row.kind.as_str())) {
+        let net_ms = (row.elapsed_ms - read.elapsed_ms).max(0.0);
+        writeln!(text, "{:<32} {:>10.1} {:>10.1} {:>7.0}% {:>14.0}", row.name, row.elapsed_ms, net_ms,
+                 read.elapsed_ms.min(row.elapsed_ms) / row.elapsed_ms.max(0.001) * 100.0, row.records as f64 / (net_ms / 1000.0).max(1e-9)).unwrap();
+    }
+    Some(text)
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::time::Duration;
+    use crate::io::Decoder;
+    use crate::measure::Stats;
+
+    fn result(name: &str, elapsed_ms: u64) -> BenchResult {
+        let mut parts = name.split(':').map(|part| part.to_owned());
+        BenchResult {
+            name: name.to_owned(),
+            kind: parts.next().unwrap(),
+            parameters: parts.collect(),
+            records: 100,
+            errors: 0,
+            elapsed_ms: elapsed_ms as f64,
+            records_per_second: 100000.0 / elapsed_ms as f64,
+            input_bytes_per_second: 1e9 / elapsed_ms as f64,
+            decompressed_bytes_per_second: 1e10 / elapsed_ms as f64,
+            joules: None,
+            allocations: Default::default(),
+            latency: None,
+            passes: 1,
+            checksum: 0,
+            samples_ms: Vec::new(),
+            perf: None,
+            positions: Vec::new(),
+            compression_ratio: None,
+            stats: Stats::of(&[Duration::from_millis(elapsed_ms)]).unwrap()
+        }
+    }
+
+    #[test]
+    fn test_markdown() {
+        let results = BenchResults {
+            input: "tweets.json.gz".to_owned(),
+            input_bytes: 1_000_000,
+            decoder: Decoder::Flate2,
+            warmup: 1,
+            runs: 5,
+            results: vec![result("parse:serde", 200), result("parse:simd", 100),
+                          BenchResult { compression_ratio: Some(8.5), ..result("compress:zstd:3", 50) }],
+            failed: BTreeMap::new()
+        };
+        let text = markdown(&results);
+        assert!(text.contains("| records/s | MB/s | speedup | ratio |"));
+        assert!(text.contains("| compress:zstd:3 | 100 | 50.0 | 0.0 | 2000 | 20.0 | 1.00x | 8.50x |"), "{}", text);
+        let compress = text.find("### compress").unwrap();
+        let parse = text.find("### parse").unwrap();
+        assert!(compress < parse);
+        assert!(text.contains("| parse:simd | 100 | 100.0 | 0.0 | 1000 | 10.0 | 1.00x |"));
+        assert!(text.contains("| parse:serde | 100 | 200.0 | 0.0 | 500 | 5.0 | 0.50x |"));
+        assert!(text.find("parse:simd").unwrap() < text.find("parse:serde").unwrap());
+
+        let text = matrix(&results);
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines.len(), 4);
+        assert!(lines[1].starts_with("compress:zstd:3"));
+        assert!(lines[2].starts_with("parse:simd") && lines[2].ends_with("1.00x"));
+        assert!(lines[3].starts_with("parse:serde") && lines[3].ends_with("0.50x"));
+    }
+
+    #[test]
+    fn test_scaling() {
+        let results = BenchResults {
+            input: "tweets.json.gz".to_owned(),
+            input_bytes: 1_000_000,
+            decoder: Decoder::Flate2,
+            warmup: 0,
+            runs: 1,
+            results: vec![result("parallel:json:4", 100), result("parse:json", 300), result("parallel:json:1", 400), result("parallel:json:2", 250)],
+            failed: BTreeMap::new()
+        };
+        let text = scaling(&results);
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines.len(), 4);
+        assert!(lines[1].starts_with("parallel:json:1") && lines[1].ends_with("1.00x       100%"), "{}", text);
+        assert!(lines[2].ends_with("1.60x        80%"));
+        assert!(lines[3].ends_with("4.00x       100%"));
+    }
+
+    #[test]
+    fn test_levels() {
+        let results = BenchResults {
+            input: "tweets.json.gz".to_owned(),
+            input_bytes: 1_000_000,
+            decoder: Decoder::Flate2,
+            warmup: 1,
+            runs: 5,
+            results: vec![BenchResult { compression_ratio: Some(9.0), ..result("compress:zstd:19:64k", 1000) },
+                          BenchResult { compression_ratio: Some(6.0), ..result("compress:zstd:3:64k", 50) },
+                          result("parse:simd", 100)],
+            failed: BTreeMap::new()
+        };
+        let lines: Vec<String> = levels(&results).lines().map(|line| line.split_whitespace().collect::<Vec<_>>().join(" ")).collect();
+        assert_eq!(lines, vec!["codec level time (ms) MB/s ratio", "zstd 3 50.0 200.0 6.00x", "zstd 19 1000.0 10.0 9.00x"]);
+    }
+
+    #[test]
+    fn test_net_of_read() {
+        let mut results = BenchResults {
+            input: "tweets.json.gz".to_owned(),
+            input_bytes: 1_000_000,
+            decoder: Decoder::Flate2,
+            warmup: 0,
+            runs: 1,
+            results: vec![result("parse:json", 300), result("compress:zstd:3", 50)],
+            failed: BTreeMap::new()
+        };
+        assert!(net_of_read(&results).is_none());
+        results.results.push(result("read", 100));
+        let text = net_of_read(&results).unwrap();
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines.len(), 2, "{}", text);
+        // 100 records in the 200 ms left
+        assert!(lines[1].starts_with("parse:json") && lines[1].ends_with("300.0      200.0      33%            500"), "{}", text);
+    }
+}
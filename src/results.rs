@@ -0,0 +1,148 @@
+use std::fs::File;
+use std::sync::Arc;
+use arrow::array::{ArrayRef, Float64Array, Int32Array, StringArray, UInt64Array};
+use arrow::datatypes::{DataType, Field, Schema as ArrowSchema};
+use arrow::record_batch::RecordBatch;
+use parquet::arrow::ArrowWriter;
+use serde::Serialize;
+use failure::Error;
+use crate::batch::{BatchReport, BenchmarkReport};
+
+
+// One row per benchmark run, with the run parameters split out of the benchmark name so they can
+// be grouped on directly.
+pub fn results_table(reports: &[(String, BatchReport)]) -> Result<RecordBatch, Error> {
+    let rows: Vec<(&str, &BatchReport, &BenchmarkReport)> = reports
+        .iter()
+        .flat_map(|(source, report)| report.benchmarks.iter().map(move |b| (source.as_str(), report, b)))
+        .collect();
+    let names: Vec<Vec<&str>> = rows.iter().map(|(_, _, b)| b.name.split(':').collect()).collect();
+
+    let column_str = |f: &dyn Fn(usize) -> Option<String>| -> ArrayRef {
+        Arc::new((0..rows.len()).map(f).collect::<StringArray>())
+    };
+    let column_u64 = |f: &dyn Fn(usize) -> u64| -> ArrayRef {
+        Arc::new(UInt64Array::from((0..rows.len()).map(f).collect::<Vec<u64>>()))
+    };
+
+    let fields = vec![
+        Field::new("source", DataType::Utf8, false),
+        Field::new("input", DataType::Utf8, false),
+        Field::new("seed", DataType::UInt64, false),
+        Field::new("benchmark", DataType::Utf8, false),
+        Field::new("kind", DataType::Utf8, false),
+        Field::new("backend", DataType::Utf8, true),
+        Field::new("level", DataType::Int32, true),
+        Field::new("cache", DataType::Utf8, false),
+        Field::new("decoder", DataType::Utf8, false),
+        Field::new("status", DataType::Utf8, false),
+        Field::new("records", DataType::UInt64, false),
+        Field::new("errors", DataType::UInt64, false),
+        Field::new("elapsed_ms", DataType::UInt64, false),
+        Field::new("read_ms", DataType::UInt64, false),
+        Field::new("process_ms", DataType::UInt64, false),
+        Field::new("joules", DataType::Float64, true),
+        Field::new("records_per_second", DataType::Float64, false),
+        Field::new("p50_ns", DataType::UInt64, true),
+        Field::new("p99_ns", DataType::UInt64, true)
+    ];
+    let columns: Vec<ArrayRef> = vec![
+        column_str(&|i| Some(rows[i].0.to_owned())),
+        column_str(&|i| Some(rows[i].1.input.clone())),
+        column_u64(&|i| rows[i].1.seed),
+        column_str(&|i| Some(rows[i].2.name.clone())),
+        column_str(&|i| Some(names[i][0].to_owned())),
+        column_str(&|i| names[i].get(1).map(|backend| backend.to_string())),
+        Arc::new(names.iter().map(|parts| parts.get(2).and_then(|level| level.parse().ok())).collect::<Int32Array>()),
+        column_str(&|i| serde_name(rows[i].2.cache)),
+        column_str(&|i| serde_name(rows[i].2.decoder)),
+        column_str(&|i| serde_name(rows[i].2.status)),
+        column_u64(&|i| rows[i].2.records as u64),
+        column_u64(&|i| rows[i].2.errors as u64),
+        column_u64(&|i| rows[i].2.elapsed_ms as u64),
+        column_u64(&|i| rows[i].2.read_ms as u64),
+        column_u64(&|i| rows[i].2.process_ms as u64),
+        Arc::new(rows.iter().map(|(_, _, b)| b.joules).collect::<Float64Array>()),
+        Arc::new(rows.iter().map(|(_, _, b)| Some(b.records as f64 * 1000.0 / b.elapsed_ms.max(1) as f64)).collect::<Float64Array>()),
+        Arc::new(rows.iter().map(|(_, _, b)| percentile_ns(&b.latency_log2_ns, 0.5)).collect::<UInt64Array>()),
+        Arc::new(rows.iter().map(|(_, _, b)| percentile_ns(&b.latency_log2_ns, 0.99)).collect::<UInt64Array>())
+    ];
+    Ok(RecordBatch::try_new(Arc::new(ArrowSchema::new(fields)), columns)?)
+}
+
+// the same spelling as in report.json
+fn serde_name<T: Serialize>(value: T) -> Option<String> {
+    serde_json::to_value(value).ok()?.as_str().map(|name| name.to_owned())
+}
+
+// Upper bound of the log2 bucket holding the quantile, so at most 2x off.
+pub fn percentile_ns(histogram: &[usize], quantile: f64) -> Option<u64> {
+    let total: usize = histogram.iter().sum();
+    if total == 0 {
+        return None;
+    }
+    let rank = (total as f64 * quantile).ceil().max(1.0) as usize;
+    let mut seen = 0;
+    for (bucket, count) in histogram.iter().enumerate() {
+        seen += count;
+        if seen >= rank {
+            return Some(1u64 << (bucket + 1).min(63));
+        }
+    }
+    None
+}
+
+pub fn write_parquet(reports: &[(String, BatchReport)], file_path: &str) -> Result<usize, Error> {
+    let table = results_table(reports)?;
+    let mut writer = ArrowWriter::try_new(File::create(file_path)?, table.schema(), None)?;
+    writer.write(&table)?;
+    writer.close()?;
+    Ok(table.num_rows())
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use arrow::array::Array;
+    use crate::batch::Status;
+    use crate::cache::CacheState;
+
+    #[test]
+    fn test_results_table() {
+        let benchmark = |name: &str, cache| BenchmarkReport {
+            name: name.to_owned(),
+            cache,
+            decoder: Default::default(),
+            status: Status::Ok,
+            records: 1000,
+            errors: 0,
+            elapsed_ms: 500,
+            joules: None,
+            peak_heap_bytes: 0,
+            allocations: 0,
+            read_ms: 100,
+            process_ms: 400,
+            latency_log2_ns: vec![0, 0, 10, 80, 10],
+            records_per_second: 0.0,
+            mb_per_second: 0.0,
+            stats: None,
+            error: None
+        };
+        let report = BatchReport {
+            input: "tweets.json.gz".to_owned(),
+            seed: 1,
+            benchmarks: vec![benchmark("parse:serde", CacheState::Cold), benchmark("compress:zstd:3", CacheState::Warm)],
+            exit_code: 0
+        };
+        let table = results_table(&[("report.json".to_owned(), report)]).unwrap();
+        assert_eq!(table.num_rows(), 2);
+        let level = table.column_by_name("level").unwrap().as_any().downcast_ref::<Int32Array>().unwrap();
+        assert!(level.is_null(0));
+        assert_eq!(level.value(1), 3);
+        let cache = table.column_by_name("cache").unwrap().as_any().downcast_ref::<StringArray>().unwrap();
+        assert_eq!(cache.value(0), "cold");
+        let p50 = table.column_by_name("p50_ns").unwrap().as_any().downcast_ref::<UInt64Array>().unwrap();
+        assert_eq!(p50.value(0), 16);
+    }
+}
@@ -0,0 +1,156 @@
+use std::collections::BTreeMap;
+use json::JsonValue;
+use avro_rs::Schema;
+use failure::{Error, format_err};
+use crate::convert::{Location, RecordWriter};
+use crate::infer::SchemaInferrer;
+use crate::path::lookup;
+
+
+// route of records without the discriminator, or with a null, object or array there
+pub const UNROUTED: &str = "unrouted";
+
+// The discriminator value at a dotted key path, reduced to [A-Za-z0-9_] so it can name both the
+// output file and the Avro record of the route.
+pub fn route_of(record: &JsonValue, key_path: &str) -> String {
+    let value = lookup(record, key_path);
+    let route = match value {
+        JsonValue::Short(_) | JsonValue::String(_) => value.as_str().unwrap().to_owned(),
+        JsonValue::Number(_) | JsonValue::Boolean(_) => value.dump(),
+        _ => return UNROUTED.to_owned()
+    };
+    let mut name: String = route.chars().map(|c| if c.is_ascii_alphanumeric() { c } else { '_' }).collect();
+    if name.is_empty() || name.starts_with(|c: char| c.is_ascii_digit()) {
+        name.insert(0, '_');
+    }
+    name
+}
+
+pub fn route_path(output_prefix: &str, route: &str, extension: &str) -> String {
+    format!("{}-{}.{}", output_prefix, route, extension)
+}
+
+
+// Schema inference with one SchemaInferrer per route, so every event kind gets its own schema
+// instead of one union of all of them.
+pub struct RoutingInferrer {
+    key_path: String,
+    name: String,
+    pub routes: BTreeMap<String, SchemaInferrer>
+}
+
+impl RoutingInferrer {
+    pub fn new(key_path: &str, name: &str) -> Self {
+        RoutingInferrer { key_path: key_path.to_owned(), name: name.to_owned(), routes: BTreeMap::new() }
+    }
+
+    pub fn into_schemas(self) -> Result<BTreeMap<String, Schema>, Error> {
+        self.routes
+            .into_iter()
+            .map(|(route, inferrer)| Ok((route, inferrer.into_schema()?)))
+            .collect()
+    }
+}
+
+impl RecordWriter for RoutingInferrer {
+    fn write(&mut self, record: &JsonValue) -> Result<(), Error> {
+        let route = route_of(record, &self.key_path);
+        let name = format!("{}_{}", self.name, route);
+        self.routes
+            .entry(route)
+            .or_insert_with(|| SchemaInferrer::new(&name))
+            .update(record)
+    }
+
+    fn finish(&mut self) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+
+// Sends every record to the writer of its route. A record of a route without a writer fails the
+// conversion, the routes are expected to come from a RoutingInferrer pass over the same input.
+pub struct RoutingWriter<'w> {
+    key_path: String,
+    routes: BTreeMap<String, Box<dyn RecordWriter + 'w>>,
+    pub records: BTreeMap<String, usize>,
+    // of the record being routed, passed on to its route only
+    location: Location
+}
+
+impl<'w> RoutingWriter<'w> {
+    pub fn new(key_path: &str, routes: BTreeMap<String, Box<dyn RecordWriter + 'w>>) -> Self {
+        RoutingWriter { key_path: key_path.to_owned(), routes, records: BTreeMap::new(), location: Location::default() }
+    }
+}
+
+impl<'w> RecordWriter for RoutingWriter<'w> {
+    fn write(&mut self, record: &JsonValue) -> Result<(), Error> {
+        let route = route_of(record, &self.key_path);
+        let writer = self.routes.get_mut(&route).ok_or_else(|| format_err!("no output for route {}", route))?;
+        writer.locate(&self.location);
+        writer.write(record)?;
+        *self.records.entry(route).or_insert(0) += 1;
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<(), Error> {
+        for writer in self.routes.values_mut() {
+            writer.finish()?;
+        }
+        Ok(())
+    }
+
+    fn locate(&mut self, location: &Location) {
+        self.location = location.clone();
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::convert::JsonLinesWriter;
+
+    #[test]
+    fn test_routing() {
+        let records: Vec<JsonValue> = vec![
+            r#"{"type":"push","repo":"a","commits":3}"#,
+            r#"{"type":"watch","repo":"b"}"#,
+            r#"{"type":"push","repo":"c","commits":1}"#,
+            r#"{"repo":"d"}"#
+        ].into_iter().map(|record| json::parse(record).unwrap()).collect();
+        assert_eq!(route_of(&json::parse(r#"{"e":{"type":"pull-request"}}"#).unwrap(), "e.type"), "pull_request");
+        assert_eq!(route_of(&json::parse(r#"{"type":7}"#).unwrap(), "type"), "_7");
+
+        let mut inferrer = RoutingInferrer::new("type", "event");
+        for record in &records {
+            inferrer.write(record).unwrap();
+        }
+        let schemas = inferrer.into_schemas().unwrap();
+        assert_eq!(schemas.keys().collect::<Vec<_>>(), vec!["push", "unrouted", "watch"]);
+        match &schemas["watch"] {
+            Schema::Record { name, fields, .. } => {
+                assert_eq!(name.name, "event_watch");
+                assert_eq!(fields.len(), 2);
+            },
+            other => panic!("expected a record, got {:?}", other)
+        }
+
+        let (mut push, mut watch) = (Vec::new(), Vec::new());
+        {
+            let mut routes: BTreeMap<String, Box<dyn RecordWriter + '_>> = BTreeMap::new();
+            routes.insert("push".to_owned(), Box::new(JsonLinesWriter::new(&mut push)));
+            routes.insert("watch".to_owned(), Box::new(JsonLinesWriter::new(&mut watch)));
+            let mut writer = RoutingWriter::new("type", routes);
+            for record in &records[..3] {
+                writer.write(record).unwrap();
+            }
+            assert!(writer.write(&records[3]).is_err());
+            writer.finish().unwrap();
+            assert_eq!(writer.records["push"], 2);
+        }
+        assert_eq!(String::from_utf8(push).unwrap().lines().count(), 2);
+        assert_eq!(String::from_utf8(watch).unwrap().lines().count(), 1);
+    }
+}
@@ -0,0 +1,189 @@
+use memchr::{memchr, memchr2, memmem};
+
+
+// Finds a field's value in a raw line without parsing it: a substring search for the quoted key,
+// then a scan to the end of the value. Only right for flat records. The first key that matches
+// wins, even when it belongs to a nested object, and objects and arrays are not extracted at all.
+pub struct FieldScanner {
+    key: memmem::Finder<'static>
+}
+
+impl FieldScanner {
+    pub fn new(field: &str) -> Self {
+        FieldScanner { key: memmem::Finder::new(format!("\"{}\"", field).as_bytes()).into_owned() }
+    }
+
+    // The raw value, strings without their quotes and with escapes left as they are.
+    pub fn scan<'a>(&self, line: &'a [u8]) -> Option<&'a [u8]> {
+        for position in self.key.find_iter(line) {
+            let rest = trim_start(&line[position + self.key.needle().len()..]);
+            // the same text as a string value, not a key
+            if rest.first() != Some(&b':') {
+                continue;
+            }
+            return value(trim_start(&rest[1..]));
+        }
+        None
+    }
+}
+
+fn value(rest: &[u8]) -> Option<&[u8]> {
+    match rest.first()? {
+        b'"' => {
+            let mut end = 1;
+            loop {
+                end += memchr(b'"', &rest[end..])?;
+                let backslashes = rest[..end].iter().rev().take_while(|byte| **byte == b'\\').count();
+                if backslashes % 2 == 0 {
+                    return Some(&rest[1..end]);
+                }
+                end += 1;
+            }
+        },
+        b'{' | b'[' => None,
+        _ => {
+            let end = memchr2(b',', b'}', rest).unwrap_or_else(|| rest.len());
+            Some(trim_end(&rest[..end]))
+        }
+    }
+}
+
+
+// Finds the value at a path like user.screen_name by walking the structure of the raw line, gjson
+// style: the values before the wanted key are skipped over without being parsed, nested ones
+// included, so unlike FieldScanner a key only matches at its own level and objects and arrays can
+// be extracted. The raw value is returned, strings with their quotes.
+pub struct PathScanner {
+    keys: Vec<String>
+}
+
+impl PathScanner {
+    pub fn new(path: &str) -> Self {
+        PathScanner { keys: path.split('.').map(str::to_owned).collect() }
+    }
+
+    pub fn scan<'a>(&self, line: &'a [u8]) -> Option<&'a [u8]> {
+        let mut rest = trim_start(line);
+        for key in &self.keys {
+            rest = member(rest, key.as_bytes())?;
+        }
+        value_end(rest).map(|end| &rest[..end])
+    }
+}
+
+fn skip_whitespace(bytes: &[u8], mut at: usize) -> usize {
+    while at < bytes.len() && bytes[at].is_ascii_whitespace() {
+        at += 1;
+    }
+    at
+}
+
+// just past the closing quote of the string bytes starts with
+fn string_end(bytes: &[u8]) -> Option<usize> {
+    let mut end = 1;
+    loop {
+        end += memchr(b'"', &bytes[end..])?;
+        let backslashes = bytes[..end].iter().rev().take_while(|byte| **byte == b'\\').count();
+        if backslashes % 2 == 0 {
+            return Some(end + 1);
+        }
+        end += 1;
+    }
+}
+
+// length of the value bytes starts with
+fn value_end(bytes: &[u8]) -> Option<usize> {
+    match bytes.first()? {
+        b'"' => string_end(bytes),
+        b'{' | b'[' => {
+            let (mut depth, mut at) = (0, 0);
+            while at < bytes.len() {
+                match bytes[at] {
+                    b'"' => {
+                        at += string_end(&bytes[at..])?;
+                        continue;
+                    },
+                    b'{' | b'[' => depth += 1,
+                    b'}' | b']' => {
+                        depth -= 1;
+                        if depth == 0 {
+                            return Some(at + 1);
+                        }
+                    },
+                    _ => {}
+                }
+                at += 1;
+            }
+            None
+        },
+        _ => Some(bytes.iter().position(|byte| matches!(byte, b',' | b'}' | b']') || byte.is_ascii_whitespace()).unwrap_or_else(|| bytes.len()))
+    }
+}
+
+// what follows the key in the object bytes starts with, the start of its value
+fn member<'a>(bytes: &'a [u8], key: &[u8]) -> Option<&'a [u8]> {
+    if bytes.first() != Some(&b'{') {
+        return None;
+    }
+    let mut at = 1;
+    loop {
+        at = skip_whitespace(bytes, at);
+        // the end of the object, or not JSON
+        if bytes.get(at) != Some(&b'"') {
+            return None;
+        }
+        let end = at + string_end(&bytes[at..])?;
+        let name = &bytes[at + 1..end - 1];
+        at = skip_whitespace(bytes, end);
+        if bytes.get(at) != Some(&b':') {
+            return None;
+        }
+        at = skip_whitespace(bytes, at + 1);
+        if name == key {
+            return Some(&bytes[at..]);
+        }
+        at = skip_whitespace(bytes, at + value_end(&bytes[at..])?);
+        if bytes.get(at) != Some(&b',') {
+            return None;
+        }
+        at += 1;
+    }
+}
+
+fn trim_start(bytes: &[u8]) -> &[u8] {
+    let start = bytes.iter().position(|byte| !byte.is_ascii_whitespace()).unwrap_or_else(|| bytes.len());
+    &bytes[start..]
+}
+
+fn trim_end(bytes: &[u8]) -> &[u8] {
+    let end = bytes.iter().rposition(|byte| !byte.is_ascii_whitespace()).map_or(0, |end| end + 1);
+    &bytes[..end]
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_scan() {
+        let line = br#"{"id": 42 ,"kind":"lang","text":"say \"lang\": \\","lang":"en","user":{"id":7},"tags":[1]}"#;
+        assert_eq!(FieldScanner::new("id").scan(line), Some(&b"42"[..]));
+        assert_eq!(FieldScanner::new("text").scan(line), Some(&br#"say \"lang\": \\"#[..]));
+        assert_eq!(FieldScanner::new("tags").scan(line), None);
+        assert_eq!(FieldScanner::new("missing").scan(line), None);
+        // kind has lang as its value, the escaped quotes in text do not match the key
+        assert_eq!(FieldScanner::new("lang").scan(line), Some(&b"en"[..]));
+
+        // where scanning stops being viable: a nested key comes first
+        let nested = br#"{"user":{"lang":"th"},"lang":"en"}"#;
+        assert_eq!(FieldScanner::new("lang").scan(nested), Some(&b"th"[..]));
+        assert_eq!(PathScanner::new("lang").scan(nested), Some(&br#""en""#[..]));
+        assert_eq!(PathScanner::new("user.lang").scan(nested), Some(&br#""th""#[..]));
+        assert_eq!(PathScanner::new("user").scan(nested), Some(&br#"{"lang":"th"}"#[..]));
+        assert_eq!(PathScanner::new("tags").scan(line), Some(&b"[1]"[..]));
+        assert_eq!(PathScanner::new("id").scan(line), Some(&b"42"[..]));
+        assert_eq!(PathScanner::new("text").scan(line), Some(&br#""say \"lang\": \\""#[..]));
+        assert_eq!(PathScanner::new("user.name").scan(line), None);
+    }
+}
@@ -0,0 +1,127 @@
+use std::io::Write;
+use avro_rs::{Schema, Reader, Codec};
+use failure::{Error, bail};
+use tempfile::TempDir;
+use crate::compress::compressors;
+use crate::convert::{convert, infer_file_schema, AvroWriter};
+use crate::conformity::ConformityWriter;
+use crate::generate::{generate, Generator, GeneratorConfig};
+use crate::io::{open_lines, OutputFile};
+use crate::parser::{parsers, JsonRustParser, Parser};
+use crate::avro::json_to_avro;
+use crate::seed::Seed;
+
+const RECORDS: usize = 200;
+
+const CORPUS_SCHEMA: &str = r#"{"name":"tweet","type":"record","fields":[
+    {"name":"id","type":"long"},
+    {"name":"created_at","type":"string"},
+    {"name":"text","type":"string"},
+    {"name":"retweet_count","type":["null","long"]},
+    {"name":"user","type":{"name":"user","type":"record","fields":[{"name":"name","type":"string"},{"name":"lang","type":"string"},{"name":"followers","type":"double"}]}},
+    {"name":"hashtags","type":{"type":"array","items":"string"}}
+]}"#;
+
+
+// Checks that every compiled in backend works on a small generated corpus. Each check prints one
+// line; returns false when any of them failed.
+pub fn self_test(seed: Seed) -> Result<bool, Error> {
+    let dir = TempDir::new()?;
+    let corpus = dir.path().join("corpus.json.gz").to_string_lossy().into_owned();
+    let schema = Schema::parse_str(CORPUS_SCHEMA)?;
+    let mut generator = Generator::new(seed.rng("generate"), GeneratorConfig::default());
+    let mut output = OutputFile::create(&corpus, true)?;
+    generate(&mut generator, &schema, RECORDS, &mut output)?;
+    output.finish()?;
+
+    let mut lines = Vec::new();
+    for line in open_lines(&corpus)? {
+        lines.push(line?);
+    }
+
+    let mut passed = true;
+    let mut check = |name: &str, result: Result<(), Error>| {
+        match result {
+            Ok(()) => println!("ok      {}", name),
+            Err(e) => {
+                println!("FAILED  {}: {}", name, e);
+                passed = false;
+            }
+        }
+    };
+
+    for parser in parsers() {
+        check(&format!("parser {}", parser.name()), check_parser(parser.as_ref(), &lines));
+    }
+    let corpus_bytes = lines.join("\n").into_bytes();
+    for compressor in compressors() {
+        let result = (|| {
+            let compressed = compressor.compress_block(&corpus_bytes, compressor.default_level())?;
+            if compressed.is_empty() || compressed.len() >= corpus_bytes.len() {
+                bail!("{} bytes compressed to {}", corpus_bytes.len(), compressed.len());
+            }
+            let mut encoder = compressor.compress_stream(Box::new(Vec::new()), compressor.default_level())?;
+            encoder.write_all(&corpus_bytes)?;
+            encoder.finish()?;
+            Ok(())
+        })();
+        check(&format!("compressor {}", compressor.name()), result);
+    }
+    check("infer and avro roundtrip", check_avro_roundtrip(&corpus, &lines));
+    #[cfg(feature = "orc")]
+    check("orc conversion", check_orc(&corpus, dir.path()));
+
+    Ok(passed)
+}
+
+// every backend has to produce the same DOM as json-rust
+fn check_parser(parser: &dyn Parser, lines: &[String]) -> Result<(), Error> {
+    for (i, line) in lines.iter().enumerate() {
+        let expected = JsonRustParser.parse(&mut line.clone().into_bytes())?;
+        let parsed = parser.parse(&mut line.clone().into_bytes())?;
+        if parsed != expected {
+            bail!("record {} parsed as {}", i + 1, parsed.dump());
+        }
+    }
+    Ok(())
+}
+
+fn check_avro_roundtrip(corpus: &str, lines: &[String]) -> Result<(), Error> {
+    let schema = infer_file_schema(corpus, "tweet")?;
+
+    let mut conformity = ConformityWriter::new(&schema);
+    convert(corpus, &mut conformity)?;
+    if conformity.report.conforming_records != lines.len() {
+        bail!("{} of {} records conform to the inferred schema", conformity.report.conforming_records, lines.len());
+    }
+
+    let mut encoded = Vec::new();
+    let mut writer = AvroWriter::new(&schema, &mut encoded, Codec::Deflate);
+    convert(corpus, &mut writer)?;
+    drop(writer);
+
+    let mut records = 0;
+    for (value, line) in Reader::new(&encoded[..])?.zip(lines) {
+        let expected = json_to_avro(&json::parse(line)?, &schema)?;
+        if value? != expected {
+            bail!("record {} changed in the roundtrip", records + 1);
+        }
+        records += 1;
+    }
+    if records != lines.len() {
+        bail!("read back {} of {} records", records, lines.len());
+    }
+    Ok(())
+}
+
+#[cfg(feature = "orc")]
+fn check_orc(corpus: &str, dir: &std::path::Path) -> Result<(), Error> {
+    let schema = infer_file_schema(corpus, "tweet")?;
+    let path = dir.join("corpus.orc").to_string_lossy().into_owned();
+    let mut writer = crate::orc::OrcWriter::new(&schema, &path)?;
+    convert(corpus, &mut writer)?;
+    if std::fs::metadata(&path)?.len() == 0 {
+        bail!("{} is empty", path);
+    }
+    Ok(())
+}
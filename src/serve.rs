@@ -0,0 +1,166 @@
+use std::path::Path;
+use avro_rs::Schema;
+use failure::{Error, bail, format_err};
+use serde_json::{json, Value};
+use tiny_http::{Header, Method, Response, Server};
+use crate::avro::merge_schemas;
+use crate::compat::incompatibilities;
+use crate::infer::SchemaInferrer;
+use crate::jobs::JobQueue;
+
+
+// Answers one request with a status code and a JSON body:
+//   POST /infer?name=N        NDJSON body, returns the inferred Avro schema
+//   POST /merge               JSON array of Avro schemas, returns the schema they merge into
+//   POST /compatibility       {"reader": schema, "writer": schema}, returns whether the reader can
+//                             read what the writer wrote and why not
+//   POST /jobs                {"input": path, "output": path, "schema": optional schema}, queues a
+//                             conversion of a gzipped NDJSON file on the server to Avro
+//   GET  /jobs                every job
+//   GET  /jobs/ID             a job with its status and progress
+//   POST /jobs/ID/cancel      cancels a queued or running job
+//   GET  /jobs/ID/manifest    what a done job read and wrote
+pub fn handle(method: &Method, url: &str, body: &str, jobs: &JobQueue) -> (u16, Value) {
+    let (path, query) = match url.find('?') {
+        Some(idx) => (&url[..idx], &url[idx + 1..]),
+        None => (url, "")
+    };
+    let result = match (method, path) {
+        (Method::Post, "/infer") => infer(body, query_param(query, "name").unwrap_or("inferred_schema")),
+        (Method::Post, "/merge") => merge(body),
+        (Method::Post, "/compatibility") => compatibility(body),
+        (Method::Post, "/jobs") => submit_job(body, jobs),
+        (Method::Get, "/jobs") => Ok(json!(jobs.store.list())),
+        (method, path) if path.starts_with("/jobs/") => {
+            let mut parts = path["/jobs/".len()..].splitn(2, '/');
+            let id = match parts.next().and_then(|id| id.parse::<u64>().ok()).filter(|id| jobs.store.get(*id).is_some()) {
+                Some(id) => id,
+                None => return (404, json!({"error": format!("no job at {}", path)}))
+            };
+            match (method, parts.next()) {
+                (Method::Get, None) => Ok(json!(jobs.store.get(id))),
+                (Method::Post, Some("cancel")) => jobs.store.cancel(id).map(|job| json!(job)),
+                (Method::Get, Some("manifest")) => jobs.store.manifest(id),
+                _ => return (404, json!({"error": format!("no route {} {}", method, path)}))
+            }
+        },
+        _ => return (404, json!({"error": format!("no route {} {}", method, path)}))
+    };
+    match result {
+        Ok(value) => (200, value),
+        Err(e) => (400, json!({"error": e.to_string()}))
+    }
+}
+
+fn query_param<'q>(query: &'q str, name: &str) -> Option<&'q str> {
+    query.split('&').filter_map(|pair| pair.split_once('=')).find(|(key, _)| *key == name).map(|(_, value)| value)
+}
+
+fn infer(body: &str, name: &str) -> Result<Value, Error> {
+    let mut inferrer = SchemaInferrer::new(name);
+    for (number, line) in body.lines().enumerate().filter(|(_, line)| !line.trim().is_empty()) {
+        let json = json::parse(line).map_err(|e| format_err!("line {}: {}", number + 1, e))?;
+        inferrer.update(&json).map_err(|e| format_err!("line {}: {}", number + 1, e))?;
+    }
+    Ok(serde_json::to_value(inferrer.into_schema()?)?)
+}
+
+fn merge(body: &str) -> Result<Value, Error> {
+    let schemas = match serde_json::from_str(body)? {
+        Value::Array(schemas) => schemas,
+        _ => bail!("expected a JSON array of schemas")
+    };
+    let mut merged: Option<Schema> = None;
+    for schema in &schemas {
+        let schema = Schema::parse(schema)?;
+        merged = Some(match merged {
+            Some(base) => merge_schemas(base, schema)?,
+            None => schema
+        });
+    }
+    Ok(serde_json::to_value(merged.ok_or_else(|| format_err!("no schemas to merge"))?)?)
+}
+
+fn compatibility(body: &str) -> Result<Value, Error> {
+    let request: Value = serde_json::from_str(body)?;
+    let reader = Schema::parse(request.get("reader").ok_or_else(|| format_err!("no reader schema"))?)?;
+    let writer = Schema::parse(request.get("writer").ok_or_else(|| format_err!("no writer schema"))?)?;
+    let problems = incompatibilities(&reader, &writer);
+    Ok(json!({"compatible": problems.is_empty(), "incompatibilities": problems}))
+}
+
+fn submit_job(body: &str, jobs: &JobQueue) -> Result<Value, Error> {
+    let request: Value = serde_json::from_str(body)?;
+    let path = |key: &str| request.get(key).and_then(Value::as_str).map(str::to_owned).ok_or_else(|| format_err!("no {} path", key));
+    let (input, output) = (path("input")?, path("output")?);
+    let schema = request.get("schema").filter(|schema| !schema.is_null()).cloned();
+    Ok(json!(jobs.submit(input, output, schema)?))
+}
+
+
+// Serves requests one at a time; conversion jobs run in the background, their state kept in
+// state_dir across restarts.
+pub fn serve(address: &str, state_dir: &Path) -> Result<(), Error> {
+    let jobs = JobQueue::start(state_dir)?;
+    let server = Server::http(address).map_err(|e| format_err!("{}: {}", address, e))?;
+    let content_type = Header::from_bytes(&b"Content-Type"[..], &b"application/json"[..]).unwrap();
+    println!("Listening on http://{}", address);
+    for mut request in server.incoming_requests() {
+        let mut body = String::new();
+        let (status, value) = match request.as_reader().read_to_string(&mut body) {
+            Ok(_) => handle(request.method(), request.url(), &body, &jobs),
+            Err(e) => (400, json!({"error": e.to_string()}))
+        };
+        println!("{} {} {}", request.method(), request.url(), status);
+        let response = Response::from_string(value.to_string()).with_status_code(status).with_header(content_type.clone());
+        if let Err(e) = request.respond(response) {
+            eprintln!("{}", e);
+        }
+    }
+    Ok(())
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_handle() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let jobs = JobQueue::start(dir.path()).unwrap();
+        let (status, schema) = handle(&Method::Post, "/infer?name=tweet", "{\"a\":1}\n\n{\"a\":2,\"b\":\"x\"}\n", &jobs);
+        assert_eq!(status, 200);
+        assert_eq!(schema["name"], "tweet");
+        assert_eq!(schema["fields"].as_array().unwrap().len(), 2);
+
+        let body = json!([{"name":"t","type":"record","fields":[{"name":"a","type":"long"}]},
+                          {"name":"t","type":"record","fields":[{"name":"b","type":"string"}]}]).to_string();
+        let (status, merged) = handle(&Method::Post, "/merge", &body, &jobs);
+        assert_eq!(status, 200);
+        assert_eq!(merged["fields"].as_array().unwrap().len(), 2);
+
+        let body = json!({"reader": "long", "writer": "int"}).to_string();
+        assert_eq!(handle(&Method::Post, "/compatibility", &body, &jobs), (200, json!({"compatible": true, "incompatibilities": []})));
+
+        let (status, job) = handle(&Method::Post, "/jobs", r#"{"input":"missing.json.gz","output":"missing.avro"}"#, &jobs);
+        assert_eq!(status, 200);
+        assert_eq!(job["status"], "queued");
+        let path = format!("/jobs/{}", job["id"]);
+        let job = loop {
+            let (_, job) = handle(&Method::Get, &path, "", &jobs);
+            if !job["finished_at"].is_null() {
+                break job;
+            }
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        };
+        assert_eq!(job["status"], "failed");
+        assert_eq!(handle(&Method::Post, &format!("{}/cancel", path), "", &jobs).0, 400);
+        assert_eq!(handle(&Method::Get, &format!("{}/manifest", path), "", &jobs).0, 400);
+        assert_eq!(handle(&Method::Get, "/jobs", "", &jobs).1.as_array().unwrap().len(), 1);
+
+        assert_eq!(handle(&Method::Get, "/jobs/99", "", &jobs).0, 404);
+        assert_eq!(handle(&Method::Post, "/infer", "{", &jobs).0, 400);
+        assert_eq!(handle(&Method::Delete, "/infer", "", &jobs).0, 404);
+    }
+}
@@ -0,0 +1,150 @@
+use std::io::Write;
+use rand::Rng;
+use rand::seq::SliceRandom;
+use rand::distributions::{Distribution, WeightedIndex};
+use failure::{Error, bail};
+use crate::io::{open_lines, OutputFile};
+
+
+// Uniform random sample of `sample` lines in random order, in one pass and O(sample) memory.
+pub fn reservoir<R: Rng>(file_path: &str, output: &mut dyn Write, sample: usize, rng: &mut R) -> Result<usize, Error> {
+    let mut reservoir: Vec<String> = Vec::with_capacity(sample);
+    for (i, line) in open_lines(file_path)?.enumerate() {
+        let line = line?;
+        if reservoir.len() < sample {
+            reservoir.push(line);
+        } else {
+            let j = rng.gen_range(0, i + 1);
+            if j < sample {
+                reservoir[j] = line;
+            }
+        }
+    }
+    reservoir.shuffle(rng);
+    for line in &reservoir {
+        writeln!(output, "{}", line)?;
+    }
+    Ok(reservoir.len())
+}
+
+
+// Full shuffle of inputs larger than memory: lines are scattered into random bucket files, then
+// each bucket is shuffled in memory and appended to the output.
+pub fn external_shuffle<R: Rng>(file_path: &str, output: &mut dyn Write, buckets: usize, rng: &mut R) -> Result<usize, Error> {
+    if buckets == 0 {
+        bail!("number of buckets must be positive");
+    }
+    let spill_dir = tempfile::tempdir()?;
+    let paths: Vec<String> =
+        (0..buckets)
+            .map(|i| spill_dir.path().join(format!("bucket-{:05}.json", i)).to_string_lossy().into_owned())
+            .collect();
+
+    let mut files = Vec::with_capacity(buckets);
+    for path in &paths {
+        files.push(OutputFile::create(path, false)?);
+    }
+    for line in open_lines(file_path)? {
+        writeln!(files[rng.gen_range(0, buckets)], "{}", line?)?;
+    }
+    for file in files {
+        file.finish()?;
+    }
+
+    let mut records = 0;
+    for path in &paths {
+        let mut lines = open_lines(path)?.collect::<Result<Vec<String>, _>>()?;
+        lines.shuffle(rng);
+        for line in &lines {
+            writeln!(output, "{}", line)?;
+        }
+        records += lines.len();
+    }
+    Ok(records)
+}
+
+
+// Mixes several corpora, picking the source of every record at random with the given weights.
+// Stops as soon as one input is exhausted so the output keeps the requested ratios.
+pub fn interleave<R: Rng>(inputs: &[String], weights: &[f64], output: &mut dyn Write, rng: &mut R) -> Result<Vec<usize>, Error> {
+    if inputs.len() != weights.len() {
+        bail!("{} inputs but {} ratios", inputs.len(), weights.len());
+    }
+    let distribution = WeightedIndex::new(weights)?;
+    let mut sources = Vec::with_capacity(inputs.len());
+    for input in inputs {
+        sources.push(open_lines(input)?);
+    }
+
+    let mut counts = vec![0; inputs.len()];
+    loop {
+        let i = distribution.sample(rng);
+        match sources[i].next() {
+            Some(line) => {
+                writeln!(output, "{}", line?)?;
+                counts[i] += 1;
+            },
+            None => break
+        }
+    }
+    Ok(counts)
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::seed::Seed;
+
+    fn write_lines(dir: &tempfile::TempDir, name: &str, lines: &[String]) -> String {
+        let path = dir.path().join(name).to_string_lossy().into_owned();
+        std::fs::write(&path, lines.iter().map(|line| format!("{}\n", line)).collect::<String>()).unwrap();
+        path
+    }
+
+    fn sorted(output: &[u8]) -> Vec<String> {
+        let mut lines: Vec<String> = String::from_utf8(output.to_vec()).unwrap().lines().map(|line| line.to_owned()).collect();
+        lines.sort();
+        lines
+    }
+
+    #[test]
+    fn test_shuffle() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let mut lines: Vec<String> = (0..200).map(|i| format!("{{\"i\":{:03}}}", i)).collect();
+        let input = write_lines(&dir, "lines.json", &lines);
+        lines.sort();
+
+        let (mut first, mut second) = (Vec::new(), Vec::new());
+        assert_eq!(external_shuffle(&input, &mut first, 4, &mut Seed(7).rng("shuffle")).unwrap(), 200);
+        external_shuffle(&input, &mut second, 4, &mut Seed(7).rng("shuffle")).unwrap();
+        assert_eq!(first, second);
+        assert_eq!(sorted(&first), lines);
+        assert_ne!(String::from_utf8(first).unwrap().lines().next().unwrap(), "{\"i\":000}");
+
+        let mut sample = Vec::new();
+        assert_eq!(reservoir(&input, &mut sample, 500, &mut Seed(7).rng("shuffle")).unwrap(), 200);
+        assert_eq!(sorted(&sample), lines);
+        let mut sample = Vec::new();
+        assert_eq!(reservoir(&input, &mut sample, 10, &mut Seed(7).rng("shuffle")).unwrap(), 10);
+        assert!(sorted(&sample).iter().all(|line| lines.contains(line)));
+    }
+
+    #[test]
+    fn test_interleave() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let a: Vec<String> = (0..50).map(|i| format!("a{}", i)).collect();
+        let b: Vec<String> = (0..50).map(|i| format!("b{}", i)).collect();
+        let inputs = vec![write_lines(&dir, "a.json", &a), write_lines(&dir, "b.json", &b)];
+
+        let mut output = Vec::new();
+        let counts = interleave(&inputs, &[1.0, 1.0], &mut output, &mut Seed(7).rng("interleave")).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        let from = |prefix: char| output.lines().filter(|line| line.starts_with(prefix)).map(|line| line.to_owned()).collect::<Vec<String>>();
+        // every source keeps its order, and the output ends when the first one runs out
+        assert_eq!(from('a'), a[..counts[0]].to_vec());
+        assert_eq!(from('b'), b[..counts[1]].to_vec());
+        assert!(counts[0] == 50 || counts[1] == 50, "{:?}", counts);
+        assert!(interleave(&inputs, &[1.0], &mut Vec::new(), &mut Seed(7).rng("interleave")).is_err());
+    }
+}
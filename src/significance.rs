@@ -0,0 +1,158 @@
+use std::fmt;
+use rand::Rng;
+use crate::bench::BenchResults;
+use crate::seed::Seed;
+
+
+// Two-sided p-value of the Mann-Whitney U test that both samples come from the same distribution,
+// from the normal approximation with tie and continuity corrections. Rough below five runs a side.
+pub fn mann_whitney(a: &[f64], b: &[f64]) -> f64 {
+    let (n1, n2) = (a.len() as f64, b.len() as f64);
+    let mut values: Vec<(f64, usize)> = a.iter().map(|v| (*v, 0)).chain(b.iter().map(|v| (*v, 1))).collect();
+    values.sort_by(|x, y| x.0.partial_cmp(&y.0).unwrap());
+
+    // average ranks over ties, which also shrink the variance
+    let mut rank_sum = 0.0;
+    let mut tie_term = 0.0;
+    let mut i = 0;
+    while i < values.len() {
+        let mut j = i;
+        while j + 1 < values.len() && values[j + 1].0 == values[i].0 {
+            j += 1;
+        }
+        let rank = (i + j) as f64 / 2.0 + 1.0;
+        rank_sum += values[i..=j].iter().filter(|(_, side)| *side == 0).count() as f64 * rank;
+        let ties = (j - i + 1) as f64;
+        tie_term += ties * ties * ties - ties;
+        i = j + 1;
+    }
+    let u = rank_sum - n1 * (n1 + 1.0) / 2.0;
+    let n = n1 + n2;
+    let variance = n1 * n2 / 12.0 * ((n + 1.0) - tie_term / (n * (n - 1.0)));
+    if variance <= 0.0 {
+        return 1.0;
+    }
+    let z = ((u - n1 * n2 / 2.0).abs() - 0.5).max(0.0) / variance.sqrt();
+    (2.0 * (1.0 - normal_cdf(z))).min(1.0)
+}
+
+fn normal_cdf(z: f64) -> f64 {
+    0.5 * (1.0 + erf(z / std::f64::consts::SQRT_2))
+}
+
+// Abramowitz and Stegun 7.1.26, within 1.5e-7
+fn erf(x: f64) -> f64 {
+    let t = 1.0 / (1.0 + 0.327_591_1 * x.abs());
+    let polynomial = t * (0.254_829_592 + t * (-0.284_496_736 + t * (1.421_413_741 + t * (-1.453_152_027 + t * 1.061_405_429))));
+    let y = 1.0 - polynomial * (-x * x).exp();
+    if x < 0.0 { -y } else { y }
+}
+
+fn median(values: &mut [f64]) -> f64 {
+    values.sort_by(|x, y| x.partial_cmp(y).unwrap());
+    let middle = values.len() / 2;
+    if values.len() % 2 == 0 { (values[middle - 1] + values[middle]) / 2.0 } else { values[middle] }
+}
+
+// 95% interval of the relative change of the median from a to b, from resampling both sides.
+pub fn bootstrap_change<R: Rng>(a: &[f64], b: &[f64], resamples: usize, rng: &mut R) -> (f64, f64) {
+    let mut resample = |values: &[f64]| -> f64 {
+        let mut drawn: Vec<f64> = (0..values.len()).map(|_| values[rng.gen_range(0, values.len())]).collect();
+        median(&mut drawn)
+    };
+    let mut changes: Vec<f64> = (0..resamples.max(1))
+        .map(|_| {
+            let base = resample(a);
+            resample(b) / base.max(1e-9) - 1.0
+        })
+        .collect();
+    changes.sort_by(|x, y| x.partial_cmp(y).unwrap());
+    let at = |quantile: f64| changes[((changes.len() - 1) as f64 * quantile).round() as usize];
+    (at(0.025), at(0.975))
+}
+
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Significance {
+    pub name: String,
+    pub baseline_ms: f64,
+    pub current_ms: f64,
+    // relative change of the median time, 0.1 is 10% slower
+    pub change: f64,
+    // None without at least two runs on each side
+    pub p_value: Option<f64>,
+    pub interval: Option<(f64, f64)>,
+    // the test rejects at alpha and the interval does not contain 0
+    pub significant: bool
+}
+
+impl Significance {
+    pub fn regressed(&self) -> bool {
+        self.significant && self.change > 0.0
+    }
+}
+
+impl fmt::Display for Significance {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:<32} {:>10.1} {:>10.1} {:>+8.1}%", self.name, self.baseline_ms, self.current_ms, self.change * 100.0)?;
+        match (self.p_value, self.interval) {
+            (Some(p_value), Some((low, high))) => write!(f, " {:>8.4} [{:+.1}%, {:+.1}%]", p_value, low * 100.0, high * 100.0)?,
+            _ => write!(f, " {:>8} too few runs", "-")?
+        }
+        if self.significant {
+            write!(f, "  {}", if self.change > 0.0 { "SLOWER" } else { "FASTER" })?;
+        }
+        Ok(())
+    }
+}
+
+// Benchmarks present in both result sets, in the order of the current one, tested on the times of
+// their measured runs.
+pub fn compare(baseline: &BenchResults, current: &BenchResults, alpha: f64, resamples: usize, seed: Seed) -> Vec<Significance> {
+    let mut rng = seed.rng("compare");
+    current.results
+        .iter()
+        .filter_map(|result| {
+            let base = baseline.results.iter().find(|base| base.name == result.name)?;
+            let (a, b) = (&base.samples_ms, &result.samples_ms);
+            let tested = a.len() >= 2 && b.len() >= 2;
+            let p_value = if tested { Some(mann_whitney(a, b)) } else { None };
+            let interval = if tested { Some(bootstrap_change(a, b, resamples, &mut rng)) } else { None };
+            let significant = match (p_value, interval) {
+                (Some(p_value), Some((low, high))) => p_value < alpha && (low > 0.0 || high < 0.0),
+                _ => false
+            };
+            Some(Significance {
+                name: result.name.clone(),
+                baseline_ms: base.elapsed_ms,
+                current_ms: result.elapsed_ms,
+                change: result.elapsed_ms / base.elapsed_ms.max(0.001) - 1.0,
+                p_value,
+                interval,
+                significant
+            })
+        })
+        .collect()
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_mann_whitney() {
+        let a = [10.0, 11.0, 10.5, 10.2, 10.8, 10.1, 10.9, 10.4];
+        let b = [12.0, 12.5, 12.2, 11.9, 12.8, 12.1, 12.4, 12.6];
+        assert!(mann_whitney(&a, &b) < 0.001);
+        assert!((mann_whitney(&a, &b) - mann_whitney(&b, &a)).abs() < 1e-12);
+        assert!(mann_whitney(&a, &a) > 0.9);
+        assert_eq!(mann_whitney(&[1.0, 1.0], &[1.0, 1.0]), 1.0);
+        assert!((normal_cdf(1.96) - 0.975).abs() < 0.001);
+
+        let (low, high) = bootstrap_change(&a, &b, 2000, &mut Seed(1).rng("test"));
+        assert!(low > 0.1 && high < 0.25, "{} {}", low, high);
+        let (low, high) = bootstrap_change(&a, &a, 2000, &mut Seed(1).rng("test"));
+        assert!(low < 0.0 && high > 0.0);
+    }
+}
@@ -0,0 +1,91 @@
+use std::fmt;
+use std::hint::black_box;
+use json::JsonValue;
+use serde_json::Value;
+#[cfg(not(target_arch = "wasm32"))]
+use simd_json::value::borrowed::Value as BorrowedValue;
+#[cfg(not(target_arch = "wasm32"))]
+use simd_json::value::owned::Value as OwnedValue;
+
+
+// Where benchmark results end up instead of being dropped, so the optimizer cannot elide the work
+// that produced them. Every value goes into an order dependent checksum that is printed with the
+// results; backends doing the same work, e.g. all parsers, agree on it.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct Sink {
+    pub values: u64,
+    pub checksum: u64
+}
+
+impl Sink {
+    pub fn consume(&mut self, value: u64) {
+        self.values += 1;
+        self.checksum = self.checksum.wrapping_mul(31).wrapping_add(black_box(value));
+    }
+}
+
+impl fmt::Display for Sink {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:016x} over {} values", self.checksum, self.values)
+    }
+}
+
+
+// Object keys and array items in a document, counted the same way for every DOM.
+pub fn json_size(json: &JsonValue) -> u64 {
+    match json {
+        JsonValue::Object(_) => json.entries().map(|(_, value)| 1 + json_size(value)).sum(),
+        JsonValue::Array(items) => items.iter().map(|item| 1 + json_size(item)).sum(),
+        _ => 0
+    }
+}
+
+pub fn serde_size(value: &Value) -> u64 {
+    match value {
+        Value::Object(map) => map.values().map(|value| 1 + serde_size(value)).sum(),
+        Value::Array(items) => items.iter().map(|item| 1 + serde_size(item)).sum(),
+        _ => 0
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub fn simd_size(value: &BorrowedValue) -> u64 {
+    match value {
+        BorrowedValue::Object(map) => map.values().map(|value| 1 + simd_size(value)).sum(),
+        BorrowedValue::Array(items) => items.iter().map(|item| 1 + simd_size(item)).sum(),
+        _ => 0
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub fn simd_owned_size(value: &OwnedValue) -> u64 {
+    match value {
+        OwnedValue::Object(map) => map.values().map(|value| 1 + simd_owned_size(value)).sum(),
+        OwnedValue::Array(items) => items.iter().map(|item| 1 + simd_owned_size(item)).sum(),
+        _ => 0
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_sizes() {
+        let line = r#"{"a": 1, "b": [true, {"c": null}], "d": {}}"#;
+        assert_eq!(json_size(&json::parse(line).unwrap()), 6);
+        assert_eq!(serde_size(&serde_json::from_str(line).unwrap()), 6);
+        let mut bytes = line.as_bytes().to_vec();
+        assert_eq!(simd_size(&simd_json::to_borrowed_value(&mut bytes.clone()).unwrap()), 6);
+        assert_eq!(simd_owned_size(&simd_json::to_owned_value(&mut bytes).unwrap()), 6);
+
+        let (mut first, mut second) = (Sink::default(), Sink::default());
+        first.consume(1);
+        first.consume(2);
+        second.consume(2);
+        second.consume(1);
+        assert_eq!(first.values, 2);
+        assert_ne!(first.checksum, second.checksum);
+    }
+}
@@ -0,0 +1,223 @@
+use std::fmt;
+use std::time::{Duration, Instant};
+use avro_rs::Codec;
+use serde::Serialize;
+use failure::{Error, bail, format_err};
+use crate::allocation;
+use crate::convert::{infer_file_schema, AvroWriter, RecordWriter};
+use crate::infer::SchemaInferrer;
+use crate::io::{open_input, Decoder};
+use crate::parser::{find_parser, Parser};
+use crate::sink::Sink;
+
+
+// What a soak run does with every record, pass after pass over the input.
+pub enum Workload {
+    // each parser's native value into a sink
+    Parse(Box<dyn Parser>),
+    // into one SchemaInferrer for the whole run, whose state should stop growing after a pass
+    Infer,
+    // to Avro with the schema of the input, inferred before the run, and discarded
+    Convert
+}
+
+// parse:PARSER, infer or convert
+pub fn parse_workload(spec: &str) -> Result<Workload, Error> {
+    match spec.split_once(':') {
+        Some(("parse", parser)) => find_parser(parser).map(Workload::Parse).ok_or_else(|| format_err!("unknown parser {}", parser)),
+        None if spec == "infer" => Ok(Workload::Infer),
+        None if spec == "convert" => Ok(Workload::Convert),
+        _ => bail!("unknown workload {}, expected parse:PARSER, infer or convert", spec)
+    }
+}
+
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Checkpoint {
+    pub elapsed_seconds: f64,
+    // complete passes over the input so far
+    pub passes: usize,
+    pub records: u64,
+    pub errors: u64,
+    // since the previous checkpoint
+    pub records_per_second: f64,
+    pub bytes_per_second: f64,
+    pub heap_bytes: usize,
+    // Linux only
+    pub rss_bytes: Option<u64>
+}
+
+impl fmt::Display for Checkpoint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:>8.0} {:>7} {:>14} {:>8} {:>12.0} {:>9.1} {:>10.1} {:>10}", self.elapsed_seconds, self.passes, self.records, self.errors,
+               self.records_per_second, self.bytes_per_second / 1e6, self.heap_bytes as f64 / 1048576.0,
+               self.rss_bytes.map_or("-".to_owned(), |rss| format!("{:.1}", rss as f64 / 1048576.0)))
+    }
+}
+
+pub const HEADER: &str = "    time  passes        records   errors    records/s      MB/s  heap (MiB)  rss (MiB)";
+
+#[cfg(target_os = "linux")]
+fn rss_bytes() -> Option<u64> {
+    let statm = std::fs::read_to_string("/proc/self/statm").ok()?;
+    let pages: u64 = statm.split_whitespace().nth(1)?.parse().ok()?;
+    Some(pages * unsafe { libc::sysconf(libc::_SC_PAGESIZE) } as u64)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn rss_bytes() -> Option<u64> {
+    None
+}
+
+#[derive(Default)]
+struct Counters {
+    records: u64,
+    bytes: u64,
+    errors: u64,
+    passes: usize
+}
+
+struct Checkpoints {
+    started: Instant,
+    last: Instant,
+    last_records: u64,
+    last_bytes: u64,
+    taken: Vec<Checkpoint>
+}
+
+impl Checkpoints {
+    fn take(&mut self, counters: &Counters, now: Instant) -> &Checkpoint {
+        let seconds = (now - self.last).as_secs_f64().max(1e-9);
+        self.taken.push(Checkpoint {
+            elapsed_seconds: (now - self.started).as_secs_f64(),
+            passes: counters.passes,
+            records: counters.records,
+            errors: counters.errors,
+            records_per_second: (counters.records - self.last_records) as f64 / seconds,
+            bytes_per_second: (counters.bytes - self.last_bytes) as f64 / seconds,
+            heap_bytes: allocation::current_bytes(),
+            rss_bytes: rss_bytes()
+        });
+        self.last = now;
+        self.last_records = counters.records;
+        self.last_bytes = counters.bytes;
+        self.taken.last().unwrap()
+    }
+}
+
+// Runs the workload over the input again and again until duration is spent, with a checkpoint
+// every so often, handed to on_checkpoint as it is taken, and one at the end.
+pub fn soak(input: &str, workload: &Workload, duration: Duration, every: Duration,
+            on_checkpoint: &mut dyn FnMut(&Checkpoint)) -> Result<Vec<Checkpoint>, Error> {
+    let schema = match workload {
+        Workload::Convert => Some(infer_file_schema(input, "soak")?),
+        _ => None
+    };
+    let mut avro = schema.as_ref().map(|schema| AvroWriter::new(schema, std::io::sink(), Codec::Deflate));
+    let mut inferrer = SchemaInferrer::new("soak");
+    let mut sink = Sink::default();
+    let mut counters = Counters::default();
+    let started = Instant::now();
+    let mut checkpoints = Checkpoints { started, last: started, last_records: 0, last_bytes: 0, taken: Vec::new() };
+    'soak: loop {
+        for line in open_input(input, Decoder::Flate2)? {
+            let line = line?;
+            counters.records += 1;
+            counters.bytes += line.len() as u64 + 1;
+            let result = match workload {
+                Workload::Parse(parser) => parser.parse_native(&mut line.into_bytes()).map(|value| sink.consume(value)),
+                Workload::Infer => json::parse(&line).map_err(Error::from).and_then(|json| inferrer.update(&json)),
+                Workload::Convert => json::parse(&line).map_err(Error::from).and_then(|json| avro.as_mut().unwrap().write(&json))
+            };
+            if result.is_err() {
+                counters.errors += 1;
+            }
+            // the clock is read every so many records only
+            if counters.records % 1024 == 0 {
+                let now = Instant::now();
+                if now - started >= duration {
+                    break 'soak;
+                }
+                if now - checkpoints.last >= every {
+                    on_checkpoint(checkpoints.take(&counters, now));
+                }
+            }
+        }
+        counters.passes += 1;
+        if counters.records == 0 {
+            bail!("{} has no records", input);
+        }
+        let now = Instant::now();
+        if now - started >= duration {
+            break;
+        }
+        if now - checkpoints.last >= every {
+            on_checkpoint(checkpoints.take(&counters, now));
+        }
+    }
+    on_checkpoint(checkpoints.take(&counters, Instant::now()));
+    if let Some(mut avro) = avro {
+        avro.finish()?;
+    }
+    Ok(checkpoints.taken)
+}
+
+
+// The last checkpoint against the first, which already had an interval to warm up in. The last
+// one may cover a shorter interval than the others.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Drift {
+    // relative, -0.1 is 10% slower
+    pub throughput_change: f64,
+    pub heap_growth_bytes: i64
+}
+
+pub fn drift(checkpoints: &[Checkpoint]) -> Option<Drift> {
+    let (first, last) = match checkpoints {
+        [first, .., last] => (first, last),
+        _ => return None
+    };
+    Some(Drift {
+        throughput_change: last.records_per_second / first.records_per_second.max(1e-9) - 1.0,
+        heap_growth_bytes: last.heap_bytes as i64 - first.heap_bytes as i64
+    })
+}
+
+impl fmt::Display for Drift {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "From the first checkpoint to the last: throughput {:+.1}%, heap {:+.1} MiB", self.throughput_change * 100.0,
+               self.heap_growth_bytes as f64 / 1048576.0)
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_soak() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("records.json").to_string_lossy().into_owned();
+        std::fs::write(&path, "{\"a\":1}\n{\"a\":[2]}\nnot json\n").unwrap();
+        assert!(parse_workload("parse:nope").is_err());
+        assert!(parse_workload("infer:json").is_err());
+
+        let mut printed = 0;
+        let checkpoints = soak(&path, &parse_workload("infer").unwrap(), Duration::from_millis(50), Duration::from_millis(10),
+                               &mut |_| printed += 1).unwrap();
+        assert_eq!(printed, checkpoints.len());
+        let last = checkpoints.last().unwrap();
+        assert!(last.passes >= 1 && last.records >= 3);
+        // one bad line a pass
+        assert_eq!(last.errors * 3, last.records - last.records % 3);
+
+        let checkpoint = |records_per_second: f64, heap_bytes: usize| Checkpoint {
+            elapsed_seconds: 0.0, passes: 0, records: 0, errors: 0, records_per_second, bytes_per_second: 0.0, heap_bytes, rss_bytes: None
+        };
+        assert!(drift(&[checkpoint(100.0, 0)]).is_none());
+        let drift = drift(&[checkpoint(100.0, 1 << 20), checkpoint(90.0, 1 << 20), checkpoint(80.0, 3 << 20)]).unwrap();
+        assert!((drift.throughput_change + 0.2).abs() < 1e-9);
+        assert_eq!(drift.heap_growth_bytes, 2 << 20);
+    }
+}
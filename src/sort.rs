@@ -0,0 +1,180 @@
+use std::cmp::{Ordering, Reverse};
+use std::collections::BinaryHeap;
+use std::io::Write;
+use json::JsonValue;
+use failure::Error;
+use crate::io::{open_lines, OutputFile};
+use crate::path::lookup;
+
+
+// Sort order of key values: null < booleans < numbers < strings < anything else (by its JSON text).
+// Integers keep their exact value, tweet ids are above 2^53 where neighbours are the same double.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SortKey {
+    Null,
+    Boolean(bool),
+    Integer(i128),
+    Number(f64),
+    String(String),
+    Other(String)
+}
+
+impl SortKey {
+    pub fn of(json: &JsonValue, key_path: &str) -> Self {
+        let value = lookup(json, key_path);
+        match value {
+            JsonValue::Null => SortKey::Null,
+            JsonValue::Boolean(b) => SortKey::Boolean(*b),
+            JsonValue::Number(number) => match number.as_parts() {
+                (positive, mantissa, 0) => SortKey::Integer(if positive { mantissa as i128 } else { -(mantissa as i128) }),
+                _ => SortKey::Number(value.as_f64().unwrap())
+            },
+            JsonValue::String(_) | JsonValue::Short(_) => SortKey::String(value.as_str().unwrap().to_owned()),
+            _ => SortKey::Other(value.dump())
+        }
+    }
+
+    fn rank(&self) -> u8 {
+        match self {
+            SortKey::Null => 0,
+            SortKey::Boolean(_) => 1,
+            SortKey::Integer(_) | SortKey::Number(_) => 2,
+            SortKey::String(_) => 3,
+            SortKey::Other(_) => 4
+        }
+    }
+}
+
+impl Eq for SortKey {}
+
+impl Ord for SortKey {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self, other) {
+            (SortKey::Boolean(a), SortKey::Boolean(b)) => a.cmp(b),
+            (SortKey::Integer(a), SortKey::Integer(b)) => a.cmp(b),
+            (SortKey::Number(a), SortKey::Number(b)) => a.partial_cmp(b).unwrap_or(Ordering::Equal),
+            (SortKey::Integer(a), SortKey::Number(b)) => (*a as f64).partial_cmp(b).unwrap_or(Ordering::Equal),
+            (SortKey::Number(a), SortKey::Integer(b)) => a.partial_cmp(&(*b as f64)).unwrap_or(Ordering::Equal),
+            (SortKey::String(a), SortKey::String(b)) | (SortKey::Other(a), SortKey::Other(b)) => a.cmp(b),
+            (a, b) => a.rank().cmp(&b.rank())
+        }
+    }
+}
+
+impl PartialOrd for SortKey {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+
+// External merge sort: chunks of `chunk_records` lines are sorted in memory and spilled to
+// temporary files, which are then merged. Stable, records with equal keys keep their input order.
+pub fn sort(file_path: &str, output_path: &str, key_path: &str, chunk_records: usize, gzip: bool) -> Result<usize, Error> {
+    let spill_dir = tempfile::tempdir()?;
+    let mut chunks = Vec::new();
+    let mut buffer: Vec<(SortKey, String)> = Vec::with_capacity(chunk_records);
+
+    for line in open_lines(file_path)? {
+        let line = line?;
+        let key = SortKey::of(&json::parse(&line)?, key_path);
+        buffer.push((key, line));
+        if buffer.len() >= chunk_records {
+            chunks.push(spill(&mut buffer, spill_dir.path(), chunks.len())?);
+        }
+    }
+    if !buffer.is_empty() {
+        chunks.push(spill(&mut buffer, spill_dir.path(), chunks.len())?);
+    }
+
+    merge(&chunks, output_path, key_path, gzip)
+}
+
+fn spill(buffer: &mut Vec<(SortKey, String)>, dir: &std::path::Path, chunk: usize) -> Result<String, Error> {
+    buffer.sort_by(|a, b| a.0.cmp(&b.0));
+    let path = dir.join(format!("chunk-{:05}.json", chunk)).to_string_lossy().into_owned();
+    let mut output = OutputFile::create(&path, false)?;
+    for (_, line) in buffer.drain(..) {
+        writeln!(output, "{}", line)?;
+    }
+    output.finish()?;
+    Ok(path)
+}
+
+
+// K-way merge of inputs that are each already sorted by the key.
+pub fn merge(inputs: &[String], output_path: &str, key_path: &str, gzip: bool) -> Result<usize, Error> {
+    let mut sources = Vec::with_capacity(inputs.len());
+    for input in inputs {
+        sources.push(open_lines(input)?);
+    }
+
+    let mut heap = BinaryHeap::new();
+    for (i, source) in sources.iter_mut().enumerate() {
+        if let Some(entry) = next_entry(source, key_path, i)? {
+            heap.push(Reverse(entry));
+        }
+    }
+
+    let mut output = OutputFile::create(output_path, gzip)?;
+    let mut records = 0;
+    while let Some(Reverse((_, i, line))) = heap.pop() {
+        writeln!(output, "{}", line)?;
+        records += 1;
+        if let Some(entry) = next_entry(&mut sources[i], key_path, i)? {
+            heap.push(Reverse(entry));
+        }
+    }
+    output.finish()?;
+    Ok(records)
+}
+
+fn next_entry(source: &mut Box<dyn Iterator<Item=std::io::Result<String>>>, key_path: &str, i: usize) -> Result<Option<(SortKey, usize, String)>, Error> {
+    match source.next() {
+        Some(line) => {
+            let line = line?;
+            let key = SortKey::of(&json::parse(&line)?, key_path);
+            Ok(Some((key, i, line)))
+        },
+        None => Ok(None)
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_sort_key_order() {
+        let mut keys = vec![
+            SortKey::String("b".to_owned()),
+            SortKey::Integer(10),
+            SortKey::Null,
+            SortKey::Number(2.5),
+            SortKey::String("a".to_owned()),
+            SortKey::Boolean(true)
+        ];
+        keys.sort();
+        assert_eq!(keys, vec![
+            SortKey::Null,
+            SortKey::Boolean(true),
+            SortKey::Number(2.5),
+            SortKey::Integer(10),
+            SortKey::String("a".to_owned()),
+            SortKey::String("b".to_owned())
+        ]);
+    }
+
+    #[test]
+    fn test_sort_large_ids() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let input = dir.path().join("ids.json").to_string_lossy().into_owned();
+        let output = dir.path().join("sorted.json").to_string_lossy().into_owned();
+        std::fs::write(&input, "{\"id\":1000514497163542529}\n{\"id\":-3}\n{\"id\":1000514497163542528}\n{\"id\":2.5}\n").unwrap();
+        assert_eq!(sort(&input, &output, "id", 2, false).unwrap(), 4);
+        assert_eq!(std::fs::read_to_string(&output).unwrap(),
+                   "{\"id\":-3}\n{\"id\":2.5}\n{\"id\":1000514497163542528}\n{\"id\":1000514497163542529}\n");
+        assert!(SortKey::Integer(1000514497163542528) < SortKey::Integer(1000514497163542529));
+    }
+}
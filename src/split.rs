@@ -0,0 +1,102 @@
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::io::Write;
+use failure::{Error, bail, format_err};
+use crate::io::{open_input, Decoder, OutputFile};
+use crate::path::lookup;
+
+
+pub enum Partitioning {
+    RoundRobin,
+    // hash of the value at a dotted key path, records with the same key end up in the same shard
+    Key(String)
+}
+
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ShardStats {
+    pub path: String,
+    pub records: usize,
+    pub bytes: u64,
+    pub file_bytes: u64
+}
+
+
+pub fn shard_path(output_prefix: &str, shard: usize, gzip: bool) -> String {
+    format!("{}-{:05}.json{}", output_prefix, shard, if gzip { ".gz" } else { "" })
+}
+
+pub fn split(file_path: &str, output_prefix: &str, shards: usize, partitioning: &Partitioning, gzip: bool) -> Result<Vec<ShardStats>, Error> {
+    if shards == 0 {
+        bail!("number of shards must be positive");
+    }
+    let lines = open_input(file_path, Decoder::Flate2).map_err(|e| format_err!("{}: {}", file_path, e))?;
+
+    let mut outputs = Vec::with_capacity(shards);
+    let mut stats = Vec::with_capacity(shards);
+    for shard in 0..shards {
+        let path = shard_path(output_prefix, shard, gzip);
+        outputs.push(OutputFile::create(&path, gzip)?);
+        stats.push(ShardStats { path, records: 0, bytes: 0, file_bytes: 0 });
+    }
+
+    for (i, line) in lines.enumerate() {
+        let line = line?;
+        let shard = match partitioning {
+            Partitioning::RoundRobin => i % shards,
+            Partitioning::Key(key_path) => {
+                let json = json::parse(&line)?;
+                let mut hasher = DefaultHasher::new();
+                lookup(&json, key_path).dump().hash(&mut hasher);
+                (hasher.finish() % shards as u64) as usize
+            }
+        };
+        writeln!(outputs[shard], "{}", line)?;
+        stats[shard].records += 1;
+        stats[shard].bytes += line.len() as u64 + 1;
+    }
+
+    for (output, shard_stats) in outputs.into_iter().zip(stats.iter_mut()) {
+        output.finish()?;
+        shard_stats.file_bytes = std::fs::metadata(&shard_stats.path)?.len();
+    }
+    Ok(stats)
+}
+
+// Ratio of the largest to the smallest shard by record count, 1.0 is perfectly balanced.
+pub fn imbalance(stats: &[ShardStats]) -> f64 {
+    let max = stats.iter().map(|s| s.records).max().unwrap_or(0);
+    let min = stats.iter().map(|s| s.records).min().unwrap_or(0);
+    if min == 0 {
+        if max == 0 { 1.0 } else { std::f64::INFINITY }
+    } else {
+        max as f64 / min as f64
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_split() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let input = dir.path().join("lines.json").to_string_lossy().into_owned();
+        let prefix = dir.path().join("shard").to_string_lossy().into_owned();
+        std::fs::write(&input, "{\"k\":1}\n{\"k\":2}\n{\"k\":1}\n").unwrap();
+
+        let stats = split(&input, &prefix, 2, &Partitioning::RoundRobin, false).unwrap();
+        assert_eq!(std::fs::read_to_string(&stats[0].path).unwrap(), "{\"k\":1}\n{\"k\":1}\n");
+        assert_eq!(std::fs::read_to_string(&stats[1].path).unwrap(), "{\"k\":2}\n");
+        assert_eq!((stats[0].records, stats[0].bytes, stats[0].file_bytes), (2, 16, 16));
+        assert_eq!(imbalance(&stats), 2.0);
+
+        let stats = split(&input, &prefix, 2, &Partitioning::Key("k".to_owned()), false).unwrap();
+        let shards: Vec<String> = stats.iter().map(|shard| std::fs::read_to_string(&shard.path).unwrap()).collect();
+        assert!(shards.iter().any(|shard| shard.matches("{\"k\":1}\n").count() == 2), "{:?}", shards);
+        assert_eq!(stats.iter().map(|shard| shard.records).sum::<usize>(), 3);
+
+        assert!(split(&dir.path().join("missing.json").to_string_lossy(), &prefix, 2, &Partitioning::RoundRobin, false).is_err());
+    }
+}
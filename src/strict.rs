@@ -0,0 +1,189 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io::Write;
+use json::JsonValue;
+use avro_rs::Schema;
+use avro_rs::schema::SchemaKind;
+use failure::{Error, bail};
+use crate::avro::json_kind;
+use crate::convert::{keep_location, Location, RecordWriter};
+
+
+// Paths of the fields in the record that the schema does not declare, "a.b", "a[].b" or "a{}.b".
+pub fn unknown_paths(json: &JsonValue, schema: &Schema) -> Vec<String> {
+    let mut paths = Vec::new();
+    collect_unknown(json, schema, "", &mut paths);
+    paths
+}
+
+fn collect_unknown(json: &JsonValue, schema: &Schema, path: &str, paths: &mut Vec<String>) {
+    match (json, schema) {
+        (_, Schema::Union(union)) => {
+            let sk = json_kind(json);
+            if let Some(variant) = union.variants().iter().find(|variant| SchemaKind::from(*variant) == sk) {
+                collect_unknown(json, variant, path, paths);
+            }
+        },
+        (JsonValue::Object(_), Schema::Record {fields, lookup, ..}) => {
+            for field in fields {
+                if json.has_key(&field.name) {
+                    collect_unknown(&json[field.name.as_str()], &field.schema, &join(path, &field.name), paths);
+                }
+            }
+            for (key, _) in json.entries() {
+                if !lookup.contains_key(key) {
+                    paths.push(join(path, key));
+                }
+            }
+        },
+        (JsonValue::Object(_), Schema::Map(values_schema)) => {
+            let values_path = format!("{}{{}}", path);
+            for (_, value) in json.entries() {
+                collect_unknown(value, values_schema, &values_path, paths);
+            }
+        },
+        (JsonValue::Array(items), Schema::Array(items_schema)) => {
+            let items_path = format!("{}[]", path);
+            for item in items {
+                collect_unknown(item, items_schema, &items_path, paths);
+            }
+        },
+        _ => {}
+    }
+}
+
+fn join(path: &str, name: &str) -> String {
+    if path.is_empty() { name.to_owned() } else { format!("{}.{}", path, name) }
+}
+
+
+#[derive(Debug, Default)]
+pub struct StrictReport {
+    pub records: usize,
+    pub rejected_records: usize,
+    pub rejected_at: Vec<Location>,
+    // records each unknown path appeared in
+    pub unknown_paths: BTreeMap<String, usize>
+}
+
+impl fmt::Display for StrictReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "Records: {}", self.records)?;
+        writeln!(f, "Rejected: {}", self.rejected_records)?;
+        for location in &self.rejected_at {
+            writeln!(f, "  at {}", location)?;
+        }
+        let mut paths: Vec<(&String, &usize)> = self.unknown_paths.iter().collect();
+        paths.sort_by(|a, b| b.1.cmp(a.1));
+        if !paths.is_empty() {
+            writeln!(f, "{:<60} {:>10}", "unknown field", "records")?;
+        }
+        for (path, records) in paths {
+            writeln!(f, "{:<60} {:>10}", path, records)?;
+        }
+        Ok(())
+    }
+}
+
+
+// Passes on only records whose fields are all declared by the schema. Others fail the conversion,
+// or go to the dead letter output when there is one, an NDJSON line per record with its source,
+// line, unknown fields and the record itself.
+pub struct StrictWriter<'w, 's> {
+    inner: &'w mut dyn RecordWriter,
+    schema: &'s Schema,
+    dead_letter: Option<&'w mut dyn Write>,
+    location: Location,
+    pub report: StrictReport
+}
+
+impl<'w, 's> StrictWriter<'w, 's> {
+    pub fn new(inner: &'w mut dyn RecordWriter, schema: &'s Schema, dead_letter: Option<&'w mut dyn Write>) -> Self {
+        StrictWriter { inner, schema, dead_letter, location: Location::default(), report: StrictReport::default() }
+    }
+}
+
+impl<'w, 's> RecordWriter for StrictWriter<'w, 's> {
+    fn write(&mut self, record: &JsonValue) -> Result<(), Error> {
+        self.report.records += 1;
+        let mut paths = unknown_paths(record, self.schema);
+        if paths.is_empty() {
+            return self.inner.write(record);
+        }
+
+        paths.sort();
+        paths.dedup();
+        self.report.rejected_records += 1;
+        keep_location(&mut self.report.rejected_at, &self.location);
+        for path in &paths {
+            *self.report.unknown_paths.entry(path.clone()).or_insert(0) += 1;
+        }
+        match &mut self.dead_letter {
+            Some(output) => {
+                let entry = json::object! {
+                    "source" => self.location.source.as_str(),
+                    "line" => self.location.line,
+                    "unknown_fields" => paths,
+                    "record" => record.clone()
+                };
+                writeln!(output, "{}", entry.dump())?;
+                Ok(())
+            },
+            None => bail!("record has fields not in the schema: {}", paths.join(", "))
+        }
+    }
+
+    fn finish(&mut self) -> Result<(), Error> {
+        if let Some(output) = &mut self.dead_letter {
+            output.flush()?;
+        }
+        self.inner.finish()
+    }
+
+    fn locate(&mut self, location: &Location) {
+        self.location = location.clone();
+        self.inner.locate(location);
+    }
+
+    fn full(&self) -> bool {
+        self.inner.full()
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::convert::JsonLinesWriter;
+
+    #[test]
+    fn test_strict_writer() {
+        let schema = Schema::parse_str(r#"{"name":"t","type":"record","fields":[
+            {"name":"id","type":"long"},
+            {"name":"user","type":["null",{"name":"user","type":"record","fields":[{"name":"name","type":"string"}]}]},
+            {"name":"media","type":{"type":"array","items":{"name":"media","type":"record","fields":[{"name":"url","type":"string"}]}}}
+        ]}"#).unwrap();
+        let record = json::parse(r#"{"id":1,"user":{"name":"a","lang":"en"},"media":[{"url":"u","w":1},{"url":"v","w":2}],"x":null}"#).unwrap();
+        assert_eq!(unknown_paths(&record, &schema), vec!["user.lang", "media[].w", "media[].w", "x"]);
+
+        let mut accepted = JsonLinesWriter::new(Vec::new());
+        let mut rejected = Vec::new();
+        {
+            let mut writer = StrictWriter::new(&mut accepted, &schema, Some(&mut rejected));
+            writer.locate(&Location { source: "tweets.json".to_owned(), line: 3 });
+            writer.write(&record).unwrap();
+            writer.write(&json::parse(r#"{"id":2,"user":null,"media":[]}"#).unwrap()).unwrap();
+            assert_eq!(writer.report.rejected_records, 1);
+            assert_eq!(writer.report.unknown_paths["media[].w"], 1);
+        }
+        assert_eq!(String::from_utf8(accepted.into_inner()).unwrap().lines().count(), 1);
+        let rejected = String::from_utf8(rejected).unwrap();
+        assert_eq!(rejected.lines().count(), 1);
+        let entry = json::parse(&rejected).unwrap();
+        assert_eq!((entry["source"].as_str(), entry["line"].as_usize()), (Some("tweets.json"), Some(3)));
+        assert_eq!(entry["record"], record);
+
+        let mut sink = JsonLinesWriter::new(Vec::new());
+        assert!(StrictWriter::new(&mut sink, &schema, None).write(&record).is_err());
+    }
+}
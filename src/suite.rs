@@ -0,0 +1,138 @@
+use std::collections::BTreeMap;
+use std::fs;
+use serde::Deserialize;
+use failure::{Error, format_err};
+use crate::bench::Repetitions;
+use crate::gate::Targets;
+use crate::io::{Decoder, Subset};
+use crate::measure::parse_duration;
+
+
+pub const DEFAULT_SUITES: &str = "benchmarks.toml";
+
+// A comparison run of bench --suite, kept in a file so it can be repeated as it was, e.g.
+//
+//   [suite.parsers]
+//   inputs = ["tweets.json.gz", "logs.json.gz"]
+//   parsers = ["serde", "simd"]
+//   codecs = ["zstd:3"]
+//   runs = 5
+//   limit = 100000
+//   targets = { "parse:simd" = "50MB/s" }
+//
+// What a suite leaves out comes from the command line.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Suite {
+    // each runs the whole suite, none for --input, $INPUT_FILE or the default input
+    #[serde(default)]
+    pub inputs: Vec<String>,
+    // registry names, added to the parse:PARSER and compress:CODEC ones
+    #[serde(default)]
+    pub benchmarks: Vec<String>,
+    #[serde(default)]
+    pub parsers: Vec<String>,
+    #[serde(default)]
+    pub codecs: Vec<String>,
+    pub filter: Option<String>,
+    pub decoder: Option<Decoder>,
+    pub warmup: Option<usize>,
+    pub runs: Option<usize>,
+    pub latency: Option<bool>,
+    // like bench --duration, e.g. "30s"
+    pub duration: Option<String>,
+    // of the lines of every input, like --skip, --limit and --sample-rate
+    pub skip: Option<usize>,
+    pub limit: Option<usize>,
+    pub sample_rate: Option<f64>,
+    // like bench --targets
+    #[serde(default)]
+    pub targets: BTreeMap<String, String>
+}
+
+#[derive(Debug, Deserialize)]
+struct Suites {
+    #[serde(rename = "suite", default)]
+    suites: BTreeMap<String, Suite>
+}
+
+pub fn load(path: &str, name: &str) -> Result<Suite, Error> {
+    let text = fs::read_to_string(path).map_err(|e| format_err!("suites {}: {}", path, e))?;
+    let mut suites: Suites = toml::from_str(&text).map_err(|e| format_err!("suites {}: {}", path, e))?;
+    let names: Vec<&str> = suites.suites.keys().map(|name| name.as_str()).collect();
+    let known = names.join(", ");
+    suites.suites.remove(name).ok_or_else(|| format_err!("no suite {} in {}, expected one of {}", name, path, known))
+}
+
+impl Suite {
+    pub fn repetitions(&self, repetitions: Repetitions) -> Result<Repetitions, Error> {
+        Ok(Repetitions {
+            warmup: self.warmup.unwrap_or(repetitions.warmup),
+            runs: self.runs.unwrap_or(repetitions.runs),
+            latency: self.latency.unwrap_or(repetitions.latency),
+            duration: match &self.duration {
+                Some(duration) => Some(parse_duration(duration)?),
+                None => repetitions.duration
+            }
+        })
+    }
+
+    pub fn subset(&self, subset: Subset) -> Subset {
+        Subset {
+            skip: self.skip.unwrap_or(subset.skip),
+            limit: self.limit.or(subset.limit),
+            sample_rate: self.sample_rate.unwrap_or(subset.sample_rate),
+            seed: subset.seed
+        }
+    }
+
+    // under those of the command line
+    pub fn targets(&self) -> Result<Targets, Error> {
+        self.targets
+            .iter()
+            .map(|(name, target)| Ok((if name == "*" { String::new() } else { name.clone() }, target.parse()?)))
+            .collect()
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::time::Duration;
+    use crate::gate::Target;
+
+    #[test]
+    fn test_suite() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join(DEFAULT_SUITES).to_string_lossy().into_owned();
+        fs::write(&path, r#"
+            [suite.parsers]
+            inputs = ["a.json.gz", "b.json"]
+            parsers = ["serde", "simd"]
+            runs = 5
+            duration = "2s"
+            limit = 1000
+            targets = { "parse:simd" = "50MB/s", "*" = "100ms" }
+
+            [suite.quick]
+            benchmarks = ["read"]
+        "#).unwrap();
+        let suite = load(&path, "parsers").unwrap();
+        assert_eq!((suite.inputs.len(), suite.parsers.len(), suite.codecs.len()), (2, 2, 0));
+
+        let repetitions = suite.repetitions(Repetitions { warmup: 2, ..Repetitions::default() }).unwrap();
+        assert_eq!((repetitions.warmup, repetitions.runs, repetitions.duration), (2, 5, Some(Duration::from_secs(2))));
+        let subset = suite.subset(Subset { skip: 10, ..Subset::default() });
+        assert_eq!((subset.skip, subset.limit), (10, Some(1000)));
+        let targets = suite.targets().unwrap();
+        assert_eq!(targets["parse:simd"], Target::MegabytesPerSecond(50.0));
+        assert_eq!(targets[""], Target::Millis(100.0));
+
+        assert_eq!(load(&path, "quick").unwrap().repetitions(Repetitions::default()).unwrap(), Repetitions::default());
+        let missing = load(&path, "nope").unwrap_err().to_string();
+        assert!(missing.contains("expected one of parsers, quick"), "{}", missing);
+        fs::write(&path, "[suite.typo]\nparser = [\"serde\"]\n").unwrap();
+        assert!(load(&path, "typo").is_err());
+    }
+}
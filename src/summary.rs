@@ -0,0 +1,144 @@
+use std::fmt;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use lazy_static::lazy_static;
+use serde::Serialize;
+use failure::Error;
+
+
+// What a command did, printed when it ends so that wrapping scripts get counters instead of
+// parsing the free form output: a block on stderr, and with --summary-json the same as JSON.
+// Counters a command has no notion of stay null, e.g. dead-lettered records of infer.
+#[derive(Debug, Default, Clone, PartialEq, Serialize)]
+pub struct Summary {
+    pub command: String,
+    pub exit_code: i32,
+    pub records_read: Option<u64>,
+    pub records_parsed: Option<u64>,
+    // left out of the output, e.g. unrepairable ones with --repair
+    pub records_skipped: Option<u64>,
+    // written to the output
+    pub records_converted: Option<u64>,
+    pub records_dead_lettered: Option<u64>,
+    // of the input and output files as on disk
+    pub bytes_in: Option<u64>,
+    pub bytes_out: Option<u64>,
+    // in the order they ran
+    pub stages: Vec<Stage>,
+    pub elapsed_ms: f64
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Stage {
+    pub name: String,
+    pub elapsed_ms: f64
+}
+
+lazy_static! {
+    static ref SUMMARY: Mutex<Summary> = Mutex::new(Summary::default());
+}
+
+fn millis(elapsed: Duration) -> f64 {
+    elapsed.as_secs_f64() * 1000.0
+}
+
+pub fn update(change: impl FnOnce(&mut Summary)) {
+    change(&mut SUMMARY.lock().unwrap());
+}
+
+pub fn stage(name: &str, elapsed: Duration) {
+    update(|summary| summary.stages.push(Stage { name: name.to_owned(), elapsed_ms: millis(elapsed) }));
+}
+
+// Runs a stage of the command and records its time.
+pub fn timed<T>(name: &str, run: impl FnOnce() -> T) -> T {
+    let now = Instant::now();
+    let result = run();
+    stage(name, now.elapsed());
+    result
+}
+
+// for bytes_in and bytes_out, None for what is not a file
+pub fn file_size(path: &str) -> Option<u64> {
+    std::fs::metadata(path).ok().map(|metadata| metadata.len())
+}
+
+// Reading and parsing went together, as in convert::convert, which stops at the first line that
+// does not parse.
+pub fn read_and_parsed(records: usize) {
+    update(|summary| {
+        summary.records_read = Some(records as u64);
+        summary.records_parsed = Some(records as u64);
+    });
+}
+
+// Takes what the command recorded, at its end.
+pub fn finish(command: &str, exit_code: i32, elapsed: Duration) -> Summary {
+    let mut summary = std::mem::take(&mut *SUMMARY.lock().unwrap());
+    summary.command = command.to_owned();
+    summary.exit_code = exit_code;
+    summary.elapsed_ms = millis(elapsed);
+    summary
+}
+
+// - for stdout
+pub fn write_json(summary: &Summary, path: &str) -> Result<(), Error> {
+    if path == "-" {
+        println!("{}", serde_json::to_string(summary)?);
+    } else {
+        std::fs::write(path, serde_json::to_string_pretty(summary)?)?;
+    }
+    Ok(())
+}
+
+impl fmt::Display for Summary {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "--- {} summary, exit code {} ---", self.command, self.exit_code)?;
+        let counters = [
+            ("records read", self.records_read),
+            ("records parsed", self.records_parsed),
+            ("records skipped", self.records_skipped),
+            ("records converted", self.records_converted),
+            ("records dead-lettered", self.records_dead_lettered),
+            ("bytes in", self.bytes_in),
+            ("bytes out", self.bytes_out)
+        ];
+        for (name, value) in counters.iter() {
+            if let Some(value) = value {
+                writeln!(f, "{:<24} {:>14}", name, value)?;
+            }
+        }
+        for stage in &self.stages {
+            writeln!(f, "{:<24} {:>11.1} ms", stage.name, stage.elapsed_ms)?;
+        }
+        writeln!(f, "{:<24} {:>11.1} ms", "elapsed", self.elapsed_ms)
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_summary() {
+        update(|summary| summary.records_converted = Some(3));
+        read_and_parsed(4);
+        let records: u64 = timed("inference", || 1);
+        assert_eq!(records, 1);
+        let summary = finish("convert", 0, Duration::from_millis(12));
+        assert_eq!((summary.records_read, summary.records_converted, summary.records_skipped), (Some(4), Some(3), None));
+        assert_eq!(summary.stages[0].name, "inference");
+        // taken, the next command starts over
+        assert_eq!(finish("infer", 0, Duration::default()).records_read, None);
+
+        let text = summary.to_string();
+        assert!(text.starts_with("--- convert summary, exit code 0 ---\n"), "{}", text);
+        assert!(text.contains("records converted"), "{}", text);
+        assert!(!text.contains("skipped"), "{}", text);
+        let json: serde_json::Value = serde_json::to_value(&summary).unwrap();
+        assert_eq!(json["records_converted"], 3);
+        assert!(json["records_skipped"].is_null());
+        assert_eq!(json["elapsed_ms"], 12.0);
+    }
+}
@@ -0,0 +1,179 @@
+use std::time::{Duration, Instant};
+use json::JsonValue;
+use avro_rs::{Codec, Schema};
+use failure::{Error, bail, format_err};
+use crate::convert::{AvroJsonWriter, AvroWriter, JsonLinesWriter, Location, RecordWriter};
+use crate::compress::find_compressor;
+use crate::io::OutputFile;
+
+
+// what archive writes when no --format is given
+pub const DEFAULT_FORMATS: [&str; 5] = ["avro:null", "avro:deflate", "avro-json", "ndjson", "ndjson:zstd"];
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Format {
+    Avro(Codec),
+    AvroJson,
+    // compressor and level, the compressor's default level when not given
+    JsonLines(Option<(String, Option<i32>)>),
+    #[cfg(feature = "orc")]
+    Orc
+}
+
+// avro:null, avro:deflate, avro-json, ndjson, ndjson:COMPRESSOR[:LEVEL] or orc
+pub fn parse_format(spec: &str) -> Result<Format, Error> {
+    let parts: Vec<&str> = spec.split(':').collect();
+    Ok(match parts.as_slice() {
+        ["avro", "null"] => Format::Avro(Codec::Null),
+        ["avro", "deflate"] | ["avro"] => Format::Avro(Codec::Deflate),
+        ["avro-json"] => Format::AvroJson,
+        ["ndjson"] => Format::JsonLines(None),
+        ["ndjson", compressor] | ["ndjson", compressor, _] => {
+            if find_compressor(compressor).is_none() {
+                bail!("unknown compressor {} in {}", compressor, spec);
+            }
+            let level = match parts.get(2) {
+                Some(level) => Some(level.parse().map_err(|_| format_err!("invalid level in {}", spec))?),
+                None => None
+            };
+            Format::JsonLines(Some((compressor.to_string(), level)))
+        },
+        #[cfg(feature = "orc")]
+        ["orc"] => Format::Orc,
+        #[cfg(not(feature = "orc"))]
+        ["orc"] => bail!("orc output needs a build with the orc feature"),
+        _ => bail!("unknown format {}, expected avro:null, avro:deflate, avro-json, ndjson[:COMPRESSOR[:LEVEL]] or orc", spec)
+    })
+}
+
+impl Format {
+    // appended to the output prefix, distinct for every format
+    pub fn extension(&self) -> String {
+        match self {
+            Format::Avro(Codec::Null) => "null.avro".to_owned(),
+            Format::Avro(_) => "deflate.avro".to_owned(),
+            Format::AvroJson => "avro.json".to_owned(),
+            Format::JsonLines(None) => "ndjson".to_owned(),
+            Format::JsonLines(Some((compressor, None))) => format!("ndjson.{}", compressor),
+            Format::JsonLines(Some((compressor, Some(level)))) => format!("ndjson.{}-{}", compressor, level),
+            #[cfg(feature = "orc")]
+            Format::Orc => "orc".to_owned()
+        }
+    }
+
+    pub fn create<'s>(&self, schema: &'s Schema, path: &str) -> Result<Box<dyn RecordWriter + 's>, Error> {
+        Ok(match self {
+            Format::Avro(codec) => Box::new(AvroWriter::new(schema, OutputFile::create(path, false)?, *codec)),
+            Format::AvroJson => Box::new(AvroJsonWriter::new(schema, OutputFile::create(path, false)?)),
+            Format::JsonLines(None) => Box::new(JsonLinesFile(Some(JsonLinesWriter::new(OutputFile::create(path, false)?)))),
+            Format::JsonLines(Some((name, level))) => {
+                let compressor = find_compressor(name).ok_or_else(|| format_err!("unknown compressor {}", name))?;
+                let level = level.unwrap_or_else(|| compressor.default_level());
+                Box::new(JsonLinesFile(Some(JsonLinesWriter::new(OutputFile::with_compressor(path, compressor.as_ref(), level)?))))
+            },
+            #[cfg(feature = "orc")]
+            Format::Orc => Box::new(crate::orc::OrcWriter::new(schema, path)?)
+        })
+    }
+}
+
+// JsonLinesWriter only flushes, the stream encoders write their trailer on OutputFile::finish.
+struct JsonLinesFile(Option<JsonLinesWriter<OutputFile>>);
+
+impl RecordWriter for JsonLinesFile {
+    fn write(&mut self, record: &JsonValue) -> Result<(), Error> {
+        self.0.as_mut().ok_or_else(|| format_err!("output already finished"))?.write(record)
+    }
+
+    fn finish(&mut self) -> Result<(), Error> {
+        if let Some(writer) = self.0.take() {
+            writer.into_inner().finish()?;
+        }
+        Ok(())
+    }
+}
+
+
+pub struct TeeSink<'w> {
+    pub name: String,
+    writer: Box<dyn RecordWriter + 'w>,
+    // spent in this sink's write and finish, encoding and compression included
+    pub elapsed: Duration
+}
+
+// Writes every record to all of its sinks, so one parse and convert pass produces the same records
+// in all formats. Full as soon as one sink is, to keep the outputs holding the same records.
+#[derive(Default)]
+pub struct TeeWriter<'w> {
+    pub sinks: Vec<TeeSink<'w>>
+}
+
+impl<'w> TeeWriter<'w> {
+    pub fn new() -> Self {
+        TeeWriter::default()
+    }
+
+    pub fn add(&mut self, name: &str, writer: Box<dyn RecordWriter + 'w>) {
+        self.sinks.push(TeeSink { name: name.to_owned(), writer, elapsed: Duration::default() });
+    }
+}
+
+impl<'w> RecordWriter for TeeWriter<'w> {
+    fn write(&mut self, record: &JsonValue) -> Result<(), Error> {
+        for sink in &mut self.sinks {
+            let now = Instant::now();
+            sink.writer.write(record).map_err(|e| format_err!("{}: {}", sink.name, e))?;
+            sink.elapsed += now.elapsed();
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<(), Error> {
+        for sink in &mut self.sinks {
+            let now = Instant::now();
+            sink.writer.finish().map_err(|e| format_err!("{}: {}", sink.name, e))?;
+            sink.elapsed += now.elapsed();
+        }
+        Ok(())
+    }
+
+    fn locate(&mut self, location: &Location) {
+        for sink in &mut self.sinks {
+            sink.writer.locate(location);
+        }
+    }
+
+    fn full(&self) -> bool {
+        self.sinks.iter().any(|sink| sink.writer.full())
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_tee() {
+        assert_eq!(parse_format("avro:null").unwrap(), Format::Avro(Codec::Null));
+        assert_eq!(parse_format("ndjson:zstd:19").unwrap().extension(), "ndjson.zstd-19");
+        assert!(parse_format("ndjson:rar").is_err());
+        assert!(parse_format("parquet").is_err());
+        assert_eq!(parse_format("orc").is_ok(), cfg!(feature = "orc"));
+        let extensions: Vec<String> = DEFAULT_FORMATS.iter().map(|spec| parse_format(spec).unwrap().extension()).collect();
+        assert_eq!(extensions, vec!["null.avro", "deflate.avro", "avro.json", "ndjson", "ndjson.zstd"]);
+
+        let (mut first, mut second) = (Vec::new(), Vec::new());
+        {
+            let mut writer = TeeWriter::new();
+            writer.add("first", Box::new(JsonLinesWriter::new(&mut first)));
+            writer.add("second", Box::new(JsonLinesWriter::new(&mut second)));
+            writer.write(&json::parse(r#"{"a":1}"#).unwrap()).unwrap();
+            writer.write(&json::parse(r#"{"a":2}"#).unwrap()).unwrap();
+            writer.finish().unwrap();
+            assert!(!writer.full());
+        }
+        assert_eq!(first, second);
+        assert_eq!(String::from_utf8(first).unwrap(), "{\"a\":1}\n{\"a\":2}\n");
+    }
+}
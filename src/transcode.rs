@@ -0,0 +1,224 @@
+use std::fmt;
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::time::{Duration, Instant};
+use flate2::Compression;
+use flate2::read::MultiGzDecoder;
+use flate2::write::GzEncoder;
+use sha2::{Digest, Sha256};
+use failure::{Error, bail, format_err};
+use crate::compress::{Compressor, find_compressor};
+use crate::io::OutputFile;
+
+
+// How an NDJSON file is compressed: not at all, gzip as for the benchmark inputs, or a stream of
+// one of the compressors.
+pub enum Format {
+    Plain,
+    Gzip,
+    Compressed(Box<dyn Compressor>)
+}
+
+impl fmt::Debug for Format {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+// none, gzip or a compressor name
+pub fn parse_format(name: &str) -> Result<Format, Error> {
+    match name {
+        "none" => Ok(Format::Plain),
+        "gzip" => Ok(Format::Gzip),
+        _ => find_compressor(name)
+            .map(Format::Compressed)
+            .ok_or_else(|| format_err!("unknown format {}, expected none, gzip or a compressor", name))
+    }
+}
+
+// From the file extension, uncompressed when it is none of the known ones.
+pub fn format_of(file_path: &str) -> Format {
+    let compressor = match file_path.rsplit('.').next().unwrap_or_default() {
+        "gz" => return Format::Gzip,
+        "zst" => "zstd",
+        "br" => "brotli",
+        "lz4" => "lz4",
+        "sz" => "snappy",
+        "deflate" => "flate2",
+        _ => return Format::Plain
+    };
+    find_compressor(compressor).map_or(Format::Plain, Format::Compressed)
+}
+
+impl Format {
+    pub fn name(&self) -> &str {
+        match self {
+            Format::Plain => "none",
+            Format::Gzip => "gzip",
+            Format::Compressed(compressor) => compressor.name()
+        }
+    }
+
+    pub fn open(&self, file_path: &str) -> Result<Box<dyn BufRead>, Error> {
+        let file = File::open(file_path).map_err(|e| format_err!("{}: {}", file_path, e))?;
+        Ok(match self {
+            Format::Plain => Box::new(BufReader::new(file)),
+            Format::Gzip => Box::new(BufReader::new(MultiGzDecoder::new(file))),
+            Format::Compressed(compressor) => Box::new(BufReader::new(compressor.decompress_stream(Box::new(BufReader::new(file)))?))
+        })
+    }
+
+    // at the format's default level when not given
+    pub fn create(&self, file_path: &str, level: Option<i32>) -> Result<OutputFile, Error> {
+        Ok(match (self, level) {
+            (Format::Plain, None) => OutputFile::create(file_path, false)?,
+            (Format::Plain, Some(_)) => bail!("uncompressed output has no level"),
+            (Format::Gzip, None) => OutputFile::create(file_path, true)?,
+            (Format::Gzip, Some(level)) if (0..=9).contains(&level) => {
+                OutputFile::Gzip(GzEncoder::new(BufWriter::new(File::create(file_path)?), Compression::new(level as u32)))
+            },
+            (Format::Gzip, Some(level)) => bail!("gzip level {} is not in 0..=9", level),
+            (Format::Compressed(compressor), level) => {
+                let level = level.unwrap_or_else(|| compressor.default_level());
+                if !compressor.levels().contains(&level) {
+                    bail!("{} level {} is not in {:?}", compressor.name(), level, compressor.levels());
+                }
+                OutputFile::with_compressor(file_path, compressor.as_ref(), level)?
+            }
+        })
+    }
+}
+
+
+// Record count and SHA-256 over the records, each with a newline, so files that only differ in
+// compression or in \r\n line ends digest the same.
+#[derive(Clone, Default)]
+pub struct ContentDigest {
+    pub records: u64,
+    // decompressed, newlines included
+    pub bytes: u64,
+    hasher: Sha256
+}
+
+impl ContentDigest {
+    pub fn update(&mut self, line: &str) {
+        self.records += 1;
+        self.bytes += line.len() as u64 + 1;
+        self.hasher.input(line.as_bytes());
+        self.hasher.input(b"\n");
+    }
+
+    pub fn hash(&self) -> String {
+        format!("{:x}", self.hasher.clone().result())
+    }
+}
+
+impl PartialEq for ContentDigest {
+    fn eq(&self, other: &Self) -> bool {
+        self.records == other.records && self.hash() == other.hash()
+    }
+}
+
+impl fmt::Display for ContentDigest {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} records, sha256 {}", self.records, self.hash())
+    }
+}
+
+impl fmt::Debug for ContentDigest {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self)
+    }
+}
+
+pub fn digest(lines: Box<dyn BufRead>) -> Result<ContentDigest, Error> {
+    let mut digest = ContentDigest::default();
+    for line in lines.lines() {
+        digest.update(&line?);
+    }
+    Ok(digest)
+}
+
+
+#[derive(Debug)]
+pub struct Transcoded {
+    pub written: ContentDigest,
+    // of reading the output back, None with verification off
+    pub verified: Option<ContentDigest>,
+    pub input_bytes: u64,
+    pub output_bytes: u64,
+    pub elapsed: Duration,
+    pub verify_elapsed: Duration
+}
+
+impl Transcoded {
+    pub fn verified(&self) -> bool {
+        self.verified.as_ref().map_or(true, |verified| *verified == self.written)
+    }
+}
+
+impl fmt::Display for Transcoded {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let seconds = self.elapsed.as_secs_f64().max(1e-9);
+        writeln!(f, "Wrote {}", self.written)?;
+        writeln!(f, "{} -> {} bytes ({:.2}x), {:.1} MB/s of NDJSON in {} ms", self.input_bytes, self.output_bytes,
+                 self.input_bytes as f64 / self.output_bytes.max(1) as f64, self.written.bytes as f64 / seconds / 1e6, self.elapsed.as_millis())?;
+        match &self.verified {
+            Some(verified) if *verified == self.written => writeln!(f, "Verified: read back the same {} records in {} ms", verified.records, self.verify_elapsed.as_millis()),
+            Some(verified) => writeln!(f, "VERIFICATION FAILED, read back {}", verified),
+            None => writeln!(f, "Not verified")
+        }
+    }
+}
+
+// Streams the records of the input into the output in another compression, then reads the output
+// back with its own decoder and compares record count and hash with what was written.
+pub fn transcode(input: &str, from: &Format, output: &str, to: &Format, level: Option<i32>, verify: bool) -> Result<Transcoded, Error> {
+    let now = Instant::now();
+    let mut written = ContentDigest::default();
+    let mut writer = to.create(output, level)?;
+    for line in from.open(input)?.lines() {
+        let line = line.map_err(|e| format_err!("{}:{}: {}", input, written.records + 1, e))?;
+        writer.write_all(line.as_bytes())?;
+        writer.write_all(b"\n")?;
+        written.update(&line);
+    }
+    writer.finish()?;
+    let elapsed = now.elapsed();
+
+    let now = Instant::now();
+    let verified = if verify { Some(digest(to.open(output)?)?) } else { None };
+    Ok(Transcoded {
+        written,
+        verified,
+        input_bytes: std::fs::metadata(input)?.len(),
+        output_bytes: std::fs::metadata(output)?.len(),
+        elapsed,
+        verify_elapsed: now.elapsed()
+    })
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_transcode() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = |name: &str| dir.path().join(name).to_string_lossy().into_owned();
+        std::fs::write(path("records.json"), "{\"a\":1}\r\n{\"a\":2}\n{\"a\":3}").unwrap();
+        assert_eq!(format_of("tweets.json.gz").name(), "gzip");
+        assert_eq!(format_of("tweets.json.zst").name(), "zstd");
+        assert_eq!(format_of("tweets.json").name(), "none");
+        assert!(parse_format("rar").is_err());
+
+        let gzip = transcode(&path("records.json"), &Format::Plain, &path("records.json.gz"), &Format::Gzip, Some(9), true).unwrap();
+        assert!(gzip.verified(), "{}", gzip);
+        assert_eq!(gzip.written.records, 3);
+        let zstd = transcode(&path("records.json.gz"), &Format::Gzip, &path("records.json.zst"), &format_of("out.zst"), None, true).unwrap();
+        assert!(zstd.verified(), "{}", zstd);
+        assert_eq!(zstd.written, gzip.written);
+        assert!(Format::Plain.create(&path("plain.json"), Some(3)).is_err());
+    }
+}
@@ -0,0 +1,354 @@
+use std::collections::HashMap;
+use avro_rs::Schema;
+use json::JsonValue;
+use regex::Regex;
+use failure::{Error, bail, format_err};
+use crate::convert::{Location, RecordWriter};
+use crate::io::open_lines;
+use crate::path::{lookup, lookup_mut};
+use crate::protect::{Protect, Protection, Unprotect};
+
+
+// A reshaping step between parsing and the output backend. A transform may drop a record or fan it
+// out into several, so it returns all records that replace the input.
+pub trait RecordTransform {
+    fn name(&self) -> &'static str;
+    fn apply(&self, record: JsonValue) -> Result<Vec<JsonValue>, Error>;
+
+    // adjusts the schema inferred from the transformed records, see Protect
+    fn annotate(&self, schema: Schema) -> Result<Schema, Error> {
+        Ok(schema)
+    }
+}
+
+
+// Rewrites object keys at every depth that match the pattern, e.g. "^_" -> "" to strip a prefix.
+pub struct RenameKeys {
+    pattern: Regex,
+    replacement: String
+}
+
+impl RenameKeys {
+    pub fn new(pattern: &str, replacement: &str) -> Result<Self, Error> {
+        Ok(RenameKeys { pattern: Regex::new(pattern)?, replacement: replacement.to_owned() })
+    }
+
+    fn rename(&self, value: JsonValue) -> JsonValue {
+        match value {
+            JsonValue::Object(object) => {
+                let mut renamed = JsonValue::new_object();
+                for (key, value) in object.iter() {
+                    let key = self.pattern.replace_all(key, self.replacement.as_str());
+                    renamed[key.as_ref()] = self.rename(value.clone());
+                }
+                renamed
+            },
+            JsonValue::Array(items) => JsonValue::Array(items.into_iter().map(|item| self.rename(item)).collect()),
+            other => other
+        }
+    }
+}
+
+impl RecordTransform for RenameKeys {
+    fn name(&self) -> &'static str {
+        "rename"
+    }
+
+    fn apply(&self, record: JsonValue) -> Result<Vec<JsonValue>, Error> {
+        Ok(vec![self.rename(record)])
+    }
+}
+
+
+// Lifts nested object fields to the top level as "a.b.c" keys. Arrays are kept as values, explode
+// them first to flatten their items.
+pub struct Flatten {
+    separator: String
+}
+
+impl Flatten {
+    pub fn new(separator: &str) -> Self {
+        Flatten { separator: separator.to_owned() }
+    }
+
+    fn flatten_into(&self, prefix: &str, value: &JsonValue, output: &mut JsonValue) {
+        match value {
+            JsonValue::Object(object) if !object.is_empty() => {
+                for (key, value) in object.iter() {
+                    let key = if prefix.is_empty() { key.to_owned() } else { format!("{}{}{}", prefix, self.separator, key) };
+                    self.flatten_into(&key, value, output);
+                }
+            },
+            other => output[prefix] = other.clone()
+        }
+    }
+}
+
+impl RecordTransform for Flatten {
+    fn name(&self) -> &'static str {
+        "flatten"
+    }
+
+    fn apply(&self, record: JsonValue) -> Result<Vec<JsonValue>, Error> {
+        if !record.is_object() || record.is_empty() {
+            return Ok(vec![record]);
+        }
+        let mut flat = JsonValue::new_object();
+        self.flatten_into("", &record, &mut flat);
+        Ok(vec![flat])
+    }
+}
+
+
+// One output record per item of the array at a dotted path, with the item in place of the array.
+// Records where the array is empty are dropped and records without it pass unchanged.
+pub struct Explode {
+    path: String
+}
+
+impl Explode {
+    pub fn new(path: &str) -> Self {
+        Explode { path: path.to_owned() }
+    }
+}
+
+impl RecordTransform for Explode {
+    fn name(&self) -> &'static str {
+        "explode"
+    }
+
+    fn apply(&self, mut record: JsonValue) -> Result<Vec<JsonValue>, Error> {
+        let items = match lookup_mut(&mut record, &self.path) {
+            Some(value) if value.is_array() => value.take(),
+            Some(value) if value.is_null() => return Ok(vec![record]),
+            Some(_) => bail!("{} is not an array", self.path),
+            None => return Ok(vec![record])
+        };
+        let mut exploded = Vec::with_capacity(items.len());
+        for item in items.members() {
+            let mut copy = record.clone();
+            if let Some(value) = lookup_mut(&mut copy, &self.path) {
+                *value = item.clone();
+            }
+            exploded.push(copy);
+        }
+        Ok(exploded)
+    }
+}
+
+
+// A left join against a lookup table held in memory, the dimension join of an ingestion pipeline.
+// The fields of the row whose key column equals the value at the path are added next to that value,
+// fields the record already has win. Records without a matching row pass unchanged.
+pub struct Enrich {
+    path: String,
+    rows: HashMap<String, JsonValue>
+}
+
+impl Enrich {
+    // NDJSON (optionally gzipped) or, for .csv files, CSV with a header row; CSV cells are strings
+    // and empty cells null
+    pub fn from_file(path: &str, file_path: &str, key_column: &str) -> Result<Self, Error> {
+        let mut rows = HashMap::new();
+        if file_path.ends_with(".csv") {
+            let mut reader = csv::Reader::from_path(file_path)?;
+            let headers = reader.headers()?.clone();
+            for row in reader.records() {
+                let mut object = JsonValue::new_object();
+                for (header, cell) in headers.iter().zip(row?.iter()) {
+                    object[header] = if cell.is_empty() { JsonValue::Null } else { JsonValue::from(cell) };
+                }
+                Enrich::insert(&mut rows, object, key_column)?;
+            }
+        } else {
+            for line in open_lines(file_path)? {
+                Enrich::insert(&mut rows, json::parse(&line?)?, key_column)?;
+            }
+        }
+        Ok(Enrich { path: path.to_owned(), rows })
+    }
+
+    fn insert(rows: &mut HashMap<String, JsonValue>, mut row: JsonValue, key_column: &str) -> Result<(), Error> {
+        let key = match join_key(&row[key_column]) {
+            Some(key) => key,
+            None => bail!("lookup row without {}: {}", key_column, row.dump())
+        };
+        row.remove(key_column);
+        rows.insert(key, row);
+        Ok(())
+    }
+}
+
+// Strings and numbers join on their text, so "42" in a CSV matches 42 in the record.
+fn join_key(value: &JsonValue) -> Option<String> {
+    match value {
+        JsonValue::Short(_) | JsonValue::String(_) => value.as_str().map(|key| key.to_owned()),
+        JsonValue::Number(_) | JsonValue::Boolean(_) => Some(value.dump()),
+        _ => None
+    }
+}
+
+impl RecordTransform for Enrich {
+    fn name(&self) -> &'static str {
+        "enrich"
+    }
+
+    fn apply(&self, mut record: JsonValue) -> Result<Vec<JsonValue>, Error> {
+        let row = match join_key(lookup(&record, &self.path)).and_then(|key| self.rows.get(&key)) {
+            Some(row) => row,
+            None => return Ok(vec![record])
+        };
+        let parent = match self.path.rfind('.') {
+            Some(end) => lookup_mut(&mut record, &self.path[..end]),
+            None => Some(&mut record)
+        };
+        if let Some(parent) = parent {
+            for (key, value) in row.entries() {
+                if !parent.has_key(key) {
+                    parent[key] = value.clone();
+                }
+            }
+        }
+        Ok(vec![record])
+    }
+}
+
+
+// "rename:PATTERN=REPLACEMENT", "flatten", "flatten:SEPARATOR", "explode:PATH",
+// "enrich:PATH=FILE[,KEY_COLUMN]", the key column defaulting to the last segment of the path, or
+// "encrypt:PATH[,PATH..]=KEY_FILE" and "hmac:PATH[,PATH..]=KEY_FILE" with a hex key, and
+// "decrypt:PATH[,PATH..]=KEY_FILE" reversing encrypt
+pub fn parse_transform(spec: &str) -> Result<Box<dyn RecordTransform>, Error> {
+    let mut parts = spec.splitn(2, ':');
+    let kind = parts.next().unwrap_or("");
+    let argument = parts.next();
+    match (kind, argument) {
+        ("rename", Some(argument)) => {
+            let mut rule = argument.splitn(2, '=');
+            let pattern = rule.next().unwrap_or("");
+            let replacement = rule.next().ok_or_else(|| format_err!("rename needs PATTERN=REPLACEMENT, got {}", argument))?;
+            Ok(Box::new(RenameKeys::new(pattern, replacement)?))
+        },
+        ("flatten", separator) => Ok(Box::new(Flatten::new(separator.unwrap_or(".")))),
+        ("explode", Some(path)) => Ok(Box::new(Explode::new(path))),
+        ("enrich", Some(argument)) => {
+            let mut join = argument.splitn(2, '=');
+            let path = join.next().unwrap_or("");
+            let lookup = join.next().ok_or_else(|| format_err!("enrich needs PATH=FILE, got {}", argument))?;
+            let mut lookup = lookup.splitn(2, ',');
+            let file_path = lookup.next().unwrap_or("");
+            let key_column = lookup.next().unwrap_or_else(|| path.rsplit('.').next().unwrap_or(path));
+            Ok(Box::new(Enrich::from_file(path, file_path, key_column)?))
+        },
+        ("encrypt", Some(argument)) | ("hmac", Some(argument)) => {
+            let protection = if kind == "encrypt" { Protection::Encrypt } else { Protection::Hmac };
+            let mut rule = argument.splitn(2, '=');
+            let paths: Vec<&str> = rule.next().unwrap_or("").split(',').filter(|path| !path.is_empty()).collect();
+            let key_file = rule.next().ok_or_else(|| format_err!("{} needs PATH[,PATH..]=KEY_FILE, got {}", kind, argument))?;
+            Ok(Box::new(Protect::from_key_file(protection, &paths, key_file)?))
+        },
+        ("decrypt", Some(argument)) => {
+            let mut rule = argument.splitn(2, '=');
+            let paths: Vec<&str> = rule.next().unwrap_or("").split(',').filter(|path| !path.is_empty()).collect();
+            let key_file = rule.next().ok_or_else(|| format_err!("decrypt needs PATH[,PATH..]=KEY_FILE, got {}", argument))?;
+            Ok(Box::new(Unprotect::from_key_file(&paths, key_file)?))
+        },
+        _ => Err(format_err!("unknown transform {}", spec))
+    }
+}
+
+pub fn apply_all(transforms: &[Box<dyn RecordTransform>], record: JsonValue) -> Result<Vec<JsonValue>, Error> {
+    let mut records = vec![record];
+    for transform in transforms {
+        let mut next = Vec::with_capacity(records.len());
+        for record in records {
+            next.extend(transform.apply(record).map_err(|e| format_err!("{}: {}", transform.name(), e))?);
+        }
+        records = next;
+    }
+    Ok(records)
+}
+
+
+// Runs the transforms in order in front of another writer. The schema of transformed output has to
+// be inferred through the same transforms, SchemaInferrer is a RecordWriter for that.
+pub struct TransformingWriter<'w, 't> {
+    inner: &'w mut dyn RecordWriter,
+    transforms: &'t [Box<dyn RecordTransform>],
+    pub written: usize
+}
+
+impl<'w, 't> TransformingWriter<'w, 't> {
+    pub fn new(inner: &'w mut dyn RecordWriter, transforms: &'t [Box<dyn RecordTransform>]) -> Self {
+        TransformingWriter { inner, transforms, written: 0 }
+    }
+}
+
+impl<'w, 't> RecordWriter for TransformingWriter<'w, 't> {
+    fn write(&mut self, record: &JsonValue) -> Result<(), Error> {
+        for record in apply_all(self.transforms, record.clone())? {
+            self.inner.write(&record)?;
+            self.written += 1;
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<(), Error> {
+        self.inner.finish()
+    }
+
+    fn locate(&mut self, location: &Location) {
+        self.inner.locate(location);
+    }
+
+    fn full(&self) -> bool {
+        self.inner.full()
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_transforms() {
+        let record = json::parse(r#"{"_id":1,"user":{"_name":"a","geo":{"lat":1.5}},"tags":["x","y"]}"#).unwrap();
+        let transforms = vec![
+            parse_transform("rename:^_=").unwrap(),
+            parse_transform("explode:tags").unwrap(),
+            parse_transform("flatten").unwrap()
+        ];
+        let records = apply_all(&transforms, record).unwrap();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].dump(), r#"{"id":1,"user.name":"a","user.geo.lat":1.5,"tags":"x"}"#);
+        assert_eq!(records[1]["tags"], "y");
+
+        let empty = json::parse(r#"{"tags":[]}"#).unwrap();
+        assert!(apply_all(&transforms, empty).unwrap().is_empty());
+        assert!(parse_transform("explode").is_err());
+        let scalar = json::parse(r#"{"tags":1}"#).unwrap();
+        assert_eq!(apply_all(&transforms, scalar).unwrap_err().to_string(), "explode: tags is not an array");
+    }
+
+    #[test]
+    fn test_enrich() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let users = dir.path().join("users.csv").to_string_lossy().into_owned();
+        std::fs::write(&users, "id,country,tier\n1,NL,gold\n2,TH,\n").unwrap();
+        let enrich = parse_transform(&format!("enrich:user.id={}", users)).unwrap();
+
+        let record = json::parse(r#"{"user":{"id":1,"tier":"silver"}}"#).unwrap();
+        assert_eq!(enrich.apply(record).unwrap()[0].dump(), r#"{"user":{"id":1,"tier":"silver","country":"NL"}}"#);
+        let record = json::parse(r#"{"user":{"id":"2"}}"#).unwrap();
+        assert_eq!(enrich.apply(record).unwrap()[0].dump(), r#"{"user":{"id":"2","country":"TH","tier":null}}"#);
+        let record = json::parse(r#"{"user":{"id":3}}"#).unwrap();
+        assert_eq!(enrich.apply(record).unwrap()[0].dump(), r#"{"user":{"id":3}}"#);
+
+        let countries = dir.path().join("countries.json").to_string_lossy().into_owned();
+        std::fs::write(&countries, "{\"code\":\"NL\",\"name\":\"Netherlands\"}\n").unwrap();
+        let enrich = parse_transform(&format!("enrich:country={},code", countries)).unwrap();
+        let record = json::parse(r#"{"country":"NL"}"#).unwrap();
+        assert_eq!(enrich.apply(record).unwrap()[0]["name"], "Netherlands");
+    }
+}
@@ -0,0 +1,227 @@
+use std::io;
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+use std::time::Duration;
+use crossterm::event::{self, Event as TermEvent, KeyCode, KeyEventKind};
+use crossterm::execute;
+use crossterm::terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen};
+use ratatui::prelude::*;
+use ratatui::widgets::{BarChart, Block, Borders, Gauge, List, ListItem, ListState, Paragraph};
+use failure::Error;
+use crate::batch::{self, BatchReport, BenchmarkReport, Event, Overrides, Status};
+use crate::cache::CacheState;
+
+
+struct App {
+    planned: usize,
+    seed: u64,
+    running: Option<(String, CacheState)>,
+    finished: Vec<BenchmarkReport>,
+    baseline: Option<BatchReport>,
+    selected: ListState,
+    report_path: Option<String>
+}
+
+impl App {
+    fn handle(&mut self, event: Event) {
+        match event {
+            Event::Planned { runs, seed } => {
+                self.planned = runs;
+                self.seed = seed;
+            },
+            Event::Started { name, cache } => self.running = Some((name, cache)),
+            Event::Finished(report) => {
+                self.running = None;
+                self.finished.push(report);
+                if self.selected.selected().is_none() {
+                    self.selected.select(Some(0));
+                }
+            },
+            Event::Report(path) => self.report_path = Some(path)
+        }
+    }
+
+    fn select(&mut self, offset: isize) {
+        if self.finished.is_empty() {
+            return;
+        }
+        let current = self.selected.selected().unwrap_or(0) as isize;
+        let next = (current + offset).max(0).min(self.finished.len() as isize - 1);
+        self.selected.select(Some(next as usize));
+    }
+
+    fn baseline_of(&self, report: &BenchmarkReport) -> Option<&BenchmarkReport> {
+        self.baseline.as_ref()?.find(&report.name, report.cache)
+    }
+}
+
+
+// Runs the suite in the background and shows it live. Quitting leaves the suite running with plain
+// logging, so the reports are still written.
+pub fn run(config_path: &str, overrides: &Overrides, baseline: Option<&str>) -> Result<i32, Error> {
+    let baseline = match baseline {
+        Some(path) => Some(BatchReport::load(path)?),
+        None => None
+    };
+    let (sender, receiver) = mpsc::channel();
+    let config_path = config_path.to_owned();
+    let overrides = overrides.clone();
+    let suite = thread::spawn(move || {
+        batch::run_suite(&config_path, &overrides, &mut |event| { let _ = sender.send(event); })
+    });
+
+    let mut app = App {
+        planned: 0,
+        seed: 0,
+        running: None,
+        finished: Vec::new(),
+        baseline,
+        selected: ListState::default(),
+        report_path: None
+    };
+
+    enable_raw_mode()?;
+    let mut stdout = io::stdout();
+    execute!(stdout, EnterAlternateScreen)?;
+    let mut terminal = Terminal::new(CrosstermBackend::new(stdout))?;
+    let result = event_loop(&mut terminal, &mut app, &receiver);
+    disable_raw_mode()?;
+    execute!(terminal.backend_mut(), LeaveAlternateScreen)?;
+    terminal.show_cursor()?;
+    result?;
+
+    for event in receiver {
+        batch::log_event(event);
+    }
+    suite.join().map_err(|_| failure::format_err!("suite thread panicked"))?
+}
+
+fn event_loop<B: Backend>(terminal: &mut Terminal<B>, app: &mut App, receiver: &Receiver<Event>) -> Result<(), Error> {
+    loop {
+        while let Ok(event) = receiver.try_recv() {
+            app.handle(event);
+        }
+        terminal.draw(|frame| draw(frame, app))?;
+
+        if event::poll(Duration::from_millis(100))? {
+            if let TermEvent::Key(key) = event::read()? {
+                if key.kind != KeyEventKind::Press {
+                    continue;
+                }
+                match key.code {
+                    KeyCode::Char('q') | KeyCode::Esc => return Ok(()),
+                    KeyCode::Down | KeyCode::Char('j') => app.select(1),
+                    KeyCode::Up | KeyCode::Char('k') => app.select(-1),
+                    _ => {}
+                }
+            }
+        }
+    }
+}
+
+
+fn draw(frame: &mut Frame, app: &mut App) {
+    let rows = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Length(3), Constraint::Min(0), Constraint::Length(1)])
+        .split(frame.size());
+
+    let label = match (&app.running, &app.report_path) {
+        (Some((name, cache)), _) => format!("{}/{} running {} ({:?})", app.finished.len(), app.planned, name, cache),
+        (None, Some(path)) => format!("done, report at {}", path),
+        (None, None) => format!("{}/{}", app.finished.len(), app.planned)
+    };
+    let ratio = if app.planned == 0 { 0.0 } else { app.finished.len() as f64 / app.planned as f64 };
+    frame.render_widget(
+        Gauge::default()
+            .block(Block::default().borders(Borders::ALL).title(format!("suite, seed {}", app.seed)))
+            .gauge_style(Style::default().fg(Color::Green))
+            .ratio(ratio)
+            .label(label),
+        rows[0]);
+
+    let columns = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Percentage(50), Constraint::Percentage(50)])
+        .split(rows[1]);
+
+    let items: Vec<ListItem> = app.finished
+        .iter()
+        .map(|report| {
+            let delta = app.baseline_of(report)
+                .map(|base| format!("{:+.1}%", (report.elapsed_ms as f64 / base.elapsed_ms.max(1) as f64 - 1.0) * 100.0))
+                .unwrap_or_default();
+            let color = match report.status {
+                Status::Ok => Color::Green,
+                Status::Regression => Color::Yellow,
+                Status::ErrorRate | Status::Error => Color::Red
+            };
+            ListItem::new(format!("{:<24} {:<5} {:>8} ms {:>8}", report.name, format!("{:?}", report.cache), report.elapsed_ms, delta))
+                .style(Style::default().fg(color))
+        })
+        .collect();
+    let list = List::new(items)
+        .block(Block::default().borders(Borders::ALL).title("benchmarks"))
+        .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+    frame.render_stateful_widget(list, columns[0], &mut app.selected);
+
+    if let Some(report) = app.selected.selected().and_then(|i| app.finished.get(i)) {
+        draw_details(frame, columns[1], report, app.baseline_of(report));
+    }
+
+    frame.render_widget(Paragraph::new("up/down select, q quit (the suite keeps running)"), rows[2]);
+}
+
+fn draw_details(frame: &mut Frame, area: Rect, report: &BenchmarkReport, baseline: Option<&BenchmarkReport>) {
+    let parts = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Length(9), Constraint::Min(0)])
+        .split(area);
+
+    let total = (report.read_ms + report.process_ms).max(1) as f64;
+    let mut lines = vec![
+        format!("status   {:?}", report.status),
+        format!("records  {} ({} errors)", report.records, report.errors),
+        format!("elapsed  {} ms", report.elapsed_ms),
+        format!("read     {} ms ({:.0}%)", report.read_ms, report.read_ms as f64 / total * 100.0),
+        format!("process  {} ms ({:.0}%)", report.process_ms, report.process_ms as f64 / total * 100.0),
+    ];
+    if let Some(joules) = report.joules {
+        lines.push(format!("energy   {:.2} J", joules));
+    }
+    lines.push(format!("heap     {:.1} MiB peak, {} allocations", report.peak_heap_bytes as f64 / 1048576.0, report.allocations));
+    if let Some(base) = baseline {
+        lines.push(format!("baseline {} ms, read {} ms, process {} ms", base.elapsed_ms, base.read_ms, base.process_ms));
+    }
+    if let Some(error) = &report.error {
+        lines.push(format!("error    {}", error));
+    }
+    frame.render_widget(
+        Paragraph::new(lines.join("\n")).block(Block::default().borders(Borders::ALL).title(report.name.as_str())),
+        parts[0]);
+
+    // skip the empty fast buckets so the chart starts at the first populated one
+    let first = report.latency_log2_ns.iter().position(|count| *count > 0).unwrap_or(0);
+    let labels: Vec<String> = (first..report.latency_log2_ns.len()).map(bucket_label).collect();
+    let data: Vec<(&str, u64)> = labels
+        .iter()
+        .zip(&report.latency_log2_ns[first..])
+        .map(|(label, count)| (label.as_str(), *count as u64))
+        .collect();
+    frame.render_widget(
+        BarChart::default()
+            .block(Block::default().borders(Borders::ALL).title("per record latency"))
+            .data(data.as_slice())
+            .bar_width(5)
+            .bar_gap(1),
+        parts[1]);
+}
+
+fn bucket_label(bucket: usize) -> String {
+    match bucket {
+        0..=9 => format!("{}ns", 1u64 << bucket),
+        10..=19 => format!("{}us", 1u64 << (bucket - 10)),
+        20..=29 => format!("{}ms", 1u64 << (bucket - 20)),
+        _ => format!("{}s", 1u64 << (bucket - 30))
+    }
+}
@@ -0,0 +1,38 @@
+// The JavaScript API of the wasm32 build, built with `wasm-pack build --target web`. Input is NDJSON
+// text already in memory, blank lines skipped; errors are thrown as strings naming the line.
+use wasm_bindgen::prelude::*;
+use avro_rs::Schema;
+use failure::{Error, format_err};
+use crate::infer::SchemaInferrer;
+use crate::json_schema::to_json_schema;
+
+
+fn infer(ndjson: &str, name: &str) -> Result<Schema, Error> {
+    let mut inferrer = SchemaInferrer::new(name);
+    for (number, line) in ndjson.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let json = json::parse(line).map_err(|e| format_err!("line {}: {}", number + 1, e))?;
+        inferrer.update(&json).map_err(|e| format_err!("line {}: {}", number + 1, e))?;
+    }
+    inferrer.into_schema()
+}
+
+fn to_js(error: Error) -> JsValue {
+    JsValue::from_str(&error.to_string())
+}
+
+// Avro schema of the records, as pretty printed JSON.
+#[wasm_bindgen(js_name = inferAvroSchema)]
+pub fn infer_avro_schema(ndjson: &str, name: &str) -> Result<String, JsValue> {
+    let schema = infer(ndjson, name).map_err(to_js)?;
+    serde_json::to_string_pretty(&schema).map_err(|e| to_js(e.into()))
+}
+
+// JSON Schema of the records, as pretty printed JSON.
+#[wasm_bindgen(js_name = inferJsonSchema)]
+pub fn infer_json_schema(ndjson: &str, name: &str) -> Result<String, JsValue> {
+    let schema = infer(ndjson, name).map_err(to_js)?;
+    serde_json::to_string_pretty(&to_json_schema(&schema)).map_err(|e| to_js(e.into()))
+}
@@ -0,0 +1,224 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use json::JsonValue;
+use failure::Error;
+use crate::convert::RecordWriter;
+use crate::path::{lookup, lookup_all};
+
+
+// Epoch milliseconds of a time field: a number or numeric string of milliseconds, like the tweet
+// timestamp_ms, or the tweet created_at format "Sat May 26 19:00:53 +0000 2018".
+pub fn timestamp_ms(value: &JsonValue) -> Option<i64> {
+    if let Some(ms) = value.as_i64() {
+        return Some(ms);
+    }
+    let text = value.as_str()?.trim();
+    text.parse().ok().or_else(|| twitter_time_ms(text))
+}
+
+fn twitter_time_ms(text: &str) -> Option<i64> {
+    let parts: Vec<&str> = text.split_whitespace().collect();
+    if let [_, month, day, time, offset, year] = parts.as_slice() {
+        let month = ["Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec"]
+            .iter()
+            .position(|name| name == month)? as i64 + 1;
+        let clock: Vec<i64> = time.split(':').map(|part| part.parse().ok()).collect::<Option<_>>()?;
+        if clock.len() != 3 || offset.len() != 5 {
+            return None;
+        }
+        let sign = if offset.starts_with('-') { -1 } else { 1 };
+        let offset_minutes = offset[1..3].parse::<i64>().ok()? * 60 + offset[3..].parse::<i64>().ok()?;
+        let days = days_from_civil(year.parse().ok()?, month, day.parse().ok()?);
+        let seconds = days * 86400 + clock[0] * 3600 + clock[1] * 60 + clock[2] - sign * offset_minutes * 60;
+        Some(seconds * 1000)
+    } else {
+        None
+    }
+}
+
+// days since 1970-01-01 of a proleptic Gregorian date
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = if year >= 0 { year } else { year - 399 } / 400;
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146097 + day_of_era - 719468
+}
+
+pub fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let days = days + 719468;
+    let era = if days >= 0 { days } else { days - 146096 } / 146097;
+    let day_of_era = days - era * 146097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 { month_index + 3 } else { month_index - 9 };
+    (year_of_era + era * 400 + if month <= 2 { 1 } else { 0 }, month, day)
+}
+
+fn key_of(value: &JsonValue) -> Option<String> {
+    match value {
+        JsonValue::Null => None,
+        JsonValue::Short(_) | JsonValue::String(_) => value.as_str().map(|key| key.to_owned()),
+        other => Some(other.dump())
+    }
+}
+
+
+#[derive(Debug, Clone)]
+pub struct WindowSpec {
+    pub time_path: String,
+    pub window_ms: i64,
+    // counted per distinct value, e.g. lang
+    pub group_path: String,
+    // the most frequent values reported, e.g. entities.hashtags[].text
+    pub top_path: String,
+    pub top_k: usize
+}
+
+impl Default for WindowSpec {
+    fn default() -> Self {
+        WindowSpec {
+            time_path: "timestamp_ms".to_owned(),
+            window_ms: 60_000,
+            group_path: "lang".to_owned(),
+            top_path: "entities.hashtags[].text".to_owned(),
+            top_k: 10
+        }
+    }
+}
+
+
+#[derive(Debug, Default)]
+struct Window {
+    records: usize,
+    groups: HashMap<String, usize>,
+    values: HashMap<String, usize>
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct WindowSummary {
+    pub start_ms: i64,
+    pub records: usize,
+    // all groups, largest first
+    pub groups: Vec<(String, usize)>,
+    pub top: Vec<(String, usize)>
+}
+
+impl fmt::Display for WindowSummary {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let seconds = self.start_ms.div_euclid(1000);
+        let (year, month, day) = civil_from_days(seconds.div_euclid(86400));
+        let time = seconds.rem_euclid(86400);
+        write!(f, "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z {} records", year, month, day, time / 3600, time / 60 % 60, time % 60, self.records)?;
+        let join = |counts: &[(String, usize)]| counts.iter().map(|(key, count)| format!("{} {}", key, count)).collect::<Vec<_>>().join(", ");
+        write!(f, ", groups: {}, top: {}", join(&self.groups), join(&self.top))
+    }
+}
+
+fn largest_first(counts: &HashMap<String, usize>, limit: usize) -> Vec<(String, usize)> {
+    let mut counts: Vec<(String, usize)> = counts.iter().map(|(key, count)| (key.clone(), *count)).collect();
+    counts.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    counts.truncate(limit);
+    counts
+}
+
+
+// Tumbling window aggregation during the streaming pass. Records may arrive out of order, every
+// window stays open until the summaries are taken.
+pub struct WindowAggregator {
+    spec: WindowSpec,
+    windows: BTreeMap<i64, Window>,
+    // records without a readable time field
+    pub untimed: usize
+}
+
+impl WindowAggregator {
+    pub fn new(spec: WindowSpec) -> Self {
+        WindowAggregator { spec, windows: BTreeMap::new(), untimed: 0 }
+    }
+
+    pub fn update(&mut self, record: &JsonValue) {
+        let ms = match timestamp_ms(lookup(record, &self.spec.time_path)) {
+            Some(ms) => ms,
+            None => {
+                self.untimed += 1;
+                return;
+            }
+        };
+        let start = ms.div_euclid(self.spec.window_ms) * self.spec.window_ms;
+        let window = self.windows.entry(start).or_insert_with(Window::default);
+        window.records += 1;
+        if let Some(group) = key_of(lookup(record, &self.spec.group_path)) {
+            *window.groups.entry(group).or_insert(0) += 1;
+        }
+        for value in lookup_all(record, &self.spec.top_path) {
+            if let Some(value) = key_of(value) {
+                *window.values.entry(value).or_insert(0) += 1;
+            }
+        }
+    }
+
+    pub fn summaries(&self) -> Vec<WindowSummary> {
+        self.windows
+            .iter()
+            .map(|(start, window)| WindowSummary {
+                start_ms: *start,
+                records: window.records,
+                groups: largest_first(&window.groups, usize::MAX),
+                top: largest_first(&window.values, self.spec.top_k)
+            })
+            .collect()
+    }
+}
+
+impl RecordWriter for WindowAggregator {
+    fn write(&mut self, record: &JsonValue) -> Result<(), Error> {
+        self.update(record);
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_timestamp() {
+        assert_eq!(timestamp_ms(&JsonValue::from("Sat May 26 19:00:53 +0000 2018")), Some(1527361253000));
+        assert_eq!(timestamp_ms(&JsonValue::from("Sat May 26 21:00:53 +0200 2018")), Some(1527361253000));
+        assert_eq!(timestamp_ms(&JsonValue::from("1527361253680")), Some(1527361253680));
+        assert_eq!(timestamp_ms(&JsonValue::from(5)), Some(5));
+        assert_eq!(timestamp_ms(&JsonValue::from("yesterday")), None);
+        assert_eq!(civil_from_days(days_from_civil(2018, 5, 26)), (2018, 5, 26));
+    }
+
+    #[test]
+    fn test_windows() {
+        let mut aggregator = WindowAggregator::new(WindowSpec { top_k: 1, ..WindowSpec::default() });
+        for record in &[
+            r#"{"timestamp_ms":"1527361253680","lang":"th","entities":{"hashtags":[{"text":"UCLfinal"},{"text":"LFC"}]}}"#,
+            r#"{"timestamp_ms":"1527361201000","lang":"en","entities":{"hashtags":[{"text":"UCLfinal"}]}}"#,
+            r#"{"timestamp_ms":"1527361260000","lang":"en","entities":{"hashtags":[]}}"#,
+            r#"{"lang":"en"}"#
+        ] {
+            aggregator.update(&json::parse(record).unwrap());
+        }
+        let summaries = aggregator.summaries();
+        assert_eq!(aggregator.untimed, 1);
+        assert_eq!(summaries.len(), 2);
+        assert_eq!(summaries[0].start_ms, 1527361200000);
+        assert_eq!(summaries[0].records, 2);
+        assert_eq!(summaries[0].groups, vec![("en".to_owned(), 1), ("th".to_owned(), 1)]);
+        assert_eq!(summaries[0].top, vec![("UCLfinal".to_owned(), 2)]);
+        assert!(summaries[0].to_string().starts_with("2018-05-26T19:00:00Z 2 records"));
+        assert!(summaries[1].top.is_empty());
+    }
+}
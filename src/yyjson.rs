@@ -0,0 +1,31 @@
+use std::os::raw::c_char;
+use failure::{Error, bail};
+
+
+// yyjson through the shim of shim/yyjson.c, built by build.rs with the yyjson feature.
+extern "C" {
+    fn yyjson_shim_size(data: *const c_char, len: usize) -> i64;
+}
+
+// Parses a document with yyjson and drops it, returning its size as sink::json_size counts it, so
+// its checksum matches the one of the Rust parsers.
+pub fn parse_size(input: &[u8]) -> Result<u64, Error> {
+    let size = unsafe { yyjson_shim_size(input.as_ptr() as *const c_char, input.len()) };
+    if size < 0 {
+        bail!("yyjson could not parse the document");
+    }
+    Ok(size as u64)
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_yyjson() {
+        assert_eq!(parse_size(br#"{"a": 1, "b": [true, {"c": null}], "d": {}}"#).unwrap(), 6);
+        assert_eq!(parse_size(b"[]").unwrap(), 0);
+        assert!(parse_size(b"{\"a\":").is_err());
+    }
+}